@@ -2,9 +2,12 @@ extern crate image;
 extern crate rustico_core;
 extern crate rustico_ui_common;
 
+use rustico_core::debugger::Breakpoint;
+use rustico_core::debugger::BreakpointKind;
+use rustico_core::disassembler;
+use rustico_core::expression::Expression;
 use rustico_core::nes::NesState;
-use rustico_core::palettes::NTSC_PAL;
-use rustico_core::cartridge::mapper_from_file;
+use rustico_core::trace::TraceLogger;
 
 use rustico_ui_common::application::RuntimeState as RusticoRuntimeState;
 use rustico_ui_common::events;
@@ -14,11 +17,17 @@ use rustico_ui_common::event_window::EventWindow;
 
 use std::env;
 use std::fs::File;
+use std::path::Path;
+use std::path::PathBuf;
 use std::str;
+use std::sync::Arc;
+use std::thread;
+use std::time::Duration;
 
 use std::io::Read;
 use std::io::Write;
 use std::io::BufReader;
+use std::io::BufWriter;
 use std::io::BufRead;
 
 pub struct CliRuntimeState {
@@ -29,6 +38,7 @@ pub struct CliRuntimeState {
   pub piano_file: Option<File>,
   pub audio_file: Option<File>,
   pub event_file: Option<File>,
+  pub movie_path: Option<String>,
 }
 
 impl CliRuntimeState {
@@ -41,6 +51,7 @@ impl CliRuntimeState {
       piano_file: None,
       audio_file: None,
       event_file: None,
+      movie_path: None,
     }
   }
 }
@@ -54,13 +65,30 @@ pub fn dispatch_event(state: &mut CliRuntimeState, event: events::Event) {
   // Now process core state, which needs only a reference to itself
   responses.extend(state.core.handle_event(event.clone()));
 
+  // The few events meant for the shell
+  match event {
+    events::Event::SaveMovie(movie_data) => {
+      save_movie(state, &movie_data);
+    },
+    events::Event::SaveCodeDataLog(cdl_path, cdl_data) => {
+      match std::fs::write(&cdl_path, &*cdl_data) {
+        Ok(_) => println!("Wrote code/data log to {}", cdl_path),
+        Err(why) => panic!("Couldn't write {}: {}", cdl_path, why)
+      }
+    },
+    events::Event::LoadFailed(why) => {
+      panic!("{}", why);
+    },
+    _ => {}
+  }
+
   // Finally, recursively dispatch any responses we got to this event, bubbling those up the chain
   for response in responses {
     dispatch_event(state, response);
   }
 }
 
-fn load_cartridge(nes: &mut NesState, cartridge_path: &str) {
+fn load_cartridge(core: &mut RusticoRuntimeState, cartridge_path: &str) {
   // Read in the ROM file and attempt to create a new NesState:
   let file = File::open(cartridge_path);
   match file {
@@ -78,33 +106,165 @@ fn load_cartridge(nes: &mut NesState, cartridge_path: &str) {
     },
     Ok(_) => {
       println!("Loading {}...", cartridge_path);
-      let maybe_mapper = mapper_from_file(&cartridge);
-      match maybe_mapper {
-            Ok(mapper) => {
-              *nes = NesState::new(mapper);
-              nes.power_on();
-            },
-        Err(why) => {
-          panic!("{}", why);
+      for response in core.load_cartridge(cartridge_path.to_string(), &cartridge) {
+        match response {
+          events::Event::CartridgeRejected(_, why) => {
+            panic!("{}", why);
+          },
+          _ => {}
         }
       }
     },
   };
 }
 
+fn read_file(path: &Path) -> Result<Vec<u8>, String> {
+  let mut data = Vec::new();
+  let mut file = File::open(path).map_err(|why| format!("Couldn't open {}: {}", path.display(), why))?;
+  file.read_to_end(&mut data).map_err(|why| format!("Couldn't read from {}: {}", path.display(), why))?;
+  return Ok(data);
+}
+
+// symbols <file>
+// Loads a ca65 .dbg, FCEUX .nl or Mesen .mlb file
+fn load_symbols(state: &mut CliRuntimeState, symbol_path: &str) {
+  match read_file(Path::new(symbol_path)) {
+    Ok(data) => dispatch_event(state, events::Event::LoadSymbols(symbol_path.to_string(), Arc::new(data))),
+    Err(why) => panic!("{}", why)
+  }
+}
+
+// Picks up any symbol files sitting next to the ROM: game.dbg, game.mlb, and FCEUX's
+// game.nes.ram.nl, game.nes.0.nl, game.nes.1.nl and so on. A broken one is only worth
+// a warning, as nobody asked for it by name.
+fn load_symbols_beside(state: &mut CliRuntimeState, cartridge_path: &str) {
+  let rom_path = Path::new(cartridge_path);
+  let directory = match rom_path.parent() {
+    Some(parent) if parent.as_os_str().len() > 0 => parent.to_path_buf(),
+    _ => PathBuf::from(".")
+  };
+  let rom_name = rom_path.file_name().map(|name| name.to_string_lossy().to_string()).unwrap_or(String::new());
+  let stem = rom_path.file_stem().map(|name| name.to_string_lossy().to_string()).unwrap_or(String::new());
+  let mut candidates = vec![format!("{}.dbg", stem), format!("{}.mlb", stem)];
+  match std::fs::read_dir(&directory) {
+    Ok(entries) => {
+      let mut name_lists: Vec<String> = entries
+        .filter_map(|entry| entry.ok())
+        .map(|entry| entry.file_name().to_string_lossy().to_string())
+        .filter(|name| name.starts_with(&format!("{}.", rom_name)) && name.ends_with(".nl"))
+        .collect();
+      name_lists.sort();
+      candidates.extend(name_lists);
+    },
+    Err(_) => {}
+  }
+  for candidate in candidates {
+    let symbol_path = directory.join(&candidate);
+    if !symbol_path.is_file() {
+      continue;
+    }
+    let result = read_file(&symbol_path).and_then(|data| state.core.symbols.load_file(&candidate, &data));
+    match result {
+      Ok(_) => println!("Loaded symbols from {}", symbol_path.display()),
+      Err(why) => println!("Ignoring {}: {}", symbol_path.display(), why)
+    }
+  }
+}
+
+// cdl start
+// cdl load <file.cdl>
+// cdl save <file.cdl>
+// Loading merges into the current log, starting one if needed. Saving prints how much
+// of the ROM has been covered so far.
+fn code_data_log(state: &mut CliRuntimeState, command_list: &mut Vec<String>) {
+  let action = command_list.remove(0);
+  match action.as_ref() {
+    "start" => dispatch_event(state, events::Event::StartCodeDataLog),
+    "load" => {
+      let cdl_path = command_list.remove(0);
+      match read_file(Path::new(&cdl_path)) {
+        Ok(data) => dispatch_event(state, events::Event::LoadCodeDataLog(Arc::new(data))),
+        Err(why) => panic!("{}", why)
+      }
+    },
+    "save" => {
+      let cdl_path = command_list.remove(0);
+      match state.core.nes.cdl {
+        Some(ref cdl) => {
+          let (code, data) = cdl.prg_coverage();
+          let (rendered, read) = cdl.chr_coverage();
+          println!("PRG: {} of {} bytes code, {} data", code, cdl.prg.len(), data);
+          println!("CHR: {} of {} bytes drawn, {} read", rendered, cdl.chr.len(), read);
+        },
+        None => panic!("Nothing to save, code/data logging was never started")
+      }
+      dispatch_event(state, events::Event::RequestCodeDataLog(cdl_path));
+    },
+    _ => panic!("Unrecognized cdl action: {}", action)
+  }
+}
+
+// profile <frames> <file.csv>
+// Runs for that many frames with the profiler on, then writes out what it saw
+fn profile(state: &mut CliRuntimeState, frames: u64, csv_path: &str) {
+  dispatch_event(state, events::Event::StartProfiler);
+  run(state, frames);
+  let csv = match state.core.nes.profiler {
+    Some(ref profiler) => {
+      println!("Profiled {} frames, {:.1} cycles per frame", profiler.frames, profiler.cycles_per_frame());
+      profiler.to_csv(&state.core.symbols)
+    },
+    None => panic!("The profiler was stopped before it could finish")
+  };
+  let mut file = match File::create(csv_path) {
+    Err(why) => panic!("Couldn't open {}: {}", csv_path, why),
+    Ok(file) => file
+  };
+  match file.write_all(csv.as_bytes()) {
+    Err(why) => panic!("Couldn't write to {}: {}", csv_path, why),
+    Ok(_) => {}
+  }
+  dispatch_event(state, events::Event::StopProfiler);
+}
+
+// serve <port>
+// Hands control to the debug server until every client that connected has gone again.
+// Emulation starts out paused, so a harness can set up before anything runs; resuming
+// runs frames as fast as they'll go.
+fn serve(state: &mut CliRuntimeState, port: i64) {
+  dispatch_event(state, events::Event::StoreIntegerSetting("debug_server.port".to_string(), port));
+  dispatch_event(state, events::Event::StoreBooleanSetting("debug_server.enabled".to_string(), true));
+  state.core.running = false;
+  loop {
+    match state.core.debug_server {
+      Some(ref server) if server.finished() => break,
+      Some(_) => {},
+      None => panic!("The debug server couldn't be started on port {}", port)
+    }
+    if state.core.running {
+      run(state, 1);
+    } else {
+      dispatch_event(state, events::Event::Update);
+      thread::sleep(Duration::from_millis(1));
+    }
+  }
+  dispatch_event(state, events::Event::StoreBooleanSetting("debug_server.enabled".to_string(), false));
+}
+
 // Note: Later we should use the ui-common library, and dump panels instead of just the game screen. That
 // will be very flexible and useful.
 fn dump_frame(state: &mut CliRuntimeState) {
   match &mut state.game_file {
     Some(file) => {
       let mut rgba_pixels: [u8; 3 * 256 * 240] = [0; 3 * 256 * 240]; 
+      let palette = state.core.nes.ppu.model.palette();
       for x in 0 .. 256 {
         for y in 0 .. 240 {
           let palette_index = ((state.core.nes.ppu.screen[y * 256 + x]) as usize) * 3;
           let pixel_index = (256 * y + x) * 3;
-          rgba_pixels[pixel_index + 0] = NTSC_PAL[palette_index + 0];
-          rgba_pixels[pixel_index + 1] = NTSC_PAL[palette_index + 1];
-          rgba_pixels[pixel_index + 2] = NTSC_PAL[palette_index + 2];
+          rgba_pixels[pixel_index + 0] = palette[palette_index + 0];
+          rgba_pixels[pixel_index + 1] = palette[palette_index + 1];
+          rgba_pixels[pixel_index + 2] = palette[palette_index + 2];
         }
       }
       let _ = file.write_all(&rgba_pixels);
@@ -152,13 +312,15 @@ fn dump_panel(file_handle: &mut Option<File>, panel: & dyn Panel) {
 }
 
 fn run(state: &mut CliRuntimeState, frames: u64) {
+  // Continue on from wherever a breakpoint may have stopped us
+  state.core.running = true;
   for _ in 0 .. frames {
     // Run the core emulator for one frame
     // Just like the SDL build, we do this by running a bunch of individual scanlines
-    while state.core.nes.ppu.current_scanline == 242 {
+    while state.core.running && state.core.nes.ppu.current_scanline == 242 {
       dispatch_event(state, events::Event::NesRunScanline);
     }
-    while state.core.nes.ppu.current_scanline != 242 {
+    while state.core.running && state.core.nes.ppu.current_scanline != 242 {
       dispatch_event(state, events::Event::NesRunScanline);
     }
     // Run each panel for one frame, simulating a draw step
@@ -169,41 +331,187 @@ fn run(state: &mut CliRuntimeState, frames: u64) {
     dump_audio(state);
     dump_panel(&mut state.piano_file, &state.piano_roll_panel);
     dump_panel(&mut state.event_file, &state.event_viewer_panel);
+    if !state.core.running {
+      return;
+    }
   }
 }
 
-fn reset(nes: &mut NesState) {
-  nes.reset();
-}
-
 fn tap(state: &mut CliRuntimeState, button: &str, frames: u64) {
-  let button_index: u8 = match button {
-    "a" => 0,
-    "b" => 1,
-    "select" => 2,
-    "start" => 3,
-    "up" => 4,
-    "down" => 5,
-    "left" => 6,
-    "right" => 7,
+  let controller_button = match button {
+    "a" => events::StandardControllerButton::A,
+    "b" => events::StandardControllerButton::B,
+    "select" => events::StandardControllerButton::Select,
+    "start" => events::StandardControllerButton::Start,
+    "up" => events::StandardControllerButton::DPadUp,
+    "down" => events::StandardControllerButton::DPadDown,
+    "left" => events::StandardControllerButton::DPadLeft,
+    "right" => events::StandardControllerButton::DPadRight,
     _ => panic!("Invalid button to tap: {}", button)
   };
-  // TODO: change button state using application events?
-  state.core.nes.p1_input |= 0x1 << button_index;
+  dispatch_event(state, events::Event::StandardControllerPress(0, controller_button.clone()));
+  run(state, frames);
+  dispatch_event(state, events::Event::StandardControllerRelease(0, controller_button));
+  run(state, frames);
+}
+
+fn movie(state: &mut CliRuntimeState, action: &str, movie_path: &str) {
+  match action {
+    "record" => {
+      state.movie_path = Some(movie_path.to_string());
+      dispatch_event(state, events::Event::MovieRecord);
+    },
+    "play" => {
+      let mut movie_data = Vec::new();
+      match File::open(movie_path) {
+        Err(why) => {
+          panic!("Couldn't open {}: {}", movie_path, why);
+        },
+        Ok(mut file) => {
+          let _ = file.read_to_end(&mut movie_data);
+        }
+      }
+      dispatch_event(state, events::Event::MoviePlay(Arc::new(movie_data)));
+    },
+    _ => panic!("Invalid movie action: {}, expected play or record", action)
+  }
+}
+
+fn save_movie(state: &mut CliRuntimeState, movie_data: &[u8]) {
+  match &state.movie_path {
+    Some(movie_path) => {
+      match File::create(movie_path) {
+        Err(why) => {
+          panic!("Couldn't open {}: {}", movie_path, why);
+        },
+        Ok(mut file) => {
+          let _ = file.write_all(movie_data);
+          println!("Saved movie to {}", movie_path);
+        }
+      }
+    },
+    None => {}
+  }
+}
+
+// Aims at a screen pixel and holds the trigger for some frames, then lets go for as many
+fn zapper(state: &mut CliRuntimeState, x: i32, y: i32, frames: u64) {
+  if state.core.nes.port2.device.name() != "Zapper" {
+    dispatch_event(state, events::Event::StoreStringSetting("input.port2".to_string(), "zapper".to_string()));
+  }
+  dispatch_event(state, events::Event::ZapperAim(x, y));
+  dispatch_event(state, events::Event::ZapperTrigger(true));
   run(state, frames);
-  state.core.nes.p1_input ^= 0x1 << button_index;
+  dispatch_event(state, events::Event::ZapperTrigger(false));
   run(state, frames);
 }
 
+fn parse_address(text: &str) -> u16 {
+  let parsed = if text.starts_with("$") {
+    u16::from_str_radix(&text[1..], 16)
+  } else if text.starts_with("0x") {
+    u16::from_str_radix(&text[2..], 16)
+  } else {
+    text.parse()
+  };
+  match parsed {
+    Ok(address) => return address,
+    Err(_) => panic!("Invalid address: {}", text)
+  }
+}
+
+// break <kind> [address or start-end] [if <condition>]
+// Interrupt breakpoints take no address. The condition must be a single argument, so
+// quote it if it contains spaces.
+fn add_breakpoint(state: &mut CliRuntimeState, command_list: &mut Vec<String>) {
+  let kind_name = command_list.remove(0);
+  let kind = match kind_name.as_ref() {
+    "clear" => {
+      dispatch_event(state, events::Event::ClearBreakpoints);
+      return;
+    },
+    "pc" | "exec" | "execute" => BreakpointKind::Execute,
+    "read" => BreakpointKind::Read,
+    "write" => BreakpointKind::Write,
+    "ppuread" => BreakpointKind::PpuRead,
+    "ppuwrite" => BreakpointKind::PpuWrite,
+    "irq" => BreakpointKind::Irq,
+    "nmi" => BreakpointKind::Nmi,
+    _ => panic!("Invalid breakpoint kind: {}", kind_name)
+  };
+  let mut breakpoint = match kind {
+    BreakpointKind::Irq | BreakpointKind::Nmi => Breakpoint::new(kind, 0),
+    _ => {
+      let range = command_list.remove(0);
+      match range.find('-') {
+        Some(dash) => Breakpoint::range(kind, parse_address(&range[.. dash]), parse_address(&range[dash + 1 ..])),
+        None => Breakpoint::new(kind, parse_address(&range))
+      }
+    }
+  };
+  if command_list.len() > 0 && command_list[0] == "if" {
+    let _ = command_list.remove(0);
+    let condition_text = command_list.remove(0);
+    match Expression::parse(&condition_text) {
+      Ok(condition) => breakpoint.condition = Some(condition),
+      Err(why) => panic!("Invalid breakpoint condition {}: {}", condition_text, why)
+    }
+  }
+  dispatch_event(state, events::Event::AddBreakpoint(breakpoint));
+}
+
+// Prints count instructions starting at this address, as currently banked in. Use
+// "pc" as the start to begin at the program counter.
+fn disasm(state: &mut CliRuntimeState, start: &str, count: usize) {
+  let nes = &state.core.nes;
+  let start_address = if start == "pc" {nes.registers.pc} else {parse_address(start)};
+  for instruction in disassembler::disassemble_range(nes, start_address, count) {
+    match state.core.symbols.label_at(nes, instruction.address) {
+      Some(name) => println!("{}:", name),
+      None => {}
+    }
+    println!("{}", instruction.listing_line(nes, &state.core.symbols));
+  }
+}
+
+// trace <file> [range] [range]
+// Ranges are inclusive, written as start-end. Addresses ($8000-$9FFF or 0x8000-0x9FFF)
+// limit the trace to instructions in that range; plain numbers (100-200) to those frames.
+fn trace(state: &mut CliRuntimeState, command_list: &mut Vec<String>) {
+  let trace_path = command_list.remove(0);
+  if trace_path == "stop" {
+    state.core.nes.tracer = None;
+    return;
+  }
+  let file = match File::create(&trace_path) {
+    Err(why) => panic!("Couldn't open {}: {}", trace_path, why),
+    Ok(file) => file
+  };
+  let mut tracer = TraceLogger::new(Box::new(BufWriter::new(file)));
+  while command_list.len() > 0 && command_list[0].contains('-') {
+    let range = command_list.remove(0);
+    let dash = range.find('-').unwrap();
+    if range.starts_with("$") || range.starts_with("0x") {
+      tracer.address_range = Some((parse_address(&range[.. dash]), parse_address(&range[dash + 1 ..])));
+    } else {
+      let start: u32 = range[.. dash].parse().unwrap();
+      let end: u32 = range[dash + 1 ..].parse().unwrap();
+      tracer.frame_range = Some((start, end));
+    }
+  }
+  state.core.nes.tracer = Some(tracer);
+}
+
 fn save_screenshot(nes: &NesState, output_path: &str) {
   let mut img = image::ImageBuffer::new(256, 240);
+  let palette = nes.ppu.model.palette();
   for x in 0 .. 256 {
     for y in 0 .. 240 {
       let palette_index = ((nes.ppu.screen[y * 256 + x]) as usize) * 3;
       img.put_pixel(x as u32, y as u32, image::Rgba([
-        NTSC_PAL[palette_index + 0],
-        NTSC_PAL[palette_index + 1],
-        NTSC_PAL[palette_index + 2],
+        palette[palette_index + 0],
+        palette[palette_index + 1],
+        palette[palette_index + 2],
         255 as u8]));
     }
   }
@@ -267,9 +575,28 @@ fn command_file(state: &mut CliRuntimeState, command_path: &str) {
   let file_reader = BufReader::new(&unwrapped_file);
   for l in file_reader.lines() {
     let line = l.unwrap();
-    let command_list = line.split(" ").map(|s| s.to_string()).collect();
-    process_command_list(state, command_list);
+    process_command_list(state, split_command_line(&line));
+  }
+}
+
+// Splits on spaces, except within double quotes, so that command files can pass
+// breakpoint conditions the same way a shell would
+fn split_command_line(line: &str) -> Vec<String> {
+  let mut command_list = Vec::new();
+  let mut current = String::new();
+  let mut quoted = false;
+  for c in line.chars() {
+    match c {
+      '"' => {quoted = !quoted},
+      ' ' if !quoted => {
+        command_list.push(current);
+        current = String::new();
+      },
+      _ => {current.push(c)}
+    }
   }
+  command_list.push(current);
+  return command_list;
 }
 
 fn process_command_list(state: &mut CliRuntimeState, mut command_list: Vec<String>) {
@@ -279,9 +606,14 @@ fn process_command_list(state: &mut CliRuntimeState, mut command_list: Vec<Strin
       "cart" | "cartridge" | "rom" => {
         // TODO: implement this with the standard event instead
         let cartridge_path = command_list.remove(0);
-        load_cartridge(&mut state.core.nes, cartridge_path.as_ref());
+        load_cartridge(&mut state.core, cartridge_path.as_ref());
+        load_symbols_beside(state, cartridge_path.as_ref());
         state.core.running = true;
       },
+      "symbols" => {
+        let symbol_path = command_list.remove(0);
+        load_symbols(state, symbol_path.as_ref());
+      },
       "config"  => {
         let config_path = command_list.remove(0);
         state.core.settings.load(&config_path.into());
@@ -293,9 +625,27 @@ fn process_command_list(state: &mut CliRuntimeState, mut command_list: Vec<Strin
         let frames: u64 = command_list.remove(0).parse().unwrap();
         run(state, frames);
       },
+      "rewind" => {
+        let frames: u32 = command_list.remove(0).parse().unwrap();
+        dispatch_event(state, events::Event::NesRewind(frames));
+      },
       "reset" => {
-        // TODO: implement this with the standard event instead
-        reset(&mut state.core.nes);
+        dispatch_event(state, events::Event::NesReset);
+      }
+      "power" => {
+        dispatch_event(state, events::Event::NesPowerCycle);
+      }
+      "movie" => {
+        let action = command_list.remove(0);
+        match action.as_ref() {
+          "stop" => {
+            dispatch_event(state, events::Event::MovieStop);
+          },
+          _ => {
+            let movie_path = command_list.remove(0);
+            movie(state, action.as_ref(), movie_path.as_ref());
+          }
+        }
       }
       "track" => {
         let track_index: u8 = command_list.remove(0).parse().unwrap();
@@ -307,6 +657,48 @@ fn process_command_list(state: &mut CliRuntimeState, mut command_list: Vec<Strin
         let frames: u64 = command_list.remove(0).parse().unwrap();
         tap(state, button.as_ref(), frames);
       }
+      "zapper" => {
+        let x: i32 = command_list.remove(0).parse().unwrap();
+        let y: i32 = command_list.remove(0).parse().unwrap();
+        let frames: u64 = command_list.remove(0).parse().unwrap();
+        zapper(state, x, y, frames);
+      }
+      "disasm" => {
+        let start = command_list.remove(0);
+        let count: usize = command_list.remove(0).parse().unwrap();
+        disasm(state, start.as_ref(), count);
+      }
+      "trace" => {
+        trace(state, &mut command_list);
+      }
+      "break" => {
+        add_breakpoint(state, &mut command_list);
+      }
+      "cdl" => {
+        code_data_log(state, &mut command_list);
+      }
+      "serve" => {
+        let port: i64 = command_list.remove(0).parse().unwrap();
+        serve(state, port);
+      }
+      "profile" => {
+        let frames: u64 = command_list.remove(0).parse().unwrap();
+        let csv_path = command_list.remove(0);
+        profile(state, frames, csv_path.as_ref());
+      }
+      "cheat" => {
+        let code = command_list.remove(0);
+        if code == "remove" {
+          let code = command_list.remove(0);
+          dispatch_event(state, events::Event::RemoveCheat(code));
+        } else {
+          dispatch_event(state, events::Event::AddCheat(code));
+        }
+      }
+      "coin" => {
+        let slot: usize = command_list.remove(0).parse().unwrap();
+        dispatch_event(state, events::Event::InsertCoin(slot));
+      }
       "screenshot" => {
         let cartridge_path = command_list.remove(0);
         save_screenshot(&mut state.core.nes, cartridge_path.as_ref());
@@ -381,4 +773,7 @@ fn main() {
   let _ = args.remove(0);
 
   process_command_list(&mut state, args);
+
+  // Save any movie still being recorded
+  dispatch_event(&mut state, events::Event::MovieStop);
 }
@@ -3,6 +3,7 @@ use super::audio_channel::AudioChannelState;
 use super::ring_buffer::RingBuffer;
 use super::filters;
 use super::filters::DspFilter;
+use save_load::*;
 
 pub struct DmcState {
     pub name: String,
@@ -32,6 +33,9 @@ pub struct DmcState {
     pub interrupt_flag: bool,
     pub rdy_line: bool,
     pub rdy_delay: u8,
+
+    // The address of the last sample byte fetched, for the code/data logger
+    pub last_fetch_address: Option<u16>,
 }
 
 impl DmcState {
@@ -63,6 +67,7 @@ impl DmcState {
             interrupt_flag: false,
             rdy_line: false,
             rdy_delay: 0,
+            last_fetch_address: None,
         }
     }
 
@@ -73,7 +78,9 @@ impl DmcState {
     }
 
     pub fn read_next_sample(&mut self, mapper: &mut dyn Mapper) {
-        match mapper.read_cpu(0x8000 | (self.current_address & 0x7FFF)) {
+        let address = 0x8000 | (self.current_address & 0x7FFF);
+        self.last_fetch_address = Some(address);
+        match mapper.read_cpu(address) {
             Some(byte) => self.sample_buffer = byte,
             None => self.sample_buffer = 0,
         }
@@ -149,6 +156,51 @@ impl DmcState {
     pub fn output(&self) -> i16 {
         return self.output_level as i16;
     }
+
+    pub fn save_state(&self, buff: &mut Vec<u8>) {
+        save_bool(buff, self.looping);
+        save_u16(buff, self.period_initial);
+        save_u16(buff, self.period_current);
+        save_u8(buff, self.output_level);
+        save_u16(buff, self.starting_address);
+        save_u16(buff, self.sample_length);
+
+        save_u16(buff, self.current_address);
+        save_u8(buff, self.sample_buffer);
+        save_u8(buff, self.shift_register);
+        save_bool(buff, self.sample_buffer_empty);
+        save_u8(buff, self.bits_remaining);
+        save_u16(buff, self.bytes_remaining);
+        save_bool(buff, self.silence_flag);
+
+        save_bool(buff, self.interrupt_enabled);
+        save_bool(buff, self.interrupt_flag);
+        save_bool(buff, self.rdy_line);
+        save_u8(buff, self.rdy_delay);
+    }
+
+    pub fn load_state(&mut self, buff: &mut &[u8]) -> Result<(), String> {
+        self.looping = load_bool(buff)?;
+        self.period_initial = load_u16(buff)?;
+        self.period_current = load_u16(buff)?;
+        self.output_level = load_u8(buff)?;
+        self.starting_address = load_u16(buff)?;
+        self.sample_length = load_u16(buff)?;
+
+        self.current_address = load_u16(buff)?;
+        self.sample_buffer = load_u8(buff)?;
+        self.shift_register = load_u8(buff)?;
+        self.sample_buffer_empty = load_bool(buff)?;
+        self.bits_remaining = load_u8(buff)?;
+        self.bytes_remaining = load_u16(buff)?;
+        self.silence_flag = load_bool(buff)?;
+
+        self.interrupt_enabled = load_bool(buff)?;
+        self.interrupt_flag = load_bool(buff)?;
+        self.rdy_line = load_bool(buff)?;
+        self.rdy_delay = load_u8(buff)?;
+        return Ok(());
+    }
 }
 
 impl AudioChannelState for DmcState {
@@ -1,8 +1,11 @@
 use mmc::mapper::Mapper;
+use region::Region;
 
 use std::fs::OpenOptions;
 use std::io::prelude::*;
 
+use save_load::*;
+
 mod audio_channel;
 mod dmc;
 pub mod filters;
@@ -33,6 +36,7 @@ pub enum FilterType {
 }
 
 pub struct ApuState {
+    pub region: Region,
     pub current_cycle: u64,
 
     pub frame_sequencer_mode: u8,
@@ -181,11 +185,13 @@ fn construct_lq_filter_chain(clock_rate: f32, target_sample_rate: f32, filter_ty
 }
 
 impl ApuState {
-    pub fn new() -> ApuState {
+    pub fn new(region: Region) -> ApuState {
         let default_samplerate = 44100;
         let output_buffer_size = recommended_buffer_size(44100);
+        let cpu_clock_rate = region.cpu_clock_rate();
 
         return ApuState {
+            region: region,
             current_cycle: 0,
             frame_sequencer_mode: 0,
             frame_sequencer: 0,
@@ -194,9 +200,9 @@ impl ApuState {
             half_frame_counter: 0,
             frame_interrupt: false,
             disable_interrupt: false,
-            pulse_1: PulseChannelState::new("Pulse 1", "2A03", 1_789_773, true),
-            pulse_2: PulseChannelState::new("Pulse 2", "2A03", 1_789_773, false),
-            triangle: TriangleChannelState::new("Triangle", "2A03", 1_789_773),
+            pulse_1: PulseChannelState::new("Pulse 1", "2A03", cpu_clock_rate, true),
+            pulse_2: PulseChannelState::new("Pulse 2", "2A03", cpu_clock_rate, false),
+            triangle: TriangleChannelState::new("Triangle", "2A03", cpu_clock_rate),
             noise: NoiseChannelState::new("Noise", "2A03"),
             dmc: DmcState::new("DMC", "2A03"),
             staging_buffer: RingBuffer::new(output_buffer_size),
@@ -204,14 +210,14 @@ impl ApuState {
             output_buffer: vec!(0i16; output_buffer_size),
             buffer_full: false,
             sample_rate: default_samplerate,
-            cpu_clock_rate: 1_789_773,
+            cpu_clock_rate: cpu_clock_rate,
             generated_samples: 0,
             next_sample_at: 0,
             pulse_table: generate_pulse_table(),
             tnd_table: generate_tnd_table(),
 
             filter_type: FilterType::FamiCom,
-            filter_chain: construct_hq_filter_chain(1789773.0, 44100.0, FilterType::FamiCom),
+            filter_chain: construct_hq_filter_chain(cpu_clock_rate as f32, 44100.0, FilterType::FamiCom),
             filter_hq: true,
         }
     }
@@ -417,8 +423,7 @@ impl ApuState {
                 self.noise.envelope.volume_register = data & 0b0000_1111;
             },
             0x400E => {
-                let noise_period = [
-                    4, 8, 16, 32, 64, 96, 128, 160, 202, 254, 380, 508, 762, 1016, 2034, 4068];
+                let noise_period = self.region.noise_period_table();
 
                 let mode =        (data & 0b1000_0000) >> 7;
                 let period_index = data & 0b0000_1111;
@@ -435,8 +440,7 @@ impl ApuState {
 
             // DMC Channel
             0x4010 => {
-                let period_table = [
-                    428, 380, 340, 320, 286, 254, 226, 214, 190, 160, 142, 128, 106,  84,  72,  54];
+                let period_table = self.region.dmc_period_table();
                 self.dmc.looping = (data & 0b0100_0000) != 0;
                 self.dmc.interrupt_enabled = (data & 0b1000_0000) != 0;
                 if !self.dmc.interrupt_enabled {
@@ -521,28 +525,31 @@ impl ApuState {
             }
         }
 
+        // On NTSC these are 7457, 14913, 22371, 29829 and 37281; PAL runs slower
+        let [quarter_1, half_1, quarter_2, four_step_end, five_step_end] = self.region.frame_sequencer_steps();
+
         if self.frame_sequencer_mode == 0 {
             // 4-step sequence
             match self.frame_sequencer {
-                7457 => self.clock_quarter_frame(),
-                14913 => {
+                step if step == quarter_1 => self.clock_quarter_frame(),
+                step if step == half_1 => {
                     self.clock_quarter_frame();
                     self.clock_half_frame();
                 },
-                22371 => self.clock_quarter_frame(),
-                29828 => {
+                step if step == quarter_2 => self.clock_quarter_frame(),
+                step if step == four_step_end - 1 => {
                     if !self.disable_interrupt {
                         self.frame_interrupt = true;
                     }
                 },
-                29829 => {
+                step if step == four_step_end => {
                     if !self.disable_interrupt {
                         self.frame_interrupt = true;
                     }
                     self.clock_quarter_frame();
                     self.clock_half_frame();
                 },
-                29830 => {
+                step if step == four_step_end + 1 => {
                     if !self.disable_interrupt {
                         self.frame_interrupt = true;
                     }
@@ -553,17 +560,17 @@ impl ApuState {
         } else {
             match self.frame_sequencer {
                 // "5-step" sequence (uneven timing)
-                7457 => self.clock_quarter_frame(),
-                14913 => {
+                step if step == quarter_1 => self.clock_quarter_frame(),
+                step if step == half_1 => {
                     self.clock_quarter_frame();
                     self.clock_half_frame();
                 },
-                22371 => self.clock_quarter_frame(),
-                37281 => {
+                step if step == quarter_2 => self.clock_quarter_frame(),
+                step if step == five_step_end => {
                     self.clock_quarter_frame();
                     self.clock_half_frame();
                 },
-                37282 => {
+                step if step == five_step_end + 1 => {
                   self.frame_sequencer = 0;  
                 },
                 _ => ()
@@ -724,6 +731,51 @@ impl ApuState {
             channels[channel_index].unmute();
         }
     }
+
+    pub fn save_state(&self, buff: &mut Vec<u8>) {
+        save_u64(buff, self.current_cycle);
+
+        save_u8(buff, self.frame_sequencer_mode);
+        save_u16(buff, self.frame_sequencer);
+        save_u8(buff, self.frame_reset_delay);
+        save_u32(buff, self.quarter_frame_counter);
+        save_u32(buff, self.half_frame_counter);
+
+        save_bool(buff, self.frame_interrupt);
+        save_bool(buff, self.disable_interrupt);
+
+        self.pulse_1.save_state(buff);
+        self.pulse_2.save_state(buff);
+        self.triangle.save_state(buff);
+        self.noise.save_state(buff);
+        self.dmc.save_state(buff);
+
+        save_u64(buff, self.generated_samples);
+        save_u64(buff, self.next_sample_at);
+    }
+
+    pub fn load_state(&mut self, buff: &mut &[u8]) -> Result<(), String> {
+        self.current_cycle = load_u64(buff)?;
+
+        self.frame_sequencer_mode = load_u8(buff)?;
+        self.frame_sequencer = load_u16(buff)?;
+        self.frame_reset_delay = load_u8(buff)?;
+        self.quarter_frame_counter = load_u32(buff)?;
+        self.half_frame_counter = load_u32(buff)?;
+
+        self.frame_interrupt = load_bool(buff)?;
+        self.disable_interrupt = load_bool(buff)?;
+
+        self.pulse_1.load_state(buff)?;
+        self.pulse_2.load_state(buff)?;
+        self.triangle.load_state(buff)?;
+        self.noise.load_state(buff)?;
+        self.dmc.load_state(buff)?;
+
+        self.generated_samples = load_u64(buff)?;
+        self.next_sample_at = load_u64(buff)?;
+        return Ok(());
+    }
 }
 
 // The APU itself counts as a channel, loosely, mostly for debugging purposes. Its output is a
@@ -7,6 +7,8 @@ use super::audio_channel::Timbre;
 use super::ring_buffer::RingBuffer;
 use super::filters;
 use super::filters::DspFilter;
+use region::{NTSC_NOISE_PERIODS, PAL_NOISE_PERIODS};
+use save_load::*;
 
 pub struct NoiseChannelState {
     pub name: String,
@@ -81,6 +83,33 @@ impl NoiseChannelState {
             return 0;
         }
     }
+
+    pub fn save_state(&self, buff: &mut Vec<u8>) {
+        save_u8(buff, self.length);
+        save_bool(buff, self.length_halt_flag);
+
+        self.envelope.save_state(buff);
+        self.length_counter.save_state(buff);
+
+        save_u8(buff, self.mode);
+        save_u16(buff, self.period_initial);
+        save_u16(buff, self.period_current);
+        save_u16(buff, self.shift_register);
+    }
+
+    pub fn load_state(&mut self, buff: &mut &[u8]) -> Result<(), String> {
+        self.length = load_u8(buff)?;
+        self.length_halt_flag = load_bool(buff)?;
+
+        self.envelope.load_state(buff)?;
+        self.length_counter.load_state(buff)?;
+
+        self.mode = load_u8(buff)?;
+        self.period_initial = load_u16(buff)?;
+        self.period_current = load_u16(buff)?;
+        self.shift_register = load_u16(buff)?;
+        return Ok(());
+    }
 }
 
 impl AudioChannelState for NoiseChannelState {
@@ -134,25 +163,12 @@ impl AudioChannelState for NoiseChannelState {
     }
 
     fn rate(&self) -> PlaybackRate {
-        let lsfr_index = match self.period_initial {
-            4    => {0xF},
-            8    => {0xE},
-            16   => {0xD},
-            32   => {0xC},
-            64   => {0xB},
-            96   => {0xA},
-            128  => {0x9},
-            160  => {0x8},
-            202  => {0x7},
-            254  => {0x6},
-            380  => {0x5},
-            508  => {0x4},
-            762  => {0x3},
-            1016 => {0x2},
-            2034 => {0x1},
-            4068 => {0x0},
-            _ => {0x0} // also unreachable
-        };
+        // Shorter periods are higher pitched, so count the index down from the top.
+        // The period could have come from either region's table.
+        let period_index = NTSC_NOISE_PERIODS.iter().position(|&period| period == self.period_initial)
+            .or(PAL_NOISE_PERIODS.iter().position(|&period| period == self.period_initial))
+            .unwrap_or(0xF); // unreachable
+        let lsfr_index = 0xF - period_index;
         return PlaybackRate::LfsrRate {index: lsfr_index, max: 0xF};
     }
 
@@ -7,6 +7,7 @@ use super::audio_channel::Timbre;
 use super::ring_buffer::RingBuffer;
 use super::filters;
 use super::filters::DspFilter;
+use save_load::*;
 
 pub struct PulseChannelState {
     pub name: String,
@@ -136,6 +137,41 @@ impl PulseChannelState {
             self.sweep_divider -= 1;
         }
     }
+
+    pub fn save_state(&self, buff: &mut Vec<u8>) {
+        self.envelope.save_state(buff);
+        self.length_counter.save_state(buff);
+
+        save_bool(buff, self.sweep_enabled);
+        save_u8(buff, self.sweep_period);
+        save_u8(buff, self.sweep_divider);
+        save_bool(buff, self.sweep_negate);
+        save_u8(buff, self.sweep_shift);
+        save_bool(buff, self.sweep_reload);
+
+        save_u8(buff, self.duty);
+        save_u8(buff, self.sequence_counter);
+        save_u16(buff, self.period_initial);
+        save_u16(buff, self.period_current);
+    }
+
+    pub fn load_state(&mut self, buff: &mut &[u8]) -> Result<(), String> {
+        self.envelope.load_state(buff)?;
+        self.length_counter.load_state(buff)?;
+
+        self.sweep_enabled = load_bool(buff)?;
+        self.sweep_period = load_u8(buff)?;
+        self.sweep_divider = load_u8(buff)?;
+        self.sweep_negate = load_bool(buff)?;
+        self.sweep_shift = load_u8(buff)?;
+        self.sweep_reload = load_bool(buff)?;
+
+        self.duty = load_u8(buff)?;
+        self.sequence_counter = load_u8(buff)?;
+        self.period_initial = load_u16(buff)?;
+        self.period_current = load_u16(buff)?;
+        return Ok(());
+    }
 }
 
 impl AudioChannelState for PulseChannelState {
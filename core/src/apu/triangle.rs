@@ -6,6 +6,7 @@ use super::audio_channel::Timbre;
 use super::ring_buffer::RingBuffer;
 use super::filters;
 use super::filters::DspFilter;
+use save_load::*;
 
 pub struct TriangleChannelState {
     pub name: String,
@@ -104,6 +105,35 @@ impl TriangleChannelState {
             return triangle_sequence[self.sequence_counter as usize];
         }
     }
+
+    pub fn save_state(&self, buff: &mut Vec<u8>) {
+        self.length_counter.save_state(buff);
+
+        save_bool(buff, self.control_flag);
+        save_bool(buff, self.linear_reload_flag);
+        save_u8(buff, self.linear_counter_initial);
+        save_u8(buff, self.linear_counter_current);
+
+        save_u8(buff, self.sequence_counter);
+        save_u16(buff, self.period_initial);
+        save_u16(buff, self.period_current);
+        save_u8(buff, self.length);
+    }
+
+    pub fn load_state(&mut self, buff: &mut &[u8]) -> Result<(), String> {
+        self.length_counter.load_state(buff)?;
+
+        self.control_flag = load_bool(buff)?;
+        self.linear_reload_flag = load_bool(buff)?;
+        self.linear_counter_initial = load_u8(buff)?;
+        self.linear_counter_current = load_u8(buff)?;
+
+        self.sequence_counter = load_u8(buff)?;
+        self.period_initial = load_u16(buff)?;
+        self.period_current = load_u16(buff)?;
+        self.length = load_u8(buff)?;
+        return Ok(());
+    }
 }
 
 impl AudioChannelState for TriangleChannelState {
@@ -11,22 +11,98 @@ use mmc::mmc1::Mmc1;
 use mmc::mmc3::Mmc3;
 use mmc::mmc5::Mmc5;
 use mmc::n163::Namco163;
+use mmc::namco108::Namco108;
 use mmc::nrom::Nrom;
 use mmc::nsf::NsfMapper;
 use mmc::pxrom::PxRom;
 use mmc::rainbow::Rainbow;
+use mmc::sunsoft1::Sunsoft1;
+use mmc::sunsoft2::Sunsoft2;
+use mmc::sunsoft3::Sunsoft3;
+use mmc::sunsoft4::Sunsoft4;
 use mmc::uxrom::UxRom;
+use mmc::vrc4::Vrc4;
 use mmc::vrc6::Vrc6;
 use mmc::vrc7::Vrc7;
+use mmc::vs_system::VsSystem;
 
 use ines::INesCartridge;
+use ines::INesHeader;
+use ines::{ConsoleType, CpuPpuTiming, ExpansionDevice, VsHardwareType, VsPpuType};
+use ppu::PpuModel;
 use nsf::NsfFile;
 use fds::FdsFile;
 
 use std::io::Read;
 
-fn mapper_from_ines(ines: INesCartridge) -> Result<Box<dyn Mapper>, String> {
+// Details about the console a cartridge expects to be plugged into. These aren't the
+// mapper's concern, but the rest of the system can use them to pick its region,
+// palette and controllers.
+#[derive(Copy, Clone, Debug)]
+pub struct CartridgeInfo {
+    pub timing: CpuPpuTiming,
+    pub console_type: ConsoleType,
+    pub vs_ppu_type: Option<VsPpuType>,
+    pub vs_hardware_type: Option<VsHardwareType>,
+    pub default_expansion_device: ExpansionDevice,
+    pub misc_rom_count: u8,
+    // The sizes of the ROM chips, where the format says. CHR RAM doesn't count.
+    pub prg_rom_size: usize,
+    pub chr_rom_size: usize,
+}
+
+impl CartridgeInfo {
+    // For formats without any of this information (NSF, FDS)
+    pub fn new() -> CartridgeInfo {
+        return CartridgeInfo {
+            timing: CpuPpuTiming::Ntsc,
+            console_type: ConsoleType::Nes,
+            vs_ppu_type: None,
+            vs_hardware_type: None,
+            default_expansion_device: ExpansionDevice::Unspecified,
+            misc_rom_count: 0,
+            prg_rom_size: 0,
+            chr_rom_size: 0,
+        };
+    }
+
+    pub fn from_ines_header(header: &INesHeader) -> CartridgeInfo {
+        return CartridgeInfo {
+            timing: header.timing(),
+            console_type: header.console_type(),
+            vs_ppu_type: header.vs_ppu_type(),
+            vs_hardware_type: header.vs_hardware_type(),
+            default_expansion_device: header.default_expansion_device(),
+            misc_rom_count: header.misc_rom_count(),
+            prg_rom_size: header.prg_size(),
+            chr_rom_size: header.chr_rom_size(),
+        };
+    }
+
+    pub fn ppu_model(&self) -> PpuModel {
+        if self.console_type != ConsoleType::VsSystem {
+            return PpuModel::Rp2c02;
+        }
+        return match self.vs_ppu_type {
+            Some(VsPpuType::Rp2c04_0001) => PpuModel::Rp2c04_0001,
+            Some(VsPpuType::Rp2c04_0002) => PpuModel::Rp2c04_0002,
+            Some(VsPpuType::Rp2c04_0003) => PpuModel::Rp2c04_0003,
+            Some(VsPpuType::Rp2c04_0004) => PpuModel::Rp2c04_0004,
+            Some(VsPpuType::Rc2c05_01) => PpuModel::Rc2c05(0x1B),
+            Some(VsPpuType::Rc2c05_02) => PpuModel::Rc2c05(0x3D),
+            Some(VsPpuType::Rc2c05_03) => PpuModel::Rc2c05(0x1C),
+            Some(VsPpuType::Rc2c05_04) => PpuModel::Rc2c05(0x1B),
+            Some(VsPpuType::Rc2c05_05) => PpuModel::Rc2c05(0x00),
+            // Older iNES headers can mark a Vs. game but not its PPU. The 2C03 at least
+            // gets the colors right for games that were built for it.
+            _ => PpuModel::Rp2c03,
+        };
+    }
+}
+
+fn mapper_from_ines(ines: INesCartridge) -> Result<(Box<dyn Mapper>, CartridgeInfo), String> {
     let mapper_number = ines.header.mapper_number();
+    let info = CartridgeInfo::from_ines_header(&ines.header);
 
     let mapper: Box<dyn Mapper> = match mapper_number {
         0 => Box::new(Nrom::from_ines(ines)?),
@@ -37,15 +113,33 @@ fn mapper_from_ines(ines: INesCartridge) -> Result<Box<dyn Mapper>, String> {
         5 => Box::new(Mmc5::from_ines(ines)?),
         7 => Box::new(AxRom::from_ines(ines)?),
         9 => Box::new(PxRom::from_ines(ines)?),
+        10 => Box::new(PxRom::from_ines(ines)?),
         19 => Box::new(Namco163::from_ines(ines)?),
+        21 => Box::new(Vrc4::from_ines(ines)?),
+        22 => Box::new(Vrc4::from_ines(ines)?),
+        23 => Box::new(Vrc4::from_ines(ines)?),
+        25 => Box::new(Vrc4::from_ines(ines)?),
         24 => Box::new(Vrc6::from_ines(ines)?),
         26 => Box::new(Vrc6::from_ines(ines)?),
         28 => Box::new(Action53::from_ines(ines)?),
         31 => Box::new(INes31::from_ines(ines)?),
         34 => Box::new(BnRom::from_ines(ines)?),
         66 => Box::new(GxRom::from_ines(ines)?),
+        67 => Box::new(Sunsoft3::from_ines(ines)?),
+        68 => Box::new(Sunsoft4::from_ines(ines)?),
         69 => Box::new(Fme7::from_ines(ines)?),
+        76 => Box::new(Namco108::from_ines(ines)?),
         85 => Box::new(Vrc7::from_ines(ines)?),
+        88 => Box::new(Namco108::from_ines(ines)?),
+        89 => Box::new(Sunsoft2::from_ines(ines)?),
+        93 => Box::new(Sunsoft2::from_ines(ines)?),
+        95 => Box::new(Namco108::from_ines(ines)?),
+        99 => Box::new(VsSystem::from_ines(ines)?),
+        118 => Box::new(Mmc3::from_ines(ines)?),
+        119 => Box::new(Mmc3::from_ines(ines)?),
+        154 => Box::new(Namco108::from_ines(ines)?),
+        184 => Box::new(Sunsoft1::from_ines(ines)?),
+        206 => Box::new(Namco108::from_ines(ines)?),
         682 => Box::new(Rainbow::from_ines(ines)?),
         _ => {
             return Err(format!("Unsupported iNES mapper: {}", ines.header.mapper_number()));
@@ -54,10 +148,10 @@ fn mapper_from_ines(ines: INesCartridge) -> Result<Box<dyn Mapper>, String> {
 
     println!("Successfully loaded mapper: {}", mapper_number);
 
-    return Ok(mapper);
+    return Ok((mapper, info));
 }
 
-pub fn mapper_from_reader(file_reader: &mut dyn Read) -> Result<Box<dyn Mapper>, String> {
+pub fn cartridge_from_reader(file_reader: &mut dyn Read) -> Result<(Box<dyn Mapper>, CartridgeInfo), String> {
     let mut entire_file = Vec::new();
     match file_reader.read_to_end(&mut entire_file) {
         Ok(_) => {/* proceed normally */},
@@ -73,18 +167,28 @@ pub fn mapper_from_reader(file_reader: &mut dyn Read) -> Result<Box<dyn Mapper>,
     }
 
     match NsfFile::from_reader(&mut entire_file.as_slice()) {
-        Ok(nsf) => {return Ok(Box::new(NsfMapper::from_nsf(nsf)?));},
+        Ok(nsf) => {return Ok((Box::new(NsfMapper::from_nsf(nsf)?), CartridgeInfo::new()));},
         Err(e) => {errors += format!("nsf: {}\n", e).as_str()}
     }
 
     match FdsFile::from_reader(&mut entire_file.as_slice()) {
-        Ok(nsf) => {return Ok(Box::new(FdsMapper::from_fds(nsf)?));},
+        Ok(nsf) => {return Ok((Box::new(FdsMapper::from_fds(nsf)?), CartridgeInfo::new()));},
         Err(e) => {errors += format!("fds: {}\n", e).as_str()}
     }
 
     return Err(format!("Unable to open file as any known type, giving up.\n{}", errors));
 }
 
+pub fn cartridge_from_file(file_data: &[u8]) -> Result<(Box<dyn Mapper>, CartridgeInfo), String> {
+    let mut file_reader = file_data;
+    return cartridge_from_reader(&mut file_reader);
+}
+
+pub fn mapper_from_reader(file_reader: &mut dyn Read) -> Result<Box<dyn Mapper>, String> {
+    let (mapper, _) = cartridge_from_reader(file_reader)?;
+    return Ok(mapper);
+}
+
 pub fn mapper_from_file(file_data: &[u8]) -> Result<Box<dyn Mapper>, String> {
     let mut file_reader = file_data;
     return mapper_from_reader(&mut file_reader);
@@ -0,0 +1,162 @@
+// Code/Data Logging: a record of how each byte of PRG and CHR ROM has been used so far,
+// which makes it easy to spot dead code, and to split a ROM into code and data when
+// disassembling it. Bytes are tracked by their position in the ROM rather than by CPU
+// address, so that everything in a bank is accounted for no matter where it was mapped.
+//
+// The flags and file layout match FCEUX's .cdl files: one byte of flags for each byte of
+// PRG ROM, followed by one for each byte of CHR ROM, so logs can be shared with its
+// tools. Mappers which can't say which ROM byte is where (see Mapper::prg_rom_address)
+// simply log nothing.
+
+use disassembler;
+use disassembler::AddressingMode;
+use mmc::mapper::Mapper;
+
+// PRG ROM flags
+pub const PRG_CODE: u8          = 0b0000_0001;
+pub const PRG_DATA: u8          = 0b0000_0010;
+// Bits 2-3 hold which 8k slot of $8000-$FFFF the byte was last accessed through
+pub const PRG_SLOT_MASK: u8     = 0b0000_1100;
+// Code reached by JMP (indirect), and data read through a pointer
+pub const PRG_INDIRECT_CODE: u8 = 0b0001_0000;
+pub const PRG_INDIRECT_DATA: u8 = 0b0010_0000;
+// Sample data played by the DMC
+pub const PRG_PCM_DATA: u8      = 0b0100_0000;
+
+// CHR ROM flags
+pub const CHR_RENDERED: u8 = 0b0000_0001;
+// Read by the CPU through PPUDATA
+pub const CHR_READ: u8     = 0b0000_0010;
+
+pub struct CodeDataLogger {
+    pub prg: Vec<u8>,
+    pub chr: Vec<u8>,
+    // The instruction now running, whose own operand fetches (and dummy reads of the
+    // byte after) aren't data
+    instruction_start: u16,
+    instruction_length: u16,
+    reads_indirect: bool,
+    // Branches read nothing but themselves, apart from dummy reads we'd rather not count
+    ignore_reads: bool,
+    after_indirect_jump: bool,
+}
+
+impl CodeDataLogger {
+    pub fn new(prg_rom_size: usize, chr_rom_size: usize) -> CodeDataLogger {
+        return CodeDataLogger {
+            prg: vec![0u8; prg_rom_size],
+            chr: vec![0u8; chr_rom_size],
+            instruction_start: 0,
+            instruction_length: 0,
+            reads_indirect: false,
+            ignore_reads: false,
+            after_indirect_jump: false,
+        };
+    }
+
+    pub fn clear(&mut self) {
+        for flags in self.prg.iter_mut() {*flags = 0};
+        for flags in self.chr.iter_mut() {*flags = 0};
+    }
+
+    /// Merges in an FCEUX .cdl file for this same ROM
+    pub fn load(&mut self, data: &[u8]) -> Result<(), String> {
+        if data.len() != self.prg.len() + self.chr.len() {
+            return Err(format!("Code/data log is {} bytes, but this ROM needs {}", data.len(), self.prg.len() + self.chr.len()));
+        }
+        let (prg_data, chr_data) = data.split_at(self.prg.len());
+        for (flags, loaded) in self.prg.iter_mut().zip(prg_data) {*flags |= loaded};
+        for (flags, loaded) in self.chr.iter_mut().zip(chr_data) {*flags |= loaded};
+        return Ok(());
+    }
+
+    /// The log as an FCEUX .cdl file
+    pub fn save(&self) -> Vec<u8> {
+        let mut data = self.prg.clone();
+        data.extend_from_slice(&self.chr);
+        return data;
+    }
+
+    fn mark_prg(&mut self, mapper: &dyn Mapper, address: u16, flags: u8) {
+        match mapper.prg_rom_address(address) {
+            Some(offset) if offset < self.prg.len() => {
+                let slot = if address >= 0x8000 {((address >> 13) & 0x3) as u8} else {0};
+                self.prg[offset] = (self.prg[offset] & !PRG_SLOT_MASK) | flags | (slot << 2);
+            },
+            _ => {}
+        }
+    }
+
+    fn mark_chr(&mut self, mapper: &dyn Mapper, address: u16, flags: u8) {
+        match mapper.chr_rom_address(address) {
+            Some(offset) if offset < self.chr.len() => {self.chr[offset] |= flags},
+            _ => {}
+        }
+    }
+
+    /// Called as the CPU is about to fetch an opcode. The whole instruction is marked as
+    /// code at once, operands included.
+    pub fn log_instruction(&mut self, mapper: &dyn Mapper, address: u16) {
+        let opcode = match mapper.debug_read_cpu(address) {
+            Some(opcode) => opcode,
+            None => {return}
+        };
+        let mode = disassembler::addressing_mode(opcode);
+        self.instruction_start = address;
+        self.instruction_length = 1 + mode.operand_bytes();
+        self.reads_indirect = mode == AddressingMode::IndirectX || mode == AddressingMode::IndirectY;
+        self.ignore_reads = mode == AddressingMode::Relative;
+
+        let indirect = if self.after_indirect_jump {PRG_INDIRECT_CODE} else {0};
+        for i in 0 .. self.instruction_length {
+            self.mark_prg(mapper, address.wrapping_add(i), PRG_CODE | indirect);
+        }
+        self.after_indirect_jump = opcode == 0x6C;
+    }
+
+    /// Called for every CPU read, after the fact
+    pub fn log_read(&mut self, mapper: &dyn Mapper, address: u16) {
+        if self.ignore_reads || address.wrapping_sub(self.instruction_start) <= self.instruction_length {
+            return;
+        }
+        let indirect = if self.reads_indirect {PRG_INDIRECT_DATA} else {0};
+        self.mark_prg(mapper, address, PRG_DATA | indirect);
+    }
+
+    pub fn log_dmc_read(&mut self, mapper: &dyn Mapper, address: u16) {
+        self.mark_prg(mapper, address, PRG_DATA | PRG_PCM_DATA);
+    }
+
+    /// Pattern fetches made by the PPU while rendering
+    pub fn log_chr_render(&mut self, mapper: &dyn Mapper, address: u16) {
+        self.mark_chr(mapper, address, CHR_RENDERED);
+    }
+
+    pub fn log_chr_read(&mut self, mapper: &dyn Mapper, address: u16) {
+        self.mark_chr(mapper, address, CHR_READ);
+    }
+
+    /// The flags for the ROM byte currently mapped to this CPU address, if any
+    pub fn prg_flags_at(&self, mapper: &dyn Mapper, address: u16) -> Option<u8> {
+        return mapper.prg_rom_address(address).and_then(|offset| self.prg.get(offset).cloned());
+    }
+
+    /// The flags for the CHR ROM byte currently mapped to this PPU address, if any
+    pub fn chr_flags_at(&self, mapper: &dyn Mapper, address: u16) -> Option<u8> {
+        return mapper.chr_rom_address(address).and_then(|offset| self.chr.get(offset).cloned());
+    }
+
+    /// How many bytes of PRG ROM have been seen as code, and as data
+    pub fn prg_coverage(&self) -> (usize, usize) {
+        let code = self.prg.iter().filter(|&&flags| flags & PRG_CODE != 0).count();
+        let data = self.prg.iter().filter(|&&flags| flags & PRG_DATA != 0).count();
+        return (code, data);
+    }
+
+    /// How many bytes of CHR ROM have been drawn, and read by the CPU
+    pub fn chr_coverage(&self) -> (usize, usize) {
+        let rendered = self.chr.iter().filter(|&&flags| flags & CHR_RENDERED != 0).count();
+        let read = self.chr.iter().filter(|&&flags| flags & CHR_READ != 0).count();
+        return (rendered, read);
+    }
+}
@@ -0,0 +1,271 @@
+// Cheat codes. Two kinds are supported, told apart by the address they touch:
+//
+// - ROM patches, for addresses in $8000-$FFFF. Like a Game Genie, these change what the
+//   CPU reads from the cartridge, optionally only while the cartridge returns an expected
+//   "compare" byte, since the same address may hold different banks at different times.
+// - RAM freezes, for internal RAM at $0000-$1FFF and cartridge RAM at $6000-$7FFF. Like
+//   a Pro Action Replay, these write their value back into memory once per frame, so
+//   whatever the game does in between is undone. Anywhere else would be writing to a
+//   register every frame, so those addresses are refused.
+//
+// Codes may be entered as 6 or 8 letter Game Genie codes, as raw "AAAA:VV" or
+// "AAAA?CC:VV" patches (the same format FCEUX uses), or as 6 hex digit "AAAAVV" Pro
+// Action Replay codes.
+// Reference: https://www.nesdev.org/wiki/Game_Genie
+
+use nes::NesState;
+
+const GAME_GENIE_LETTERS: &str = "APZLGITYEOXUKSVN";
+
+#[derive(Clone, Debug, PartialEq)]
+pub struct Cheat {
+    // As entered, so that it can be shown back and saved
+    pub code: String,
+    pub address: u16,
+    pub value: u8,
+    pub compare: Option<u8>,
+    pub enabled: bool,
+}
+
+impl Cheat {
+    pub fn parse(code: &str) -> Result<Cheat, String> {
+        let trimmed = code.trim().to_uppercase();
+        let (address, value, compare) = if trimmed.contains(':') {
+            parse_raw(&trimmed)?
+        } else if trimmed.len() == 6 && trimmed.chars().all(|c| c.is_digit(16)) && !is_game_genie(&trimmed) {
+            parse_action_replay(&trimmed)?
+        } else {
+            parse_game_genie(&trimmed)?
+        };
+        if address < 0x8000 && compare.is_some() {
+            return Err(format!("{}: only ROM patches can have a compare value", code));
+        }
+        if address >= 0x2000 && address < 0x6000 {
+            return Err(format!("{}: can't freeze a register (${:04X})", code, address));
+        }
+        return Ok(Cheat {
+            code: trimmed,
+            address: address,
+            value: value,
+            compare: compare,
+            enabled: true,
+        });
+    }
+
+    pub fn patches_rom(&self) -> bool {
+        return self.address >= 0x8000;
+    }
+}
+
+fn parse_hex(text: &str, what: &str) -> Result<u16, String> {
+    return u16::from_str_radix(text, 16).map_err(|_| format!("Invalid {}: {}", what, text));
+}
+
+// AAAA:VV or AAAA?CC:VV
+fn parse_raw(code: &str) -> Result<(u16, u8, Option<u8>), String> {
+    let colon = code.find(':').unwrap();
+    let value = parse_hex(&code[colon + 1 ..], "value")?;
+    let (address, compare) = match code[.. colon].find('?') {
+        Some(question) => (parse_hex(&code[.. question], "address")?, Some(parse_hex(&code[question + 1 .. colon], "compare value")?)),
+        None => (parse_hex(&code[.. colon], "address")?, None)
+    };
+    if value > 0xFF || compare.unwrap_or(0) > 0xFF {
+        return Err(format!("{}: values must fit in a byte", code));
+    }
+    return Ok((address, value as u8, compare.map(|compare| compare as u8)));
+}
+
+// A and E are both hex digits and Game Genie letters; codes made only of those are
+// taken to be Game Genie codes
+fn is_game_genie(code: &str) -> bool {
+    return code.chars().all(|c| GAME_GENIE_LETTERS.contains(c));
+}
+
+// AAAAVV
+fn parse_action_replay(code: &str) -> Result<(u16, u8, Option<u8>), String> {
+    let address = parse_hex(&code[0 .. 4], "address")?;
+    let value = parse_hex(&code[4 .. 6], "value")?;
+    return Ok((address, value as u8, None));
+}
+
+// Each letter stands for four bits, and those are shuffled into the address, value and
+// compare bytes. Codes are always for $8000-$FFFF.
+fn parse_game_genie(code: &str) -> Result<(u16, u8, Option<u8>), String> {
+    if code.len() != 6 && code.len() != 8 {
+        return Err(format!("{}: Game Genie codes are 6 or 8 letters long", code));
+    }
+    let mut n = [0u16; 8];
+    for (i, letter) in code.chars().enumerate() {
+        n[i] = match GAME_GENIE_LETTERS.find(letter) {
+            Some(index) => index as u16,
+            None => {return Err(format!("{}: {} isn't a Game Genie letter", code, letter))}
+        };
+    }
+    let address = 0x8000 +
+        (((n[3] & 7) << 12) |
+         ((n[5] & 7) << 8) | ((n[4] & 8) << 8) |
+         ((n[2] & 7) << 4) | ((n[1] & 8) << 4) |
+          (n[4] & 7)       |  (n[3] & 8));
+    if code.len() == 6 {
+        let value = ((n[1] & 7) << 4) | ((n[0] & 8) << 4) | (n[0] & 7) | (n[5] & 8);
+        return Ok((address, value as u8, None));
+    }
+    let value = ((n[1] & 7) << 4) | ((n[0] & 8) << 4) | (n[0] & 7) | (n[7] & 8);
+    let compare = ((n[7] & 7) << 4) | ((n[6] & 8) << 4) | (n[6] & 7) | (n[5] & 8);
+    return Ok((address, value as u8, Some(compare as u8)));
+}
+
+pub struct CheatList {
+    pub cheats: Vec<Cheat>,
+    // ROM reads are far too common to search the list every time, so this notes whether
+    // there's anything there worth searching for
+    rom_patches_active: bool,
+}
+
+impl CheatList {
+    pub fn new() -> CheatList {
+        return CheatList {
+            cheats: Vec::new(),
+            rom_patches_active: false,
+        };
+    }
+
+    fn update(&mut self) {
+        self.rom_patches_active = self.cheats.iter().any(|cheat| cheat.enabled && cheat.patches_rom());
+    }
+
+    /// Adds a cheat, replacing any other with the same code
+    pub fn add(&mut self, cheat: Cheat) {
+        self.cheats.retain(|existing| existing.code != cheat.code);
+        self.cheats.push(cheat);
+        self.update();
+    }
+
+    /// Removes the cheat with this code, as entered or in any of its other forms.
+    /// Returns whether there was one.
+    pub fn remove(&mut self, code: &str) -> bool {
+        let old_len = self.cheats.len();
+        match Cheat::parse(code) {
+            Ok(parsed) => self.cheats.retain(|cheat| cheat.code != parsed.code &&
+                !(cheat.address == parsed.address && cheat.value == parsed.value && cheat.compare == parsed.compare)),
+            Err(_) => self.cheats.retain(|cheat| cheat.code != code)
+        }
+        self.update();
+        return self.cheats.len() != old_len;
+    }
+
+    pub fn set_enabled(&mut self, index: usize, enabled: bool) {
+        if index < self.cheats.len() {
+            self.cheats[index].enabled = enabled;
+            self.update();
+        }
+    }
+
+    pub fn clear(&mut self) {
+        self.cheats.clear();
+        self.update();
+    }
+
+    /// What the CPU should see in place of this byte from the cartridge
+    pub fn patch_rom_read(&self, address: u16, data: u8) -> u8 {
+        if !self.rom_patches_active {
+            return data;
+        }
+        for cheat in self.cheats.iter() {
+            if cheat.enabled && cheat.address == address && cheat.compare.map_or(true, |compare| compare == data) {
+                return cheat.value;
+            }
+        }
+        return data;
+    }
+}
+
+/// Writes every RAM freeze back into memory. Called once per frame.
+pub fn apply_ram_freezes(nes: &mut NesState) {
+    for i in 0 .. nes.cheats.cheats.len() {
+        let cheat = &nes.cheats.cheats[i];
+        if !cheat.enabled || cheat.patches_rom() {
+            continue;
+        }
+        let (address, value) = (cheat.address, cheat.value);
+        match address {
+            0x0000 ..= 0x1FFF => {nes.memory.iram_raw[(address & 0x7FF) as usize] = value},
+            // Cartridge RAM goes through the mapper, which knows where it is (if anywhere)
+            0x6000 ..= 0x7FFF => {nes.mapper.write_cpu(address, value)},
+            _ => {}
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use cheats::*;
+
+    fn decode(code: &str) -> (u16, u8, Option<u8>) {
+        let cheat = Cheat::parse(code).unwrap();
+        return (cheat.address, cheat.value, cheat.compare);
+    }
+
+    #[test]
+    fn decodes_six_letter_game_genie_codes() {
+        // The examples from the nesdev wiki
+        assert_eq!(decode("GOSSIP"), (0xD1DD, 0x14, None));
+        assert_eq!(decode("gossip"), (0xD1DD, 0x14, None));
+    }
+
+    #[test]
+    fn decodes_eight_letter_game_genie_codes() {
+        assert_eq!(decode("ZEXPYGLA"), (0x94A7, 0x02, Some(0x03)));
+    }
+
+    #[test]
+    fn codes_of_only_a_and_e_are_game_genie_codes() {
+        assert_eq!(decode("AEAEAE"), (0x8088, 0x08, None));
+        assert_eq!(decode("AAAAAA"), (0x8000, 0x00, None));
+    }
+
+    #[test]
+    fn decodes_raw_patches_and_action_replay_codes() {
+        assert_eq!(decode("C123?45:67"), (0xC123, 0x67, Some(0x45)));
+        assert_eq!(decode("c123:ff"), (0xC123, 0xFF, None));
+        assert_eq!(decode("0300:09"), (0x0300, 0x09, None));
+        assert_eq!(decode("00FF63"), (0x00FF, 0x63, None));
+        assert!(!Cheat::parse("00FF63").unwrap().patches_rom());
+        assert!(Cheat::parse("C123:100").is_err());
+        assert!(Cheat::parse("0300?12:34").is_err());
+        assert!(Cheat::parse("GOSSIPS").is_err());
+        assert!(Cheat::parse("GOSSIB").is_err());
+    }
+
+    #[test]
+    fn rejects_register_addresses() {
+        assert!(Cheat::parse("2000:80").is_err());
+        assert!(Cheat::parse("4015:00").is_err());
+        assert!(Cheat::parse("401F:00").is_err());
+        assert!(Cheat::parse("200080").is_err());
+        // Mapper registers live here on plenty of boards
+        assert!(Cheat::parse("4020:00").is_err());
+        assert!(Cheat::parse("5FFF:00").is_err());
+        assert!(Cheat::parse("1FFF:00").is_ok());
+        assert!(Cheat::parse("6000:00").is_ok());
+        assert!(Cheat::parse("7FFF:00").is_ok());
+    }
+
+    #[test]
+    fn removes_cheats_by_any_form_of_their_code() {
+        let mut list = CheatList::new();
+        list.add(Cheat::parse("GOSSIP").unwrap());
+        list.add(Cheat::parse("ZEXPYGLA").unwrap());
+        list.add(Cheat::parse("0300:09").unwrap());
+        assert_eq!(list.patch_rom_read(0xD1DD, 0x00), 0x14);
+        assert_eq!(list.patch_rom_read(0x94A7, 0x03), 0x02);
+        assert_eq!(list.patch_rom_read(0x94A7, 0x04), 0x04);
+
+        assert!(list.remove("D1DD:14"));
+        assert_eq!(list.patch_rom_read(0xD1DD, 0x00), 0x00);
+        assert!(list.remove("94a7?03:02"));
+        assert!(list.remove("030009"));
+        assert!(list.cheats.is_empty());
+        assert!(!list.remove("GOSSIP"));
+    }
+}
@@ -8,6 +8,7 @@ use memory::read_byte;
 use memory::write_byte;
 use nes::NesState;
 use opcodes;
+use save_load::*;
 use unofficial_opcodes;
 
 #[derive(Copy, Clone)]
@@ -73,6 +74,37 @@ impl Registers {
         self.flags.overflow = data & (1 << 6) != 0;
         self.flags.negative = data & (1 << 7) != 0;
     }
+
+    pub fn save_state(&self, buff: &mut Vec<u8>) {
+        save_u8(buff, self.a);
+        save_u8(buff, self.x);
+        save_u8(buff, self.y);
+        save_u16(buff, self.pc);
+        save_u8(buff, self.s);
+        save_bool(buff, self.flags.carry);
+        save_bool(buff, self.flags.zero);
+        save_bool(buff, self.flags.decimal);
+        save_bool(buff, self.flags.interrupts_disabled);
+        save_bool(buff, self.flags.overflow);
+        save_bool(buff, self.flags.negative);
+        save_bool(buff, self.flags.last_nmi);
+    }
+
+    pub fn load_state(&mut self, buff: &mut &[u8]) -> Result<(), String> {
+        self.a = load_u8(buff)?;
+        self.x = load_u8(buff)?;
+        self.y = load_u8(buff)?;
+        self.pc = load_u16(buff)?;
+        self.s = load_u8(buff)?;
+        self.flags.carry = load_bool(buff)?;
+        self.flags.zero = load_bool(buff)?;
+        self.flags.decimal = load_bool(buff)?;
+        self.flags.interrupts_disabled = load_bool(buff)?;
+        self.flags.overflow = load_bool(buff)?;
+        self.flags.negative = load_bool(buff)?;
+        self.flags.last_nmi = load_bool(buff)?;
+        return Ok(());
+    }
 }
 
 pub struct CpuState {
@@ -92,6 +124,10 @@ pub struct CpuState {
   pub oam_dma_address: u16,
   
   pub old_nmi_requested: bool,
+
+  // Where the current instruction began, or for interrupts, the instruction they
+  // interrupted. By the time an instruction accesses memory, PC has moved past it.
+  pub opcode_address: u16,
 }
 
 impl CpuState {
@@ -112,8 +148,53 @@ impl CpuState {
       upcoming_write: false,
       
       old_nmi_requested: false,
+
+      opcode_address: 0,
     }
   }
+
+  pub fn save_state(&self, buff: &mut Vec<u8>) {
+    save_u8(buff, self.tick);
+    save_u8(buff, self.opcode);
+    save_u8(buff, self.data1);
+    save_u8(buff, self.data2);
+    save_u16(buff, self.temp_address);
+    save_bool(buff, self.service_routine_active);
+    save_bool(buff, self.nmi_requested);
+    save_bool(buff, self.irq_requested);
+    save_bool(buff, self.last_nmi);
+    save_bool(buff, self.upcoming_write);
+
+    save_bool(buff, self.oam_dma_active);
+    save_u16(buff, self.oam_dma_cycle);
+    save_u16(buff, self.oam_dma_address);
+
+    save_bool(buff, self.old_nmi_requested);
+
+    save_u16(buff, self.opcode_address);
+  }
+
+  pub fn load_state(&mut self, buff: &mut &[u8]) -> Result<(), String> {
+    self.tick = load_u8(buff)?;
+    self.opcode = load_u8(buff)?;
+    self.data1 = load_u8(buff)?;
+    self.data2 = load_u8(buff)?;
+    self.temp_address = load_u16(buff)?;
+    self.service_routine_active = load_bool(buff)?;
+    self.nmi_requested = load_bool(buff)?;
+    self.irq_requested = load_bool(buff)?;
+    self.last_nmi = load_bool(buff)?;
+    self.upcoming_write = load_bool(buff)?;
+
+    self.oam_dma_active = load_bool(buff)?;
+    self.oam_dma_cycle = load_u16(buff)?;
+    self.oam_dma_address = load_u16(buff)?;
+
+    self.old_nmi_requested = load_bool(buff)?;
+
+    self.opcode_address = load_u16(buff)?;
+    return Ok(());
+  }
 }
 
 
@@ -146,6 +227,16 @@ pub fn interrupt_requested(nes: &NesState) -> bool {
   return nes.cpu.old_nmi_requested || nes.cpu.irq_requested;
 }
 
+// True when the next clock will fetch a new opcode from PC, rather than continue the
+// current instruction, stall for DMA, or begin servicing an interrupt
+pub fn opcode_fetch_pending(nes: &NesState) -> bool {
+  return nes.cpu.tick == 0 &&
+    !nes.cpu.oam_dma_active &&
+    !nes.cpu.service_routine_active &&
+    !(nes.cpu.upcoming_write == false && nes.apu.dmc.rdy_line == true) &&
+    !interrupt_requested(nes);
+}
+
 pub fn halt_cpu(nes: &mut NesState) {
   // HALT the CPU. It died, jim.
   if nes.cpu.tick < 10 {
@@ -398,6 +489,14 @@ pub fn run_one_clock(nes: &mut NesState) {
 
   if nes.cpu.tick == 1 && interrupt_requested(&nes) {
     nes.cpu.service_routine_active = true;
+    nes.cpu.opcode_address = nes.registers.pc;
+    match nes.profiler.take() {
+      Some(mut profiler) => {
+        profiler.log_interrupt(nes);
+        nes.profiler = Some(profiler);
+      },
+      None => {}
+    }
   }
 
   poll_for_interrupts(nes);
@@ -408,8 +507,27 @@ pub fn run_one_clock(nes: &mut NesState) {
 
   // Universal behavior for every opcode
   if nes.cpu.tick == 1 {
-    // Fetch opcode from memory
+    match nes.tracer.take() {
+      Some(mut tracer) => {
+        tracer.log_instruction(nes);
+        nes.tracer = Some(tracer);
+      },
+      None => {}
+    }
+    match nes.profiler.take() {
+      Some(mut profiler) => {
+        profiler.log_instruction(nes);
+        nes.profiler = Some(profiler);
+      },
+      None => {}
+    }
     let pc = nes.registers.pc;
+    nes.cpu.opcode_address = pc;
+    match nes.cdl {
+      Some(ref mut cdl) => cdl.log_instruction(&*nes.mapper, pc),
+      None => {}
+    }
+    // Fetch opcode from memory
     nes.cpu.opcode = read_byte(nes, pc);
     nes.registers.pc = nes.registers.pc.wrapping_add(1);
     return; // all done
@@ -0,0 +1,337 @@
+// Breakpoints and stepping, for debugging software running on the emulator. Memory
+// accesses are reported here by the same hooks that feed the EventTracker, and checked
+// against lookup tables rebuilt whenever the list of breakpoints changes, so that the
+// cost of an access with nothing to catch stays at a single table read.
+//
+// Nothing here stops emulation by itself: a hit is only noted, and it's up to
+// NesState::run_until_break to notice it once the instruction has finished. That is
+// also where breakpoint conditions are evaluated, as they need the whole NesState.
+
+use expression::Expression;
+
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum BreakpointKind {
+    // Stops before the instruction at this address runs
+    Execute,
+    // Stops after the instruction which accessed this CPU address
+    Read,
+    Write,
+    // Reads and writes to PPU memory through PPUDATA ($2007), by PPU address
+    PpuRead,
+    PpuWrite,
+    // Stops at the start of the interrupt handler. These ignore the address range.
+    Irq,
+    Nmi,
+}
+
+impl BreakpointKind {
+    pub fn name(&self) -> &'static str {
+        return match self {
+            BreakpointKind::Execute => "Execute",
+            BreakpointKind::Read => "Read",
+            BreakpointKind::Write => "Write",
+            BreakpointKind::PpuRead => "PPU Read",
+            BreakpointKind::PpuWrite => "PPU Write",
+            BreakpointKind::Irq => "IRQ",
+            BreakpointKind::Nmi => "NMI",
+        };
+    }
+
+    fn flag(&self) -> u8 {
+        return match self {
+            BreakpointKind::Execute => BREAK_EXECUTE,
+            BreakpointKind::Read | BreakpointKind::PpuRead => BREAK_READ,
+            BreakpointKind::Write | BreakpointKind::PpuWrite => BREAK_WRITE,
+            BreakpointKind::Irq => BREAK_IRQ,
+            BreakpointKind::Nmi => BREAK_NMI,
+        };
+    }
+}
+
+#[derive(Clone, Debug)]
+pub struct Breakpoint {
+    pub kind: BreakpointKind,
+    pub start_address: u16,
+    pub end_address: u16,
+    pub enabled: bool,
+    // When present, the breakpoint only stops emulation if this evaluates to non-zero
+    pub condition: Option<Expression>,
+}
+
+impl Breakpoint {
+    pub fn new(kind: BreakpointKind, address: u16) -> Breakpoint {
+        return Breakpoint::range(kind, address, address);
+    }
+
+    pub fn range(kind: BreakpointKind, start_address: u16, end_address: u16) -> Breakpoint {
+        return Breakpoint {
+            kind: kind,
+            start_address: start_address,
+            end_address: end_address,
+            enabled: true,
+            condition: None,
+        };
+    }
+
+    /// Whether this breakpoint is responsible for the hit, ignoring its condition
+    pub fn covers(&self, hit: &BreakpointHit) -> bool {
+        if !self.enabled || self.kind != hit.kind {
+            return false;
+        }
+        return match self.kind {
+            BreakpointKind::Irq | BreakpointKind::Nmi => true,
+            BreakpointKind::PpuRead | BreakpointKind::PpuWrite => {
+                hit.address >= (self.start_address & 0x3FFF) && hit.address <= (self.end_address & 0x3FFF)
+            },
+            _ => hit.address >= self.start_address && hit.address <= self.end_address
+        };
+    }
+}
+
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct BreakpointHit {
+    pub kind: BreakpointKind,
+    // The address accessed, or for interrupts, the vector that was read
+    pub address: u16,
+    // The byte read or written; for execute breakpoints, the opcode
+    pub data: u8,
+    // The instruction responsible. For interrupts, the one which was interrupted.
+    pub program_counter: u16,
+}
+
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum StopReason {
+    Breakpoint(BreakpointHit),
+    // A step over or step out reached its destination
+    StepFinished,
+    // The caller's own stopping condition was met first
+    Finished,
+}
+
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum StepTarget {
+    // Run one whole instruction
+    Instruction,
+    // Run until this return address is reached with the stack at least this high,
+    // so that recursive calls to the same routine don't count
+    ReturnTo{address: u16, stack_pointer: u8},
+    // Run until an RTS or RTI pops the stack above this point
+    StackAbove{stack_pointer: u8},
+}
+
+const BREAK_READ: u8    = 0b0000_0001;
+const BREAK_WRITE: u8   = 0b0000_0010;
+const BREAK_EXECUTE: u8 = 0b0000_0100;
+const BREAK_IRQ: u8     = 0b0000_1000;
+const BREAK_NMI: u8     = 0b0001_0000;
+
+pub struct Debugger {
+    pub breakpoints: Vec<Breakpoint>,
+    pub step_target: Option<StepTarget>,
+    // Noted by the memory hooks, and collected after the current instruction. Only
+    // run_until_break looks at these, so they're only noted while it's running.
+    pub pending_hits: Vec<BreakpointHit>,
+    pub collecting_hits: bool,
+    // When we stop on an execute breakpoint, continuing should run that instruction
+    // rather than immediately stopping on it again
+    pub resume_address: Option<u16>,
+    cpu_breaks: Vec<u8>,
+    ppu_breaks: Vec<u8>,
+    interrupt_breaks: u8,
+}
+
+impl Debugger {
+    pub fn new() -> Debugger {
+        return Debugger {
+            breakpoints: Vec::new(),
+            step_target: None,
+            pending_hits: Vec::new(),
+            collecting_hits: false,
+            resume_address: None,
+            cpu_breaks: vec![0u8; 0x10000],
+            ppu_breaks: vec![0u8; 0x4000],
+            interrupt_breaks: 0,
+        };
+    }
+
+    /// Whether any breakpoint or step could stop emulation. When this is false, the
+    /// ordinary run functions are safe (and faster) to use.
+    pub fn active(&self) -> bool {
+        return self.step_target.is_some() || self.breakpoints.iter().any(|breakpoint| breakpoint.enabled);
+    }
+
+    pub fn add_breakpoint(&mut self, breakpoint: Breakpoint) -> usize {
+        self.breakpoints.push(breakpoint);
+        self.rebuild();
+        return self.breakpoints.len() - 1;
+    }
+
+    pub fn remove_breakpoint(&mut self, index: usize) {
+        if index < self.breakpoints.len() {
+            self.breakpoints.remove(index);
+            self.rebuild();
+        }
+    }
+
+    pub fn set_enabled(&mut self, index: usize, enabled: bool) {
+        if index < self.breakpoints.len() {
+            self.breakpoints[index].enabled = enabled;
+            self.rebuild();
+        }
+    }
+
+    pub fn clear_breakpoints(&mut self) {
+        self.breakpoints.clear();
+        self.rebuild();
+    }
+
+    /// The index of the first breakpoint of this kind covering this exact address
+    pub fn find_breakpoint(&self, kind: BreakpointKind, address: u16) -> Option<usize> {
+        return self.breakpoints.iter().position(|breakpoint|
+            breakpoint.kind == kind && breakpoint.start_address == address && breakpoint.end_address == address);
+    }
+
+    fn rebuild(&mut self) {
+        for entry in self.cpu_breaks.iter_mut() {*entry = 0};
+        for entry in self.ppu_breaks.iter_mut() {*entry = 0};
+        self.interrupt_breaks = 0;
+        for breakpoint in self.breakpoints.iter().filter(|breakpoint| breakpoint.enabled) {
+            let flag = breakpoint.kind.flag();
+            match breakpoint.kind {
+                BreakpointKind::Irq | BreakpointKind::Nmi => {
+                    self.interrupt_breaks |= flag;
+                },
+                BreakpointKind::PpuRead | BreakpointKind::PpuWrite => {
+                    for address in breakpoint.start_address ..= breakpoint.end_address {
+                        self.ppu_breaks[(address & 0x3FFF) as usize] |= flag;
+                    }
+                },
+                _ => {
+                    for address in breakpoint.start_address ..= breakpoint.end_address {
+                        self.cpu_breaks[address as usize] |= flag;
+                    }
+                }
+            }
+        }
+    }
+
+    fn note_hit(&mut self, kind: BreakpointKind, address: u16, data: u8, program_counter: u16) {
+        if !self.collecting_hits {
+            return;
+        }
+        self.pending_hits.push(BreakpointHit{
+            kind: kind,
+            address: address,
+            data: data,
+            program_counter: program_counter,
+        });
+    }
+
+    pub fn snoop_cpu_read(&mut self, program_counter: u16, address: u16, data: u8) {
+        if (self.cpu_breaks[address as usize] & BREAK_READ) != 0 {
+            self.note_hit(BreakpointKind::Read, address, data, program_counter);
+        }
+    }
+
+    pub fn snoop_cpu_write(&mut self, program_counter: u16, address: u16, data: u8) {
+        if (self.cpu_breaks[address as usize] & BREAK_WRITE) != 0 {
+            self.note_hit(BreakpointKind::Write, address, data, program_counter);
+        }
+    }
+
+    pub fn snoop_ppu_read(&mut self, program_counter: u16, address: u16, data: u8) {
+        if (self.ppu_breaks[(address & 0x3FFF) as usize] & BREAK_READ) != 0 {
+            self.note_hit(BreakpointKind::PpuRead, address & 0x3FFF, data, program_counter);
+        }
+    }
+
+    pub fn snoop_ppu_write(&mut self, program_counter: u16, address: u16, data: u8) {
+        if (self.ppu_breaks[(address & 0x3FFF) as usize] & BREAK_WRITE) != 0 {
+            self.note_hit(BreakpointKind::PpuWrite, address & 0x3FFF, data, program_counter);
+        }
+    }
+
+    /// Called as the CPU reads the interrupt vector
+    pub fn snoop_interrupt(&mut self, program_counter: u16, vector: u16, nmi: bool) {
+        let flag = if nmi {BREAK_NMI} else {BREAK_IRQ};
+        if (self.interrupt_breaks & flag) != 0 {
+            let kind = if nmi {BreakpointKind::Nmi} else {BreakpointKind::Irq};
+            self.note_hit(kind, vector, 0, program_counter);
+        }
+    }
+
+    /// Called just before an opcode is fetched. Unlike the others, this is checked
+    /// before anything happens, so that the instruction hasn't run yet when we stop.
+    pub fn check_execute(&mut self, program_counter: u16, opcode: u8) -> Option<BreakpointHit> {
+        if self.resume_address.take() == Some(program_counter) {
+            return None;
+        }
+        if (self.cpu_breaks[program_counter as usize] & BREAK_EXECUTE) != 0 {
+            return Some(BreakpointHit{
+                kind: BreakpointKind::Execute,
+                address: program_counter,
+                data: opcode,
+                program_counter: program_counter,
+            });
+        }
+        return None;
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use asm::Opcode::*;
+    use asm::AddressingMode::*;
+    use debugger::*;
+    use mmc::test_rom::*;
+
+    #[test]
+    fn hits_report_the_instruction_which_made_the_access() {
+        let rom = TestRom::new(0, 0x8000, 0x2000);
+        let mut nes = rom.boot(vec![
+            // $C000
+            Lda(Immediate(0x12)),
+            // $C002
+            Sta(Absolute(0x0300)),
+            // $C005
+            Ldx(Absolute(0x0300)),
+            halt(),
+        ], vec![Rti]);
+        nes.debugger.add_breakpoint(Breakpoint::new(BreakpointKind::Write, 0x0300));
+        nes.debugger.add_breakpoint(Breakpoint::new(BreakpointKind::Read, 0x0300));
+        match nes.run_until_break(|_| false) {
+            StopReason::Breakpoint(hit) => {
+                assert_eq!(hit.kind, BreakpointKind::Write);
+                assert_eq!(hit.program_counter, 0xC002);
+            },
+            other => panic!("Expected a breakpoint, got {:?}", other),
+        }
+        match nes.run_until_break(|_| false) {
+            StopReason::Breakpoint(hit) => {
+                assert_eq!(hit.kind, BreakpointKind::Read);
+                assert_eq!(hit.program_counter, 0xC005);
+            },
+            other => panic!("Expected a breakpoint, got {:?}", other),
+        }
+    }
+
+    #[test]
+    fn hits_are_only_noted_while_running_until_a_break() {
+        let rom = TestRom::new(0, 0x8000, 0x2000);
+        let mut nes = rom.boot(vec![
+            Label(String::from("loop")),
+            Sta(Absolute(0x0300)),
+            Jmp(AbsoluteLabel(String::from("loop"))),
+        ], vec![Rti]);
+        nes.debugger.add_breakpoint(Breakpoint::new(BreakpointKind::Write, 0x0300));
+        for _ in 0 .. 1000 {
+            nes.step();
+        }
+        assert!(nes.debugger.pending_hits.is_empty());
+        match nes.run_until_break(|_| false) {
+            StopReason::Breakpoint(hit) => assert_eq!(hit.kind, BreakpointKind::Write),
+            other => panic!("Expected a breakpoint, got {:?}", other),
+        }
+        assert!(nes.debugger.pending_hits.is_empty());
+    }
+}
@@ -0,0 +1,345 @@
+// Turns the bytes of a 6502 program back into assembly, covering all 256 opcodes. The
+// unofficial ones use the names from https://www.nesdev.org/wiki/CPU_unofficial_opcodes
+// (with SBC standing in for the $EB duplicate), and operands are written the way
+// nestest.log writes them, so that traces can be compared against other emulators.
+//
+// Addresses in PRG ROM are shown with the bank currently mapped there, in 16k units as
+// FCEUX counts them, like 02:8000. Elsewhere the bank is shown as --.
+
+use memory::debug_read_byte;
+use nes::NesState;
+use symbols::SymbolTable;
+
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum AddressingMode {
+    Implied,
+    Accumulator,
+    Immediate,
+    ZeroPage,
+    ZeroPageX,
+    ZeroPageY,
+    Absolute,
+    AbsoluteX,
+    AbsoluteY,
+    Indirect,
+    IndirectX,
+    IndirectY,
+    Relative,
+}
+
+impl AddressingMode {
+    /// The number of operand bytes following the opcode
+    pub fn operand_bytes(&self) -> u16 {
+        return match self {
+            AddressingMode::Implied | AddressingMode::Accumulator => 0,
+            AddressingMode::Absolute | AddressingMode::AbsoluteX | AddressingMode::AbsoluteY | AddressingMode::Indirect => 2,
+            _ => 1
+        };
+    }
+}
+
+use self::AddressingMode::*;
+
+const OPCODES: [(&str, AddressingMode); 256] = [
+    // 0x00
+    ("BRK", Implied),   ("ORA", IndirectX), ("STP", Implied),   ("SLO", IndirectX),
+    ("NOP", ZeroPage),  ("ORA", ZeroPage),  ("ASL", ZeroPage),  ("SLO", ZeroPage),
+    ("PHP", Implied),   ("ORA", Immediate), ("ASL", Accumulator), ("ANC", Immediate),
+    ("NOP", Absolute),  ("ORA", Absolute),  ("ASL", Absolute),  ("SLO", Absolute),
+    // 0x10
+    ("BPL", Relative),  ("ORA", IndirectY), ("STP", Implied),   ("SLO", IndirectY),
+    ("NOP", ZeroPageX), ("ORA", ZeroPageX), ("ASL", ZeroPageX), ("SLO", ZeroPageX),
+    ("CLC", Implied),   ("ORA", AbsoluteY), ("NOP", Implied),   ("SLO", AbsoluteY),
+    ("NOP", AbsoluteX), ("ORA", AbsoluteX), ("ASL", AbsoluteX), ("SLO", AbsoluteX),
+    // 0x20
+    ("JSR", Absolute),  ("AND", IndirectX), ("STP", Implied),   ("RLA", IndirectX),
+    ("BIT", ZeroPage),  ("AND", ZeroPage),  ("ROL", ZeroPage),  ("RLA", ZeroPage),
+    ("PLP", Implied),   ("AND", Immediate), ("ROL", Accumulator), ("ANC", Immediate),
+    ("BIT", Absolute),  ("AND", Absolute),  ("ROL", Absolute),  ("RLA", Absolute),
+    // 0x30
+    ("BMI", Relative),  ("AND", IndirectY), ("STP", Implied),   ("RLA", IndirectY),
+    ("NOP", ZeroPageX), ("AND", ZeroPageX), ("ROL", ZeroPageX), ("RLA", ZeroPageX),
+    ("SEC", Implied),   ("AND", AbsoluteY), ("NOP", Implied),   ("RLA", AbsoluteY),
+    ("NOP", AbsoluteX), ("AND", AbsoluteX), ("ROL", AbsoluteX), ("RLA", AbsoluteX),
+    // 0x40
+    ("RTI", Implied),   ("EOR", IndirectX), ("STP", Implied),   ("SRE", IndirectX),
+    ("NOP", ZeroPage),  ("EOR", ZeroPage),  ("LSR", ZeroPage),  ("SRE", ZeroPage),
+    ("PHA", Implied),   ("EOR", Immediate), ("LSR", Accumulator), ("ALR", Immediate),
+    ("JMP", Absolute),  ("EOR", Absolute),  ("LSR", Absolute),  ("SRE", Absolute),
+    // 0x50
+    ("BVC", Relative),  ("EOR", IndirectY), ("STP", Implied),   ("SRE", IndirectY),
+    ("NOP", ZeroPageX), ("EOR", ZeroPageX), ("LSR", ZeroPageX), ("SRE", ZeroPageX),
+    ("CLI", Implied),   ("EOR", AbsoluteY), ("NOP", Implied),   ("SRE", AbsoluteY),
+    ("NOP", AbsoluteX), ("EOR", AbsoluteX), ("LSR", AbsoluteX), ("SRE", AbsoluteX),
+    // 0x60
+    ("RTS", Implied),   ("ADC", IndirectX), ("STP", Implied),   ("RRA", IndirectX),
+    ("NOP", ZeroPage),  ("ADC", ZeroPage),  ("ROR", ZeroPage),  ("RRA", ZeroPage),
+    ("PLA", Implied),   ("ADC", Immediate), ("ROR", Accumulator), ("ARR", Immediate),
+    ("JMP", Indirect),  ("ADC", Absolute),  ("ROR", Absolute),  ("RRA", Absolute),
+    // 0x70
+    ("BVS", Relative),  ("ADC", IndirectY), ("STP", Implied),   ("RRA", IndirectY),
+    ("NOP", ZeroPageX), ("ADC", ZeroPageX), ("ROR", ZeroPageX), ("RRA", ZeroPageX),
+    ("SEI", Implied),   ("ADC", AbsoluteY), ("NOP", Implied),   ("RRA", AbsoluteY),
+    ("NOP", AbsoluteX), ("ADC", AbsoluteX), ("ROR", AbsoluteX), ("RRA", AbsoluteX),
+    // 0x80
+    ("NOP", Immediate), ("STA", IndirectX), ("NOP", Immediate), ("SAX", IndirectX),
+    ("STY", ZeroPage),  ("STA", ZeroPage),  ("STX", ZeroPage),  ("SAX", ZeroPage),
+    ("DEY", Implied),   ("NOP", Immediate), ("TXA", Implied),   ("XAA", Immediate),
+    ("STY", Absolute),  ("STA", Absolute),  ("STX", Absolute),  ("SAX", Absolute),
+    // 0x90
+    ("BCC", Relative),  ("STA", IndirectY), ("STP", Implied),   ("AHX", IndirectY),
+    ("STY", ZeroPageX), ("STA", ZeroPageX), ("STX", ZeroPageY), ("SAX", ZeroPageY),
+    ("TYA", Implied),   ("STA", AbsoluteY), ("TXS", Implied),   ("TAS", AbsoluteY),
+    ("SHY", AbsoluteX), ("STA", AbsoluteX), ("SHX", AbsoluteY), ("AHX", AbsoluteY),
+    // 0xA0
+    ("LDY", Immediate), ("LDA", IndirectX), ("LDX", Immediate), ("LAX", IndirectX),
+    ("LDY", ZeroPage),  ("LDA", ZeroPage),  ("LDX", ZeroPage),  ("LAX", ZeroPage),
+    ("TAY", Implied),   ("LDA", Immediate), ("TAX", Implied),   ("LAX", Immediate),
+    ("LDY", Absolute),  ("LDA", Absolute),  ("LDX", Absolute),  ("LAX", Absolute),
+    // 0xB0
+    ("BCS", Relative),  ("LDA", IndirectY), ("STP", Implied),   ("LAX", IndirectY),
+    ("LDY", ZeroPageX), ("LDA", ZeroPageX), ("LDX", ZeroPageY), ("LAX", ZeroPageY),
+    ("CLV", Implied),   ("LDA", AbsoluteY), ("TSX", Implied),   ("LAS", AbsoluteY),
+    ("LDY", AbsoluteX), ("LDA", AbsoluteX), ("LDX", AbsoluteY), ("LAX", AbsoluteY),
+    // 0xC0
+    ("CPY", Immediate), ("CMP", IndirectX), ("NOP", Immediate), ("DCP", IndirectX),
+    ("CPY", ZeroPage),  ("CMP", ZeroPage),  ("DEC", ZeroPage),  ("DCP", ZeroPage),
+    ("INY", Implied),   ("CMP", Immediate), ("DEX", Implied),   ("AXS", Immediate),
+    ("CPY", Absolute),  ("CMP", Absolute),  ("DEC", Absolute),  ("DCP", Absolute),
+    // 0xD0
+    ("BNE", Relative),  ("CMP", IndirectY), ("STP", Implied),   ("DCP", IndirectY),
+    ("NOP", ZeroPageX), ("CMP", ZeroPageX), ("DEC", ZeroPageX), ("DCP", ZeroPageX),
+    ("CLD", Implied),   ("CMP", AbsoluteY), ("NOP", Implied),   ("DCP", AbsoluteY),
+    ("NOP", AbsoluteX), ("CMP", AbsoluteX), ("DEC", AbsoluteX), ("DCP", AbsoluteX),
+    // 0xE0
+    ("CPX", Immediate), ("SBC", IndirectX), ("NOP", Immediate), ("ISB", IndirectX),
+    ("CPX", ZeroPage),  ("SBC", ZeroPage),  ("INC", ZeroPage),  ("ISB", ZeroPage),
+    ("INX", Implied),   ("SBC", Immediate), ("NOP", Implied),   ("SBC", Immediate),
+    ("CPX", Absolute),  ("SBC", Absolute),  ("INC", Absolute),  ("ISB", Absolute),
+    // 0xF0
+    ("BEQ", Relative),  ("SBC", IndirectY), ("STP", Implied),   ("ISB", IndirectY),
+    ("NOP", ZeroPageX), ("SBC", ZeroPageX), ("INC", ZeroPageX), ("ISB", ZeroPageX),
+    ("SED", Implied),   ("SBC", AbsoluteY), ("NOP", Implied),   ("ISB", AbsoluteY),
+    ("NOP", AbsoluteX), ("SBC", AbsoluteX), ("INC", AbsoluteX), ("ISB", AbsoluteX),
+];
+
+#[derive(Clone, Debug)]
+pub struct Instruction {
+    pub address: u16,
+    pub opcode: u8,
+    pub mnemonic: &'static str,
+    pub mode: AddressingMode,
+    // The opcode followed by its operand bytes
+    pub bytes: Vec<u8>,
+    // The PRG bank this was read from, if it came from PRG ROM
+    pub bank: Option<usize>,
+}
+
+impl Instruction {
+    pub fn length(&self) -> u16 {
+        return self.bytes.len() as u16;
+    }
+
+    pub fn official(&self) -> bool {
+        return is_official(self.opcode);
+    }
+
+    /// The raw operand, as one number: a byte, a word, or nothing
+    pub fn operand(&self) -> u16 {
+        return match self.bytes.len() {
+            2 => self.bytes[1] as u16,
+            3 => self.bytes[1] as u16 | ((self.bytes[2] as u16) << 8),
+            _ => 0
+        };
+    }
+
+    /// Where a branch, JMP or JSR will take us, if it's known without running anything
+    pub fn branch_target(&self) -> Option<u16> {
+        return match (self.mode, self.mnemonic) {
+            (Relative, _) => Some(self.address.wrapping_add(2).wrapping_add(self.operand() as u8 as i8 as u16)),
+            (Absolute, "JMP") | (Absolute, "JSR") => Some(self.operand()),
+            _ => None
+        };
+    }
+
+    /// The operand as written in assembly, such as $0300,X
+    pub fn operand_text(&self) -> String {
+        let operand = self.operand();
+        return match self.mode {
+            Implied => String::new(),
+            Accumulator => "A".to_string(),
+            Immediate => format!("#${:02X}", operand),
+            ZeroPage => format!("${:02X}", operand),
+            ZeroPageX => format!("${:02X},X", operand),
+            ZeroPageY => format!("${:02X},Y", operand),
+            Absolute => format!("${:04X}", operand),
+            AbsoluteX => format!("${:04X},X", operand),
+            AbsoluteY => format!("${:04X},Y", operand),
+            Indirect => format!("(${:04X})", operand),
+            IndirectX => format!("(${:02X},X)", operand),
+            IndirectY => format!("(${:02X}),Y", operand),
+            Relative => format!("${:04X}", self.branch_target().unwrap_or(0)),
+        };
+    }
+
+    /// The mnemonic and operand together, such as LDA $0300,X
+    pub fn text(&self) -> String {
+        if self.mode == Implied {
+            return self.mnemonic.to_string();
+        }
+        return format!("{} {}", self.mnemonic, self.operand_text());
+    }
+
+    /// Like text, but with any address in the operand replaced by its label
+    pub fn labeled_text(&self, nes: &NesState, symbols: &SymbolTable) -> String {
+        let text = self.text();
+        let (target, literal) = match self.mode {
+            Implied | Accumulator | Immediate => {return text},
+            ZeroPage | ZeroPageX | ZeroPageY | IndirectX | IndirectY => (self.operand(), format!("${:02X}", self.operand())),
+            Relative => (self.branch_target().unwrap_or(0), format!("${:04X}", self.branch_target().unwrap_or(0))),
+            _ => (self.operand(), format!("${:04X}", self.operand())),
+        };
+        return match symbols.label_at(nes, target) {
+            Some(name) => text.replacen(&literal, name, 1),
+            None => text
+        };
+    }
+
+    /// One line of a disassembly listing, with the bank, address, raw bytes and
+    /// labeled text. The instruction at the program counter also shows what it's
+    /// about to access.
+    pub fn listing_line(&self, nes: &NesState, symbols: &SymbolTable) -> String {
+        let bytes: Vec<String> = self.bytes.iter().map(|byte| format!("{:02X}", byte)).collect();
+        let marker = if self.official() {" "} else {"*"};
+        let annotation = if self.address == nes.registers.pc {self.annotation(nes)} else {String::new()};
+        return format!("{}  {:<8} {}{}{}", format_address(self.address, self.bank), bytes.join(" "),
+            marker, self.labeled_text(nes, symbols), annotation);
+    }
+
+    /// The address this instruction will access if it runs next, given the current
+    /// registers. Only meaningful when the instruction is at the program counter.
+    pub fn effective_address(&self, nes: &NesState) -> Option<u16> {
+        let operand = self.operand();
+        let x = nes.registers.x as u16;
+        let y = nes.registers.y as u16;
+        return match self.mode {
+            ZeroPage => Some(operand),
+            ZeroPageX => Some((operand + x) & 0xFF),
+            ZeroPageY => Some((operand + y) & 0xFF),
+            Absolute => Some(operand),
+            AbsoluteX => Some(operand.wrapping_add(x)),
+            AbsoluteY => Some(operand.wrapping_add(y)),
+            IndirectX => Some(read_zero_page_word(nes, (operand + x) as u8)),
+            IndirectY => Some(read_zero_page_word(nes, operand as u8).wrapping_add(y)),
+            _ => None
+        };
+    }
+
+    /// The details nestest.log adds after the operand: the address being accessed and
+    /// the value there, such as " @ 0300 = 89". Reads are made without side effects.
+    pub fn annotation(&self, nes: &NesState) -> String {
+        let operand = self.operand();
+        let target = match self.effective_address(nes) {
+            Some(address) => address,
+            None => {
+                if self.mode == Indirect {
+                    // JMP ($xxFF) fetches its high byte from $xx00, not the next page
+                    let low = debug_read_byte(nes, operand) as u16;
+                    let high = debug_read_byte(nes, (operand & 0xFF00) | (operand.wrapping_add(1) & 0x00FF)) as u16;
+                    return format!(" = {:04X}", (high << 8) | low);
+                }
+                return String::new();
+            }
+        };
+        let value = debug_read_byte(nes, target);
+        return match self.mode {
+            Absolute if self.mnemonic == "JMP" || self.mnemonic == "JSR" => String::new(),
+            ZeroPage | Absolute => format!(" = {:02X}", value),
+            ZeroPageX | ZeroPageY => format!(" @ {:02X} = {:02X}", target, value),
+            AbsoluteX | AbsoluteY => format!(" @ {:04X} = {:02X}", target, value),
+            IndirectX => format!(" @ {:02X} = {:04X} = {:02X}", (operand + nes.registers.x as u16) & 0xFF, target, value),
+            IndirectY => format!(" = {:04X} @ {:04X} = {:02X}", target.wrapping_sub(nes.registers.y as u16), target, value),
+            _ => String::new()
+        };
+    }
+}
+
+fn read_zero_page_word(nes: &NesState, address: u8) -> u16 {
+    let low = debug_read_byte(nes, address as u16) as u16;
+    let high = debug_read_byte(nes, address.wrapping_add(1) as u16) as u16;
+    return (high << 8) | low;
+}
+
+/// The PRG bank, in 16k units, currently mapped at this address
+pub fn prg_bank(nes: &NesState, address: u16) -> Option<usize> {
+    return nes.mapper.prg_rom_address(address).map(|offset| offset / 0x4000);
+}
+
+/// Formats an address with its bank, as in 02:8000
+pub fn format_address(address: u16, bank: Option<usize>) -> String {
+    return match bank {
+        Some(bank) => format!("{:02X}:{:04X}", bank, address),
+        None => format!("--:{:04X}", address)
+    };
+}
+
+pub fn is_official(opcode: u8) -> bool {
+    let (mnemonic, _) = OPCODES[opcode as usize];
+    if (opcode & 0b11) == 0b11 || mnemonic == "STP" || opcode == 0x9C || opcode == 0x9E {
+        return false;
+    }
+    return mnemonic != "NOP" || opcode == 0xEA;
+}
+
+pub fn mnemonic(opcode: u8) -> &'static str {
+    return OPCODES[opcode as usize].0;
+}
+
+pub fn addressing_mode(opcode: u8) -> AddressingMode {
+    return OPCODES[opcode as usize].1;
+}
+
+/// Decodes the instruction at this address, reading memory without side effects
+pub fn disassemble(nes: &NesState, address: u16) -> Instruction {
+    let opcode = debug_read_byte(nes, address);
+    let (mnemonic, mode) = OPCODES[opcode as usize];
+    let mut bytes = vec![opcode];
+    for i in 0 .. mode.operand_bytes() {
+        bytes.push(debug_read_byte(nes, address.wrapping_add(1 + i)));
+    }
+    return Instruction {
+        address: address,
+        opcode: opcode,
+        mnemonic: mnemonic,
+        mode: mode,
+        bytes: bytes,
+        bank: prg_bank(nes, address),
+    };
+}
+
+/// Disassembles count instructions, starting at this address
+pub fn disassemble_range(nes: &NesState, start: u16, count: usize) -> Vec<Instruction> {
+    let mut instructions = Vec::new();
+    let mut address = start;
+    for _ in 0 .. count {
+        let instruction = disassemble(nes, address);
+        address = address.wrapping_add(instruction.length());
+        instructions.push(instruction);
+    }
+    return instructions;
+}
+
+/// A best guess at where the instruction before this address starts. Instructions
+/// vary in length, so there's no certain way to read a program backwards; we prefer
+/// the longest instruction which would end exactly here, as operand bytes are more
+/// likely than not to look like short opcodes.
+pub fn previous_instruction(nes: &NesState, address: u16) -> u16 {
+    for length in (1 ..= 3).rev() {
+        let candidate = address.wrapping_sub(length);
+        let opcode = debug_read_byte(nes, candidate);
+        if OPCODES[opcode as usize].1.operand_bytes() + 1 == length && mnemonic(opcode) != "STP" {
+            return candidate;
+        }
+    }
+    return address.wrapping_sub(1);
+}
@@ -0,0 +1,461 @@
+// A small expression language for conditional breakpoints and other debugging filters,
+// along the lines of: A == $40 && [$0300] > 3 && scanline >= 200
+//
+// Values are plain integers, and comparisons and logical operators produce 1 or 0, as in
+// C. Numbers may be written in decimal, or in hex with a $ or 0x prefix. [address] reads
+// a byte of CPU memory (without side effects), and {address} reads a little endian word.
+//
+// Names are not case sensitive:
+//   a, x, y, s (or sp), pc, p     CPU registers, with p being the packed status byte
+//   c, z, i, d, v, n              Individual status flags, as 0 or 1
+//   scanline, cycle, frame        The PPU's current position
+//   irq                           1 while the mapper is asserting its IRQ line
+//   address, value                The address and byte of the access which triggered a
+//                                 breakpoint; for execute breakpoints, the PC and opcode
+
+use memory;
+use nes::NesState;
+
+#[derive(Clone, Copy, Debug, PartialEq)]
+enum Variable {
+    A, X, Y, S, Pc, P,
+    Carry, Zero, InterruptsDisabled, Decimal, Overflow, Negative,
+    Scanline, Cycle, Frame,
+    Irq,
+    Address, Value,
+}
+
+#[derive(Clone, Copy, Debug, PartialEq)]
+enum UnaryOperator {
+    Negate,
+    Not,
+    Complement,
+}
+
+#[derive(Clone, Copy, Debug, PartialEq)]
+enum BinaryOperator {
+    Or, And,
+    BitOr, BitXor, BitAnd,
+    Equal, NotEqual,
+    Less, LessEqual, Greater, GreaterEqual,
+    ShiftLeft, ShiftRight,
+    Add, Subtract,
+    Multiply, Divide, Remainder,
+}
+
+impl BinaryOperator {
+    // Higher numbers bind more tightly, following C
+    fn precedence(&self) -> u8 {
+        return match self {
+            BinaryOperator::Or => 1,
+            BinaryOperator::And => 2,
+            BinaryOperator::BitOr => 3,
+            BinaryOperator::BitXor => 4,
+            BinaryOperator::BitAnd => 5,
+            BinaryOperator::Equal | BinaryOperator::NotEqual => 6,
+            BinaryOperator::Less | BinaryOperator::LessEqual | BinaryOperator::Greater | BinaryOperator::GreaterEqual => 7,
+            BinaryOperator::ShiftLeft | BinaryOperator::ShiftRight => 8,
+            BinaryOperator::Add | BinaryOperator::Subtract => 9,
+            BinaryOperator::Multiply | BinaryOperator::Divide | BinaryOperator::Remainder => 10,
+        };
+    }
+
+    fn apply(&self, lhs: i64, rhs: i64) -> i64 {
+        return match self {
+            BinaryOperator::Or => ((lhs != 0) || (rhs != 0)) as i64,
+            BinaryOperator::And => ((lhs != 0) && (rhs != 0)) as i64,
+            BinaryOperator::BitOr => lhs | rhs,
+            BinaryOperator::BitXor => lhs ^ rhs,
+            BinaryOperator::BitAnd => lhs & rhs,
+            BinaryOperator::Equal => (lhs == rhs) as i64,
+            BinaryOperator::NotEqual => (lhs != rhs) as i64,
+            BinaryOperator::Less => (lhs < rhs) as i64,
+            BinaryOperator::LessEqual => (lhs <= rhs) as i64,
+            BinaryOperator::Greater => (lhs > rhs) as i64,
+            BinaryOperator::GreaterEqual => (lhs >= rhs) as i64,
+            BinaryOperator::ShiftLeft => lhs.wrapping_shl(rhs as u32),
+            BinaryOperator::ShiftRight => lhs.wrapping_shr(rhs as u32),
+            BinaryOperator::Add => lhs.wrapping_add(rhs),
+            BinaryOperator::Subtract => lhs.wrapping_sub(rhs),
+            BinaryOperator::Multiply => lhs.wrapping_mul(rhs),
+            // Dividing by zero gives zero, rather than taking the emulator down with it
+            BinaryOperator::Divide => if rhs == 0 {0} else {lhs.wrapping_div(rhs)},
+            BinaryOperator::Remainder => if rhs == 0 {0} else {lhs.wrapping_rem(rhs)},
+        };
+    }
+}
+
+#[derive(Clone, Debug, PartialEq)]
+enum Node {
+    Number(i64),
+    Variable(Variable),
+    ReadByte(Box<Node>),
+    ReadWord(Box<Node>),
+    Unary(UnaryOperator, Box<Node>),
+    Binary(BinaryOperator, Box<Node>, Box<Node>),
+}
+
+#[derive(Clone, Debug, PartialEq)]
+enum Token {
+    Number(i64),
+    Name(String),
+    Unary(UnaryOperator),
+    Binary(BinaryOperator),
+    // Minus is either, depending on where it appears
+    Minus,
+    OpenParen,
+    CloseParen,
+    OpenBracket,
+    CloseBracket,
+    OpenBrace,
+    CloseBrace,
+}
+
+fn tokenize(text: &str) -> Result<Vec<Token>, String> {
+    let chars: Vec<char> = text.chars().collect();
+    let mut tokens = Vec::new();
+    let mut i = 0;
+    while i < chars.len() {
+        let c = chars[i];
+        let next = chars.get(i + 1).cloned().unwrap_or('\0');
+        if c.is_whitespace() {
+            i += 1;
+            continue;
+        }
+        if c == '$' || (c == '0' && (next == 'x' || next == 'X')) {
+            i += if c == '$' {1} else {2};
+            let start = i;
+            while i < chars.len() && chars[i].is_ascii_hexdigit() {
+                i += 1;
+            }
+            let digits: String = chars[start .. i].iter().collect();
+            match i64::from_str_radix(&digits, 16) {
+                Ok(number) => tokens.push(Token::Number(number)),
+                Err(_) => {return Err(format!("Invalid hex number at position {}", start))}
+            }
+            continue;
+        }
+        if c.is_ascii_digit() {
+            let start = i;
+            while i < chars.len() && chars[i].is_ascii_digit() {
+                i += 1;
+            }
+            let digits: String = chars[start .. i].iter().collect();
+            match digits.parse::<i64>() {
+                Ok(number) => tokens.push(Token::Number(number)),
+                Err(_) => {return Err(format!("Invalid number at position {}", start))}
+            }
+            continue;
+        }
+        if c.is_ascii_alphabetic() || c == '_' {
+            let start = i;
+            while i < chars.len() && (chars[i].is_ascii_alphanumeric() || chars[i] == '_') {
+                i += 1;
+            }
+            tokens.push(Token::Name(chars[start .. i].iter().collect::<String>().to_lowercase()));
+            continue;
+        }
+        let (token, length) = match (c, next) {
+            ('|', '|') => (Token::Binary(BinaryOperator::Or), 2),
+            ('&', '&') => (Token::Binary(BinaryOperator::And), 2),
+            ('=', '=') => (Token::Binary(BinaryOperator::Equal), 2),
+            ('!', '=') => (Token::Binary(BinaryOperator::NotEqual), 2),
+            ('<', '=') => (Token::Binary(BinaryOperator::LessEqual), 2),
+            ('>', '=') => (Token::Binary(BinaryOperator::GreaterEqual), 2),
+            ('<', '<') => (Token::Binary(BinaryOperator::ShiftLeft), 2),
+            ('>', '>') => (Token::Binary(BinaryOperator::ShiftRight), 2),
+            ('|', _) => (Token::Binary(BinaryOperator::BitOr), 1),
+            ('^', _) => (Token::Binary(BinaryOperator::BitXor), 1),
+            ('&', _) => (Token::Binary(BinaryOperator::BitAnd), 1),
+            ('<', _) => (Token::Binary(BinaryOperator::Less), 1),
+            ('>', _) => (Token::Binary(BinaryOperator::Greater), 1),
+            ('+', _) => (Token::Binary(BinaryOperator::Add), 1),
+            ('*', _) => (Token::Binary(BinaryOperator::Multiply), 1),
+            ('/', _) => (Token::Binary(BinaryOperator::Divide), 1),
+            ('%', _) => (Token::Binary(BinaryOperator::Remainder), 1),
+            ('-', _) => (Token::Minus, 1),
+            ('!', _) => (Token::Unary(UnaryOperator::Not), 1),
+            ('~', _) => (Token::Unary(UnaryOperator::Complement), 1),
+            ('(', _) => (Token::OpenParen, 1),
+            (')', _) => (Token::CloseParen, 1),
+            ('[', _) => (Token::OpenBracket, 1),
+            (']', _) => (Token::CloseBracket, 1),
+            ('{', _) => (Token::OpenBrace, 1),
+            ('}', _) => (Token::CloseBrace, 1),
+            _ => {return Err(format!("Unexpected '{}' at position {}", c, i))}
+        };
+        tokens.push(token);
+        i += length;
+    }
+    return Ok(tokens);
+}
+
+fn variable(name: &str) -> Option<Variable> {
+    return match name {
+        "a" => Some(Variable::A),
+        "x" => Some(Variable::X),
+        "y" => Some(Variable::Y),
+        "s" | "sp" => Some(Variable::S),
+        "pc" => Some(Variable::Pc),
+        "p" => Some(Variable::P),
+        "c" => Some(Variable::Carry),
+        "z" => Some(Variable::Zero),
+        "i" => Some(Variable::InterruptsDisabled),
+        "d" => Some(Variable::Decimal),
+        "v" => Some(Variable::Overflow),
+        "n" => Some(Variable::Negative),
+        "scanline" => Some(Variable::Scanline),
+        "cycle" => Some(Variable::Cycle),
+        "frame" => Some(Variable::Frame),
+        "irq" => Some(Variable::Irq),
+        "address" => Some(Variable::Address),
+        "value" => Some(Variable::Value),
+        _ => None
+    };
+}
+
+struct Parser {
+    tokens: Vec<Token>,
+    position: usize,
+}
+
+impl Parser {
+    fn peek(&self) -> Option<&Token> {
+        return self.tokens.get(self.position);
+    }
+
+    fn next(&mut self) -> Option<Token> {
+        let token = self.tokens.get(self.position).cloned();
+        self.position += 1;
+        return token;
+    }
+
+    fn expect(&mut self, expected: Token, description: &str) -> Result<(), String> {
+        return match self.next() {
+            Some(ref token) if *token == expected => Ok(()),
+            _ => Err(format!("Expected {}", description))
+        };
+    }
+
+    fn binary_operator(&self) -> Option<BinaryOperator> {
+        return match self.peek() {
+            Some(Token::Binary(operator)) => Some(*operator),
+            Some(Token::Minus) => Some(BinaryOperator::Subtract),
+            _ => None
+        };
+    }
+
+    // Precedence climbing: parses operands joined by operators at least this tightly bound
+    fn expression(&mut self, minimum_precedence: u8) -> Result<Node, String> {
+        let mut lhs = self.operand()?;
+        loop {
+            let operator = match self.binary_operator() {
+                Some(operator) if operator.precedence() >= minimum_precedence => operator,
+                _ => {return Ok(lhs)}
+            };
+            self.position += 1;
+            let rhs = self.expression(operator.precedence() + 1)?;
+            lhs = Node::Binary(operator, Box::new(lhs), Box::new(rhs));
+        }
+    }
+
+    fn operand(&mut self) -> Result<Node, String> {
+        return match self.next() {
+            Some(Token::Number(number)) => Ok(Node::Number(number)),
+            Some(Token::Name(name)) => match variable(&name) {
+                Some(variable) => Ok(Node::Variable(variable)),
+                None => Err(format!("Unknown name: {}", name))
+            },
+            Some(Token::Minus) => Ok(Node::Unary(UnaryOperator::Negate, Box::new(self.operand()?))),
+            Some(Token::Unary(operator)) => Ok(Node::Unary(operator, Box::new(self.operand()?))),
+            Some(Token::OpenParen) => {
+                let inner = self.expression(0)?;
+                self.expect(Token::CloseParen, "a closing )")?;
+                Ok(inner)
+            },
+            Some(Token::OpenBracket) => {
+                let address = self.expression(0)?;
+                self.expect(Token::CloseBracket, "a closing ]")?;
+                Ok(Node::ReadByte(Box::new(address)))
+            },
+            Some(Token::OpenBrace) => {
+                let address = self.expression(0)?;
+                self.expect(Token::CloseBrace, "a closing }")?;
+                Ok(Node::ReadWord(Box::new(address)))
+            },
+            Some(token) => Err(format!("Unexpected {:?}", token)),
+            None => Err("Unexpected end of expression".to_string())
+        };
+    }
+}
+
+#[derive(Clone, Debug, PartialEq)]
+pub struct Expression {
+    // The text this was parsed from, for display
+    pub source: String,
+    root: Node,
+}
+
+impl Expression {
+    pub fn parse(text: &str) -> Result<Expression, String> {
+        let mut parser = Parser{
+            tokens: tokenize(text)?,
+            position: 0,
+        };
+        if parser.tokens.len() == 0 {
+            return Err("Expression is empty".to_string());
+        }
+        let root = parser.expression(0)?;
+        if parser.position < parser.tokens.len() {
+            return Err(format!("Unexpected {:?} after the end of the expression", parser.tokens[parser.position]));
+        }
+        return Ok(Expression{
+            source: text.trim().to_string(),
+            root: root,
+        });
+    }
+
+    /// Evaluates against the current state of the NES. Address and value read as 0.
+    pub fn evaluate(&self, nes: &NesState) -> i64 {
+        return evaluate_node(&self.root, nes, 0, 0);
+    }
+
+    /// Evaluates on behalf of a memory access, which supplies address and value
+    pub fn evaluate_access(&self, nes: &NesState, address: u16, value: u8) -> i64 {
+        return evaluate_node(&self.root, nes, address, value);
+    }
+}
+
+fn read_variable(variable: Variable, nes: &NesState, address: u16, value: u8) -> i64 {
+    let registers = &nes.registers;
+    return match variable {
+        Variable::A => registers.a as i64,
+        Variable::X => registers.x as i64,
+        Variable::Y => registers.y as i64,
+        Variable::S => registers.s as i64,
+        Variable::Pc => registers.pc as i64,
+        Variable::P => {
+            (registers.flags.carry as i64) |
+            ((registers.flags.zero as i64) << 1) |
+            ((registers.flags.interrupts_disabled as i64) << 2) |
+            ((registers.flags.decimal as i64) << 3) |
+            (1 << 5) |
+            ((registers.flags.overflow as i64) << 6) |
+            ((registers.flags.negative as i64) << 7)
+        },
+        Variable::Carry => registers.flags.carry as i64,
+        Variable::Zero => registers.flags.zero as i64,
+        Variable::InterruptsDisabled => registers.flags.interrupts_disabled as i64,
+        Variable::Decimal => registers.flags.decimal as i64,
+        Variable::Overflow => registers.flags.overflow as i64,
+        Variable::Negative => registers.flags.negative as i64,
+        Variable::Scanline => nes.ppu.current_scanline as i64,
+        Variable::Cycle => nes.ppu.current_scanline_cycle as i64,
+        Variable::Frame => nes.ppu.current_frame as i64,
+        Variable::Irq => nes.mapper.irq_flag() as i64,
+        Variable::Address => address as i64,
+        Variable::Value => value as i64,
+    };
+}
+
+fn evaluate_node(node: &Node, nes: &NesState, address: u16, value: u8) -> i64 {
+    return match node {
+        Node::Number(number) => *number,
+        Node::Variable(variable) => read_variable(*variable, nes, address, value),
+        Node::ReadByte(inner) => {
+            let target = evaluate_node(inner, nes, address, value) as u16;
+            memory::debug_read_byte(nes, target) as i64
+        },
+        Node::ReadWord(inner) => {
+            let target = evaluate_node(inner, nes, address, value) as u16;
+            let low = memory::debug_read_byte(nes, target) as i64;
+            let high = memory::debug_read_byte(nes, target.wrapping_add(1)) as i64;
+            (high << 8) | low
+        },
+        Node::Unary(operator, inner) => {
+            let operand = evaluate_node(inner, nes, address, value);
+            match operator {
+                UnaryOperator::Negate => operand.wrapping_neg(),
+                UnaryOperator::Not => (operand == 0) as i64,
+                UnaryOperator::Complement => !operand,
+            }
+        },
+        Node::Binary(operator, lhs, rhs) => {
+            let lhs_value = evaluate_node(lhs, nes, address, value);
+            // Skip the right side when the left already decides the outcome
+            match operator {
+                BinaryOperator::And if lhs_value == 0 => 0,
+                BinaryOperator::Or if lhs_value != 0 => 1,
+                _ => operator.apply(lhs_value, evaluate_node(rhs, nes, address, value))
+            }
+        },
+    };
+}
+
+#[cfg(test)]
+mod tests {
+    use asm::Opcode::*;
+    use asm::AddressingMode::*;
+    use expression::*;
+    use mmc::test_rom::*;
+
+    fn nes() -> NesState {
+        let rom = TestRom::new(0, 0x8000, 0x2000);
+        return rom.run(vec![
+            Lda(Immediate(0x34)),
+            Sta(Absolute(0x0300)),
+            Lda(Immediate(0x12)),
+            Sta(Absolute(0x0301)),
+            Lda(Immediate(0x40)),
+            Ldx(Immediate(0x03)),
+        ]);
+    }
+
+    fn evaluate(text: &str) -> i64 {
+        return Expression::parse(text).unwrap().evaluate(&nes());
+    }
+
+    #[test]
+    fn binary_operators_follow_c_precedence() {
+        assert_eq!(evaluate("1 + 2 * 3"), 7);
+        assert_eq!(evaluate("(1 + 2) * 3"), 9);
+        assert_eq!(evaluate("8 - 2 - 1"), 5);
+        assert_eq!(evaluate("16 / 4 / 2"), 2);
+        assert_eq!(evaluate("1 << 2 + 1"), 8);
+        assert_eq!(evaluate("6 & 3 == 3"), 0);
+        assert_eq!(evaluate("1 | 2 ^ 3 & 1"), 3);
+        assert_eq!(evaluate("1 || 0 && 0"), 1);
+        assert_eq!(evaluate("3 < 4 == 1"), 1);
+        assert_eq!(evaluate("7 % 4 + 10 / 0"), 3);
+    }
+
+    #[test]
+    fn unary_operators_bind_to_the_next_operand() {
+        assert_eq!(evaluate("-3 + 5"), 2);
+        assert_eq!(evaluate("2 - -1"), 3);
+        assert_eq!(evaluate("- -4"), 4);
+        assert_eq!(evaluate("!0"), 1);
+        assert_eq!(evaluate("!5 + 1"), 1);
+        assert_eq!(evaluate("~0"), -1);
+        assert_eq!(evaluate("~$0F & $FF"), 0xF0);
+    }
+
+    #[test]
+    fn reads_numbers_registers_and_memory() {
+        assert_eq!(evaluate("$10 + 0x10 + 10"), 42);
+        assert_eq!(evaluate("A == $40 && x == 3"), 1);
+        assert_eq!(evaluate("[$0300]"), 0x34);
+        assert_eq!(evaluate("[$0300 + X - 2]"), 0x12);
+        assert_eq!(evaluate("{$0300}"), 0x1234);
+        let expression = Expression::parse("address == $2007 && value > 3").unwrap();
+        assert_eq!(expression.evaluate_access(&nes(), 0x2007, 4), 1);
+        assert_eq!(expression.evaluate_access(&nes(), 0x2007, 3), 0);
+    }
+
+    #[test]
+    fn rejects_malformed_expressions() {
+        for text in ["", "   ", "1 +", "(1 + 2", "[$10", "{$10", "1 2", "* 3", "bogus == 1", "a # 1", "$", "$FFFFFFFFFFFFFFFFFF"].iter() {
+            assert!(Expression::parse(text).is_err(), "{:?} should not parse", text);
+        }
+    }
+}
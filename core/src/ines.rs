@@ -10,6 +10,7 @@ use std::fmt;
 use mmc::mapper::Mirroring;
 use memoryblock::MemoryBlock;
 use memoryblock::MemoryType;
+use region::Region;
 
 #[derive(Debug)]
 pub enum INesError {
@@ -53,17 +54,97 @@ const INES_FLAGS_7: usize = 7;
 
 // here the constants diverge depending on type
 const INES1_PRG_RAM_SIZE: usize = 8;
-//const INES1_TV_SYSTEM: usize = 9;
+const INES1_TV_SYSTEM: usize = 9;
 //const INES1_FLAGS_10: usize = 10;
 
 const INES2_MAPPER_SUB_MSB: usize = 8;
 const INES2_PRG_CHR_MSB: usize = 9;
 const INES2_PRG_RAM: usize = 10;
 const INES2_CHR_RAM: usize = 11;
-//const INES2_CPU_PPU_TIMING: usize = 12;
-//const INES2_SYSTEM_TYPE: usize = 13;
-//const INES2_MISC_ROM_COUNT: usize = 14;
-//const INES2_DEFAULT_EXPANSION: usize = 15;
+const INES2_CPU_PPU_TIMING: usize = 12;
+const INES2_SYSTEM_TYPE: usize = 13;
+const INES2_MISC_ROM_COUNT: usize = 14;
+const INES2_DEFAULT_EXPANSION: usize = 15;
+
+// https://wiki.nesdev.com/w/index.php/NES_2.0#CPU.2FPPU_Timing
+#[derive(Copy, Clone, Debug, PartialEq)]
+pub enum CpuPpuTiming {
+    Ntsc,
+    Pal,
+    // The game adapts to whichever console it finds itself running on
+    MultipleRegion,
+    Dendy,
+}
+
+impl CpuPpuTiming {
+    /// The region a game with this timing should be run in. Multi-region games
+    /// are happy anywhere, so they get the most common one.
+    pub fn region(&self) -> Region {
+        return match self {
+            CpuPpuTiming::Ntsc => Region::Ntsc,
+            CpuPpuTiming::Pal => Region::Pal,
+            CpuPpuTiming::MultipleRegion => Region::Ntsc,
+            CpuPpuTiming::Dendy => Region::Dendy,
+        };
+    }
+}
+
+// https://wiki.nesdev.com/w/index.php/NES_2.0#Console_Type
+#[derive(Copy, Clone, Debug, PartialEq)]
+pub enum ConsoleType {
+    Nes,
+    VsSystem,
+    Playchoice10,
+    // Famiclones and other oddities, identified by the extended console type in byte 13
+    Extended(u8),
+}
+
+// https://wiki.nesdev.com/w/index.php/NES_2.0#Vs._System_Type
+#[derive(Copy, Clone, Debug, PartialEq)]
+pub enum VsPpuType {
+    Rp2c03b,
+    Rp2c03g,
+    Rp2c04_0001,
+    Rp2c04_0002,
+    Rp2c04_0003,
+    Rp2c04_0004,
+    Rc2c03b,
+    Rc2c03c,
+    Rc2c05_01,
+    Rc2c05_02,
+    Rc2c05_03,
+    Rc2c05_04,
+    Rc2c05_05,
+    Unknown(u8),
+}
+
+#[derive(Copy, Clone, Debug, PartialEq)]
+pub enum VsHardwareType {
+    UniSystem,
+    UniSystemRbiBaseball,
+    UniSystemTkoBoxing,
+    UniSystemSuperXevious,
+    UniSystemIceClimberJapan,
+    DualSystem,
+    DualSystemRaidOnBungelingBay,
+    Unknown(u8),
+}
+
+// https://wiki.nesdev.com/w/index.php/NES_2.0#Default_Expansion_Device
+// Only the devices we have some hope of emulating get their own entry.
+#[derive(Copy, Clone, Debug, PartialEq)]
+pub enum ExpansionDevice {
+    Unspecified,
+    StandardControllers,
+    FourScore,
+    FamicomFourPlayers,
+    VsSystem,
+    VsSystemSwapped,
+    VsZapper,
+    Zapper,
+    TwoZappers,
+    Other(u8),
+}
 
 impl INesHeader {
     pub fn from(raw_bytes: &[u8]) -> INesHeader {
@@ -330,6 +411,118 @@ impl INesHeader {
             _ => 0
         }
     }
+
+    fn _timing_ines1(&self) -> CpuPpuTiming {
+        // Byte 9 is rarely set, and the padding bytes of DiskDude! headers can't be trusted
+        if self.ines1_extended_attributes_valid() && self.raw_bytes[INES1_TV_SYSTEM] & 0b0000_0001 != 0 {
+            return CpuPpuTiming::Pal;
+        }
+        return CpuPpuTiming::Ntsc;
+    }
+
+    fn _timing_ines2(&self) -> CpuPpuTiming {
+        return match self.raw_bytes[INES2_CPU_PPU_TIMING] & 0b0000_0011 {
+            0 => CpuPpuTiming::Ntsc,
+            1 => CpuPpuTiming::Pal,
+            2 => CpuPpuTiming::MultipleRegion,
+            _ => CpuPpuTiming::Dendy,
+        };
+    }
+
+    pub fn timing(&self) -> CpuPpuTiming {
+        return match self.version() {
+            2 => self._timing_ines2(),
+            _ => self._timing_ines1(),
+        };
+    }
+
+    pub fn console_type(&self) -> ConsoleType {
+        if self.version() == 1 && !self.ines1_extended_attributes_valid() {
+            // Another DiskDude! casualty; byte 7 is probably garbage
+            return ConsoleType::Nes;
+        }
+        return match self.raw_bytes[INES_FLAGS_7] & 0b0000_0011 {
+            0 => ConsoleType::Nes,
+            1 => ConsoleType::VsSystem,
+            2 => ConsoleType::Playchoice10,
+            _ => {
+                if self.version() != 2 {
+                    return ConsoleType::Nes;
+                }
+                match self.raw_bytes[INES2_SYSTEM_TYPE] & 0b0000_1111 {
+                    0 => ConsoleType::Nes,
+                    1 => ConsoleType::VsSystem,
+                    2 => ConsoleType::Playchoice10,
+                    extended_type => ConsoleType::Extended(extended_type),
+                }
+            }
+        };
+    }
+
+    /// Only meaningful for Vs. System games with an NES 2.0 header
+    pub fn vs_ppu_type(&self) -> Option<VsPpuType> {
+        if self.version() != 2 || self.console_type() != ConsoleType::VsSystem {
+            return None;
+        }
+        return Some(match self.raw_bytes[INES2_SYSTEM_TYPE] & 0b0000_1111 {
+            0x0 => VsPpuType::Rp2c03b,
+            0x1 => VsPpuType::Rp2c03g,
+            0x2 => VsPpuType::Rp2c04_0001,
+            0x3 => VsPpuType::Rp2c04_0002,
+            0x4 => VsPpuType::Rp2c04_0003,
+            0x5 => VsPpuType::Rp2c04_0004,
+            0x6 => VsPpuType::Rc2c03b,
+            0x7 => VsPpuType::Rc2c03c,
+            0x8 => VsPpuType::Rc2c05_01,
+            0x9 => VsPpuType::Rc2c05_02,
+            0xA => VsPpuType::Rc2c05_03,
+            0xB => VsPpuType::Rc2c05_04,
+            0xC => VsPpuType::Rc2c05_05,
+            other => VsPpuType::Unknown(other),
+        });
+    }
+
+    /// Only meaningful for Vs. System games with an NES 2.0 header
+    pub fn vs_hardware_type(&self) -> Option<VsHardwareType> {
+        if self.version() != 2 || self.console_type() != ConsoleType::VsSystem {
+            return None;
+        }
+        return Some(match (self.raw_bytes[INES2_SYSTEM_TYPE] & 0b1111_0000) >> 4 {
+            0x0 => VsHardwareType::UniSystem,
+            0x1 => VsHardwareType::UniSystemRbiBaseball,
+            0x2 => VsHardwareType::UniSystemTkoBoxing,
+            0x3 => VsHardwareType::UniSystemSuperXevious,
+            0x4 => VsHardwareType::UniSystemIceClimberJapan,
+            0x5 => VsHardwareType::DualSystem,
+            0x6 => VsHardwareType::DualSystemRaidOnBungelingBay,
+            other => VsHardwareType::Unknown(other),
+        });
+    }
+
+    pub fn misc_rom_count(&self) -> u8 {
+        if self.version() != 2 {
+            return 0;
+        }
+        return self.raw_bytes[INES2_MISC_ROM_COUNT] & 0b0000_0011;
+    }
+
+    pub fn default_expansion_device(&self) -> ExpansionDevice {
+        if self.version() != 2 {
+            return ExpansionDevice::Unspecified;
+        }
+        return match self.raw_bytes[INES2_DEFAULT_EXPANSION] & 0b0011_1111 {
+            0x00 => ExpansionDevice::Unspecified,
+            0x01 => ExpansionDevice::StandardControllers,
+            0x02 => ExpansionDevice::FourScore,
+            0x03 => ExpansionDevice::FamicomFourPlayers,
+            0x04 => ExpansionDevice::VsSystem,
+            0x05 => ExpansionDevice::VsSystemSwapped,
+            0x07 => ExpansionDevice::VsZapper,
+            0x08 => ExpansionDevice::Zapper,
+            0x09 => ExpansionDevice::TwoZappers,
+            other => ExpansionDevice::Other(other),
+        };
+    }
 }
 
 #[derive(Clone)]
@@ -0,0 +1,166 @@
+// Devices plugged into the controller ports. The CPU talks to these through just a few
+// signals: writes to $4016 drive the OUT0 (strobe) line shared by both ports, while
+// reads from $4016 and $4017 return whatever the device in port 1 or 2 places on the
+// D0-D4 data lines, and pulse that port's clock line once the read completes.
+// Reference: https://www.nesdev.org/wiki/Input_devices
+
+use ppu::PpuState;
+use save_load::*;
+
+pub trait InputDevice: Send {
+    fn name(&self) -> &'static str;
+
+    /// Called whenever the CPU writes to $4016, with the new state of the strobe line
+    fn latch(&mut self, strobe: bool);
+    /// Called after each read from this device's port
+    fn clock(&mut self);
+    /// The current state of the D0-D4 lines, in the low 5 bits. Some devices watch the
+    /// picture being drawn, so this is given access to the PPU.
+    fn read(&self, ppu: &PpuState) -> u8;
+
+    // The buttons of a standard controller, in the order they are reported
+    // (A, B, Select, Start, Up, Down, Left, Right) from the low bit up. Devices without
+    // buttons may ignore these.
+    fn buttons(&self) -> u8 {return 0;}
+    fn set_buttons(&mut self, _buttons: u8) {}
+    // Four player adapters carry a second controller on each port: players 3 and 4
+    fn secondary_buttons(&self) -> u8 {return 0;}
+    fn set_secondary_buttons(&mut self, _buttons: u8) {}
+
+    // For light guns, the screen pixel they are pointed at. This may be off screen.
+    fn aim(&mut self, _x: i32, _y: i32) {}
+    fn set_trigger(&mut self, _pulled: bool) {}
+
+    /// Everything about the device, for save states
+    fn save_state(&self, _buff: &mut Vec<u8>) {}
+    fn load_state(&mut self, _buff: &mut &[u8]) -> Result<(), String> {return Ok(());}
+
+    /// Only the parts of the device the player controls (buttons held, where it is
+    /// pointed, etc), and none of its internal state. Used to replay past inputs.
+    fn save_input(&self, _buff: &mut Vec<u8>) {}
+    fn load_input(&mut self, _buff: &mut &[u8]) -> Result<(), String> {return Ok(());}
+}
+
+pub struct ControllerPort {
+    pub device: Box<dyn InputDevice>,
+}
+
+impl ControllerPort {
+    pub fn new(device: Box<dyn InputDevice>) -> ControllerPort {
+        return ControllerPort {
+            device: device,
+        };
+    }
+
+    pub fn plug(&mut self, device: Box<dyn InputDevice>) {
+        self.device = device;
+    }
+
+    pub fn latch(&mut self, strobe: bool) {
+        self.device.latch(strobe);
+    }
+
+    pub fn read(&mut self, ppu: &PpuState) -> u8 {
+        let data = self.device.read(ppu) & 0b0001_1111;
+        self.device.clock();
+        return data;
+    }
+
+    /// Reads the data lines without clocking the device
+    pub fn debug_read(&self, ppu: &PpuState) -> u8 {
+        return self.device.read(ppu) & 0b0001_1111;
+    }
+
+    pub fn save_state(&self, buff: &mut Vec<u8>) {
+        save_vec_u8(buff, self.device.name().as_bytes());
+        self.device.save_state(buff);
+    }
+
+    pub fn load_state(&mut self, buff: &mut &[u8]) -> Result<(), String> {
+        let mut name = vec![0u8; self.device.name().len()];
+        if load_vec_u8(buff, &mut name).is_err() || name != self.device.name().as_bytes() {
+            return Err(format!("Save state was made with a different device plugged in, expected a {}", self.device.name()));
+        }
+        return self.device.load_state(buff);
+    }
+}
+
+// The standard NES controller, and the basis of most other pads. Pressing the strobe
+// copies the buttons into an 8-bit shift register, which is then read out one bit at
+// a time on D0.
+pub struct StandardController {
+    pub buttons: u8,
+    pub shift_register: u8,
+    pub strobe: bool,
+}
+
+impl StandardController {
+    pub fn new() -> StandardController {
+        return StandardController {
+            buttons: 0,
+            shift_register: 0,
+            strobe: false,
+        };
+    }
+}
+
+impl InputDevice for StandardController {
+    fn name(&self) -> &'static str {
+        return "Standard Controller";
+    }
+
+    fn latch(&mut self, strobe: bool) {
+        self.strobe = strobe;
+        if strobe {
+            self.shift_register = self.buttons;
+        }
+    }
+
+    fn clock(&mut self) {
+        if self.strobe {
+            // While the strobe is held high the register is continuously reloaded, so
+            // every read reports the A button
+            self.shift_register = self.buttons;
+        }
+        // Once all 8 buttons are read out, official controllers report 1s from here on,
+        // which affects controller detection routines
+        self.shift_register = (self.shift_register >> 1) | 0x80;
+    }
+
+    fn read(&self, _ppu: &PpuState) -> u8 {
+        if self.strobe {
+            return self.buttons & 0x1;
+        }
+        return self.shift_register & 0x1;
+    }
+
+    fn buttons(&self) -> u8 {
+        return self.buttons;
+    }
+
+    fn set_buttons(&mut self, buttons: u8) {
+        self.buttons = buttons;
+    }
+
+    fn save_state(&self, buff: &mut Vec<u8>) {
+        save_u8(buff, self.buttons);
+        save_u8(buff, self.shift_register);
+        save_bool(buff, self.strobe);
+    }
+
+    fn load_state(&mut self, buff: &mut &[u8]) -> Result<(), String> {
+        self.buttons = load_u8(buff)?;
+        self.shift_register = load_u8(buff)?;
+        self.strobe = load_bool(buff)?;
+        return Ok(());
+    }
+
+    fn save_input(&self, buff: &mut Vec<u8>) {
+        save_u8(buff, self.buttons);
+    }
+
+    fn load_input(&mut self, buff: &mut &[u8]) -> Result<(), String> {
+        self.buttons = load_u8(buff)?;
+        return Ok(());
+    }
+}
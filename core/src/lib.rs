@@ -2,10 +2,16 @@ pub mod addressing;
 pub mod apu;
 pub mod asm;
 pub mod cartridge;
+pub mod cdl;
+pub mod cheats;
 pub mod cycle_cpu;
+pub mod debugger;
+pub mod disassembler;
+pub mod expression;
 pub mod fds;
 pub mod tracked_events;
 pub mod ines;
+pub mod input;
 pub mod memory;
 pub mod memoryblock;
 pub mod mmc;
@@ -15,4 +21,11 @@ pub mod opcodes;
 pub mod opcode_info;
 pub mod palettes;
 pub mod ppu;
-pub mod unofficial_opcodes;
\ No newline at end of file
+pub mod profiler;
+pub mod region;
+pub mod save_load;
+pub mod symbols;
+pub mod trace;
+pub mod unofficial_opcodes;
+pub mod zapper;
+pub mod multitap;
\ No newline at end of file
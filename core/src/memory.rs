@@ -1,4 +1,5 @@
 use nes::NesState;
+use save_load::*;
 
 pub struct CpuMemory {
     pub iram_raw: Vec<u8>,
@@ -17,6 +18,17 @@ impl CpuMemory {
             open_bus: 0,
         }
     }
+
+    pub fn save_state(&self, buff: &mut Vec<u8>) {
+        save_vec_u8(buff, &self.iram_raw);
+        save_u8(buff, self.open_bus);
+    }
+
+    pub fn load_state(&mut self, buff: &mut &[u8]) -> Result<(), String> {
+        load_vec_u8(buff, &mut self.iram_raw)?;
+        self.open_bus = load_u8(buff)?;
+        return Ok(());
+    }
 }
 
 pub fn debug_read_byte(nes: &NesState, address: u16) -> u8 {
@@ -40,11 +52,24 @@ pub fn debug_read_byte(nes: &NesState, address: u16) -> u8 {
     }
 
     let mapped_byte = nes.mapper.debug_read_cpu(address).unwrap_or(nes.memory.open_bus);
+    let mapped_byte = if address >= 0x8000 {nes.cheats.patch_rom_read(address, mapped_byte)} else {mapped_byte};
     return _read_byte(nes, address, mapped_byte);
 }
 
+// Reports a read to everything watching the bus
+fn snoop_read(nes: &mut NesState, address: u16, data: u8) {
+    nes.event_tracker.snoop_cpu_read(nes.registers.pc, address, data);
+    nes.debugger.snoop_cpu_read(nes.cpu.opcode_address, address, data);
+    match nes.cdl {
+        Some(ref mut cdl) => cdl.log_read(&*nes.mapper, address),
+        None => {}
+    }
+}
+
 pub fn read_byte(nes: &mut NesState, address: u16) -> u8 {
     let mapped_byte = nes.mapper.read_cpu(address).unwrap_or(nes.memory.open_bus);
+    // Game Genie style cheats sit between the cartridge and the CPU
+    let mapped_byte = if address >= 0x8000 {nes.cheats.patch_rom_read(address, mapped_byte)} else {mapped_byte};
 
     // This is a live read, handle any side effects
     match address {
@@ -56,20 +81,30 @@ pub fn read_byte(nes: &mut NesState, address: u16) -> u8 {
                     nes.ppu.write_toggle = false;
                     nes.ppu.latch = (nes.ppu.status & 0xE0) + (nes.ppu.latch & 0x1F);
                     nes.ppu.status = nes.ppu.status & 0x7F; // Clear VBlank bit
-                    nes.event_tracker.snoop_cpu_read(nes.registers.pc, address, nes.ppu.latch);
+                    let data = nes.ppu.latch;
+                    snoop_read(nes, address, data);
                     return nes.ppu.latch;
                 },
                 // OAMDATA
                 4 => {
                     nes.ppu.latch = nes.ppu.oam[nes.ppu.oam_addr as usize];
-                    nes.event_tracker.snoop_cpu_read(nes.registers.pc, address, nes.ppu.latch);
+                    let data = nes.ppu.latch;
+                    snoop_read(nes, address, data);
                 },
                 // PPUDATA
                 7 => {
                     let ppu_addr = nes.ppu.current_vram_address;
                     nes.ppu.latch = nes.ppu.read_latched_byte(&mut *nes.mapper, ppu_addr);
+                    let data = nes.ppu.latch;
+                    nes.debugger.snoop_ppu_read(nes.cpu.opcode_address, ppu_addr, data);
+                    // This was the CPU reading CHR, not the PPU drawing it
+                    nes.ppu.last_pattern_fetch = None;
+                    match nes.cdl {
+                        Some(ref mut cdl) if (ppu_addr & 0x3FFF) < 0x2000 => cdl.log_chr_read(&*nes.mapper, ppu_addr & 0x3FFF),
+                        _ => {}
+                    }
                     if nes.ppu.rendering_enabled() && 
-                    (nes.ppu.current_scanline == 261 ||
+                    (nes.ppu.current_scanline == nes.ppu.region.prerender_scanline() ||
                      nes.ppu.current_scanline <= 239) {
                         // Glitchy increment, a fine y and a coarse x 
                         nes.ppu.increment_coarse_x();
@@ -94,31 +129,17 @@ pub fn read_byte(nes: &mut NesState, address: u16) -> u8 {
         },
         0x4015 => {
             let apu_byte = nes.apu.read_register(address);
-            nes.event_tracker.snoop_cpu_read(nes.registers.pc, address, apu_byte);
+            snoop_read(nes, address, apu_byte);
             return apu_byte;
         },
         0x4016 => {
-            if nes.input_latch {
-                // strobe register is high, so copy input data to latch (probably bad if this
-                // actually occurs here, but it matches what real hardware would do)
-                nes.p1_data = nes.p1_input;
-            }
-            let result = 0x40 | (nes.p1_data & 0x1);
-            // Standard Controllers set extra bits to 1, which affects controller detection routines
-            nes.p1_data = (nes.p1_data >> 1) | 0x80; 
-            nes.event_tracker.snoop_cpu_read(nes.registers.pc, address, result);
+            let result = controller_port_high_bits(nes, address) | nes.port1.read(&nes.ppu);
+            snoop_read(nes, address, result);
             return result;
         },
         0x4017 => {
-            if nes.input_latch {
-                // strobe register is high, so copy input data to latch (probably bad if this
-                // actually occurs here, but it matches what real hardware would do)
-                nes.p2_data = nes.p2_input;
-            }
-            let result = 0x40 | (nes.p2_data & 0x1);
-            // Standard Controllers set extra bits to 1, which affects controller detection routines
-            nes.p2_data = (nes.p2_data >> 1) | 0x80; 
-            nes.event_tracker.snoop_cpu_read(nes.registers.pc, address, result);
+            let result = controller_port_high_bits(nes, address) | nes.port2.read(&nes.ppu);
+            snoop_read(nes, address, result);
             return result;
         },
         _ => {}
@@ -126,10 +147,27 @@ pub fn read_byte(nes: &mut NesState, address: u16) -> u8 {
 
     let byte = _read_byte(nes, address, mapped_byte);
     nes.memory.open_bus = byte;
-    nes.event_tracker.snoop_cpu_read(nes.registers.pc, address, byte);
+    snoop_read(nes, address, byte);
     return byte;
 }
 
+// Everything but the controller data lines. On a home console bit 6 is left over from
+// the open bus; a Vs. System uses the spare bits for its coin slots and DIP switches.
+// Reference: https://www.nesdev.org/wiki/Vs._System#Registers
+fn controller_port_high_bits(nes: &NesState, address: u16) -> u8 {
+    if !nes.vs_system {
+        return 0x40;
+    }
+    if address == 0x4016 {
+        let dip_bits = (nes.vs_dip_switches & 0b0000_0011) << 3;
+        let coin_1 = if nes.vs_coin_timers[0] > 0 {0b0010_0000} else {0};
+        let coin_2 = if nes.vs_coin_timers[1] > 0 {0b0100_0000} else {0};
+        return dip_bits | coin_1 | coin_2;
+    } else {
+        return nes.vs_dip_switches & 0b1111_1100;
+    }
+}
+
 fn _read_byte(nes: &NesState, address: u16, mapped_byte: u8) -> u8 {
     match address {
         0x0000 ..= 0x1FFF => {
@@ -145,7 +183,10 @@ fn _read_byte(nes: &NesState, address: u16, mapped_byte: u8) -> u8 {
                 },
                 // PPUSTATUS
                 2 => {
-                    return (nes.ppu.status & 0xE0) + (nes.ppu.latch & 0x1F);
+                    return match nes.ppu.model.status_id() {
+                        Some(id) => (nes.ppu.status & 0xE0) | id,
+                        None => (nes.ppu.status & 0xE0) + (nes.ppu.latch & 0x1F)
+                    };
                 },
                 // OAMDATA
                 4 => {
@@ -162,11 +203,11 @@ fn _read_byte(nes: &NesState, address: u16, mapped_byte: u8) -> u8 {
             return mapped_byte;
         },
         0x4016 => {
-            let result = 0x40 | (nes.p1_data & 0x1);
+            let result = controller_port_high_bits(nes, address) | nes.port1.debug_read(&nes.ppu);
             return result;
         },
         0x4017 => {
-            let result = 0x40 | (nes.p2_data & 0x1);
+            let result = controller_port_high_bits(nes, address) | nes.port2.debug_read(&nes.ppu);
             return result;
         },
         0x4020 ..= 0xFFFF => {
@@ -182,6 +223,7 @@ pub fn write_byte(nes: &mut NesState, address: u16, data: u8) {
     // Track every byte written, unconditionally
     // (filtering is done inside the tracker)
     nes.event_tracker.snoop_cpu_write(nes.registers.pc, address, data);
+    nes.debugger.snoop_cpu_write(nes.cpu.opcode_address, address, data);
 
     // The mapper *always* sees the write. Even to RAM, and even to internal registers.
     // Most mappers ignore writes to addresses below 0x6000. Some (notably MMC5) do not.
@@ -190,7 +232,10 @@ pub fn write_byte(nes: &mut NesState, address: u16, data: u8) {
         0x0000 ..= 0x1FFF => nes.memory.iram_raw[(address & 0x7FF) as usize] = data,
         0x2000 ..= 0x3FFF => {
             // PPU
-            let ppu_reg = address & 0x7;
+            let mut ppu_reg = address & 0x7;
+            if nes.ppu.model.swaps_control_and_mask() && ppu_reg < 2 {
+                ppu_reg ^= 1;
+            }
             nes.ppu.latch = data;
             match ppu_reg {
                 // PPUCTRL
@@ -262,7 +307,7 @@ pub fn write_byte(nes: &mut NesState, address: u16, data: u8) {
                 7 => {
                     let ppu_addr = nes.ppu.current_vram_address;
                     if nes.ppu.rendering_enabled() && 
-                    (nes.ppu.current_scanline == 261 ||
+                    (nes.ppu.current_scanline == nes.ppu.region.prerender_scanline() ||
                     nes.ppu.current_scanline <= 239) {
                         // Glitchy increment, a fine y and a coarse x 
                         nes.ppu.increment_coarse_x();
@@ -277,6 +322,7 @@ pub fn write_byte(nes: &mut NesState, address: u16, data: u8) {
                         nes.ppu.current_vram_address &= 0b0111_1111_1111_1111;
                     }
                     nes.ppu.write_byte(&mut *nes.mapper, ppu_addr, data);
+                    nes.debugger.snoop_ppu_write(nes.cpu.opcode_address, ppu_addr, data);
 
                     // Perform a dummy access immediately, to simulte the behavior of the PPU
                     // address lines changing, so the mapper can react accordingly
@@ -307,10 +353,8 @@ pub fn write_byte(nes: &mut NesState, address: u16, data: u8) {
         0x4016 => {
             // Input latch
             nes.input_latch = data & 0x1 != 0;
-            if nes.input_latch {
-                nes.p1_data = nes.p1_input;
-                nes.p2_data = nes.p2_input;
-            }
+            nes.port1.latch(nes.input_latch);
+            nes.port2.latch(nes.input_latch);
         },
         0x4017 => {
             nes.apu.write_register(address, data);
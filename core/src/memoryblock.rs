@@ -1,3 +1,5 @@
+use save_load::*;
+
 /// Represents one contiguous block of memory, typically residing on a single
 /// physical chip. Implementations have varying behavior, but provide one
 /// consistent guarantee: all memory access will return some value, possibly
@@ -72,6 +74,20 @@ impl MemoryBlock {
         return self.wrapping_read(effective_address);
     }
 
+    /// Where in the block wrapping_read would find this address, for debuggers which
+    /// want to know which physical byte is being accessed
+    pub fn wrapping_address(&self, address: usize) -> Option<usize> {
+        if self.bytes.len() == 0 {
+            return None;
+        }
+        return Some(address % self.len());
+    }
+
+    pub fn banked_address(&self, bank_size: usize, bank_index: usize, offset: usize) -> Option<usize> {
+        let effective_address = (bank_size * bank_index) + (offset % bank_size);
+        return self.wrapping_address(effective_address);
+    }
+
     pub fn banked_write(&mut self, bank_size: usize, bank_index: usize, offset: usize, data: u8) {
         let effective_address = (bank_size * bank_index) + (offset % bank_size);
         self.wrapping_write(effective_address, data);
@@ -84,5 +100,23 @@ impl MemoryBlock {
     pub fn as_mut_vec(&mut self) -> &mut Vec<u8> {
         return &mut self.bytes;
     }
-}
 
+    /// Writes the contents of this block to a save state. ROM is skipped, as it
+    /// cannot change and is instead verified by checksum.
+    pub fn save_state(&self, buff: &mut Vec<u8>) {
+        if !self.readonly {
+            save_vec_u8(buff, &self.bytes);
+        }
+    }
+
+    pub fn load_state(&mut self, buff: &mut &[u8]) -> Result<(), String> {
+        if !self.readonly {
+            load_vec_u8(buff, &mut self.bytes)?;
+        }
+        return Ok(());
+    }
+
+    pub fn checksum(&self) -> u32 {
+        return crc32(0, &self.bytes);
+    }
+}
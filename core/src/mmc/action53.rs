@@ -6,6 +6,7 @@ use memoryblock::MemoryBlock;
 
 use mmc::mapper::*;
 use mmc::mirroring;
+use save_load::*;
 
 pub struct Action53 {
     prg_rom: MemoryBlock,
@@ -106,6 +107,20 @@ impl Mapper for Action53 {
         }
     }
 
+    fn prg_rom_address(&self, address: u16) -> Option<usize> {
+        match address {
+            0x8000 ..= 0xFFFF => {self.prg_rom.wrapping_address(self.prg_address(address))},
+            _ => None
+        }
+    }
+
+    fn chr_rom_address(&self, address: u16) -> Option<usize> {
+        match address {
+            0x0000 ..= 0x1FFF if self.chr.is_readonly() => {self.chr.wrapping_address(self.chr_address(address))},
+            _ => None
+        }
+    }
+
     fn write_cpu(&mut self, address: u16, data: u8) {
         match address {
             0x5000 ..= 0x5FFF => {self.register_select = data & 0x81;},
@@ -168,4 +183,35 @@ impl Mapper for Action53 {
             _ => {}
         }
     }
+
+    fn rom_checksum(&self) -> u32 {
+        return self.prg_rom.checksum();
+    }
+
+    fn save_state(&self, buff: &mut Vec<u8>) {
+        self.prg_ram.save_state(buff);
+        self.chr.save_state(buff);
+        save_vec_u8(buff, &self.vram);
+        save_u8(buff, self.register_select);
+        save_u8(buff, self.mirroring_mode);
+        save_usize(buff, self.chr_ram_a13_a14);
+        save_usize(buff, self.prg_inner_bank);
+        save_usize(buff, self.prg_outer_bank);
+        save_u8(buff, self.prg_mode);
+        save_usize(buff, self.prg_outer_bank_size);
+    }
+
+    fn load_state(&mut self, buff: &mut &[u8]) -> Result<(), String> {
+        self.prg_ram.load_state(buff)?;
+        self.chr.load_state(buff)?;
+        load_vec_u8(buff, &mut self.vram)?;
+        self.register_select = load_u8(buff)?;
+        self.mirroring_mode = load_u8(buff)?;
+        self.chr_ram_a13_a14 = load_usize(buff)?;
+        self.prg_inner_bank = load_usize(buff)?;
+        self.prg_outer_bank = load_usize(buff)?;
+        self.prg_mode = load_u8(buff)?;
+        self.prg_outer_bank_size = load_usize(buff)?;
+        return Ok(());
+    }
 }
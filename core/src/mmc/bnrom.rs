@@ -7,6 +7,7 @@ use ines::INesCartridge;
 use memoryblock::MemoryBlock;
 
 use mmc::mapper::*;
+use save_load::*;
 use mmc::mirroring;
 
 pub struct BnRom {
@@ -50,6 +51,20 @@ impl Mapper for BnRom {
         }
     }
 
+    fn prg_rom_address(&self, address: u16) -> Option<usize> {
+        match address {
+            0x8000 ..= 0xFFFF => {self.prg_rom.banked_address(0x8000, self.prg_bank, (address - 0x8000) as usize)},
+            _ => None
+        }
+    }
+
+    fn chr_rom_address(&self, address: u16) -> Option<usize> {
+        match address {
+            0x0000 ..= 0x1FFF if self.chr.is_readonly() => {self.chr.wrapping_address(address as usize)},
+            _ => None
+        }
+    }
+
     fn write_cpu(&mut self, address: u16, data: u8) {
         match address {
             0x8000 ..= 0xFFFF => {self.prg_bank = data as usize;}
@@ -80,4 +95,23 @@ impl Mapper for BnRom {
             _ => {}
         }
     }
+
+    fn rom_checksum(&self) -> u32 {
+        return self.prg_rom.checksum();
+    }
+
+    fn save_state(&self, buff: &mut Vec<u8>) {
+        self.chr.save_state(buff);
+        save_mirroring(buff, self.mirroring);
+        save_usize(buff, self.prg_bank);
+        save_vec_u8(buff, &self.vram);
+    }
+
+    fn load_state(&mut self, buff: &mut &[u8]) -> Result<(), String> {
+        self.chr.load_state(buff)?;
+        self.mirroring = load_mirroring(buff)?;
+        self.prg_bank = load_usize(buff)?;
+        load_vec_u8(buff, &mut self.vram)?;
+        return Ok(());
+    }
 }
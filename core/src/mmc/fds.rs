@@ -5,6 +5,7 @@ use fds::FdsFile;
 
 use mmc::mapper::*;
 use mmc::mirroring;
+use save_load::*;
 
 use apu::AudioChannelState;
 use apu::PlaybackRate;
@@ -59,6 +60,8 @@ pub struct FdsMapper {
 
     old_4025: u8,
 
+    rom_checksum: u32,
+
     debug_old_cpuread: u16,
     debug_mode: bool,
 
@@ -69,8 +72,11 @@ impl FdsMapper {
     pub fn from_fds(fds: FdsFile) -> Result<FdsMapper, String> {
         // FOR NOW, use just the first disk and ignore the rest
         let mut expanded_disks = Vec::new();
+        let mut rom_checksum = 0;
         for i in 0 .. fds.disk_sides.len() {
             expanded_disks.push(expand_disk_image(&fds.disk_sides[i]));
+            // Disks are modified as the game saves, so identify the game by its original image
+            rom_checksum = crc32(rom_checksum, &fds.disk_sides[i]);
         }
 
         return Ok(FdsMapper {
@@ -113,6 +119,8 @@ impl FdsMapper {
 
             old_4025: 0,
 
+            rom_checksum: rom_checksum,
+
             debug_old_cpuread: 0,
             debug_mode: false,
 
@@ -538,6 +546,100 @@ impl Mapper for FdsMapper {
     fn record_expansion_audio_output(&mut self, _nes_sample: f32) {
         self.audio.record_current_output();
     }
+
+    fn rom_checksum(&self) -> u32 {
+        return self.rom_checksum;
+    }
+
+    fn save_state(&self, buff: &mut Vec<u8>) {
+        save_vec_u8(buff, &self.prg_ram);
+        save_vec_u8(buff, &self.chr);
+
+        save_mirroring(buff, self.mirroring);
+        save_vec_u8(buff, &self.vram);
+
+        save_u16(buff, self.timer_reload_value);
+        save_u16(buff, self.timer_current_value);
+        save_bool(buff, self.timer_enabled);
+        save_bool(buff, self.timer_repeat);
+        save_bool(buff, self.timer_pending);
+        save_bool(buff, self.enable_disk_registers);
+
+        save_u8(buff, self.write_buffer);
+        save_u8(buff, self.read_buffer);
+        save_u8(buff, self.expansion_port_buffer);
+
+        // Disks are writable, so every side is stored in full
+        for disk in self.disk_images.iter() {
+            save_vec_u8(buff, disk);
+        }
+        save_usize(buff, self.current_side);
+        save_usize(buff, self.desired_side);
+        save_u32(buff, self.disk_change_cooldown);
+
+        save_usize(buff, self.head_position);
+        save_bool(buff, self.rewinding);
+        save_bool(buff, self.motor_on);
+        save_bool(buff, self.disk_irq_enabled);
+        save_bool(buff, self.disk_irq_pending);
+        save_bool(buff, self.byte_transfer_flag);
+        save_bool(buff, self.write_mode);
+        save_i16(buff, self.motor_delay_counter);
+        save_bool(buff, self.disk_ready_flag);
+        save_bool(buff, self.transfer_reset_flag);
+        save_bool(buff, self.transfer_active_flag);
+        save_u16(buff, self.checksum);
+        save_bool(buff, self.crc_control);
+
+        save_u8(buff, self.old_4025);
+
+        self.audio.save_state(buff);
+    }
+
+    fn load_state(&mut self, buff: &mut &[u8]) -> Result<(), String> {
+        load_vec_u8(buff, &mut self.prg_ram)?;
+        load_vec_u8(buff, &mut self.chr)?;
+
+        self.mirroring = load_mirroring(buff)?;
+        load_vec_u8(buff, &mut self.vram)?;
+
+        self.timer_reload_value = load_u16(buff)?;
+        self.timer_current_value = load_u16(buff)?;
+        self.timer_enabled = load_bool(buff)?;
+        self.timer_repeat = load_bool(buff)?;
+        self.timer_pending = load_bool(buff)?;
+        self.enable_disk_registers = load_bool(buff)?;
+
+        self.write_buffer = load_u8(buff)?;
+        self.read_buffer = load_u8(buff)?;
+        self.expansion_port_buffer = load_u8(buff)?;
+
+        for disk in self.disk_images.iter_mut() {
+            load_vec_u8(buff, disk)?;
+        }
+        self.current_side = load_usize(buff)?;
+        self.desired_side = load_usize(buff)?;
+        self.disk_change_cooldown = load_u32(buff)?;
+
+        self.head_position = load_usize(buff)?;
+        self.rewinding = load_bool(buff)?;
+        self.motor_on = load_bool(buff)?;
+        self.disk_irq_enabled = load_bool(buff)?;
+        self.disk_irq_pending = load_bool(buff)?;
+        self.byte_transfer_flag = load_bool(buff)?;
+        self.write_mode = load_bool(buff)?;
+        self.motor_delay_counter = load_i16(buff)?;
+        self.disk_ready_flag = load_bool(buff)?;
+        self.transfer_reset_flag = load_bool(buff)?;
+        self.transfer_active_flag = load_bool(buff)?;
+        self.checksum = load_u16(buff)?;
+        self.crc_control = load_bool(buff)?;
+
+        self.old_4025 = load_u8(buff)?;
+
+        self.audio.load_state(buff)?;
+        return Ok(());
+    }
 }
 
 pub fn expand_disk_image(compact_disk_image: &Vec<u8>) -> Vec<u8> {
@@ -1000,6 +1102,99 @@ impl FdsAudio {
             _ => {}
         }
     }
+
+    pub fn save_state(&self, buff: &mut Vec<u8>) {
+        save_bool(buff, self.enable_sound_registers);
+        save_vec_u8(buff, &self.wavetable_ram);
+
+        save_u8(buff, self.volume_envelope_output);
+        save_u8(buff, self.volume_envelope_value);
+        save_bool(buff, self.volume_envelope_positive);
+        save_bool(buff, self.volume_envelope_disabled);
+
+        save_usize(buff, self.volume_envelope_counter_current);
+        save_usize(buff, self.volume_envelope_counter_initial);
+
+        save_usize(buff, self.frequency);
+        save_bool(buff, self.frequency_envelope_disable);
+        save_bool(buff, self.frequency_halt);
+
+        save_usize(buff, self.frequency_accumulator);
+
+        save_u8(buff, self.mod_envelope_output);
+        save_u8(buff, self.mod_envelope_value);
+        save_bool(buff, self.mod_envelope_positive);
+        save_bool(buff, self.mod_envelope_disabled);
+
+        save_usize(buff, self.mod_accumulator);
+
+        save_usize(buff, self.mod_envelope_counter_current);
+        save_usize(buff, self.mod_envelope_counter_initial);
+
+        save_i8(buff, self.mod_counter);
+
+        save_usize(buff, self.mod_frequency);
+        save_bool(buff, self.mod_always_carry);
+        save_bool(buff, self.mod_table_halt);
+
+        save_vec_u8(buff, &self.mod_table);
+        save_u8(buff, self.master_volume);
+        save_bool(buff, self.wave_write_enabled);
+
+        save_u8(buff, self.master_envelope_speed);
+
+        save_usize(buff, self.mod_position);
+        save_usize(buff, self.wave_position);
+
+        save_f32(buff, self.current_output);
+    }
+
+    pub fn load_state(&mut self, buff: &mut &[u8]) -> Result<(), String> {
+        self.enable_sound_registers = load_bool(buff)?;
+        load_vec_u8(buff, &mut self.wavetable_ram)?;
+
+        self.volume_envelope_output = load_u8(buff)?;
+        self.volume_envelope_value = load_u8(buff)?;
+        self.volume_envelope_positive = load_bool(buff)?;
+        self.volume_envelope_disabled = load_bool(buff)?;
+
+        self.volume_envelope_counter_current = load_usize(buff)?;
+        self.volume_envelope_counter_initial = load_usize(buff)?;
+
+        self.frequency = load_usize(buff)?;
+        self.frequency_envelope_disable = load_bool(buff)?;
+        self.frequency_halt = load_bool(buff)?;
+
+        self.frequency_accumulator = load_usize(buff)?;
+
+        self.mod_envelope_output = load_u8(buff)?;
+        self.mod_envelope_value = load_u8(buff)?;
+        self.mod_envelope_positive = load_bool(buff)?;
+        self.mod_envelope_disabled = load_bool(buff)?;
+
+        self.mod_accumulator = load_usize(buff)?;
+
+        self.mod_envelope_counter_current = load_usize(buff)?;
+        self.mod_envelope_counter_initial = load_usize(buff)?;
+
+        self.mod_counter = load_i8(buff)?;
+
+        self.mod_frequency = load_usize(buff)?;
+        self.mod_always_carry = load_bool(buff)?;
+        self.mod_table_halt = load_bool(buff)?;
+
+        load_vec_u8(buff, &mut self.mod_table)?;
+        self.master_volume = load_u8(buff)?;
+        self.wave_write_enabled = load_bool(buff)?;
+
+        self.master_envelope_speed = load_u8(buff)?;
+
+        self.mod_position = load_usize(buff)?;
+        self.wave_position = load_usize(buff)?;
+
+        self.current_output = load_f32(buff)?;
+        return Ok(());
+    }
 }
 
 impl AudioChannelState for FdsAudio {
@@ -6,6 +6,7 @@ use memoryblock::MemoryBlock;
 
 use mmc::mapper::*;
 use mmc::mirroring;
+use save_load::*;
 
 use apu::AudioChannelState;
 use apu::PlaybackRate;
@@ -96,6 +97,26 @@ impl Mapper for Fme7 {
         }
     }
 
+    fn prg_rom_address(&self, address: u16) -> Option<usize> {
+        match address {
+            0x6000 ..= 0x7FFF if !self.prg_ram_selected => self.prg_rom.banked_address(0x2000, self.prg_banks[0], (address - 0x6000) as usize),
+            0x8000 ..= 0x9FFF => self.prg_rom.banked_address(0x2000, self.prg_banks[1], (address - 0x8000) as usize),
+            0xA000 ..= 0xBFFF => self.prg_rom.banked_address(0x2000, self.prg_banks[2], (address - 0xA000) as usize),
+            0xC000 ..= 0xDFFF => self.prg_rom.banked_address(0x2000, self.prg_banks[3], (address - 0xC000) as usize),
+            0xE000 ..= 0xFFFF => self.prg_rom.banked_address(0x2000, 0xFF, (address - 0xE000) as usize),
+            _ => None
+        }
+    }
+
+    fn chr_rom_address(&self, address: u16) -> Option<usize> {
+        match address {
+            0x0000 ..= 0x1FFF if self.chr_rom.is_readonly() => {
+                self.chr_rom.banked_address(0x400, self.chr_banks[(address >> 10) as usize], address as usize)
+            },
+            _ => None
+        }
+    }
+
     fn clock_cpu(&mut self) {
         self.clock_irq();
         self.expansion_audio_chip.clock();
@@ -223,6 +244,47 @@ impl Mapper for Fme7 {
     fn record_expansion_audio_output(&mut self, _nes_sample: f32) {
         self.expansion_audio_chip.record_output();
     }
+
+    fn rom_checksum(&self) -> u32 {
+        return self.prg_rom.checksum();
+    }
+
+    fn save_state(&self, buff: &mut Vec<u8>) {
+        self.prg_ram.save_state(buff);
+        self.chr_rom.save_state(buff);
+        save_u8(buff, self.command);
+        save_vec_usize(buff, &self.chr_banks);
+        save_vec_usize(buff, &self.prg_banks);
+        save_bool(buff, self.prg_ram_enabled);
+        save_bool(buff, self.prg_ram_selected);
+        save_vec_u8(buff, &self.vram);
+        save_mirroring(buff, self.mirroring);
+        save_bool(buff, self.irq_enabled);
+        save_bool(buff, self.irq_counter_enabled);
+        save_u16(buff, self.irq_counter);
+        save_bool(buff, self.irq_pending);
+        save_u8(buff, self.audio_command_select);
+        self.expansion_audio_chip.save_state(buff);
+    }
+
+    fn load_state(&mut self, buff: &mut &[u8]) -> Result<(), String> {
+        self.prg_ram.load_state(buff)?;
+        self.chr_rom.load_state(buff)?;
+        self.command = load_u8(buff)?;
+        load_vec_usize(buff, &mut self.chr_banks)?;
+        load_vec_usize(buff, &mut self.prg_banks)?;
+        self.prg_ram_enabled = load_bool(buff)?;
+        self.prg_ram_selected = load_bool(buff)?;
+        load_vec_u8(buff, &mut self.vram)?;
+        self.mirroring = load_mirroring(buff)?;
+        self.irq_enabled = load_bool(buff)?;
+        self.irq_counter_enabled = load_bool(buff)?;
+        self.irq_counter = load_u16(buff)?;
+        self.irq_pending = load_bool(buff)?;
+        self.audio_command_select = load_u8(buff)?;
+        self.expansion_audio_chip.load_state(buff)?;
+        return Ok(());
+    }
 }
 
 pub struct ToneGenerator {
@@ -256,6 +318,19 @@ impl ToneGenerator {
     pub fn output(&self) -> u8 {
         return self.output;
     }
+
+    pub fn save_state(&self, buff: &mut Vec<u8>) {
+        save_u16(buff, self.period_compare);
+        save_u16(buff, self.period_current);
+        save_u8(buff, self.output);
+    }
+
+    pub fn load_state(&mut self, buff: &mut &[u8]) -> Result<(), String> {
+        self.period_compare = load_u16(buff)?;
+        self.period_current = load_u16(buff)?;
+        self.output = load_u8(buff)?;
+        return Ok(());
+    }
 }
 
 pub struct NoiseGenerator {
@@ -293,6 +368,19 @@ impl NoiseGenerator {
     pub fn output(&self) -> u8 {
         return (self.shift_register & 0b1) as u8;
     }
+
+    pub fn save_state(&self, buff: &mut Vec<u8>) {
+        save_u16(buff, self.period_compare);
+        save_u16(buff, self.period_current);
+        save_u32(buff, self.shift_register);
+    }
+
+    pub fn load_state(&mut self, buff: &mut &[u8]) -> Result<(), String> {
+        self.period_compare = load_u16(buff)?;
+        self.period_current = load_u16(buff)?;
+        self.shift_register = load_u32(buff)?;
+        return Ok(());
+    }
 }
 
 pub struct EnvelopeGenerator {
@@ -398,6 +486,31 @@ impl EnvelopeGenerator {
     pub fn output(&self) -> usize {
         return self.current_value as usize;
     }
+
+    pub fn save_state(&self, buff: &mut Vec<u8>) {
+        save_u16(buff, self.period_compare);
+        save_u16(buff, self.period_current);
+        save_bool(buff, self.continue_flag);
+        save_bool(buff, self.attack_flag);
+        save_bool(buff, self.alternate_flag);
+        save_bool(buff, self.hold_flag);
+        save_i8(buff, self.current_value);
+        save_bool(buff, self.increasing);
+        save_bool(buff, self.holding);
+    }
+
+    pub fn load_state(&mut self, buff: &mut &[u8]) -> Result<(), String> {
+        self.period_compare = load_u16(buff)?;
+        self.period_current = load_u16(buff)?;
+        self.continue_flag = load_bool(buff)?;
+        self.attack_flag = load_bool(buff)?;
+        self.alternate_flag = load_bool(buff)?;
+        self.hold_flag = load_bool(buff)?;
+        self.current_value = load_i8(buff)?;
+        self.increasing = load_bool(buff)?;
+        self.holding = load_bool(buff)?;
+        return Ok(());
+    }
 }
 
 pub struct YmChannel {
@@ -439,6 +552,27 @@ impl YmChannel {
         self.edge_buffer.push(self.tone.last_edge as i16);
         self.tone.last_edge = false;
     }
+
+    pub fn save_state(&self, buff: &mut Vec<u8>) {
+        self.tone.save_state(buff);
+        save_bool(buff, self.tone_enabled);
+        save_bool(buff, self.noise_enabled);
+        save_bool(buff, self.envelope_enabled);
+        save_u8(buff, self.static_volume);
+        save_usize(buff, self.effective_volume);
+        save_f32(buff, self.effective_amplitude);
+    }
+
+    pub fn load_state(&mut self, buff: &mut &[u8]) -> Result<(), String> {
+        self.tone.load_state(buff)?;
+        self.tone_enabled = load_bool(buff)?;
+        self.noise_enabled = load_bool(buff)?;
+        self.envelope_enabled = load_bool(buff)?;
+        self.static_volume = load_u8(buff)?;
+        self.effective_volume = load_usize(buff)?;
+        self.effective_amplitude = load_f32(buff)?;
+        return Ok(());
+    }
 }
 
 impl AudioChannelState for YmChannel {
@@ -674,4 +808,23 @@ impl YM2149F {
             _ => {}
         }
     }
+
+    pub fn save_state(&self, buff: &mut Vec<u8>) {
+        self.channel_a.save_state(buff);
+        self.channel_b.save_state(buff);
+        self.channel_c.save_state(buff);
+        self.noise.save_state(buff);
+        self.envelope.save_state(buff);
+        save_u8(buff, self.clock_divider_counter);
+    }
+
+    pub fn load_state(&mut self, buff: &mut &[u8]) -> Result<(), String> {
+        self.channel_a.load_state(buff)?;
+        self.channel_b.load_state(buff)?;
+        self.channel_c.load_state(buff)?;
+        self.noise.load_state(buff)?;
+        self.envelope.load_state(buff)?;
+        self.clock_divider_counter = load_u8(buff)?;
+        return Ok(());
+    }
 }
\ No newline at end of file
@@ -6,6 +6,7 @@ use ines::INesCartridge;
 use memoryblock::MemoryBlock;
 
 use mmc::mapper::*;
+use save_load::*;
 use mmc::mirroring;
 
 pub struct INes31 {
@@ -56,6 +57,23 @@ impl Mapper for INes31 {
         }
     }
 
+    fn prg_rom_address(&self, address: u16) -> Option<usize> {
+        match address {
+            0x8000 ..= 0xFFFF => {
+                let slot = ((address - 0x8000) >> 12) as usize;
+                self.prg_rom.banked_address(0x1000, self.prg_banks[slot], (address & 0x0FFF) as usize)
+            },
+            _ => None
+        }
+    }
+
+    fn chr_rom_address(&self, address: u16) -> Option<usize> {
+        match address {
+            0x0000 ..= 0x1FFF if self.chr.is_readonly() => {self.chr.wrapping_address(address as usize)},
+            _ => None
+        }
+    }
+
     fn write_cpu(&mut self, address: u16, data: u8) {
         match address {
             0x5FF8 => {self.prg_banks[0] = data as usize},
@@ -93,4 +111,23 @@ impl Mapper for INes31 {
             _ => {}
         }
     }
+
+    fn rom_checksum(&self) -> u32 {
+        return self.prg_rom.checksum();
+    }
+
+    fn save_state(&self, buff: &mut Vec<u8>) {
+        self.chr.save_state(buff);
+        save_mirroring(buff, self.mirroring);
+        save_vec_usize(buff, &self.prg_banks);
+        save_vec_u8(buff, &self.vram);
+    }
+
+    fn load_state(&mut self, buff: &mut &[u8]) -> Result<(), String> {
+        self.chr.load_state(buff)?;
+        self.mirroring = load_mirroring(buff)?;
+        load_vec_usize(buff, &mut self.prg_banks)?;
+        load_vec_u8(buff, &mut self.vram)?;
+        return Ok(());
+    }
 }
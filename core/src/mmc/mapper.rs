@@ -27,6 +27,11 @@ pub trait Mapper: Send {
     fn write_ppu(&mut self, address: u16, data: u8);
     fn debug_read_cpu(&self, address: u16) -> Option<u8>;
     fn debug_read_ppu(&self, address: u16) -> Option<u8>;
+    // The offset into PRG ROM which the CPU currently sees at this address, if it sees
+    // PRG ROM there at all. Used by debugging tools to tell banks apart.
+    fn prg_rom_address(&self, _address: u16) -> Option<usize> {return None;}
+    // Likewise for the PPU and CHR ROM. CHR RAM isn't ROM, so this is None there.
+    fn chr_rom_address(&self, _address: u16) -> Option<usize> {return None;}
     fn print_debug_status(&self) {}
     fn mirroring(&self) -> Mirroring;
     fn has_sram(&self) -> bool {return false;}
@@ -44,4 +49,9 @@ pub trait Mapper: Send {
     fn needs_bios(&self) -> bool {return false;}
     fn load_bios(&mut self, _: Vec<u8>) {}
     fn switch_disk(&mut self, _: usize) {}
+    // Save states: every mapper serializes its own registers, counters, audio
+    // and RAM, in a fixed order, and identifies the ROM those belong to.
+    fn rom_checksum(&self) -> u32;
+    fn save_state(&self, buff: &mut Vec<u8>);
+    fn load_state(&mut self, buff: &mut &[u8]) -> Result<(), String>;
 }
@@ -6,6 +6,7 @@ use memoryblock::MemoryBlock;
 
 use mmc::mapper::*;
 use mmc::mirroring;
+use save_load::*;
 
 pub struct Mmc1 {
     pub prg_rom: MemoryBlock,
@@ -141,6 +142,35 @@ impl Mapper for Mmc1 {
         }
     }
 
+    fn prg_rom_address(&self, address: u16) -> Option<usize> {
+        let prg_mode = (self.control >> 2) & 0x3;
+        let bank = match (address, prg_mode) {
+            (0x8000 ..= 0xBFFF, 0) | (0x8000 ..= 0xBFFF, 1) => self.prg_bank & 0xFFFE,
+            (0x8000 ..= 0xBFFF, 2) => 0,
+            (0x8000 ..= 0xBFFF, _) => self.prg_bank,
+            (0xC000 ..= 0xFFFF, 0) | (0xC000 ..= 0xFFFF, 1) => self.prg_bank | 0x0001,
+            (0xC000 ..= 0xFFFF, 2) => self.prg_bank,
+            (0xC000 ..= 0xFFFF, _) => 0xFF,
+            _ => {return None}
+        };
+        return self.prg_rom.banked_address(0x4000, bank, (address - 0x8000) as usize);
+    }
+
+    fn chr_rom_address(&self, address: u16) -> Option<usize> {
+        if !self.chr.is_readonly() {
+            return None;
+        }
+        // In 8k mode, bit 0 of the first bank is replaced by A12
+        let bank = match (address, self.control & 0x10 == 0) {
+            (0x0000 ..= 0x0FFF, true) => self.chr_bank_0 & 0xFFFE,
+            (0x0000 ..= 0x0FFF, false) => self.chr_bank_0,
+            (0x1000 ..= 0x1FFF, true) => self.chr_bank_0 | 0x0001,
+            (0x1000 ..= 0x1FFF, false) => self.chr_bank_1,
+            _ => {return None}
+        };
+        return self.chr.banked_address(0x1000, bank, address as usize);
+    }
+
     fn write_cpu(&mut self, address: u16, data: u8) {
         match address {
             // PRG RAM
@@ -292,4 +322,41 @@ impl Mapper for Mmc1 {
     fn load_sram(&mut self, sram_data: Vec<u8>) {
         *self.prg_ram.as_mut_vec() = sram_data;
     }
+
+    fn rom_checksum(&self) -> u32 {
+        return self.prg_rom.checksum();
+    }
+
+    fn save_state(&self, buff: &mut Vec<u8>) {
+        self.prg_ram.save_state(buff);
+        self.chr.save_state(buff);
+        save_vec_u8(buff, &self.vram);
+        save_u8(buff, self.shift_counter);
+        save_u8(buff, self.shift_data);
+        save_usize(buff, self.chr_bank_0);
+        save_usize(buff, self.chr_bank_1);
+        save_usize(buff, self.prg_bank);
+        save_bool(buff, self.prg_ram_enabled);
+        save_usize(buff, self.prg_ram_bank);
+        save_u8(buff, self.control);
+        save_mirroring(buff, self.mirroring);
+        save_bool(buff, self.last_write);
+    }
+
+    fn load_state(&mut self, buff: &mut &[u8]) -> Result<(), String> {
+        self.prg_ram.load_state(buff)?;
+        self.chr.load_state(buff)?;
+        load_vec_u8(buff, &mut self.vram)?;
+        self.shift_counter = load_u8(buff)?;
+        self.shift_data = load_u8(buff)?;
+        self.chr_bank_0 = load_usize(buff)?;
+        self.chr_bank_1 = load_usize(buff)?;
+        self.prg_bank = load_usize(buff)?;
+        self.prg_ram_enabled = load_bool(buff)?;
+        self.prg_ram_bank = load_usize(buff)?;
+        self.control = load_u8(buff)?;
+        self.mirroring = load_mirroring(buff)?;
+        self.last_write = load_bool(buff)?;
+        return Ok(());
+    }
 }
@@ -1,16 +1,24 @@
 // Advanced mapper with bank-switched PRG ROM and CHR ROM, and a scanline counter feeding into IRQ
 // Reference capabilities: https://wiki.nesdev.com/w/index.php/MMC3
+// Also covers a few boards and chips which are close enough to share the implementation:
+// - Mapper 118 (TxSROM): bit 7 of the CHR banks picks the nametable, in place of $A000
+// - Mapper 119 (TQROM): bit 6 of the CHR banks switches in 8k of CHR RAM alongside the ROM
+// - Mapper 4, submapper 1 (MMC6): 1k of internal PRG RAM at $7000, with per-half protection
+// - Mapper 4, submapper 4 (MMC3A): the older IRQ reload behavior, which the MMC6 shares
 
 use ines::INesCartridge;
 use memoryblock::MemoryBlock;
+use memoryblock::MemoryType;
 
 use mmc::mapper::*;
 use mmc::mirroring;
+use save_load::*;
 
 pub struct Mmc3 {
     pub prg_rom: MemoryBlock,
     pub prg_ram: MemoryBlock,
     pub chr: MemoryBlock,
+    pub chr_ram: MemoryBlock,
     pub vram: Vec<u8>,
 
     pub chr2_bank_0: usize,
@@ -42,18 +50,51 @@ pub struct Mmc3 {
     pub last_chr_read: u16,
 
     pub mirroring: Mirroring,
+
+    // Board and chip variants
+    pub txsrom: bool,
+    pub tqrom: bool,
+    pub mmc6: bool,
+    // Revision A only raises an IRQ when the counter reaches 0 from 1, or from a
+    // reload requested with $C001, not every time it reloads to 0
+    pub irq_revision_a: bool,
+
+    // MMC6 PRG RAM control
+    pub prg_ram_enabled: bool,
+    pub prg_ram_protect: u8,
 }
 
 impl Mmc3 {
     pub fn from_ines(ines: INesCartridge) -> Result<Mmc3, String> {
+        let mapper_number = ines.header.mapper_number();
+        let submapper_number = ines.header.submapper_number();
+        let tqrom = mapper_number == 119;
+        let mmc6 = mapper_number == 4 && submapper_number == 1;
+
         let prg_rom_block = ines.prg_rom_block();
-        let prg_ram_block = ines.prg_ram_block()?;
-        let chr_block = ines.chr_block()?;
+        // The MMC6 has its RAM on the chip, regardless of what the header says
+        let prg_ram_block = if mmc6 {
+            MemoryBlock::new(&[0u8; 0x400], if ines.header.has_sram() {MemoryType::NvRam} else {MemoryType::Ram})
+        } else {
+            ines.prg_ram_block()?
+        };
+        // TQROM always has both CHR ROM and CHR RAM, so we'll set both up by hand
+        let chr_block = if tqrom {
+            MemoryBlock::new(&ines.chr, MemoryType::Rom)
+        } else {
+            ines.chr_block()?
+        };
+        let chr_ram_block = if tqrom {
+            MemoryBlock::new(&[0u8; 0x2000], MemoryType::Ram)
+        } else {
+            MemoryBlock::new(&Vec::new(), MemoryType::Ram)
+        };
 
         return Ok(Mmc3 {
             prg_rom: prg_rom_block.clone(),
             prg_ram: prg_ram_block.clone(),
             chr: chr_block.clone(),
+            chr_ram: chr_ram_block.clone(),
             vram: vec![0u8; 0x2000],
             // Note: On real MMC3-based hardware, many of these values are random on startup, so
             // the defaults presented below are arbitrary.
@@ -84,6 +125,14 @@ impl Mmc3 {
             low_a12_counter: 0,
 
             mirroring: ines.header.mirroring(),
+
+            txsrom: mapper_number == 118,
+            tqrom: tqrom,
+            mmc6: mmc6,
+            irq_revision_a: mmc6 || (mapper_number == 4 && submapper_number == 4),
+
+            prg_ram_enabled: false,
+            prg_ram_protect: 0,
         })
     }
 
@@ -117,49 +166,99 @@ impl Mmc3 {
     }
 
     fn clock_irq_counter(&mut self) {
+        let last_counter = self.irq_counter;
+        let reload_requested = self.irq_reload_requested;
         if self.irq_counter == 0 || self.irq_reload_requested {
             self.irq_counter = self.irq_reload;
             self.irq_reload_requested = false;
         } else {
             self.irq_counter -= 1;                        
         }
+        if self.irq_revision_a && last_counter == 0 && !reload_requested {
+            return;
+        }
         if self.irq_counter == 0 && self.irq_enabled {
             self.irq_flag = true;                        
         }
     }
 
+    // The 1k CHR bank at a given address, including the high bits some boards use for
+    // other things. The 2k banks are treated as a pair of 1k banks.
+    fn chr_bank(&self, address: u16) -> usize {
+        // Switching CHR banks swaps the 2k and 1k halves
+        let chr_address = if self.switch_chr_banks {address ^ 0x1000} else {address};
+        return match chr_address {
+            0x0000 ..= 0x07FF => self.chr2_bank_0 | ((address as usize & 0x400) >> 10),
+            0x0800 ..= 0x0FFF => self.chr2_bank_1 | ((address as usize & 0x400) >> 10),
+            0x1000 ..= 0x13FF => self.chr1_bank_2,
+            0x1400 ..= 0x17FF => self.chr1_bank_3,
+            0x1800 ..= 0x1BFF => self.chr1_bank_4,
+            _ => self.chr1_bank_5,
+        };
+    }
+
+    fn chr_ram_selected(&self, bank: usize) -> bool {
+        return self.tqrom && (bank & 0b0100_0000) != 0;
+    }
+
+    fn read_chr(&self, address: u16) -> Option<u8> {
+        let bank = self.chr_bank(address);
+        if self.chr_ram_selected(bank) {
+            return self.chr_ram.banked_read(0x400, bank & 0b0000_0111, address as usize);
+        }
+        return self.chr.banked_read(0x400, bank, address as usize);
+    }
+
+    fn write_chr(&mut self, address: u16, data: u8) {
+        let bank = self.chr_bank(address);
+        if self.chr_ram_selected(bank) {
+            self.chr_ram.banked_write(0x400, bank & 0b0000_0111, address as usize, data);
+        } else {
+            self.chr.banked_write(0x400, bank, address as usize, data);
+        }
+    }
+
+    fn nametable_address(&self, address: u16) -> Option<usize> {
+        if self.txsrom {
+            // Each nametable follows the CHR bank for the matching 1k of $0000-$0FFF
+            let page = (self.chr_bank(address & 0x0FFF) & 0b1000_0000) >> 7;
+            return Some(page * 0x400 + (address as usize & 0x3FF));
+        }
+        return match self.mirroring {
+            Mirroring::Horizontal => Some(mirroring::horizontal_mirroring(address) as usize),
+            Mirroring::Vertical   => Some(mirroring::vertical_mirroring(address) as usize),
+            Mirroring::FourScreen => Some(mirroring::four_banks(address) as usize),
+            _ => None
+        };
+    }
+
+    // The MMC6 maps 1k of RAM at $7000-$7FFF, as two 512 byte halves which can
+    // each be made readable and writable on their own
+    fn mmc6_ram_read(&self, address: u16) -> Option<u8> {
+        let readable_halves = self.prg_ram_protect & 0b1010_0000;
+        if !self.prg_ram_enabled || address < 0x7000 || readable_halves == 0 {
+            return None;
+        }
+        let read_bit = if address & 0x200 != 0 {0b1000_0000} else {0b0010_0000};
+        if self.prg_ram_protect & read_bit == 0 {
+            // With the other half readable, this one reads back as 0
+            return Some(0);
+        }
+        return self.prg_ram.wrapping_read(address as usize & 0x3FF);
+    }
+
+    fn mmc6_ram_write(&mut self, address: u16, data: u8) {
+        let write_bit = if address & 0x200 != 0 {0b0100_0000} else {0b0001_0000};
+        if self.prg_ram_enabled && address >= 0x7000 && self.prg_ram_protect & write_bit != 0 {
+            self.prg_ram.wrapping_write(address as usize & 0x3FF, data);
+        }
+    }
+
     fn _read_ppu(&self, address: u16) -> Option<u8> {
         match address {
             // CHR
-            0x0000 ..= 0x1FFF => {
-                if self.switch_chr_banks {
-                    match address {
-                        0x0000 ..= 0x03FF => self.chr.banked_read(0x400, self.chr1_bank_2, address as usize -  0x000),
-                        0x0400 ..= 0x07FF => self.chr.banked_read(0x400, self.chr1_bank_3, address as usize -  0x400),
-                        0x0800 ..= 0x0BFF => self.chr.banked_read(0x400, self.chr1_bank_4, address as usize -  0x800),
-                        0x0C00 ..= 0x0FFF => self.chr.banked_read(0x400, self.chr1_bank_5, address as usize -  0xC00),
-                        0x1000 ..= 0x17FF => self.chr.banked_read(0x800, self.chr2_bank_0 >> 1, address as usize - 0x1000),
-                        0x1800 ..= 0x1FFF => self.chr.banked_read(0x800, self.chr2_bank_1 >> 1, address as usize - 0x1800),
-                        _ => None,
-                    }
-                } else {
-                    match address {
-                        0x0000 ..= 0x07FF => self.chr.banked_read(0x800, self.chr2_bank_0 >> 1, address as usize -  0x000),
-                        0x0800 ..= 0x0FFF => self.chr.banked_read(0x800, self.chr2_bank_1 >> 1, address as usize -  0x800),
-                        0x1000 ..= 0x13FF => self.chr.banked_read(0x400, self.chr1_bank_2, address as usize - 0x1000),
-                        0x1400 ..= 0x17FF => self.chr.banked_read(0x400, self.chr1_bank_3, address as usize - 0x1400),
-                        0x1800 ..= 0x1BFF => self.chr.banked_read(0x400, self.chr1_bank_4, address as usize - 0x1800),
-                        0x1C00 ..= 0x1FFF => self.chr.banked_read(0x400, self.chr1_bank_5, address as usize - 0x1C00),
-                        _ => None,
-                    }
-                }
-            },
-            0x2000 ..= 0x3FFF => match self.mirroring {
-                Mirroring::Horizontal => Some(self.vram[mirroring::horizontal_mirroring(address) as usize]),
-                Mirroring::Vertical   => Some(self.vram[mirroring::vertical_mirroring(address) as usize]),
-                Mirroring::FourScreen => Some(self.vram[mirroring::four_banks(address) as usize]),
-                _ => None
-            },
+            0x0000 ..= 0x1FFF => self.read_chr(address),
+            0x2000 ..= 0x3FFF => self.nametable_address(address).map(|nametable_address| self.vram[nametable_address]),
             _ => None
         }
     }
@@ -171,6 +270,10 @@ impl Mapper for Mmc3 {
         println!("IRQ: Current: {}, Reload: {}", self.irq_counter, self.irq_reload);
         println!("Last A12: {}, Last CHR Read: 0x{:04X}", self.last_a12, self.last_chr_read);
         println!("Mirroring Mode: {}", mirroring_mode_name(self.mirroring));
+        println!("TxSROM: {}, TQROM: {}, MMC6: {}, Revision A IRQ: {}", self.txsrom, self.tqrom, self.mmc6, self.irq_revision_a);
+        if self.mmc6 {
+            println!("PRG RAM Enabled: {}, Protect: 0x{:02X}", self.prg_ram_enabled, self.prg_ram_protect);
+        }
         println!("====================");
     }
 
@@ -189,6 +292,7 @@ impl Mapper for Mmc3 {
     fn debug_read_cpu(&self, address: u16) -> Option<u8> {
         match address {
             // PRG RAM
+            0x6000 ..= 0x7FFF if self.mmc6 => self.mmc6_ram_read(address),
             0x6000 ..= 0x7FFF => {
                 self.prg_ram.wrapping_read(address as usize - 0x6000)
             },
@@ -216,9 +320,34 @@ impl Mapper for Mmc3 {
         }
     }
 
+    fn prg_rom_address(&self, address: u16) -> Option<usize> {
+        let bank = match (address, self.switch_prg_banks) {
+            (0x8000 ..= 0x9FFF, true) => 0xFE,
+            (0x8000 ..= 0x9FFF, false) => self.prg_bank_6,
+            (0xA000 ..= 0xBFFF, _) => self.prg_bank_7,
+            (0xC000 ..= 0xDFFF, true) => self.prg_bank_6,
+            (0xC000 ..= 0xDFFF, false) => 0xFE,
+            (0xE000 ..= 0xFFFF, _) => 0xFF,
+            _ => {return None}
+        };
+        return self.prg_rom.banked_address(0x2000, bank, address as usize % 0x2000);
+    }
+
+    fn chr_rom_address(&self, address: u16) -> Option<usize> {
+        if !self.chr.is_readonly() || address > 0x1FFF {
+            return None;
+        }
+        let bank = self.chr_bank(address);
+        if self.chr_ram_selected(bank) {
+            return None;
+        }
+        return self.chr.banked_address(0x400, bank, address as usize);
+    }
+
     fn write_cpu(&mut self, address: u16, data: u8) {
         match address {
             // PRG RAM
+            0x6000 ..= 0x7FFF if self.mmc6 => self.mmc6_ram_write(address, data),
             0x6000 ..= 0x7FFF => {
                 // Note: Intentionally omitting PRG RAM protection feature, since this
                 // retains compatability with assumptions about iNES mapper 004
@@ -234,9 +363,13 @@ impl Mapper for Mmc3 {
                             self.bank_select =      data & 0b0000_0111;
                             self.switch_prg_banks = (data & 0b0100_0000) != 0;
                             self.switch_chr_banks = (data & 0b1000_0000) != 0;
+                            if self.mmc6 {
+                                self.prg_ram_enabled = (data & 0b0010_0000) != 0;
+                            }
                         },
                         0xA000 ..= 0xBFFF => {
-                            if self.mirroring != Mirroring::FourScreen {
+                            // TxSROM has no use for this, the CHR banks pick nametables instead
+                            if self.mirroring != Mirroring::FourScreen && !self.txsrom {
                                 if data & 0b1 == 0 {
                                     self.mirroring = Mirroring::Vertical;
                                 } else {
@@ -273,7 +406,11 @@ impl Mapper for Mmc3 {
                         },
                         0xA000 ..= 0xBFFF => {
                             // PRG RAM Protect
-                            // Intentionally not emulated, for compatability with iNES mapper 004
+                            // Intentionally not emulated for the MMC3, for compatability with iNES
+                            // mapper 004, but the MMC6 games depend on theirs
+                            if self.mmc6 && self.prg_ram_enabled {
+                                self.prg_ram_protect = data & 0b1111_0000;
+                            }
                         },
                         0xC000 ..= 0xDFFF => {
                             self.irq_reload_requested = true;
@@ -306,49 +443,12 @@ impl Mapper for Mmc3 {
         self.snoop_ppu_a12(address);
         match address {
             // CHR RAM (if enabled)
-            0x0000 ..= 0x1FFF => {
-                self.last_chr_read = address;
-                let current_a12 = ((address & 0b0001_0000_0000_0000) >> 12) as u8;
-                if current_a12 == 1 && self.last_a12 == 0 {
-                    if self.irq_counter == 0 || self.irq_reload_requested {
-                        self.irq_counter = self.irq_reload;
-                        self.irq_reload_requested = false;
-                    } else {
-                        self.irq_counter -= 1;                        
-                    }
-                    if self.irq_counter == 0 && self.irq_enabled {
-                        self.irq_flag = true;                        
-                    }
-                }
-                self.last_a12 = current_a12;
-                if self.switch_chr_banks {
-                    match address {
-                        0x0000 ..= 0x03FF => self.chr.banked_write(0x400, self.chr1_bank_2, address as usize -  0x000, data),
-                        0x0400 ..= 0x07FF => self.chr.banked_write(0x400, self.chr1_bank_3, address as usize -  0x400, data),
-                        0x0800 ..= 0x0BFF => self.chr.banked_write(0x400, self.chr1_bank_4, address as usize -  0x800, data),
-                        0x0C00 ..= 0x0FFF => self.chr.banked_write(0x400, self.chr1_bank_5, address as usize -  0xC00, data),
-                        0x1000 ..= 0x17FF => self.chr.banked_write(0x800, self.chr2_bank_0 >> 1, address as usize - 0x1000, data),
-                        0x1800 ..= 0x1FFF => self.chr.banked_write(0x800, self.chr2_bank_1 >> 1, address as usize - 0x1800, data),
-                        _ => {},
-                    }
-                } else {
-                    match address {
-                        0x0000 ..= 0x07FF => self.chr.banked_write(0x800, self.chr2_bank_0 >> 1, address as usize -  0x000, data),
-                        0x0800 ..= 0x0FFF => self.chr.banked_write(0x800, self.chr2_bank_1 >> 1, address as usize -  0x800, data),
-                        0x1000 ..= 0x13FF => self.chr.banked_write(0x400, self.chr1_bank_2, address as usize - 0x1000, data),
-                        0x1400 ..= 0x17FF => self.chr.banked_write(0x400, self.chr1_bank_3, address as usize - 0x1400, data),
-                        0x1800 ..= 0x1BFF => self.chr.banked_write(0x400, self.chr1_bank_4, address as usize - 0x1800, data),
-                        0x1C00 ..= 0x1FFF => self.chr.banked_write(0x400, self.chr1_bank_5, address as usize - 0x1C00, data),
-                        _ => {},
-                    }
+            0x0000 ..= 0x1FFF => self.write_chr(address, data),
+            0x2000 ..= 0x3FFF => {
+                if let Some(nametable_address) = self.nametable_address(address) {
+                    self.vram[nametable_address] = data;
                 }
             },
-            0x2000 ..= 0x3FFF => match self.mirroring {
-                Mirroring::Horizontal => self.vram[mirroring::horizontal_mirroring(address) as usize] = data,
-                Mirroring::Vertical   => self.vram[mirroring::vertical_mirroring(address) as usize] = data,
-                Mirroring::FourScreen => self.vram[mirroring::four_banks(address) as usize] = data,
-                _ => {}
-            },
             _ => (),
         }
     }
@@ -364,4 +464,229 @@ impl Mapper for Mmc3 {
     fn load_sram(&mut self, sram_data: Vec<u8>) {
         *self.prg_ram.as_mut_vec() = sram_data;
     }
+
+    fn rom_checksum(&self) -> u32 {
+        return self.prg_rom.checksum();
+    }
+
+    fn save_state(&self, buff: &mut Vec<u8>) {
+        self.prg_ram.save_state(buff);
+        self.chr.save_state(buff);
+        save_vec_u8(buff, &self.vram);
+        save_usize(buff, self.chr2_bank_0);
+        save_usize(buff, self.chr2_bank_1);
+        save_usize(buff, self.chr1_bank_2);
+        save_usize(buff, self.chr1_bank_3);
+        save_usize(buff, self.chr1_bank_4);
+        save_usize(buff, self.chr1_bank_5);
+        save_usize(buff, self.prg_bank_6);
+        save_usize(buff, self.prg_bank_7);
+        save_bool(buff, self.switch_chr_banks);
+        save_bool(buff, self.switch_prg_banks);
+        save_u8(buff, self.bank_select);
+        save_u8(buff, self.irq_counter);
+        save_u8(buff, self.irq_reload);
+        save_bool(buff, self.irq_reload_requested);
+        save_bool(buff, self.irq_enabled);
+        save_bool(buff, self.irq_flag);
+        save_u8(buff, self.last_a12);
+        save_u8(buff, self.filtered_a12);
+        save_u8(buff, self.low_a12_counter);
+        save_mirroring(buff, self.mirroring);
+        self.chr_ram.save_state(buff);
+        save_bool(buff, self.prg_ram_enabled);
+        save_u8(buff, self.prg_ram_protect);
+    }
+
+    fn load_state(&mut self, buff: &mut &[u8]) -> Result<(), String> {
+        self.prg_ram.load_state(buff)?;
+        self.chr.load_state(buff)?;
+        load_vec_u8(buff, &mut self.vram)?;
+        self.chr2_bank_0 = load_usize(buff)?;
+        self.chr2_bank_1 = load_usize(buff)?;
+        self.chr1_bank_2 = load_usize(buff)?;
+        self.chr1_bank_3 = load_usize(buff)?;
+        self.chr1_bank_4 = load_usize(buff)?;
+        self.chr1_bank_5 = load_usize(buff)?;
+        self.prg_bank_6 = load_usize(buff)?;
+        self.prg_bank_7 = load_usize(buff)?;
+        self.switch_chr_banks = load_bool(buff)?;
+        self.switch_prg_banks = load_bool(buff)?;
+        self.bank_select = load_u8(buff)?;
+        self.irq_counter = load_u8(buff)?;
+        self.irq_reload = load_u8(buff)?;
+        self.irq_reload_requested = load_bool(buff)?;
+        self.irq_enabled = load_bool(buff)?;
+        self.irq_flag = load_bool(buff)?;
+        self.last_a12 = load_u8(buff)?;
+        self.filtered_a12 = load_u8(buff)?;
+        self.low_a12_counter = load_u8(buff)?;
+        self.mirroring = load_mirroring(buff)?;
+        self.chr_ram.load_state(buff)?;
+        self.prg_ram_enabled = load_bool(buff)?;
+        self.prg_ram_protect = load_u8(buff)?;
+        return Ok(());
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use asm::Opcode::*;
+    use asm::AddressingMode::*;
+    use mmc::test_rom::*;
+    use nes::NesState;
+
+
+    // One rising edge on PPU A12, held low long enough beforehand to pass the filter
+    fn clock_scanline(nes: &mut NesState) {
+        nes.mapper.access_ppu(0x0000);
+        for _ in 0 .. 3 {
+            nes.mapper.clock_cpu();
+        }
+        nes.mapper.access_ppu(0x1000);
+    }
+
+    // Reloads the counter with 0, then checks for an IRQ on the first and second clocks
+    fn irqs_with_zero_reload(submapper: u8) -> (bool, bool) {
+        let mut rom = TestRom::new(4, 0x20000, 0x20000);
+        rom.submapper = submapper;
+        let mut nes = rom.boot(vec![halt()], vec![Rti]);
+        nes.mapper.write_cpu(0xC000, 0);
+        nes.mapper.write_cpu(0xC001, 0);
+        nes.mapper.write_cpu(0xE001, 0);
+        clock_scanline(&mut nes);
+        let first = nes.mapper.irq_flag();
+        nes.mapper.write_cpu(0xE000, 0);
+        nes.mapper.write_cpu(0xE001, 0);
+        clock_scanline(&mut nes);
+        let second = nes.mapper.irq_flag();
+        return (first, second);
+    }
+
+    #[test]
+    fn revision_b_irqs_on_every_reload_to_zero() {
+        assert_eq!(irqs_with_zero_reload(0), (true, true));
+    }
+
+    #[test]
+    fn revision_a_irqs_only_on_requested_reload_to_zero() {
+        assert_eq!(irqs_with_zero_reload(4), (true, false));
+        // The MMC6 behaves the same way
+        assert_eq!(irqs_with_zero_reload(1), (true, false));
+    }
+
+    #[test]
+    fn txsrom_picks_nametables_with_chr_banks() {
+        let rom = TestRom::new(118, 0x20000, 0x20000);
+        let mut nes = rom.run(vec![
+            select_bank(0, 0x80),
+            select_bank(1, 0x02),
+            // Ignored, this board has no mirroring control
+            Lda(Immediate(0x00)),
+            Sta(Absolute(0xA000)),
+        ]);
+        nes.mapper.write_ppu(0x2000, 0x11);
+        nes.mapper.write_ppu(0x2800, 0x22);
+        assert_eq!(nes.mapper.debug_read_ppu(0x2400), Some(0x11));
+        assert_eq!(nes.mapper.debug_read_ppu(0x2C00), Some(0x22));
+        // Bank 0x80 wraps back around to the first 1k of CHR ROM
+        assert_eq!(nes.mapper.debug_read_ppu(0x0000), Some(0));
+        assert_eq!(nes.mapper.debug_read_ppu(0x0800), Some(2));
+        // Swapping the CHR halves moves the nametables over to the 1k banks
+        nes.mapper.write_cpu(0x8000, 0x82);
+        nes.mapper.write_cpu(0x8001, 0x00);
+        assert_eq!(nes.mapper.debug_read_ppu(0x2000), Some(0x22));
+    }
+
+    #[test]
+    fn tqrom_mixes_chr_rom_and_chr_ram() {
+        let rom = TestRom::new(119, 0x20000, 0x10000);
+        let mut nes = rom.run(vec![
+            select_bank(2, 0x05),
+            select_bank(3, 0x41),
+        ]);
+        assert_eq!(nes.mapper.debug_read_ppu(0x1000), Some(5));
+        nes.mapper.write_ppu(0x1000, 0xAA);
+        assert_eq!(nes.mapper.debug_read_ppu(0x1000), Some(5));
+        nes.mapper.write_ppu(0x1400, 0x55);
+        assert_eq!(nes.mapper.debug_read_ppu(0x1400), Some(0x55));
+        assert_eq!(nes.mapper.chr_rom_address(0x1400), None);
+        // CHR RAM bank 1 is visible through any register
+        nes.mapper.write_cpu(0x8000, 4);
+        nes.mapper.write_cpu(0x8001, 0x49);
+        assert_eq!(nes.mapper.debug_read_ppu(0x1800), Some(0x55));
+    }
+
+    #[test]
+    fn mmc6_protects_each_half_of_its_ram() {
+        let mut rom = TestRom::new(4, 0x20000, 0x20000);
+        rom.submapper = 1;
+        let mut nes = rom.run(vec![
+            // Enable the RAM, then make the lower half readable and writable, and the
+            // upper half read only
+            Lda(Immediate(0x20)),
+            Sta(Absolute(0x8000)),
+            Lda(Immediate(0b1011_0000)),
+            Sta(Absolute(0xA001)),
+            Lda(Immediate(0x12)),
+            Sta(Absolute(0x7000)),
+            Sta(Absolute(0x7200)),
+        ]);
+        assert_eq!(nes.mapper.debug_read_cpu(0x6000), None);
+        assert_eq!(nes.mapper.debug_read_cpu(0x7000), Some(0x12));
+        // The 1k repeats through $7000-$7FFF
+        assert_eq!(nes.mapper.debug_read_cpu(0x7C00), Some(0x12));
+        assert_eq!(nes.mapper.debug_read_cpu(0x7200), Some(0x00));
+        // With only the upper half readable, the lower half reads as 0
+        nes.mapper.write_cpu(0xA001, 0b1000_0000);
+        assert_eq!(nes.mapper.debug_read_cpu(0x7000), Some(0x00));
+        // With neither, the whole thing is open bus
+        nes.mapper.write_cpu(0xA001, 0);
+        assert_eq!(nes.mapper.debug_read_cpu(0x7000), None);
+        // Disabling the RAM also locks the protection register
+        nes.mapper.write_cpu(0x8000, 0);
+        nes.mapper.write_cpu(0xA001, 0b1111_0000);
+        nes.mapper.write_cpu(0x8000, 0x20);
+        assert_eq!(nes.mapper.debug_read_cpu(0x7000), None);
+    }
+
+    #[test]
+    fn saved_states_restore_banks_ram_and_the_irq_counter() {
+        let mut rom = TestRom::new(4, 0x20000, 0x20000);
+        rom.prg_ram_shift = 7;
+        let mut nes = rom.run(vec![
+            select_bank(6, 3),
+            select_bank(2, 9),
+            Lda(Immediate(0x80)),
+            Sta(Absolute(0xA001)),
+            Lda(Immediate(0x5A)),
+            Sta(Absolute(0x6000)),
+        ]);
+        nes.mapper.write_cpu(0xC000, 5);
+        nes.mapper.write_cpu(0xC001, 0);
+        nes.mapper.write_cpu(0xE001, 0);
+        // Reloads the counter with 5
+        clock_scanline(&mut nes);
+        let mut state = Vec::new();
+        nes.mapper.save_state(&mut state);
+
+        nes.mapper.write_cpu(0x8000, 6);
+        nes.mapper.write_cpu(0x8001, 7);
+        nes.mapper.write_cpu(0x8000, 2);
+        nes.mapper.write_cpu(0x8001, 1);
+        nes.mapper.write_cpu(0x6000, 0);
+        nes.mapper.write_cpu(0xE000, 0);
+        clock_scanline(&mut nes);
+
+        nes.mapper.load_state(&mut &state[..]).unwrap();
+        assert_eq!(nes.mapper.debug_read_cpu(0x8000), Some(3));
+        assert_eq!(nes.mapper.debug_read_ppu(0x1000), Some(9));
+        assert_eq!(nes.mapper.debug_read_cpu(0x6000), Some(0x5A));
+        for _ in 0 .. 4 {
+            clock_scanline(&mut nes);
+            assert!(!nes.mapper.irq_flag());
+        }
+        clock_scanline(&mut nes);
+        assert!(nes.mapper.irq_flag());
+    }
 }
@@ -5,6 +5,7 @@ use ines::INesCartridge;
 use memoryblock::MemoryBlock;
 
 use mmc::mapper::*;
+use save_load::*;
 use apu::PulseChannelState;
 
 use apu::AudioChannelState;
@@ -19,6 +20,25 @@ pub enum PpuMode {
     PpuData
 }
 
+fn save_ppu_mode(buff: &mut Vec<u8>, mode: PpuMode) {
+    let value = match mode {
+        PpuMode::Backgrounds => 0,
+        PpuMode::Sprites => 1,
+        PpuMode::PpuData => 2,
+    };
+    save_u8(buff, value);
+}
+
+fn load_ppu_mode(buff: &mut &[u8]) -> Result<PpuMode, String> {
+    let value = load_u8(buff)?;
+    return match value {
+        0 => Ok(PpuMode::Backgrounds),
+        1 => Ok(PpuMode::Sprites),
+        2 => Ok(PpuMode::PpuData),
+        _ => Err(format!("Invalid MMC5 PPU mode {} in save state", value))
+    };
+}
+
 pub struct Mmc5PcmChannel {
     pub level: u8,
     pub read_mode: bool,
@@ -43,6 +63,21 @@ impl Mmc5PcmChannel {
             debug_filter: filters::HighPassIIR::new(44100.0, 300.0),
         }
     }
+
+    pub fn save_state(&self, buff: &mut Vec<u8>) {
+        save_u8(buff, self.level);
+        save_bool(buff, self.read_mode);
+        save_bool(buff, self.irq_enable);
+        save_bool(buff, self.irq_pending);
+    }
+
+    pub fn load_state(&mut self, buff: &mut &[u8]) -> Result<(), String> {
+        self.level = load_u8(buff)?;
+        self.read_mode = load_bool(buff)?;
+        self.irq_enable = load_bool(buff)?;
+        self.irq_pending = load_bool(buff)?;
+        return Ok(());
+    }
 }
 
 impl AudioChannelState for Mmc5PcmChannel {
@@ -891,5 +926,104 @@ impl Mapper for Mmc5 {
         self.pulse_2.record_current_output();
         self.pcm_channel.record_current_output();
     }
+
+    fn rom_checksum(&self) -> u32 {
+        return self.prg_rom.checksum();
+    }
+
+    fn save_state(&self, buff: &mut Vec<u8>) {
+        self.prg_ram.save_state(buff);
+        self.chr.save_state(buff);
+        save_mirroring(buff, self.mirroring);
+        save_u8(buff, self.ppuctrl_monitor);
+        save_u8(buff, self.ppumask_monitor);
+        save_u8(buff, self.prg_mode);
+        save_u8(buff, self.chr_mode);
+        save_u8(buff, self.prg_ram_magic_low);
+        save_u8(buff, self.prg_ram_magic_high);
+        save_u8(buff, self.extended_ram_mode);
+        save_vec_u8(buff, &self.vram);
+        save_vec_u8(buff, &self.extram);
+        save_u8(buff, self.nametable_mapping);
+        save_u8(buff, self.fill_tile);
+        save_u8(buff, self.fill_attr);
+        save_bool(buff, self.prg_bank_a_isram);
+        save_bool(buff, self.prg_bank_b_isram);
+        save_bool(buff, self.prg_bank_c_isram);
+        save_u8(buff, self.prg_bank_a);
+        save_u8(buff, self.prg_bank_b);
+        save_u8(buff, self.prg_bank_c);
+        save_u8(buff, self.prg_bank_d);
+        save_u8(buff, self.prg_ram_bank);
+        save_vec_usize(buff, &self.chr_banks);
+        save_vec_usize(buff, &self.chr_ext_banks);
+        save_bool(buff, self.chr_last_write_ext);
+        save_ppu_mode(buff, self.ppu_read_mode);
+        save_usize(buff, self.chr_bank_high_bits);
+        save_u8(buff, self.irq_scanline_compare);
+        save_bool(buff, self.irq_enabled);
+        save_bool(buff, self.irq_pending);
+        save_bool(buff, self.in_frame);
+        save_u8(buff, self.current_scanline);
+        save_u16(buff, self.last_ppu_fetch);
+        save_u16(buff, self.last_bg_tile_fetch);
+        save_u8(buff, self.consecutive_nametable_count);
+        save_u8(buff, self.cpu_cycles_since_last_ppu_read);
+        save_u16(buff, self.ppu_fetches_this_scanline);
+        save_u8(buff, self.multiplicand_a);
+        save_u8(buff, self.multiplicand_b);
+        self.pulse_1.save_state(buff);
+        self.pulse_2.save_state(buff);
+        save_u16(buff, self.audio_sequencer_counter);
+        self.pcm_channel.save_state(buff);
+    }
+
+    fn load_state(&mut self, buff: &mut &[u8]) -> Result<(), String> {
+        self.prg_ram.load_state(buff)?;
+        self.chr.load_state(buff)?;
+        self.mirroring = load_mirroring(buff)?;
+        self.ppuctrl_monitor = load_u8(buff)?;
+        self.ppumask_monitor = load_u8(buff)?;
+        self.prg_mode = load_u8(buff)?;
+        self.chr_mode = load_u8(buff)?;
+        self.prg_ram_magic_low = load_u8(buff)?;
+        self.prg_ram_magic_high = load_u8(buff)?;
+        self.extended_ram_mode = load_u8(buff)?;
+        load_vec_u8(buff, &mut self.vram)?;
+        load_vec_u8(buff, &mut self.extram)?;
+        self.nametable_mapping = load_u8(buff)?;
+        self.fill_tile = load_u8(buff)?;
+        self.fill_attr = load_u8(buff)?;
+        self.prg_bank_a_isram = load_bool(buff)?;
+        self.prg_bank_b_isram = load_bool(buff)?;
+        self.prg_bank_c_isram = load_bool(buff)?;
+        self.prg_bank_a = load_u8(buff)?;
+        self.prg_bank_b = load_u8(buff)?;
+        self.prg_bank_c = load_u8(buff)?;
+        self.prg_bank_d = load_u8(buff)?;
+        self.prg_ram_bank = load_u8(buff)?;
+        load_vec_usize(buff, &mut self.chr_banks)?;
+        load_vec_usize(buff, &mut self.chr_ext_banks)?;
+        self.chr_last_write_ext = load_bool(buff)?;
+        self.ppu_read_mode = load_ppu_mode(buff)?;
+        self.chr_bank_high_bits = load_usize(buff)?;
+        self.irq_scanline_compare = load_u8(buff)?;
+        self.irq_enabled = load_bool(buff)?;
+        self.irq_pending = load_bool(buff)?;
+        self.in_frame = load_bool(buff)?;
+        self.current_scanline = load_u8(buff)?;
+        self.last_ppu_fetch = load_u16(buff)?;
+        self.last_bg_tile_fetch = load_u16(buff)?;
+        self.consecutive_nametable_count = load_u8(buff)?;
+        self.cpu_cycles_since_last_ppu_read = load_u8(buff)?;
+        self.ppu_fetches_this_scanline = load_u16(buff)?;
+        self.multiplicand_a = load_u8(buff)?;
+        self.multiplicand_b = load_u8(buff)?;
+        self.pulse_1.load_state(buff)?;
+        self.pulse_2.load_state(buff)?;
+        self.audio_sequencer_counter = load_u16(buff)?;
+        self.pcm_channel.load_state(buff)?;
+        return Ok(());
+    }
 }
 
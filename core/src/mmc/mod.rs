@@ -13,11 +13,21 @@ pub mod mmc1;
 pub mod mmc3;
 pub mod mmc5;
 pub mod n163;
+pub mod namco108;
 pub mod none;
 pub mod nrom;
 pub mod nsf;
 pub mod pxrom;
 pub mod rainbow;
+pub mod sunsoft1;
+pub mod sunsoft2;
+pub mod sunsoft3;
+pub mod sunsoft4;
 pub mod uxrom;
+pub mod vrc4;
 pub mod vrc6;
 pub mod vrc7;
+pub mod vs_system;
+
+#[cfg(test)]
+pub mod test_rom;
@@ -6,6 +6,7 @@ use memoryblock::MemoryBlock;
 use memoryblock::MemoryType;
 
 use mmc::mapper::*;
+use save_load::*;
 
 use apu::AudioChannelState;
 use apu::PlaybackRate;
@@ -138,6 +139,15 @@ impl Namco163AudioChannel {
         self.tracked_length = length as usize;
         self.tracked_sample_data[sample_index as usize] = raw_sample;
     }
+
+    pub fn save_state(&self, buff: &mut Vec<u8>) {
+        save_f32(buff, self.current_output);
+    }
+
+    pub fn load_state(&mut self, buff: &mut &[u8]) -> Result<(), String> {
+        self.current_output = load_f32(buff)?;
+        return Ok(());
+    }
 }
 
 impl AudioChannelState for Namco163AudioChannel {
@@ -343,6 +353,39 @@ impl Namco163Audio {
         self.channel7.record_current_output();
         self.channel8.record_current_output();
     }
+
+    pub fn save_state(&self, buff: &mut Vec<u8>) {
+        save_vec_u8(buff, &self.internal_ram);
+        self.channel1.save_state(buff);
+        self.channel2.save_state(buff);
+        self.channel3.save_state(buff);
+        self.channel4.save_state(buff);
+        self.channel5.save_state(buff);
+        self.channel6.save_state(buff);
+        self.channel7.save_state(buff);
+        self.channel8.save_state(buff);
+        save_u8(buff, self.channel_delay_counter);
+        save_usize(buff, self.current_channel);
+        save_f32(buff, self.current_output);
+        save_usize(buff, self.maximum_channels_enabled);
+    }
+
+    pub fn load_state(&mut self, buff: &mut &[u8]) -> Result<(), String> {
+        load_vec_u8(buff, &mut self.internal_ram)?;
+        self.channel1.load_state(buff)?;
+        self.channel2.load_state(buff)?;
+        self.channel3.load_state(buff)?;
+        self.channel4.load_state(buff)?;
+        self.channel5.load_state(buff)?;
+        self.channel6.load_state(buff)?;
+        self.channel7.load_state(buff)?;
+        self.channel8.load_state(buff)?;
+        self.channel_delay_counter = load_u8(buff)?;
+        self.current_channel = load_usize(buff)?;
+        self.current_output = load_f32(buff)?;
+        self.maximum_channels_enabled = load_usize(buff)?;
+        return Ok(());
+    }
 }
 
 pub struct Namco163 {
@@ -479,6 +522,16 @@ impl Mapper for Namco163 {
         }
     }
 
+    fn prg_rom_address(&self, address: u16) -> Option<usize> {
+        match address {
+            0x8000 ..= 0x9FFF => self.prg_rom.banked_address(0x2000, self.prg_banks[0] as usize, address as usize),
+            0xA000 ..= 0xBFFF => self.prg_rom.banked_address(0x2000, self.prg_banks[1] as usize, address as usize),
+            0xC000 ..= 0xDFFF => self.prg_rom.banked_address(0x2000, self.prg_banks[2] as usize, address as usize),
+            0xE000 ..= 0xFFFF => self.prg_rom.banked_address(0x2000, 0xFF, address as usize),
+            _ => None
+        }
+    }
+
     fn read_cpu(&mut self, address: u16) -> Option<u8> {
         let data = self.debug_read_cpu(address);
         match address {
@@ -662,4 +715,51 @@ impl Mapper for Namco163 {
     fn audio_multiplexing(&mut self, emulate: bool) {
         self.expansion_audio_chip.emulate_multiplexing = emulate;
     }
+
+    fn rom_checksum(&self) -> u32 {
+        return self.prg_rom.checksum();
+    }
+
+    fn save_state(&self, buff: &mut Vec<u8>) {
+        self.prg_ram.save_state(buff);
+        self.chr.save_state(buff);
+        self.vram.save_state(buff);
+        self.expansion_audio_chip.save_state(buff);
+
+        save_bool(buff, self.irq_enabled);
+        save_bool(buff, self.irq_pending);
+        save_u16(buff, self.irq_counter);
+
+        save_vec_u8(buff, &self.chr_banks);
+        save_vec_u8(buff, &self.nt_banks);
+        save_vec_u8(buff, &self.prg_banks);
+
+        save_u8(buff, self.internal_ram_addr);
+        save_bool(buff, self.internal_ram_auto_increment);
+        save_bool(buff, self.sound_enabled);
+        save_bool(buff, self.nt_ram_at_0000);
+        save_bool(buff, self.nt_ram_at_1000);
+    }
+
+    fn load_state(&mut self, buff: &mut &[u8]) -> Result<(), String> {
+        self.prg_ram.load_state(buff)?;
+        self.chr.load_state(buff)?;
+        self.vram.load_state(buff)?;
+        self.expansion_audio_chip.load_state(buff)?;
+
+        self.irq_enabled = load_bool(buff)?;
+        self.irq_pending = load_bool(buff)?;
+        self.irq_counter = load_u16(buff)?;
+
+        load_vec_u8(buff, &mut self.chr_banks)?;
+        load_vec_u8(buff, &mut self.nt_banks)?;
+        load_vec_u8(buff, &mut self.prg_banks)?;
+
+        self.internal_ram_addr = load_u8(buff)?;
+        self.internal_ram_auto_increment = load_bool(buff)?;
+        self.sound_enabled = load_bool(buff)?;
+        self.nt_ram_at_0000 = load_bool(buff)?;
+        self.nt_ram_at_1000 = load_bool(buff)?;
+        return Ok(());
+    }
 }
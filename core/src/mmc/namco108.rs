@@ -0,0 +1,308 @@
+// Namco 108 (and the Namco 118, and Tengen's MIMIC-1), the predecessor of the MMC3. It has the
+// same eight bank registers, but no PRG or CHR mode bits, no IRQ, and no mirroring control.
+// Several Namco boards rewire the CHR lines for their own purposes, which are handled here:
+// - Mapper 206: the plain chip, with mirroring fixed by the board
+// - Mapper 88: CHR A16 is tied to PPU A12, so the 2k banks and 1k banks see different halves
+// - Mapper 154: mapper 88, plus one-screen mirroring picked by bit 6 of any register write
+// - Mapper 95: bit 5 of the two 2k CHR banks picks the nametable for each half of $2000-$2FFF
+// - Mapper 76: the four 1k CHR banks become 2k banks, and the 2k bank registers do nothing
+// https://www.nesdev.org/wiki/INES_Mapper_206
+// https://www.nesdev.org/wiki/INES_Mapper_088
+// https://www.nesdev.org/wiki/INES_Mapper_154
+// https://www.nesdev.org/wiki/INES_Mapper_095
+// https://www.nesdev.org/wiki/INES_Mapper_076
+
+use ines::INesCartridge;
+use memoryblock::MemoryBlock;
+
+use mmc::mapper::*;
+use mmc::mirroring;
+use save_load::*;
+
+pub struct Namco108 {
+    pub prg_rom: MemoryBlock,
+    pub chr: MemoryBlock,
+    pub vram: Vec<u8>,
+    pub mirroring: Mirroring,
+    pub mapper_number: u16,
+
+    pub bank_select: u8,
+    pub bank_registers: Vec<usize>,
+
+    // Variant wiring, decided by the mapper number
+    pub chr_a16_from_a12: bool,
+    pub one_screen_select: bool,
+    pub nametables_from_chr: bool,
+    pub wide_chr_banks: bool,
+}
+
+impl Namco108 {
+    pub fn from_ines(ines: INesCartridge) -> Result<Namco108, String> {
+        let prg_rom_block = ines.prg_rom_block();
+        let chr_block = ines.chr_block()?;
+        let mapper_number = ines.header.mapper_number();
+
+        return Ok(Namco108 {
+            prg_rom: prg_rom_block.clone(),
+            chr: chr_block.clone(),
+            vram: vec![0u8; 0x1000],
+            mirroring: if mapper_number == 154 {Mirroring::OneScreenLower} else {ines.header.mirroring()},
+            mapper_number: mapper_number,
+
+            bank_select: 0,
+            bank_registers: vec![0usize; 8],
+
+            chr_a16_from_a12: mapper_number == 88 || mapper_number == 154,
+            one_screen_select: mapper_number == 154,
+            nametables_from_chr: mapper_number == 95,
+            wide_chr_banks: mapper_number == 76,
+        });
+    }
+
+    fn prg_bank(&self, address: u16) -> usize {
+        return match address {
+            0x8000 ..= 0x9FFF => self.bank_registers[6],
+            0xA000 ..= 0xBFFF => self.bank_registers[7],
+            0xC000 ..= 0xDFFF => 0xFE,
+            _ => 0xFF,
+        };
+    }
+
+    // Returns the bank size and bank number for a given CHR address
+    fn chr_bank(&self, address: u16) -> (usize, usize) {
+        if self.wide_chr_banks {
+            return (0x800, self.bank_registers[2 + (address as usize >> 11)]);
+        }
+        return match address {
+            0x0000 ..= 0x07FF => (0x800, self.bank_registers[0] >> 1),
+            0x0800 ..= 0x0FFF => (0x800, self.bank_registers[1] >> 1),
+            _ => {
+                let bank = self.bank_registers[2 + ((address as usize - 0x1000) >> 10)];
+                if self.chr_a16_from_a12 {
+                    (0x400, bank | 0x40)
+                } else {
+                    (0x400, bank)
+                }
+            }
+        };
+    }
+
+    fn nametable_address(&self, address: u16) -> usize {
+        if self.nametables_from_chr {
+            let register = if address & 0x800 == 0 {self.bank_registers[0]} else {self.bank_registers[1]};
+            let page = (register & 0b0010_0000) >> 5;
+            return page * 0x400 + (address as usize & 0x3FF);
+        }
+        return match self.mirroring {
+            Mirroring::Horizontal => mirroring::horizontal_mirroring(address),
+            Mirroring::Vertical   => mirroring::vertical_mirroring(address),
+            Mirroring::OneScreenLower => mirroring::one_screen_lower(address),
+            Mirroring::OneScreenUpper => mirroring::one_screen_upper(address),
+            Mirroring::FourScreen => mirroring::four_banks(address),
+        } as usize;
+    }
+}
+
+impl Mapper for Namco108 {
+    fn print_debug_status(&self) {
+        println!("======= Namco 108 (Mapper {}) =======", self.mapper_number);
+        println!("Bank Select: {}, Bank Registers: {:?}", self.bank_select, self.bank_registers);
+        println!("Mirroring Mode: {}", mirroring_mode_name(self.mirroring()));
+        println!("====================");
+    }
+
+    fn mirroring(&self) -> Mirroring {
+        if self.nametables_from_chr {
+            // The closest fixed mode, for display; one arrangement has no equivalent
+            return match ((self.bank_registers[0] & 0b0010_0000) != 0, (self.bank_registers[1] & 0b0010_0000) != 0) {
+                (false, false) => Mirroring::OneScreenLower,
+                (true, true) => Mirroring::OneScreenUpper,
+                _ => Mirroring::Horizontal,
+            };
+        }
+        return self.mirroring;
+    }
+
+    fn debug_read_cpu(&self, address: u16) -> Option<u8> {
+        match address {
+            0x8000 ..= 0xFFFF => self.prg_rom.banked_read(0x2000, self.prg_bank(address), address as usize % 0x2000),
+            _ => None
+        }
+    }
+
+    fn prg_rom_address(&self, address: u16) -> Option<usize> {
+        match address {
+            0x8000 ..= 0xFFFF => self.prg_rom.banked_address(0x2000, self.prg_bank(address), address as usize % 0x2000),
+            _ => None
+        }
+    }
+
+    fn chr_rom_address(&self, address: u16) -> Option<usize> {
+        match address {
+            0x0000 ..= 0x1FFF if self.chr.is_readonly() => {
+                let (bank_size, bank) = self.chr_bank(address);
+                self.chr.banked_address(bank_size, bank, address as usize % bank_size)
+            },
+            _ => None
+        }
+    }
+
+    fn write_cpu(&mut self, address: u16, data: u8) {
+        if address >= 0x8000 && self.one_screen_select {
+            self.mirroring = if data & 0b0100_0000 == 0 {Mirroring::OneScreenLower} else {Mirroring::OneScreenUpper};
+        }
+        match address {
+            // Only A0 and A15 are decoded, but the games stick to $8000-$9FFF, and writes
+            // above that are often meant for an MMC3 on another board revision
+            0x8000 ..= 0x9FFF => {
+                if address & 0b1 == 0 {
+                    self.bank_select = data & 0b0000_0111;
+                } else {
+                    let register = self.bank_select as usize;
+                    self.bank_registers[register] = match register {
+                        0 ..= 5 => (data & 0b0011_1111) as usize,
+                        _ => (data & 0b0000_1111) as usize,
+                    };
+                }
+            },
+            _ => {}
+        }
+    }
+
+    fn debug_read_ppu(&self, address: u16) -> Option<u8> {
+        match address {
+            0x0000 ..= 0x1FFF => {
+                let (bank_size, bank) = self.chr_bank(address);
+                self.chr.banked_read(bank_size, bank, address as usize % bank_size)
+            },
+            0x2000 ..= 0x3FFF => Some(self.vram[self.nametable_address(address)]),
+            _ => None
+        }
+    }
+
+    fn write_ppu(&mut self, address: u16, data: u8) {
+        match address {
+            0x0000 ..= 0x1FFF => {
+                let (bank_size, bank) = self.chr_bank(address);
+                self.chr.banked_write(bank_size, bank, address as usize % bank_size, data)
+            },
+            0x2000 ..= 0x3FFF => {
+                let nametable_address = self.nametable_address(address);
+                self.vram[nametable_address] = data;
+            },
+            _ => {}
+        }
+    }
+
+    fn rom_checksum(&self) -> u32 {
+        return self.prg_rom.checksum();
+    }
+
+    fn save_state(&self, buff: &mut Vec<u8>) {
+        self.chr.save_state(buff);
+        save_vec_u8(buff, &self.vram);
+        save_mirroring(buff, self.mirroring);
+        save_u8(buff, self.bank_select);
+        for i in 0 .. 8 {
+            save_usize(buff, self.bank_registers[i]);
+        }
+    }
+
+    fn load_state(&mut self, buff: &mut &[u8]) -> Result<(), String> {
+        self.chr.load_state(buff)?;
+        load_vec_u8(buff, &mut self.vram)?;
+        self.mirroring = load_mirroring(buff)?;
+        self.bank_select = load_u8(buff)?;
+        for i in 0 .. 8 {
+            self.bank_registers[i] = load_usize(buff)?;
+        }
+        return Ok(());
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use asm::Opcode::*;
+    use asm::AddressingMode::*;
+    use mmc::mapper::Mirroring;
+    use mmc::test_rom::*;
+
+    #[test]
+    fn mapper_206_switches_prg_and_chr() {
+        let rom = TestRom::new(206, 0x20000, 0x10000);
+        let nes = rom.run(vec![
+            select_bank(0, 5),
+            select_bank(1, 8),
+            select_bank(5, 33),
+            select_bank(6, 3),
+            select_bank(7, 12),
+        ]);
+        assert_eq!(nes.mapper.debug_read_cpu(0x8000), Some(3));
+        assert_eq!(nes.mapper.debug_read_cpu(0xA000), Some(12));
+        assert_eq!(nes.mapper.debug_read_cpu(0xC800), Some(14));
+        assert_eq!(nes.mapper.debug_read_cpu(0xE000), Some(15));
+        // The low bit of the 2k banks is ignored
+        assert_eq!(nes.mapper.debug_read_ppu(0x0000), Some(4));
+        assert_eq!(nes.mapper.debug_read_ppu(0x0400), Some(5));
+        assert_eq!(nes.mapper.debug_read_ppu(0x0800), Some(8));
+        assert_eq!(nes.mapper.debug_read_ppu(0x1C00), Some(33));
+        assert_eq!(nes.mapper.chr_rom_address(0x1C00), Some(33 * 0x400));
+    }
+
+    #[test]
+    fn mapper_206_ignores_writes_above_9fff() {
+        let rom = TestRom::new(206, 0x20000, 0x10000);
+        let nes = rom.run(vec![
+            select_bank(6, 3),
+            Lda(Immediate(6)),
+            Sta(Absolute(0xA000)),
+            Sta(Absolute(0xA001)),
+            Lda(Immediate(0x40)),
+            Sta(Absolute(0x8000)),
+        ]);
+        assert_eq!(nes.mapper.debug_read_cpu(0x8000), Some(3));
+        assert!(nes.mapper.mirroring() == Mirroring::Horizontal);
+    }
+
+    #[test]
+    fn mapper_154_splits_chr_and_selects_one_screen() {
+        let rom = TestRom::new(154, 0x20000, 0x20000);
+        let nes = rom.run(vec![
+            select_bank(0, 2),
+            select_bank(2, 7),
+            Lda(Immediate(0x40)),
+            Sta(Absolute(0xE000)),
+        ]);
+        assert_eq!(nes.mapper.debug_read_ppu(0x0000), Some(2));
+        assert_eq!(nes.mapper.debug_read_ppu(0x1000), Some(0x40 + 7));
+        assert!(nes.mapper.mirroring() == Mirroring::OneScreenUpper);
+    }
+
+    #[test]
+    fn mapper_95_picks_nametables_with_chr_banks() {
+        let rom = TestRom::new(95, 0x20000, 0x8000);
+        let mut nes = rom.run(vec![
+            select_bank(0, 0x20),
+            select_bank(1, 0x00),
+        ]);
+        nes.mapper.write_ppu(0x2000, 0x11);
+        nes.mapper.write_ppu(0x2800, 0x22);
+        assert_eq!(nes.mapper.debug_read_ppu(0x2400), Some(0x11));
+        assert_eq!(nes.mapper.debug_read_ppu(0x2C00), Some(0x22));
+        nes.mapper.write_cpu(0x8001, 0x20);
+        assert_eq!(nes.mapper.debug_read_ppu(0x2800), Some(0x11));
+    }
+
+    #[test]
+    fn mapper_76_uses_2k_chr_banks() {
+        let rom = TestRom::new(76, 0x20000, 0x20000);
+        let nes = rom.run(vec![
+            select_bank(0, 9),
+            select_bank(2, 3),
+            select_bank(5, 40),
+        ]);
+        assert_eq!(nes.mapper.debug_read_ppu(0x0000), Some(6));
+        assert_eq!(nes.mapper.debug_read_ppu(0x0400), Some(7));
+        assert_eq!(nes.mapper.debug_read_ppu(0x1800), Some(80));
+    }
+}
@@ -33,4 +33,16 @@ impl Mapper for NoneMapper {
     fn write_ppu(&mut self, _: u16, _: u8) {
         //Do nothing
     }    
+
+    fn rom_checksum(&self) -> u32 {
+        return 0;
+    }
+
+    fn save_state(&self, _: &mut Vec<u8>) {
+        //Do nothing
+    }
+
+    fn load_state(&mut self, _: &mut &[u8]) -> Result<(), String> {
+        return Ok(());
+    }
 }
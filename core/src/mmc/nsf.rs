@@ -10,6 +10,7 @@ use memoryblock::MemoryBlock;
 use memoryblock::MemoryType;
 use mmc::mapper::*;
 use mmc::mirroring;
+use save_load::*;
 use nsf::NsfFile;
 use nsf::NsfHeader;
 
@@ -327,6 +328,25 @@ enum TrackAdvanceMode {
     Manual
 }
 
+fn save_advance_mode(buff: &mut Vec<u8>, mode: &TrackAdvanceMode) {
+    let value = match *mode {
+        TrackAdvanceMode::Timer => 0,
+        TrackAdvanceMode::Silence => 1,
+        TrackAdvanceMode::Manual => 2,
+    };
+    save_u8(buff, value);
+}
+
+fn load_advance_mode(buff: &mut &[u8]) -> Result<TrackAdvanceMode, String> {
+    let value = load_u8(buff)?;
+    return match value {
+        0 => Ok(TrackAdvanceMode::Timer),
+        1 => Ok(TrackAdvanceMode::Silence),
+        2 => Ok(TrackAdvanceMode::Manual),
+        _ => Err(format!("Invalid NSF track advance mode {} in save state", value))
+    };
+}
+
 pub struct NsfMapper {
     prg: MemoryBlock,
     prg_ram: Vec<u8>,
@@ -388,6 +408,9 @@ pub struct NsfMapper {
 
     fds_enabled: bool,
     fds_audio: FdsAudio,
+
+    // PRG is writable for bank switching convenience, so the original is checksummed up front
+    rom_checksum: u32,
 }
 
 impl NsfMapper {
@@ -477,6 +500,8 @@ impl NsfMapper {
             fds_enabled: nsf.header.fds(),
             fds_audio: FdsAudio::new(),
 
+            rom_checksum: crc32(0, &nsf.prg),
+
             prg_rom_banks: prg_rom_banks,
 
             mirroring: Mirroring::FourScreen,
@@ -1402,4 +1427,105 @@ impl Mapper for NsfMapper {
     fn audio_multiplexing(&mut self, emulate: bool) {
         self.n163_expansion_audio_chip.emulate_multiplexing = emulate;
     }
+
+    fn rom_checksum(&self) -> u32 {
+        return self.rom_checksum;
+    }
+
+    fn save_state(&self, buff: &mut Vec<u8>) {
+        self.prg.save_state(buff);
+        save_vec_u8(buff, &self.prg_ram);
+        save_vec_u8(buff, &self.chr);
+
+        save_u8(buff, self.current_track);
+        save_advance_mode(buff, &self.advance_mode);
+        save_u64(buff, self.current_cycles);
+        save_f32(buff, self.current_sample);
+        save_f32(buff, self.last_sample);
+        save_u64(buff, self.silence_counter);
+        save_u8(buff, self.gui_row);
+
+        save_u8(buff, self.p1_held);
+        save_u8(buff, self.p1_pressed);
+
+        save_vec_usize(buff, &self.prg_rom_banks);
+        save_f32(buff, self.playback_accumulator);
+        save_u8(buff, self.playback_counter);
+
+        save_mirroring(buff, self.mirroring);
+        save_vec_u8(buff, &self.vram);
+
+        self.vrc6_pulse1.save_state(buff);
+        self.vrc6_pulse2.save_state(buff);
+        self.vrc6_sawtooth.save_state(buff);
+
+        save_u8(buff, self.mmc5_multiplicand_a);
+        save_u8(buff, self.mmc5_multiplicand_b);
+        self.mmc5_pulse_1.save_state(buff);
+        self.mmc5_pulse_2.save_state(buff);
+        save_u16(buff, self.mmc5_audio_sequencer_counter);
+        self.mmc5_pcm_channel.save_state(buff);
+        save_vec_u8(buff, &self.mmc5_exram);
+
+        save_u8(buff, self.s5b_audio_command_select);
+        self.s5b_expansion_audio_chip.save_state(buff);
+
+        save_u8(buff, self.n163_ram_addr);
+        save_bool(buff, self.n163_ram_auto_increment);
+        self.n163_expansion_audio_chip.save_state(buff);
+
+        self.vrc7_audio.save_state(buff);
+        save_u8(buff, self.vrc7_audio_register);
+
+        self.fds_audio.save_state(buff);
+    }
+
+    fn load_state(&mut self, buff: &mut &[u8]) -> Result<(), String> {
+        self.prg.load_state(buff)?;
+        load_vec_u8(buff, &mut self.prg_ram)?;
+        load_vec_u8(buff, &mut self.chr)?;
+
+        self.current_track = load_u8(buff)?;
+        self.advance_mode = load_advance_mode(buff)?;
+        self.current_cycles = load_u64(buff)?;
+        self.current_sample = load_f32(buff)?;
+        self.last_sample = load_f32(buff)?;
+        self.silence_counter = load_u64(buff)?;
+        self.gui_row = load_u8(buff)?;
+
+        self.p1_held = load_u8(buff)?;
+        self.p1_pressed = load_u8(buff)?;
+
+        load_vec_usize(buff, &mut self.prg_rom_banks)?;
+        self.playback_accumulator = load_f32(buff)?;
+        self.playback_counter = load_u8(buff)?;
+
+        self.mirroring = load_mirroring(buff)?;
+        load_vec_u8(buff, &mut self.vram)?;
+
+        self.vrc6_pulse1.load_state(buff)?;
+        self.vrc6_pulse2.load_state(buff)?;
+        self.vrc6_sawtooth.load_state(buff)?;
+
+        self.mmc5_multiplicand_a = load_u8(buff)?;
+        self.mmc5_multiplicand_b = load_u8(buff)?;
+        self.mmc5_pulse_1.load_state(buff)?;
+        self.mmc5_pulse_2.load_state(buff)?;
+        self.mmc5_audio_sequencer_counter = load_u16(buff)?;
+        self.mmc5_pcm_channel.load_state(buff)?;
+        load_vec_u8(buff, &mut self.mmc5_exram)?;
+
+        self.s5b_audio_command_select = load_u8(buff)?;
+        self.s5b_expansion_audio_chip.load_state(buff)?;
+
+        self.n163_ram_addr = load_u8(buff)?;
+        self.n163_ram_auto_increment = load_bool(buff)?;
+        self.n163_expansion_audio_chip.load_state(buff)?;
+
+        self.vrc7_audio.load_state(buff)?;
+        self.vrc7_audio_register = load_u8(buff)?;
+
+        self.fds_audio.load_state(buff)?;
+        return Ok(());
+    }
 }
@@ -1,11 +1,17 @@
-// MMC2, a somewhat advanced bank switcher with extended CHR memory
+// MMC2 and MMC4, somewhat advanced bank switchers with extended CHR memory. Each half
+// of the pattern table has two banks, and the PPU picks between them itself: fetching
+// tile $FD or $FE flips a latch, so that the tiles which follow come from the other bank.
+// MMC4 switches 16 KiB of PRG ROM instead of 8, latches on all eight rows of tiles $FD
+// and $FE in both halves, and usually has battery backed WRAM.
 // https://wiki.nesdev.com/w/index.php/MMC2
+// https://wiki.nesdev.com/w/index.php/MMC4
 
 use ines::INesCartridge;
 use memoryblock::MemoryBlock;
 
 use mmc::mapper::*;
 use mmc::mirroring;
+use save_load::*;
 
 pub struct PxRom {
     pub prg_rom: MemoryBlock,
@@ -20,6 +26,8 @@ pub struct PxRom {
     pub chr_1_fe_bank: usize,
     pub prg_bank: usize,
     pub vram: Vec<u8>,
+    pub mmc4: bool,
+    pub has_battery: bool,
 }
 
 impl PxRom {
@@ -41,13 +49,45 @@ impl PxRom {
             chr_1_fe_bank: 0,
             prg_bank: 0,
             vram: vec![0u8; 0x1000],
+            mmc4: ines.header.mapper_number() == 10,
+            has_battery: ines.header.has_sram(),
         })
     }
+
+    // The bank size, bank and offset within it which the CPU sees at this address
+    fn prg_mapping(&self, address: u16) -> Option<(usize, usize, usize)> {
+        if self.mmc4 {
+            return match address {
+                0x8000 ..= 0xBFFF => Some((0x4000, self.prg_bank, address as usize - 0x8000)),
+                0xC000 ..= 0xFFFF => Some((0x4000, 0xFF,          address as usize - 0xC000)),
+                _ => None
+            };
+        }
+        return match address {
+            0x8000 ..= 0x9FFF => Some((0x2000, self.prg_bank, address as usize - 0x8000)),
+            0xA000 ..= 0xBFFF => Some((0x2000, 0xFD,          address as usize - 0xA000)),
+            0xC000 ..= 0xDFFF => Some((0x2000, 0xFE,          address as usize - 0xC000)),
+            0xE000 ..= 0xFFFF => Some((0x2000, 0xFF,          address as usize - 0xE000)),
+            _ => None
+        };
+    }
+
+    fn chr_bank(&self, address: u16) -> Option<usize> {
+        let (latch, fd_bank, fe_bank) = match address {
+            0x0000 ..= 0x0FFF => (self.chr_0_latch, self.chr_0_fd_bank, self.chr_0_fe_bank),
+            0x1000 ..= 0x1FFF => (self.chr_1_latch, self.chr_1_fd_bank, self.chr_1_fe_bank),
+            _ => {return None}
+        };
+        return match latch {
+            0 => Some(fd_bank),
+            _ => Some(fe_bank)
+        };
+    }
 }
 
 impl Mapper for PxRom {
     fn print_debug_status(&self) {
-        println!("======= PxROM =======");
+        println!("======= {} =======", if self.mmc4 {"MMC4"} else {"MMC2"});
         println!("PRG Bank: {}, ", self.prg_bank);
         println!("CHR0 0xFD Bank: {}. CHR0 0xFE Bank: {}", self.chr_0_fd_bank, self.chr_0_fe_bank);
         println!("CHR1 0xFD Bank: {}. CHR1 0xFE Bank: {}", self.chr_1_fd_bank, self.chr_1_fe_bank);
@@ -62,17 +102,21 @@ impl Mapper for PxRom {
     fn debug_read_cpu(&self, address: u16) -> Option<u8> {
         match address {
             0x6000 ..= 0x7FFF => self.prg_ram.wrapping_read((address - 0x6000) as usize),
-            0x8000 ..= 0x9FFF => self.prg_rom.banked_read(0x2000, self.prg_bank, address as usize - 0x8000),
-            0xA000 ..= 0xBFFF => self.prg_rom.banked_read(0x2000, 0xFD,          address as usize - 0xA000),
-            0xC000 ..= 0xDFFF => self.prg_rom.banked_read(0x2000, 0xFE,          address as usize - 0xC000),
-            0xE000 ..= 0xFFFF => self.prg_rom.banked_read(0x2000, 0xFF,          address as usize - 0xE000),
-            _ => None
+            _ => self.prg_mapping(address).and_then(|(bank_size, bank, offset)| self.prg_rom.banked_read(bank_size, bank, offset))
         }
     }
 
+    fn prg_rom_address(&self, address: u16) -> Option<usize> {
+        return self.prg_mapping(address).and_then(|(bank_size, bank, offset)| self.prg_rom.banked_address(bank_size, bank, offset));
+    }
+
+    fn chr_rom_address(&self, address: u16) -> Option<usize> {
+        return self.chr_bank(address).and_then(|chr_bank| self.chr.banked_address(0x1000, chr_bank, address as usize & 0x0FFF));
+    }
+
     fn write_cpu(&mut self, address: u16, data: u8) {
         match address {
-            0x6000 ..= 0x7FFF => self.prg_ram.wrapping_write(address as usize, data),
+            0x6000 ..= 0x7FFF => self.prg_ram.wrapping_write((address - 0x6000) as usize, data),
             0xA000 ..= 0xAFFF => { self.prg_bank = (data & 0b0000_1111) as usize; },
             0xB000 ..= 0xBFFF => { self.chr_0_fd_bank = (data & 0b0001_1111) as usize; },
             0xC000 ..= 0xCFFF => { self.chr_0_fe_bank = (data & 0b0001_1111) as usize; },
@@ -89,34 +133,32 @@ impl Mapper for PxRom {
         }
     }
 
-    fn read_ppu(&mut self, address: u16) -> Option<u8> {
+    // The latches flip once the fetch which triggers them is over, so tile $FD or $FE
+    // itself is still drawn from the old bank
+    fn access_ppu(&mut self, address: u16) {
         match address {
             0x0FD8 => {self.chr_0_latch = 0;},
             0x0FE8 => {self.chr_0_latch = 1;},
+            // MMC2 only watches the first row of the left pattern table's tiles
+            0x0FD9 ..= 0x0FDF if self.mmc4 => {self.chr_0_latch = 0;},
+            0x0FE9 ..= 0x0FEF if self.mmc4 => {self.chr_0_latch = 1;},
             0x1FD8 ..= 0x1FDF => {self.chr_1_latch = 0;},
             0x1FE8 ..= 0x1FEF => {self.chr_1_latch = 1;},
             _ => {}
         }
-        return self.debug_read_ppu(address);
+    }
+
+    fn read_ppu(&mut self, address: u16) -> Option<u8> {
+        let data = self.debug_read_ppu(address);
+        self.access_ppu(address);
+        return data;
     }
 
     fn debug_read_ppu(&self, address: u16) -> Option<u8> {
         match address {
-            0x0000 ..= 0x0FFF => {
-                let chr_bank = match self.chr_0_latch {
-                    0 => self.chr_0_fd_bank,
-                    1 => self.chr_0_fe_bank,
-                    _ => 0
-                };
-                self.chr.banked_read(0x1000, chr_bank, address as usize - 0x0000)
-            },
-            0x1000 ..= 0x1FFF => {
-                let chr_bank = match self.chr_1_latch {
-                    0 => self.chr_1_fd_bank,
-                    1 => self.chr_1_fe_bank,
-                    _ => 0
-                };
-                self.chr.banked_read(0x1000, chr_bank, address as usize - 0x0000)
+            0x0000 ..= 0x1FFF => {
+                let chr_bank = self.chr_bank(address).unwrap_or(0);
+                self.chr.banked_read(0x1000, chr_bank, address as usize & 0x0FFF)
             },
             0x2000 ..= 0x3FFF => match self.mirroring {
                 Mirroring::Horizontal => Some(self.vram[mirroring::horizontal_mirroring(address) as usize]),
@@ -137,4 +179,49 @@ impl Mapper for PxRom {
             _ => {}
         }
     }
+
+    fn has_sram(&self) -> bool {
+        return self.has_battery;
+    }
+
+    fn get_sram(&self) -> Vec<u8> {
+        return self.prg_ram.as_vec().clone();
+    }
+
+    fn load_sram(&mut self, sram_data: Vec<u8>) {
+        *self.prg_ram.as_mut_vec() = sram_data;
+    }
+
+    fn rom_checksum(&self) -> u32 {
+        return self.prg_rom.checksum();
+    }
+
+    fn save_state(&self, buff: &mut Vec<u8>) {
+        self.prg_ram.save_state(buff);
+        self.chr.save_state(buff);
+        save_mirroring(buff, self.mirroring);
+        save_u8(buff, self.chr_0_latch);
+        save_usize(buff, self.chr_0_fd_bank);
+        save_usize(buff, self.chr_0_fe_bank);
+        save_u8(buff, self.chr_1_latch);
+        save_usize(buff, self.chr_1_fd_bank);
+        save_usize(buff, self.chr_1_fe_bank);
+        save_usize(buff, self.prg_bank);
+        save_vec_u8(buff, &self.vram);
+    }
+
+    fn load_state(&mut self, buff: &mut &[u8]) -> Result<(), String> {
+        self.prg_ram.load_state(buff)?;
+        self.chr.load_state(buff)?;
+        self.mirroring = load_mirroring(buff)?;
+        self.chr_0_latch = load_u8(buff)?;
+        self.chr_0_fd_bank = load_usize(buff)?;
+        self.chr_0_fe_bank = load_usize(buff)?;
+        self.chr_1_latch = load_u8(buff)?;
+        self.chr_1_fd_bank = load_usize(buff)?;
+        self.chr_1_fe_bank = load_usize(buff)?;
+        self.prg_bank = load_usize(buff)?;
+        load_vec_u8(buff, &mut self.vram)?;
+        return Ok(());
+    }
 }
@@ -14,6 +14,7 @@ use memoryblock::MemoryBlock;
 use memoryblock::MemoryType;
 
 use mmc::mapper::*;
+use save_load::*;
 
 use apu::AudioChannelState;
 use mmc::vrc6::Vrc6PulseChannel;
@@ -65,6 +66,97 @@ pub enum PpuMode {
     PpuData
 }
 
+// Save state helpers for the banking and chip select modes above. These are
+// stored by their position in the enum, so new modes should be added at the end.
+
+fn save_prg_rom_mode(buff: &mut Vec<u8>, mode: PrgRomBankingMode) {
+    save_u8(buff, mode as u8);
+}
+
+fn load_prg_rom_mode(buff: &mut &[u8]) -> Result<PrgRomBankingMode, String> {
+    let value = load_u8(buff)?;
+    return match value {
+        0 => Ok(PrgRomBankingMode::Mode0Bank1x32k),
+        1 => Ok(PrgRomBankingMode::Mode1Bank2x16k),
+        2 => Ok(PrgRomBankingMode::Mode2Bank1x16k2x8k),
+        3 => Ok(PrgRomBankingMode::Mode3Bank4x8k),
+        4 => Ok(PrgRomBankingMode::Mode4Bank8x4k),
+        _ => Err(format!("Invalid Rainbow PRG ROM banking mode {} in save state", value))
+    };
+}
+
+fn save_prg_ram_mode(buff: &mut Vec<u8>, mode: PrgRamBankingMode) {
+    save_u8(buff, mode as u8);
+}
+
+fn load_prg_ram_mode(buff: &mut &[u8]) -> Result<PrgRamBankingMode, String> {
+    let value = load_u8(buff)?;
+    return match value {
+        0 => Ok(PrgRamBankingMode::Mode0Bank1x8k),
+        1 => Ok(PrgRamBankingMode::Mode1Bank2x4k),
+        _ => Err(format!("Invalid Rainbow PRG RAM banking mode {} in save state", value))
+    };
+}
+
+fn save_chr_mode(buff: &mut Vec<u8>, mode: ChrBankingMode) {
+    save_u8(buff, mode as u8);
+}
+
+fn load_chr_mode(buff: &mut &[u8]) -> Result<ChrBankingMode, String> {
+    let value = load_u8(buff)?;
+    return match value {
+        0 => Ok(ChrBankingMode::Mode0Bank1x8k),
+        1 => Ok(ChrBankingMode::Mode1Bank2x4k),
+        2 => Ok(ChrBankingMode::Mode2Bank4x2k),
+        3 => Ok(ChrBankingMode::Mode3Bank8x1k),
+        4 => Ok(ChrBankingMode::Mode4Bank16x512b),
+        _ => Err(format!("Invalid Rainbow CHR banking mode {} in save state", value))
+    };
+}
+
+fn save_chr_chip(buff: &mut Vec<u8>, mode: ChrChipSelect) {
+    save_u8(buff, mode as u8);
+}
+
+fn load_chr_chip(buff: &mut &[u8]) -> Result<ChrChipSelect, String> {
+    let value = load_u8(buff)?;
+    return match value {
+        0 => Ok(ChrChipSelect::ChrRom),
+        1 => Ok(ChrChipSelect::ChrRam),
+        2 => Ok(ChrChipSelect::FpgaRam),
+        _ => Err(format!("Invalid Rainbow CHR chip {} in save state", value))
+    };
+}
+
+fn save_nametable_chip(buff: &mut Vec<u8>, mode: NametableChipSelect) {
+    save_u8(buff, mode as u8);
+}
+
+fn load_nametable_chip(buff: &mut &[u8]) -> Result<NametableChipSelect, String> {
+    let value = load_u8(buff)?;
+    return match value {
+        0 => Ok(NametableChipSelect::CiRam),
+        1 => Ok(NametableChipSelect::ChrRam),
+        2 => Ok(NametableChipSelect::FpgaRam),
+        3 => Ok(NametableChipSelect::ChrRom),
+        _ => Err(format!("Invalid Rainbow nametable chip {} in save state", value))
+    };
+}
+
+fn save_ppu_mode(buff: &mut Vec<u8>, mode: PpuMode) {
+    save_u8(buff, mode as u8);
+}
+
+fn load_ppu_mode(buff: &mut &[u8]) -> Result<PpuMode, String> {
+    let value = load_u8(buff)?;
+    return match value {
+        0 => Ok(PpuMode::Backgrounds),
+        1 => Ok(PpuMode::Sprites),
+        2 => Ok(PpuMode::PpuData),
+        _ => Err(format!("Invalid Rainbow PPU mode {} in save state", value))
+    };
+}
+
 pub struct Rainbow {
     prg_rom: MemoryBlock,
     prg_ram: MemoryBlock,
@@ -1310,6 +1402,215 @@ impl Mapper for Rainbow {
         self.vrc6_pulse2.record_current_output();
         self.vrc6_sawtooth.record_current_output();
     }
+
+    fn rom_checksum(&self) -> u32 {
+        return self.prg_rom.checksum();
+    }
+
+    fn save_state(&self, buff: &mut Vec<u8>) {
+        self.prg_ram.save_state(buff);
+        self.chr_ram.save_state(buff);
+
+        save_prg_rom_mode(buff, self.prg_rom_mode);
+        save_prg_ram_mode(buff, self.prg_ram_mode);
+        save_chr_mode(buff, self.chr_mode);
+        save_chr_chip(buff, self.chr_chip);
+
+        save_usize(buff, self.prg_bank_at_8000);
+        save_usize(buff, self.prg_bank_at_9000);
+        save_usize(buff, self.prg_bank_at_a000);
+        save_usize(buff, self.prg_bank_at_b000);
+        save_usize(buff, self.prg_bank_at_c000);
+        save_usize(buff, self.prg_bank_at_d000);
+        save_usize(buff, self.prg_bank_at_e000);
+        save_usize(buff, self.prg_bank_at_f000);
+
+        save_bool(buff, self.prg_ram_at_8000);
+        save_bool(buff, self.prg_ram_at_9000);
+        save_bool(buff, self.prg_ram_at_a000);
+        save_bool(buff, self.prg_ram_at_b000);
+        save_bool(buff, self.prg_ram_at_c000);
+        save_bool(buff, self.prg_ram_at_d000);
+        save_bool(buff, self.prg_ram_at_e000);
+        save_bool(buff, self.prg_ram_at_f000);
+
+        save_usize(buff, self.prg_bank_at_6000);
+        save_usize(buff, self.prg_bank_at_7000);
+
+        save_bool(buff, self.prg_ram_at_6000);
+        save_bool(buff, self.prg_ram_at_7000);
+        save_bool(buff, self.fpga_ram_at_6000);
+        save_bool(buff, self.fpga_ram_at_7000);
+
+        save_usize(buff, self.fpga_bank_at_5000);
+        save_vec_usize(buff, &self.chr_banks);
+        save_usize(buff, self.chr_bank_high_bits);
+
+        save_bool(buff, self.window_split);
+        save_bool(buff, self.extended_sprites);
+
+        save_mirroring(buff, self.mirroring);
+        self.ciram.save_state(buff);
+        self.fpga_ram.save_state(buff);
+
+        self.vrc6_pulse1.save_state(buff);
+        self.vrc6_pulse2.save_state(buff);
+        self.vrc6_sawtooth.save_state(buff);
+
+        save_bool(buff, self.vrc6_exp6);
+        save_bool(buff, self.vrc6_exp9);
+        save_bool(buff, self.vrc6_zpcm);
+
+        save_u16(buff, self.cpu_irq_counter);
+        save_u16(buff, self.cpu_irq_latch);
+        save_bool(buff, self.cpu_irq_enable);
+        save_bool(buff, self.cpu_irq_auto_repeat);
+        save_bool(buff, self.cpu_irq_pending);
+
+        save_usize(buff, self.nametable_bank_at_2000);
+        save_usize(buff, self.nametable_bank_at_2400);
+        save_usize(buff, self.nametable_bank_at_2800);
+        save_usize(buff, self.nametable_bank_at_2c00);
+
+        save_nametable_chip(buff, self.nametable_chip_at_2000);
+        save_nametable_chip(buff, self.nametable_chip_at_2400);
+        save_nametable_chip(buff, self.nametable_chip_at_2800);
+        save_nametable_chip(buff, self.nametable_chip_at_2c00);
+
+        save_bool(buff, self.extended_attributes_2000);
+        save_bool(buff, self.extended_attributes_2400);
+        save_bool(buff, self.extended_attributes_2800);
+        save_bool(buff, self.extended_attributes_2c00);
+
+        save_bool(buff, self.extended_backgrounds_2000);
+        save_bool(buff, self.extended_backgrounds_2400);
+        save_bool(buff, self.extended_backgrounds_2800);
+        save_bool(buff, self.extended_backgrounds_2c00);
+
+        save_usize(buff, self.exram_bank_2000);
+        save_usize(buff, self.exram_bank_2400);
+        save_usize(buff, self.exram_bank_2800);
+        save_usize(buff, self.exram_bank_2c00);
+
+        save_bool(buff, self.scanline_irq_pending);
+        save_bool(buff, self.scanline_irq_enabled);
+        save_u8(buff, self.scanline_irq_compare);
+        save_u8(buff, self.scanline_irq_offset);
+        save_u8(buff, self.scanline_jitter_counter);
+
+        save_ppu_mode(buff, self.ppu_read_mode);
+        save_bool(buff, self.in_frame);
+        save_bool(buff, self.in_hblank);
+        save_u8(buff, self.current_scanline);
+        save_u8(buff, self.consecutive_nametable_count);
+        save_u8(buff, self.cpu_cycles_since_last_ppu_read);
+        save_u8(buff, self.ppu_fetches_this_scanline);
+        save_u16(buff, self.last_ppu_fetch);
+        save_u16(buff, self.last_bg_tile_fetch);
+    }
+
+    fn load_state(&mut self, buff: &mut &[u8]) -> Result<(), String> {
+        self.prg_ram.load_state(buff)?;
+        self.chr_ram.load_state(buff)?;
+
+        self.prg_rom_mode = load_prg_rom_mode(buff)?;
+        self.prg_ram_mode = load_prg_ram_mode(buff)?;
+        self.chr_mode = load_chr_mode(buff)?;
+        self.chr_chip = load_chr_chip(buff)?;
+
+        self.prg_bank_at_8000 = load_usize(buff)?;
+        self.prg_bank_at_9000 = load_usize(buff)?;
+        self.prg_bank_at_a000 = load_usize(buff)?;
+        self.prg_bank_at_b000 = load_usize(buff)?;
+        self.prg_bank_at_c000 = load_usize(buff)?;
+        self.prg_bank_at_d000 = load_usize(buff)?;
+        self.prg_bank_at_e000 = load_usize(buff)?;
+        self.prg_bank_at_f000 = load_usize(buff)?;
+
+        self.prg_ram_at_8000 = load_bool(buff)?;
+        self.prg_ram_at_9000 = load_bool(buff)?;
+        self.prg_ram_at_a000 = load_bool(buff)?;
+        self.prg_ram_at_b000 = load_bool(buff)?;
+        self.prg_ram_at_c000 = load_bool(buff)?;
+        self.prg_ram_at_d000 = load_bool(buff)?;
+        self.prg_ram_at_e000 = load_bool(buff)?;
+        self.prg_ram_at_f000 = load_bool(buff)?;
+
+        self.prg_bank_at_6000 = load_usize(buff)?;
+        self.prg_bank_at_7000 = load_usize(buff)?;
+
+        self.prg_ram_at_6000 = load_bool(buff)?;
+        self.prg_ram_at_7000 = load_bool(buff)?;
+        self.fpga_ram_at_6000 = load_bool(buff)?;
+        self.fpga_ram_at_7000 = load_bool(buff)?;
+
+        self.fpga_bank_at_5000 = load_usize(buff)?;
+        load_vec_usize(buff, &mut self.chr_banks)?;
+        self.chr_bank_high_bits = load_usize(buff)?;
+
+        self.window_split = load_bool(buff)?;
+        self.extended_sprites = load_bool(buff)?;
+
+        self.mirroring = load_mirroring(buff)?;
+        self.ciram.load_state(buff)?;
+        self.fpga_ram.load_state(buff)?;
+
+        self.vrc6_pulse1.load_state(buff)?;
+        self.vrc6_pulse2.load_state(buff)?;
+        self.vrc6_sawtooth.load_state(buff)?;
+
+        self.vrc6_exp6 = load_bool(buff)?;
+        self.vrc6_exp9 = load_bool(buff)?;
+        self.vrc6_zpcm = load_bool(buff)?;
+
+        self.cpu_irq_counter = load_u16(buff)?;
+        self.cpu_irq_latch = load_u16(buff)?;
+        self.cpu_irq_enable = load_bool(buff)?;
+        self.cpu_irq_auto_repeat = load_bool(buff)?;
+        self.cpu_irq_pending = load_bool(buff)?;
+
+        self.nametable_bank_at_2000 = load_usize(buff)?;
+        self.nametable_bank_at_2400 = load_usize(buff)?;
+        self.nametable_bank_at_2800 = load_usize(buff)?;
+        self.nametable_bank_at_2c00 = load_usize(buff)?;
+
+        self.nametable_chip_at_2000 = load_nametable_chip(buff)?;
+        self.nametable_chip_at_2400 = load_nametable_chip(buff)?;
+        self.nametable_chip_at_2800 = load_nametable_chip(buff)?;
+        self.nametable_chip_at_2c00 = load_nametable_chip(buff)?;
+
+        self.extended_attributes_2000 = load_bool(buff)?;
+        self.extended_attributes_2400 = load_bool(buff)?;
+        self.extended_attributes_2800 = load_bool(buff)?;
+        self.extended_attributes_2c00 = load_bool(buff)?;
+
+        self.extended_backgrounds_2000 = load_bool(buff)?;
+        self.extended_backgrounds_2400 = load_bool(buff)?;
+        self.extended_backgrounds_2800 = load_bool(buff)?;
+        self.extended_backgrounds_2c00 = load_bool(buff)?;
+
+        self.exram_bank_2000 = load_usize(buff)?;
+        self.exram_bank_2400 = load_usize(buff)?;
+        self.exram_bank_2800 = load_usize(buff)?;
+        self.exram_bank_2c00 = load_usize(buff)?;
+
+        self.scanline_irq_pending = load_bool(buff)?;
+        self.scanline_irq_enabled = load_bool(buff)?;
+        self.scanline_irq_compare = load_u8(buff)?;
+        self.scanline_irq_offset = load_u8(buff)?;
+        self.scanline_jitter_counter = load_u8(buff)?;
+
+        self.ppu_read_mode = load_ppu_mode(buff)?;
+        self.in_frame = load_bool(buff)?;
+        self.in_hblank = load_bool(buff)?;
+        self.current_scanline = load_u8(buff)?;
+        self.consecutive_nametable_count = load_u8(buff)?;
+        self.cpu_cycles_since_last_ppu_read = load_u8(buff)?;
+        self.ppu_fetches_this_scanline = load_u8(buff)?;
+        self.last_ppu_fetch = load_u16(buff)?;
+        self.last_bg_tile_fetch = load_u16(buff)?;
+        return Ok(());
+    }
 }
 
 // Provided courtesy of Broke Studio. The raster font contained within is assumed
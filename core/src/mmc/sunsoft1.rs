@@ -0,0 +1,167 @@
+// Sunsoft-1, a simple latch for switching two 4k CHR banks, on boards with 16-32k of PRG ROM.
+// The latch sits where WRAM would, at $6000-$7FFF.
+// https://www.nesdev.org/wiki/INES_Mapper_184
+
+use ines::INesCartridge;
+use memoryblock::MemoryBlock;
+
+use mmc::mapper::*;
+use mmc::mirroring;
+use save_load::*;
+
+pub struct Sunsoft1 {
+    pub prg_rom: MemoryBlock,
+    pub chr: MemoryBlock,
+    pub mirroring: Mirroring,
+    pub chr_0_bank: usize,
+    pub chr_1_bank: usize,
+    pub vram: Vec<u8>,
+}
+
+impl Sunsoft1 {
+    pub fn from_ines(ines: INesCartridge) -> Result<Sunsoft1, String> {
+        let prg_rom_block = ines.prg_rom_block();
+        let chr_block = ines.chr_block()?;
+
+        return Ok(Sunsoft1 {
+            prg_rom: prg_rom_block.clone(),
+            chr: chr_block.clone(),
+            mirroring: ines.header.mirroring(),
+            chr_0_bank: 0,
+            chr_1_bank: 0,
+            vram: vec![0u8; 0x1000],
+        });
+    }
+
+    fn chr_bank(&self, address: u16) -> usize {
+        return if address < 0x1000 {self.chr_0_bank} else {self.chr_1_bank};
+    }
+}
+
+impl Mapper for Sunsoft1 {
+    fn print_debug_status(&self) {
+        println!("======= Sunsoft-1 =======");
+        println!("CHR0 Bank: {}, CHR1 Bank: {}", self.chr_0_bank, self.chr_1_bank);
+        println!("Mirroring Mode: {}", mirroring_mode_name(self.mirroring));
+        println!("====================");
+    }
+
+    fn mirroring(&self) -> Mirroring {
+        return self.mirroring;
+    }
+
+    fn debug_read_cpu(&self, address: u16) -> Option<u8> {
+        match address {
+            0x8000 ..= 0xFFFF => {self.prg_rom.wrapping_read((address - 0x8000) as usize)},
+            _ => None
+        }
+    }
+
+    fn prg_rom_address(&self, address: u16) -> Option<usize> {
+        match address {
+            0x8000 ..= 0xFFFF => {self.prg_rom.wrapping_address((address - 0x8000) as usize)},
+            _ => None
+        }
+    }
+
+    fn chr_rom_address(&self, address: u16) -> Option<usize> {
+        match address {
+            0x0000 ..= 0x1FFF if self.chr.is_readonly() => {self.chr.banked_address(0x1000, self.chr_bank(address), address as usize & 0x0FFF)},
+            _ => None
+        }
+    }
+
+    fn write_cpu(&mut self, address: u16, data: u8) {
+        match address {
+            // The upper bank's high bit is usually wired high, but the games all set it
+            // themselves anyway
+            0x6000 ..= 0x7FFF => {
+                self.chr_0_bank = (data & 0b0000_0111) as usize;
+                self.chr_1_bank = ((data & 0b0111_0000) >> 4) as usize;
+            },
+            _ => {}
+        }
+    }
+
+    fn debug_read_ppu(&self, address: u16) -> Option<u8> {
+        match address {
+            0x0000 ..= 0x1FFF => {self.chr.banked_read(0x1000, self.chr_bank(address), address as usize & 0x0FFF)},
+            0x2000 ..= 0x3FFF => match self.mirroring {
+                Mirroring::Horizontal => Some(self.vram[mirroring::horizontal_mirroring(address) as usize]),
+                Mirroring::Vertical   => Some(self.vram[mirroring::vertical_mirroring(address) as usize]),
+                _ => None
+            },
+            _ => None
+        }
+    }
+
+    fn write_ppu(&mut self, address: u16, data: u8) {
+        match address {
+            0x0000 ..= 0x1FFF => {
+                let chr_bank = self.chr_bank(address);
+                self.chr.banked_write(0x1000, chr_bank, address as usize & 0x0FFF, data)
+            },
+            0x2000 ..= 0x3FFF => match self.mirroring {
+                Mirroring::Horizontal => self.vram[mirroring::horizontal_mirroring(address) as usize] = data,
+                Mirroring::Vertical   => self.vram[mirroring::vertical_mirroring(address) as usize] = data,
+                _ => {}
+            },
+            _ => {}
+        }
+    }
+
+    fn rom_checksum(&self) -> u32 {
+        return self.prg_rom.checksum();
+    }
+
+    fn save_state(&self, buff: &mut Vec<u8>) {
+        self.chr.save_state(buff);
+        save_mirroring(buff, self.mirroring);
+        save_usize(buff, self.chr_0_bank);
+        save_usize(buff, self.chr_1_bank);
+        save_vec_u8(buff, &self.vram);
+    }
+
+    fn load_state(&mut self, buff: &mut &[u8]) -> Result<(), String> {
+        self.chr.load_state(buff)?;
+        self.mirroring = load_mirroring(buff)?;
+        self.chr_0_bank = load_usize(buff)?;
+        self.chr_1_bank = load_usize(buff)?;
+        load_vec_u8(buff, &mut self.vram)?;
+        return Ok(());
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use asm::Opcode::*;
+    use asm::AddressingMode::*;
+    use mmc::test_rom::*;
+
+    #[test]
+    fn latch_switches_both_chr_banks() {
+        let rom = TestRom::new(184, 0x8000, 0x8000);
+        let nes = rom.run(vec![
+            Lda(Immediate(0b0101_0011)),
+            Sta(Absolute(0x6000)),
+        ]);
+        // 4k banks 3 and 5 are 1k banks 12 and 20
+        assert_eq!(nes.mapper.debug_read_ppu(0x0000), Some(12));
+        assert_eq!(nes.mapper.debug_read_ppu(0x0FFF), Some(15));
+        assert_eq!(nes.mapper.debug_read_ppu(0x1000), Some(20));
+        assert_eq!(nes.mapper.chr_rom_address(0x1000), Some(0x5000));
+    }
+
+    #[test]
+    fn prg_rom_is_fixed() {
+        let rom = TestRom::new(184, 0x8000, 0x8000);
+        let nes = rom.run(vec![
+            Lda(Immediate(0x77)),
+            Sta(Absolute(0x7FFF)),
+            Sta(Absolute(0x8000)),
+        ]);
+        assert_eq!(nes.mapper.debug_read_cpu(0x8000), Some(0));
+        assert_eq!(nes.mapper.debug_read_cpu(0xA000), Some(1));
+        assert_eq!(nes.mapper.debug_read_cpu(0xE000), Some(3));
+    }
+}
@@ -0,0 +1,189 @@
+// Sunsoft-2, a latch for switching 16k of PRG ROM at $8000, with the last 16k fixed at $C000.
+// It was fitted to two boards which use the rest of the latch differently:
+// - Mapper 89 (Sunsoft-3 board): also an 8k CHR ROM bank and one-screen mirroring
+// - Mapper 93 (Sunsoft-3R board): CHR RAM, which can be disabled, and fixed mirroring
+// https://www.nesdev.org/wiki/INES_Mapper_089
+// https://www.nesdev.org/wiki/INES_Mapper_093
+
+use ines::INesCartridge;
+use memoryblock::MemoryBlock;
+
+use mmc::mapper::*;
+use mmc::mirroring;
+use save_load::*;
+
+pub struct Sunsoft2 {
+    pub prg_rom: MemoryBlock,
+    pub chr: MemoryBlock,
+    pub mirroring: Mirroring,
+    pub mapper_number: u16,
+    pub prg_bank: usize,
+    pub chr_bank: usize,
+    pub chr_enabled: bool,
+    pub vram: Vec<u8>,
+}
+
+impl Sunsoft2 {
+    pub fn from_ines(ines: INesCartridge) -> Result<Sunsoft2, String> {
+        let prg_rom_block = ines.prg_rom_block();
+        let chr_block = ines.chr_block()?;
+        let mapper_number = ines.header.mapper_number();
+
+        return Ok(Sunsoft2 {
+            prg_rom: prg_rom_block.clone(),
+            chr: chr_block.clone(),
+            mirroring: if mapper_number == 89 {Mirroring::OneScreenLower} else {ines.header.mirroring()},
+            mapper_number: mapper_number,
+            prg_bank: 0,
+            chr_bank: 0,
+            chr_enabled: true,
+            vram: vec![0u8; 0x1000],
+        });
+    }
+}
+
+impl Mapper for Sunsoft2 {
+    fn print_debug_status(&self) {
+        println!("======= Sunsoft-2 =======");
+        println!("PRG Bank: {}, CHR Bank: {}, CHR Enabled: {}", self.prg_bank, self.chr_bank, self.chr_enabled);
+        println!("Mirroring Mode: {}", mirroring_mode_name(self.mirroring));
+        println!("====================");
+    }
+
+    fn mirroring(&self) -> Mirroring {
+        return self.mirroring;
+    }
+
+    fn debug_read_cpu(&self, address: u16) -> Option<u8> {
+        match address {
+            0x8000 ..= 0xBFFF => self.prg_rom.banked_read(0x4000, self.prg_bank, address as usize - 0x8000),
+            0xC000 ..= 0xFFFF => self.prg_rom.banked_read(0x4000, 0xFF,          address as usize - 0xC000),
+            _ => None
+        }
+    }
+
+    fn prg_rom_address(&self, address: u16) -> Option<usize> {
+        match address {
+            0x8000 ..= 0xBFFF => self.prg_rom.banked_address(0x4000, self.prg_bank, address as usize - 0x8000),
+            0xC000 ..= 0xFFFF => self.prg_rom.banked_address(0x4000, 0xFF,          address as usize - 0xC000),
+            _ => None
+        }
+    }
+
+    fn chr_rom_address(&self, address: u16) -> Option<usize> {
+        match address {
+            0x0000 ..= 0x1FFF if self.chr.is_readonly() => {self.chr.banked_address(0x2000, self.chr_bank, address as usize)},
+            _ => None
+        }
+    }
+
+    fn write_cpu(&mut self, address: u16, data: u8) {
+        match address {
+            0x8000 ..= 0xFFFF => {
+                self.prg_bank = ((data & 0b0111_0000) >> 4) as usize;
+                if self.mapper_number == 89 {
+                    self.chr_bank = (((data & 0b1000_0000) >> 4) | (data & 0b0000_0111)) as usize;
+                    self.mirroring = if data & 0b0000_1000 == 0 {Mirroring::OneScreenLower} else {Mirroring::OneScreenUpper};
+                } else {
+                    self.chr_enabled = (data & 0b0000_0001) != 0;
+                }
+            },
+            _ => {}
+        }
+    }
+
+    fn debug_read_ppu(&self, address: u16) -> Option<u8> {
+        match address {
+            0x0000 ..= 0x1FFF if self.chr_enabled => {self.chr.banked_read(0x2000, self.chr_bank, address as usize)},
+            0x2000 ..= 0x3FFF => match self.mirroring {
+                Mirroring::Horizontal => Some(self.vram[mirroring::horizontal_mirroring(address) as usize]),
+                Mirroring::Vertical   => Some(self.vram[mirroring::vertical_mirroring(address) as usize]),
+                Mirroring::OneScreenLower => Some(self.vram[mirroring::one_screen_lower(address) as usize]),
+                Mirroring::OneScreenUpper => Some(self.vram[mirroring::one_screen_upper(address) as usize]),
+                _ => None
+            },
+            _ => None
+        }
+    }
+
+    fn write_ppu(&mut self, address: u16, data: u8) {
+        match address {
+            0x0000 ..= 0x1FFF if self.chr_enabled => {self.chr.banked_write(0x2000, self.chr_bank, address as usize, data)},
+            0x2000 ..= 0x3FFF => match self.mirroring {
+                Mirroring::Horizontal => self.vram[mirroring::horizontal_mirroring(address) as usize] = data,
+                Mirroring::Vertical   => self.vram[mirroring::vertical_mirroring(address) as usize] = data,
+                Mirroring::OneScreenLower => self.vram[mirroring::one_screen_lower(address) as usize] = data,
+                Mirroring::OneScreenUpper => self.vram[mirroring::one_screen_upper(address) as usize] = data,
+                _ => {}
+            },
+            _ => {}
+        }
+    }
+
+    fn rom_checksum(&self) -> u32 {
+        return self.prg_rom.checksum();
+    }
+
+    fn save_state(&self, buff: &mut Vec<u8>) {
+        self.chr.save_state(buff);
+        save_mirroring(buff, self.mirroring);
+        save_usize(buff, self.prg_bank);
+        save_usize(buff, self.chr_bank);
+        save_bool(buff, self.chr_enabled);
+        save_vec_u8(buff, &self.vram);
+    }
+
+    fn load_state(&mut self, buff: &mut &[u8]) -> Result<(), String> {
+        self.chr.load_state(buff)?;
+        self.mirroring = load_mirroring(buff)?;
+        self.prg_bank = load_usize(buff)?;
+        self.chr_bank = load_usize(buff)?;
+        self.chr_enabled = load_bool(buff)?;
+        load_vec_u8(buff, &mut self.vram)?;
+        return Ok(());
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use asm::Opcode::*;
+    use asm::AddressingMode::*;
+    use mmc::mapper::Mirroring;
+    use mmc::test_rom::*;
+
+    #[test]
+    fn mapper_89_switches_prg_chr_and_mirroring() {
+        let rom = TestRom::new(89, 0x20000, 0x20000);
+        let nes = rom.run(vec![
+            Lda(Immediate(0b1011_1010)),
+            Sta(Absolute(0x8000)),
+        ]);
+        // 16k bank 3 is 8k bank 6, and 8k CHR bank 10 is 1k bank 80
+        assert_eq!(nes.mapper.debug_read_cpu(0x8000), Some(6));
+        assert_eq!(nes.mapper.debug_read_cpu(0xE000), Some(15));
+        assert_eq!(nes.mapper.debug_read_ppu(0x0000), Some(80));
+        assert_eq!(nes.mapper.debug_read_ppu(0x1C00), Some(87));
+        assert!(nes.mapper.mirroring() == Mirroring::OneScreenUpper);
+    }
+
+    #[test]
+    fn mapper_93_switches_prg_and_disables_chr_ram() {
+        let mut rom = TestRom::new(93, 0x20000, 0);
+        rom.vertical_mirroring = true;
+        let mut nes = rom.run(vec![
+            // Write a byte to CHR RAM through the PPU
+            Lda(Immediate(0x00)),
+            Sta(Absolute(0x2006)),
+            Sta(Absolute(0x2006)),
+            Lda(Immediate(0x5A)),
+            Sta(Absolute(0x2007)),
+            Lda(Immediate(0b0101_0000)),
+            Sta(Absolute(0x8000)),
+        ]);
+        assert_eq!(nes.mapper.debug_read_cpu(0x8000), Some(10));
+        assert_eq!(nes.mapper.debug_read_ppu(0x0000), None);
+        assert!(nes.mapper.mirroring() == Mirroring::Vertical);
+        nes.mapper.write_cpu(0x8000, 0b0101_0001);
+        assert_eq!(nes.mapper.debug_read_ppu(0x0000), Some(0x5A));
+    }
+}
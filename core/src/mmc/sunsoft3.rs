@@ -0,0 +1,286 @@
+// Sunsoft-3, with four 2k CHR banks, a 16k PRG bank, and a 16-bit IRQ counter which
+// counts down once per CPU cycle.
+// https://www.nesdev.org/wiki/INES_Mapper_067
+
+use ines::INesCartridge;
+use memoryblock::MemoryBlock;
+
+use mmc::mapper::*;
+use mmc::mirroring;
+use save_load::*;
+
+pub struct Sunsoft3 {
+    pub prg_rom: MemoryBlock,
+    pub prg_ram: MemoryBlock,
+    pub chr: MemoryBlock,
+    pub vram: Vec<u8>,
+    pub mirroring: Mirroring,
+    pub prg_bank: usize,
+    pub chr_banks: Vec<usize>,
+
+    pub irq_counter: u16,
+    pub irq_enable: bool,
+    pub irq_pending: bool,
+    // The counter is loaded a byte at a time, high byte first
+    pub irq_write_low: bool,
+}
+
+impl Sunsoft3 {
+    pub fn from_ines(ines: INesCartridge) -> Result<Sunsoft3, String> {
+        let prg_rom_block = ines.prg_rom_block();
+        let prg_ram_block = ines.prg_ram_block()?;
+        let chr_block = ines.chr_block()?;
+
+        return Ok(Sunsoft3 {
+            prg_rom: prg_rom_block.clone(),
+            prg_ram: prg_ram_block.clone(),
+            chr: chr_block.clone(),
+            vram: vec![0u8; 0x1000],
+            mirroring: ines.header.mirroring(),
+            prg_bank: 0,
+            chr_banks: vec![0usize; 4],
+
+            irq_counter: 0,
+            irq_enable: false,
+            irq_pending: false,
+            irq_write_low: false,
+        });
+    }
+}
+
+impl Mapper for Sunsoft3 {
+    fn print_debug_status(&self) {
+        println!("======= Sunsoft-3 =======");
+        println!("PRG Bank: {}, CHR Banks: {:?}", self.prg_bank, self.chr_banks);
+        println!("IRQ Counter: {}, Enabled: {}, Pending: {}", self.irq_counter, self.irq_enable, self.irq_pending);
+        println!("Mirroring Mode: {}", mirroring_mode_name(self.mirroring));
+        println!("====================");
+    }
+
+    fn mirroring(&self) -> Mirroring {
+        return self.mirroring;
+    }
+
+    // Once the counter wraps around it stops, until the game enables it again
+    fn clock_cpu(&mut self) {
+        if self.irq_enable {
+            self.irq_counter = self.irq_counter.wrapping_sub(1);
+            if self.irq_counter == 0xFFFF {
+                self.irq_pending = true;
+                self.irq_enable = false;
+            }
+        }
+    }
+
+    fn irq_flag(&self) -> bool {
+        return self.irq_pending;
+    }
+
+    fn debug_read_cpu(&self, address: u16) -> Option<u8> {
+        match address {
+            0x6000 ..= 0x7FFF => self.prg_ram.wrapping_read((address - 0x6000) as usize),
+            0x8000 ..= 0xBFFF => self.prg_rom.banked_read(0x4000, self.prg_bank, address as usize - 0x8000),
+            0xC000 ..= 0xFFFF => self.prg_rom.banked_read(0x4000, 0xFF,          address as usize - 0xC000),
+            _ => None
+        }
+    }
+
+    fn prg_rom_address(&self, address: u16) -> Option<usize> {
+        match address {
+            0x8000 ..= 0xBFFF => self.prg_rom.banked_address(0x4000, self.prg_bank, address as usize - 0x8000),
+            0xC000 ..= 0xFFFF => self.prg_rom.banked_address(0x4000, 0xFF,          address as usize - 0xC000),
+            _ => None
+        }
+    }
+
+    fn chr_rom_address(&self, address: u16) -> Option<usize> {
+        match address {
+            0x0000 ..= 0x1FFF if self.chr.is_readonly() => {
+                self.chr.banked_address(0x800, self.chr_banks[(address >> 11) as usize], address as usize & 0x7FF)
+            },
+            _ => None
+        }
+    }
+
+    fn write_cpu(&mut self, address: u16, data: u8) {
+        match address {
+            0x6000 ..= 0x7FFF => {self.prg_ram.wrapping_write((address - 0x6000) as usize, data);},
+            0x8000 ..= 0xFFFF => {
+                match address & 0xF800 {
+                    0x8800 => {self.chr_banks[0] = data as usize;},
+                    0x9800 => {self.chr_banks[1] = data as usize;},
+                    0xA800 => {self.chr_banks[2] = data as usize;},
+                    0xB800 => {self.chr_banks[3] = data as usize;},
+                    0xC800 => {
+                        if self.irq_write_low {
+                            self.irq_counter = (self.irq_counter & 0xFF00) | (data as u16);
+                        } else {
+                            self.irq_counter = (self.irq_counter & 0x00FF) | ((data as u16) << 8);
+                        }
+                        self.irq_write_low = !self.irq_write_low;
+                    },
+                    0xD800 => {
+                        self.irq_enable = (data & 0b0001_0000) != 0;
+                        self.irq_write_low = false;
+                        self.irq_pending = false;
+                    },
+                    0xE800 => {
+                        match data & 0b0000_0011 {
+                            0 => self.mirroring = Mirroring::Vertical,
+                            1 => self.mirroring = Mirroring::Horizontal,
+                            2 => self.mirroring = Mirroring::OneScreenLower,
+                            3 => self.mirroring = Mirroring::OneScreenUpper,
+                            _ => {}
+                        }
+                    },
+                    0xF800 => {self.prg_bank = (data & 0b0000_1111) as usize;},
+                    _ => {}
+                }
+            },
+            _ => {}
+        }
+    }
+
+    fn debug_read_ppu(&self, address: u16) -> Option<u8> {
+        match address {
+            0x0000 ..= 0x1FFF => self.chr.banked_read(0x800, self.chr_banks[(address >> 11) as usize], address as usize & 0x7FF),
+            0x2000 ..= 0x3FFF => match self.mirroring {
+                Mirroring::Horizontal => Some(self.vram[mirroring::horizontal_mirroring(address) as usize]),
+                Mirroring::Vertical   => Some(self.vram[mirroring::vertical_mirroring(address) as usize]),
+                Mirroring::OneScreenLower => Some(self.vram[mirroring::one_screen_lower(address) as usize]),
+                Mirroring::OneScreenUpper => Some(self.vram[mirroring::one_screen_upper(address) as usize]),
+                _ => None
+            },
+            _ => None
+        }
+    }
+
+    fn write_ppu(&mut self, address: u16, data: u8) {
+        match address {
+            0x0000 ..= 0x1FFF => {
+                let chr_bank = self.chr_banks[(address >> 11) as usize];
+                self.chr.banked_write(0x800, chr_bank, address as usize & 0x7FF, data)
+            },
+            0x2000 ..= 0x3FFF => match self.mirroring {
+                Mirroring::Horizontal => self.vram[mirroring::horizontal_mirroring(address) as usize] = data,
+                Mirroring::Vertical   => self.vram[mirroring::vertical_mirroring(address) as usize] = data,
+                Mirroring::OneScreenLower => self.vram[mirroring::one_screen_lower(address) as usize] = data,
+                Mirroring::OneScreenUpper => self.vram[mirroring::one_screen_upper(address) as usize] = data,
+                _ => {}
+            },
+            _ => {}
+        }
+    }
+
+    fn rom_checksum(&self) -> u32 {
+        return self.prg_rom.checksum();
+    }
+
+    fn save_state(&self, buff: &mut Vec<u8>) {
+        self.prg_ram.save_state(buff);
+        self.chr.save_state(buff);
+        save_vec_u8(buff, &self.vram);
+        save_mirroring(buff, self.mirroring);
+        save_usize(buff, self.prg_bank);
+        for i in 0 .. 4 {
+            save_usize(buff, self.chr_banks[i]);
+        }
+        save_u16(buff, self.irq_counter);
+        save_bool(buff, self.irq_enable);
+        save_bool(buff, self.irq_pending);
+        save_bool(buff, self.irq_write_low);
+    }
+
+    fn load_state(&mut self, buff: &mut &[u8]) -> Result<(), String> {
+        self.prg_ram.load_state(buff)?;
+        self.chr.load_state(buff)?;
+        load_vec_u8(buff, &mut self.vram)?;
+        self.mirroring = load_mirroring(buff)?;
+        self.prg_bank = load_usize(buff)?;
+        for i in 0 .. 4 {
+            self.chr_banks[i] = load_usize(buff)?;
+        }
+        self.irq_counter = load_u16(buff)?;
+        self.irq_enable = load_bool(buff)?;
+        self.irq_pending = load_bool(buff)?;
+        self.irq_write_low = load_bool(buff)?;
+        return Ok(());
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use asm::Opcode::*;
+    use asm::AddressingMode::*;
+    use mmc::mapper::Mirroring;
+    use mmc::test_rom::*;
+
+    #[test]
+    fn switches_prg_chr_and_mirroring() {
+        let rom = TestRom::new(67, 0x20000, 0x20000);
+        let nes = rom.run(vec![
+            Lda(Immediate(5)),
+            Sta(Absolute(0xF800)),
+            Lda(Immediate(9)),
+            Sta(Absolute(0xB800)),
+            Lda(Immediate(3)),
+            Sta(Absolute(0xE800)),
+        ]);
+        assert_eq!(nes.mapper.debug_read_cpu(0x8000), Some(10));
+        assert_eq!(nes.mapper.debug_read_cpu(0xA000), Some(11));
+        assert_eq!(nes.mapper.debug_read_cpu(0xC000 + 0x2000), Some(15));
+        // 2k bank 9 is 1k banks 18 and 19
+        assert_eq!(nes.mapper.debug_read_ppu(0x1800), Some(18));
+        assert_eq!(nes.mapper.debug_read_ppu(0x1C00), Some(19));
+        assert!(nes.mapper.mirroring() == Mirroring::OneScreenUpper);
+    }
+
+    #[test]
+    fn irq_fires_after_counter_wraps_and_then_stops() {
+        let rom = TestRom::new(67, 0x20000, 0x20000);
+        let mut nes = rom.boot(vec![
+            // Keep the APU's frame IRQ out of the way
+            Lda(Immediate(0x40)),
+            Sta(Absolute(0x4017)),
+            Lda(Immediate(0x00)),
+            Sta(ZeroPage(0x00)),
+            // Load $1000, high byte first
+            Lda(Immediate(0x10)),
+            Sta(Absolute(0xC800)),
+            Lda(Immediate(0x00)),
+            Sta(Absolute(0xC800)),
+            Lda(Immediate(0x10)),
+            Sta(Absolute(0xD800)),
+            Cli,
+            halt(),
+        ], vec![
+            Inc(ZeroPage(0x00)),
+            // Acknowledge, leaving the counter disabled
+            Lda(Immediate(0x00)),
+            Sta(Absolute(0xD800)),
+            Rti,
+        ]);
+        run_cycles(&mut nes, 0x1000);
+        assert_eq!(nes.memory.iram_raw[0], 0);
+        run_cycles(&mut nes, 100);
+        assert_eq!(nes.memory.iram_raw[0], 1);
+        assert!(!nes.mapper.irq_flag());
+        run_cycles(&mut nes, 0x20000);
+        assert_eq!(nes.memory.iram_raw[0], 1);
+    }
+
+    #[test]
+    fn counter_counts_down_once_per_cpu_cycle() {
+        let rom = TestRom::new(67, 0x20000, 0x20000);
+        let mut nes = rom.boot(vec![halt()], vec![Rti]);
+        nes.mapper.write_cpu(0xC800, 0x00);
+        nes.mapper.write_cpu(0xC800, 0x05);
+        nes.mapper.write_cpu(0xD800, 0x10);
+        for _ in 0 .. 5 {
+            nes.mapper.clock_cpu();
+        }
+        assert!(!nes.mapper.irq_flag());
+        nes.mapper.clock_cpu();
+        assert!(nes.mapper.irq_flag());
+    }
+}
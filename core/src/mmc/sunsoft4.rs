@@ -0,0 +1,357 @@
+// Sunsoft-4, with four 2k CHR banks, a 16k PRG bank, and the ability to use CHR ROM as
+// nametables, which After Burner does for its backgrounds.
+// Nantettatte!! Baseball also plugs a sub-cartridge into the top of the main one. Its ROM
+// takes the place of the switchable PRG bank, but only as long as the game keeps writing
+// to $6000-$7FFF, as a licensing chip cuts it off if left alone for too long.
+// https://www.nesdev.org/wiki/INES_Mapper_068
+
+use ines::INesCartridge;
+use memoryblock::MemoryBlock;
+
+use mmc::mapper::*;
+use mmc::mirroring;
+use save_load::*;
+
+// CPU cycles the sub-cartridge stays readable after each write to $6000-$7FFF
+const LICENSE_TIMEOUT: u32 = 1024 * 105;
+
+pub struct Sunsoft4 {
+    pub prg_rom: MemoryBlock,
+    pub prg_ram: MemoryBlock,
+    pub chr: MemoryBlock,
+    pub vram: Vec<u8>,
+    pub mirroring: Mirroring,
+    pub has_battery: bool,
+
+    pub prg_bank: usize,
+    pub prg_ram_enable: bool,
+    pub chr_banks: Vec<usize>,
+    pub nametable_banks: Vec<usize>,
+    pub nametable_chr_rom: bool,
+
+    // The sub-cartridge's ROM follows the main cartridge's 128k in the file
+    pub has_sub_cartridge: bool,
+    pub sub_cartridge_selected: bool,
+    pub license_timer: u32,
+}
+
+impl Sunsoft4 {
+    pub fn from_ines(ines: INesCartridge) -> Result<Sunsoft4, String> {
+        let prg_rom_block = ines.prg_rom_block();
+        let prg_ram_block = ines.prg_ram_block()?;
+        let chr_block = ines.chr_block()?;
+        // Older headers don't say, but the main and sub-cartridge together are an odd size
+        let has_sub_cartridge = ines.header.submapper_number() == 1 || !prg_rom_block.len().is_power_of_two();
+
+        return Ok(Sunsoft4 {
+            prg_rom: prg_rom_block.clone(),
+            prg_ram: prg_ram_block.clone(),
+            chr: chr_block.clone(),
+            vram: vec![0u8; 0x1000],
+            mirroring: ines.header.mirroring(),
+            has_battery: ines.header.has_sram(),
+
+            prg_bank: 0,
+            prg_ram_enable: false,
+            chr_banks: vec![0usize; 4],
+            nametable_banks: vec![0usize; 2],
+            nametable_chr_rom: false,
+
+            has_sub_cartridge: has_sub_cartridge,
+            sub_cartridge_selected: false,
+            license_timer: 0,
+        });
+    }
+
+    // None while the licensing chip has the sub-cartridge switched off
+    fn prg_bank_at_8000(&self) -> Option<usize> {
+        if self.sub_cartridge_selected {
+            if self.license_timer == 0 {
+                return None;
+            }
+            return Some(8 + (self.prg_bank & 0b0001));
+        }
+        return Some(self.prg_bank);
+    }
+
+    // The main cartridge's last bank, even when there's a sub-cartridge after it
+    fn fixed_prg_bank(&self) -> usize {
+        return if self.has_sub_cartridge {7} else {0xFF};
+    }
+
+    // Which of the two nametable banks the PPU sees in each quarter of $2000-$2FFF
+    fn nametable_bank(&self, address: u16) -> usize {
+        let quarter = ((address & 0x0C00) >> 10) as usize;
+        let index = match self.mirroring {
+            Mirroring::Vertical => quarter & 0b01,
+            Mirroring::Horizontal => (quarter & 0b10) >> 1,
+            Mirroring::OneScreenUpper => 1,
+            _ => 0
+        };
+        // Only the 1k banks at the top of the first 256k of CHR ROM are selectable
+        return self.nametable_banks[index] | 0x80;
+    }
+
+    fn read_nametable(&self, address: u16) -> Option<u8> {
+        if self.nametable_chr_rom {
+            return self.chr.banked_read(0x400, self.nametable_bank(address), address as usize & 0x3FF);
+        }
+        return match self.mirroring {
+            Mirroring::Horizontal => Some(self.vram[mirroring::horizontal_mirroring(address) as usize]),
+            Mirroring::Vertical   => Some(self.vram[mirroring::vertical_mirroring(address) as usize]),
+            Mirroring::OneScreenLower => Some(self.vram[mirroring::one_screen_lower(address) as usize]),
+            Mirroring::OneScreenUpper => Some(self.vram[mirroring::one_screen_upper(address) as usize]),
+            _ => None
+        };
+    }
+}
+
+impl Mapper for Sunsoft4 {
+    fn print_debug_status(&self) {
+        println!("======= Sunsoft-4 =======");
+        println!("PRG Bank: {}, CHR Banks: {:?}", self.prg_bank, self.chr_banks);
+        println!("Nametable Banks: {:?}, CHR ROM Nametables: {}", self.nametable_banks, self.nametable_chr_rom);
+        if self.has_sub_cartridge {
+            println!("Sub-cartridge Selected: {}, License Timer: {}", self.sub_cartridge_selected, self.license_timer);
+        }
+        println!("Mirroring Mode: {}", mirroring_mode_name(self.mirroring));
+        println!("====================");
+    }
+
+    fn mirroring(&self) -> Mirroring {
+        return self.mirroring;
+    }
+
+    fn clock_cpu(&mut self) {
+        self.license_timer = self.license_timer.saturating_sub(1);
+    }
+
+    fn debug_read_cpu(&self, address: u16) -> Option<u8> {
+        match address {
+            0x6000 ..= 0x7FFF if self.prg_ram_enable => self.prg_ram.wrapping_read((address - 0x6000) as usize),
+            0x8000 ..= 0xBFFF => self.prg_bank_at_8000().and_then(|bank| self.prg_rom.banked_read(0x4000, bank, address as usize - 0x8000)),
+            0xC000 ..= 0xFFFF => self.prg_rom.banked_read(0x4000, self.fixed_prg_bank(), address as usize - 0xC000),
+            _ => None
+        }
+    }
+
+    fn prg_rom_address(&self, address: u16) -> Option<usize> {
+        match address {
+            0x8000 ..= 0xBFFF => self.prg_bank_at_8000().and_then(|bank| self.prg_rom.banked_address(0x4000, bank, address as usize - 0x8000)),
+            0xC000 ..= 0xFFFF => self.prg_rom.banked_address(0x4000, self.fixed_prg_bank(), address as usize - 0xC000),
+            _ => None
+        }
+    }
+
+    fn chr_rom_address(&self, address: u16) -> Option<usize> {
+        match address {
+            0x0000 ..= 0x1FFF if self.chr.is_readonly() => {
+                self.chr.banked_address(0x800, self.chr_banks[(address >> 11) as usize], address as usize & 0x7FF)
+            },
+            0x2000 ..= 0x3EFF if self.nametable_chr_rom => {
+                self.chr.banked_address(0x400, self.nametable_bank(address), address as usize & 0x3FF)
+            },
+            _ => None
+        }
+    }
+
+    fn write_cpu(&mut self, address: u16, data: u8) {
+        match address {
+            0x6000 ..= 0x7FFF => {
+                if self.prg_ram_enable {
+                    self.prg_ram.wrapping_write((address - 0x6000) as usize, data);
+                }
+                if self.has_sub_cartridge {
+                    self.license_timer = LICENSE_TIMEOUT;
+                }
+            },
+            0x8000 ..= 0x8FFF => {self.chr_banks[0] = data as usize;},
+            0x9000 ..= 0x9FFF => {self.chr_banks[1] = data as usize;},
+            0xA000 ..= 0xAFFF => {self.chr_banks[2] = data as usize;},
+            0xB000 ..= 0xBFFF => {self.chr_banks[3] = data as usize;},
+            0xC000 ..= 0xCFFF => {self.nametable_banks[0] = (data & 0b0111_1111) as usize;},
+            0xD000 ..= 0xDFFF => {self.nametable_banks[1] = (data & 0b0111_1111) as usize;},
+            0xE000 ..= 0xEFFF => {
+                match data & 0b0000_0011 {
+                    0 => self.mirroring = Mirroring::Vertical,
+                    1 => self.mirroring = Mirroring::Horizontal,
+                    2 => self.mirroring = Mirroring::OneScreenLower,
+                    3 => self.mirroring = Mirroring::OneScreenUpper,
+                    _ => {}
+                }
+                self.nametable_chr_rom = (data & 0b0001_0000) != 0;
+            },
+            0xF000 ..= 0xFFFF => {
+                // With a sub-cartridge, bit 3 picks between it and the main cartridge
+                if self.has_sub_cartridge {
+                    self.prg_bank = (data & 0b0000_0111) as usize;
+                    self.sub_cartridge_selected = (data & 0b0000_1000) == 0;
+                } else {
+                    self.prg_bank = (data & 0b0000_1111) as usize;
+                }
+                self.prg_ram_enable = (data & 0b0001_0000) != 0;
+            },
+            _ => {}
+        }
+    }
+
+    fn debug_read_ppu(&self, address: u16) -> Option<u8> {
+        match address {
+            0x0000 ..= 0x1FFF => self.chr.banked_read(0x800, self.chr_banks[(address >> 11) as usize], address as usize & 0x7FF),
+            0x2000 ..= 0x3FFF => self.read_nametable(address),
+            _ => None
+        }
+    }
+
+    fn write_ppu(&mut self, address: u16, data: u8) {
+        match address {
+            0x0000 ..= 0x1FFF => {
+                let chr_bank = self.chr_banks[(address >> 11) as usize];
+                self.chr.banked_write(0x800, chr_bank, address as usize & 0x7FF, data)
+            },
+            // CHR ROM nametables can't be written to, of course
+            0x2000 ..= 0x3FFF if self.nametable_chr_rom => {},
+            0x2000 ..= 0x3FFF => match self.mirroring {
+                Mirroring::Horizontal => self.vram[mirroring::horizontal_mirroring(address) as usize] = data,
+                Mirroring::Vertical   => self.vram[mirroring::vertical_mirroring(address) as usize] = data,
+                Mirroring::OneScreenLower => self.vram[mirroring::one_screen_lower(address) as usize] = data,
+                Mirroring::OneScreenUpper => self.vram[mirroring::one_screen_upper(address) as usize] = data,
+                _ => {}
+            },
+            _ => {}
+        }
+    }
+
+    fn has_sram(&self) -> bool {
+        return self.has_battery;
+    }
+
+    fn get_sram(&self) -> Vec<u8> {
+        return self.prg_ram.as_vec().clone();
+    }
+
+    fn load_sram(&mut self, sram_data: Vec<u8>) {
+        *self.prg_ram.as_mut_vec() = sram_data;
+    }
+
+    fn rom_checksum(&self) -> u32 {
+        return self.prg_rom.checksum();
+    }
+
+    fn save_state(&self, buff: &mut Vec<u8>) {
+        self.prg_ram.save_state(buff);
+        self.chr.save_state(buff);
+        save_vec_u8(buff, &self.vram);
+        save_mirroring(buff, self.mirroring);
+        save_usize(buff, self.prg_bank);
+        save_bool(buff, self.prg_ram_enable);
+        for i in 0 .. 4 {
+            save_usize(buff, self.chr_banks[i]);
+        }
+        save_usize(buff, self.nametable_banks[0]);
+        save_usize(buff, self.nametable_banks[1]);
+        save_bool(buff, self.nametable_chr_rom);
+        save_bool(buff, self.sub_cartridge_selected);
+        save_u32(buff, self.license_timer);
+    }
+
+    fn load_state(&mut self, buff: &mut &[u8]) -> Result<(), String> {
+        self.prg_ram.load_state(buff)?;
+        self.chr.load_state(buff)?;
+        load_vec_u8(buff, &mut self.vram)?;
+        self.mirroring = load_mirroring(buff)?;
+        self.prg_bank = load_usize(buff)?;
+        self.prg_ram_enable = load_bool(buff)?;
+        for i in 0 .. 4 {
+            self.chr_banks[i] = load_usize(buff)?;
+        }
+        self.nametable_banks[0] = load_usize(buff)?;
+        self.nametable_banks[1] = load_usize(buff)?;
+        self.nametable_chr_rom = load_bool(buff)?;
+        self.sub_cartridge_selected = load_bool(buff)?;
+        self.license_timer = load_u32(buff)?;
+        return Ok(());
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use asm::Opcode::*;
+    use asm::AddressingMode::*;
+    use mmc::test_rom::*;
+
+    #[test]
+    fn switches_prg_chr_and_prg_ram() {
+        let mut rom = TestRom::new(68, 0x20000, 0x40000);
+        // 8k of PRG RAM
+        rom.prg_ram_shift = 7;
+        let mut nes = rom.run(vec![
+            Lda(Immediate(0b0001_0011)),
+            Sta(Absolute(0xF000)),
+            Lda(Immediate(0x42)),
+            Sta(Absolute(0x9000)),
+            Sta(Absolute(0x6000)),
+        ]);
+        assert_eq!(nes.mapper.debug_read_cpu(0x8000), Some(6));
+        assert_eq!(nes.mapper.debug_read_cpu(0xE000), Some(15));
+        assert_eq!(nes.mapper.debug_read_cpu(0x6000), Some(0x42));
+        // 2k bank $42 is 1k banks $84 and $85
+        assert_eq!(nes.mapper.debug_read_ppu(0x0800), Some(0x84));
+        assert_eq!(nes.mapper.debug_read_ppu(0x0C00), Some(0x85));
+        // Disabling PRG RAM hides it
+        nes.mapper.write_cpu(0xF000, 0b0000_0011);
+        assert_eq!(nes.mapper.debug_read_cpu(0x6000), None);
+    }
+
+    #[test]
+    fn chr_rom_nametables() {
+        let rom = TestRom::new(68, 0x20000, 0x40000);
+        let mut nes = rom.run(vec![
+            // Nametable banks $85 and $8A, with vertical mirroring
+            Lda(Immediate(0x05)),
+            Sta(Absolute(0xC000)),
+            Lda(Immediate(0x0A)),
+            Sta(Absolute(0xD000)),
+            Lda(Immediate(0b0001_0000)),
+            Sta(Absolute(0xE000)),
+        ]);
+        assert_eq!(nes.mapper.debug_read_ppu(0x2000), Some(0x85));
+        assert_eq!(nes.mapper.debug_read_ppu(0x2400), Some(0x8A));
+        assert_eq!(nes.mapper.debug_read_ppu(0x2800), Some(0x85));
+        assert_eq!(nes.mapper.debug_read_ppu(0x2C00), Some(0x8A));
+        assert_eq!(nes.mapper.chr_rom_address(0x2400), Some(0x8A * 0x400));
+        // Horizontal mirroring, back to CIRAM
+        nes.mapper.write_cpu(0xE000, 0b0001_0001);
+        assert_eq!(nes.mapper.debug_read_ppu(0x2400), Some(0x85));
+        nes.mapper.write_ppu(0x2000, 0x33);
+        assert_eq!(nes.mapper.debug_read_ppu(0x2000), Some(0x85));
+        nes.mapper.write_cpu(0xE000, 0b0000_0001);
+        nes.mapper.write_ppu(0x2000, 0x33);
+        assert_eq!(nes.mapper.debug_read_ppu(0x2000), Some(0x33));
+    }
+
+    #[test]
+    fn sub_cartridge_needs_license_writes() {
+        // 128k main cartridge, followed by a 32k sub-cartridge
+        let mut rom = TestRom::new(68, 0x28000, 0x40000);
+        rom.submapper = 1;
+        rom.fixed_bank_offset = 0x1C000;
+        let mut nes = rom.run(vec![
+            Lda(Immediate(0b0000_0001)),
+            Sta(Absolute(0xF000)),
+            Sta(Absolute(0x6000)),
+        ]);
+        // Sub-cartridge bank 1 is the 16k bank after the main cartridge's eight
+        assert_eq!(nes.mapper.debug_read_cpu(0x8000), Some(18));
+        assert_eq!(nes.mapper.debug_read_cpu(0xC000 + 0x2000), Some(15));
+        run_cycles(&mut nes, 1024 * 105);
+        assert_eq!(nes.mapper.debug_read_cpu(0x8000), None);
+        // Another write brings it back
+        nes.mapper.write_cpu(0x6000, 0);
+        assert_eq!(nes.mapper.debug_read_cpu(0x8000), Some(18));
+        // And the main cartridge's banks are there regardless
+        nes.mapper.write_cpu(0xF000, 0b0000_1010);
+        run_cycles(&mut nes, 1024 * 105);
+        assert_eq!(nes.mapper.debug_read_cpu(0x8000), Some(4));
+    }
+}
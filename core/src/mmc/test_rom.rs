@@ -0,0 +1,133 @@
+// Builds small NES 2.0 images around code from the asm module, for testing mappers.
+// Every 8k of PRG ROM is filled with its bank number, and every 1k of CHR ROM with its
+// own, so that tests can tell which banks are mapped in by reading any byte. The reset
+// code goes at the start of the last 16k (or wherever the mapper keeps its fixed bank),
+// followed by the IRQ handler at $D000, so both stay put whatever a mapper does with the
+// rest.
+
+use asm::*;
+use cartridge::mapper_from_file;
+use nes::NesState;
+
+pub const RESET_ADDRESS: u16 = 0xC000;
+pub const IRQ_ADDRESS: u16 = 0xD000;
+
+pub struct TestRom {
+    pub mapper: u16,
+    pub submapper: u8,
+    pub prg_size: usize,
+    pub chr_size: usize,
+    pub prg_ram_shift: u8,
+    pub vertical_mirroring: bool,
+    pub battery: bool,
+    // Where in PRG ROM the 16k seen at $C000 is
+    pub fixed_bank_offset: usize,
+}
+
+impl TestRom {
+    pub fn new(mapper: u16, prg_size: usize, chr_size: usize) -> TestRom {
+        return TestRom {
+            mapper: mapper,
+            submapper: 0,
+            prg_size: prg_size,
+            chr_size: chr_size,
+            prg_ram_shift: 0,
+            vertical_mirroring: false,
+            battery: false,
+            fixed_bank_offset: prg_size - 0x4000,
+        };
+    }
+
+    pub fn image(&self, reset: Vec<Opcode>, irq: Vec<Opcode>) -> Vec<u8> {
+        let mut header = vec![0x4Eu8, 0x45, 0x53, 0x1A, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0];
+        header[4] = (self.prg_size / 0x4000) as u8;
+        header[5] = (self.chr_size / 0x2000) as u8;
+        header[6] = (((self.mapper & 0x0F) as u8) << 4) |
+            (if self.battery {0b10} else {0}) |
+            (if self.vertical_mirroring {0b01} else {0});
+        header[7] = ((self.mapper & 0xF0) as u8) | 0x08;
+        header[8] = (self.submapper << 4) | ((self.mapper >> 8) as u8);
+        header[10] = if self.battery {self.prg_ram_shift << 4} else {self.prg_ram_shift};
+        // 8k of CHR RAM, if there's no CHR ROM
+        header[11] = if self.chr_size == 0 {0x07} else {0};
+
+        let mut prg: Vec<u8> = (0 .. self.prg_size).map(|i| (i / 0x2000) as u8).collect();
+        let fixed = self.fixed_bank_offset;
+        let reset_code = assemble(reset, RESET_ADDRESS).unwrap();
+        let irq_code = assemble(irq, IRQ_ADDRESS).unwrap();
+        prg[fixed .. fixed + reset_code.len()].copy_from_slice(&reset_code);
+        prg[fixed + 0x1000 .. fixed + 0x1000 + irq_code.len()].copy_from_slice(&irq_code);
+        // NMI goes to the IRQ handler too; tests leave NMIs off
+        let vectors = [low(IRQ_ADDRESS), high(IRQ_ADDRESS), low(RESET_ADDRESS), high(RESET_ADDRESS), low(IRQ_ADDRESS), high(IRQ_ADDRESS)];
+        prg[fixed + 0x3FFA .. fixed + 0x4000].copy_from_slice(&vectors);
+
+        let chr: Vec<u8> = (0 .. self.chr_size).map(|i| (i / 0x400) as u8).collect();
+
+        let mut image = header;
+        image.extend(prg);
+        image.extend(chr);
+        return image;
+    }
+
+    pub fn boot(&self, reset: Vec<Opcode>, irq: Vec<Opcode>) -> NesState {
+        let mapper = mapper_from_file(&self.image(reset, irq)).unwrap();
+        let mut nes = NesState::new(mapper);
+        nes.power_on();
+        return nes;
+    }
+
+    /// Boots, runs the code, and stops at the end of it, for tests which only need to
+    /// set things up before looking at the result
+    pub fn run(&self, code: Vec<Opcode>) -> NesState {
+        let mut reset = code;
+        reset.push(halt());
+        let mut nes = self.boot(reset, vec![Opcode::Rti]);
+        run_until_halted(&mut nes);
+        return nes;
+    }
+}
+
+fn low(word: u16) -> u8 {
+    return (word & 0x00FF) as u8;
+}
+
+fn high(word: u16) -> u8 {
+    return ((word & 0xFF00) >> 8) as u8;
+}
+
+/// Writes a bank number through the bank select and data registers at $8000 and $8001,
+/// as used by the MMC3 and the Namco 108 family
+pub fn select_bank(register: u8, bank: u8) -> Opcode {
+    return Opcode::List(vec![
+        Opcode::Lda(AddressingMode::Immediate(register)),
+        Opcode::Sta(AddressingMode::Absolute(0x8000)),
+        Opcode::Lda(AddressingMode::Immediate(bank)),
+        Opcode::Sta(AddressingMode::Absolute(0x8001)),
+    ]);
+}
+
+/// Code which spins in place forever, for the end of a test program
+pub fn halt() -> Opcode {
+    return Opcode::List(vec![
+        Opcode::Label(String::from("halt")),
+        Opcode::Jmp(AddressingMode::AbsoluteLabel(String::from("halt"))),
+    ]);
+}
+
+/// Runs until the CPU is spinning in halt(), or gives up after a few frames
+pub fn run_until_halted(nes: &mut NesState) {
+    for _ in 0 .. 100_000 {
+        let pc = nes.registers.pc;
+        nes.step();
+        if nes.registers.pc == pc {
+            return;
+        }
+    }
+    panic!("Test program never halted");
+}
+
+pub fn run_cycles(nes: &mut NesState, cycles: u32) {
+    for _ in 0 .. cycles {
+        nes.cycle();
+    }
+}
@@ -0,0 +1,524 @@
+// VRC4, and its predecessor VRC2, which lacks the IRQ counter and reaches less CHR ROM.
+// Konami wired the two register select lines to different CPU address lines from board
+// to board, so the same chip shows up as four mappers, with submappers telling the
+// wirings apart. Older headers have no submapper, so there every wiring the mapper might
+// use is listened to at once. No game writes to the registers in a way that'd tell. Such
+// boards are run as VRC4, which has every register VRC2 does, but keep VRC2's latch when
+// there's no PRG RAM in its place.
+// https://www.nesdev.org/wiki/VRC2_and_VRC4
+
+use ines::INesCartridge;
+use memoryblock::MemoryBlock;
+
+use mmc::mapper::*;
+use mmc::mirroring;
+use save_load::*;
+
+pub struct Vrc4 {
+    pub prg_rom: MemoryBlock,
+    pub prg_ram: MemoryBlock,
+    pub chr: MemoryBlock,
+    pub vram: Vec<u8>,
+    pub mirroring: Mirroring,
+    pub mapper_number: u16,
+    pub submapper: u8,
+
+    pub vrc2: bool,
+    // The CPU address lines feeding the chip's A0 and A1 inputs
+    pub a0_lines: u16,
+    pub a1_lines: u16,
+    // VRC2a drops the low bit of each CHR bank
+    pub chr_shift: usize,
+    pub has_battery: bool,
+
+    pub prg_banks: Vec<usize>,
+    pub prg_swap_mode: bool,
+    pub chr_banks: Vec<usize>,
+    // VRC2 boards without WRAM have a one bit latch at $6000, meant for a serial EEPROM
+    // that was never fitted
+    pub has_microwire: bool,
+    pub microwire_latch: u8,
+
+    pub irq_scanline_prescaler: i16,
+    pub irq_latch: u8,
+    pub irq_scanline_mode: bool,
+    pub irq_enable: bool,
+    pub irq_enable_after_acknowledgement: bool,
+    pub irq_pending: bool,
+    pub irq_counter: u8,
+}
+
+impl Vrc4 {
+    pub fn from_ines(ines: INesCartridge) -> Result<Vrc4, String> {
+        let prg_rom_block = ines.prg_rom_block();
+        let prg_ram_block = ines.prg_ram_block()?;
+        let chr_block = ines.chr_block()?;
+
+        let mapper_number = ines.header.mapper_number();
+        let submapper = ines.header.submapper_number();
+        let (vrc2, a0_lines, a1_lines) = match (mapper_number, submapper) {
+            (21, 1) => (false, 0x02, 0x04), // VRC4a
+            (21, 2) => (false, 0x40, 0x80), // VRC4c
+            (21, _) => (false, 0x42, 0x84),
+            (22, _) => (true,  0x02, 0x01), // VRC2a
+            (23, 1) => (false, 0x01, 0x02), // VRC4f
+            (23, 2) => (false, 0x04, 0x08), // VRC4e
+            (23, 3) => (true,  0x01, 0x02), // VRC2b
+            (23, _) => (false, 0x05, 0x0A),
+            (25, 1) => (false, 0x02, 0x01), // VRC4b
+            (25, 2) => (false, 0x08, 0x04), // VRC4d
+            (25, 3) => (true,  0x02, 0x01), // VRC2c
+            (25, _) => (false, 0x0A, 0x05),
+            _ => {return Err(format!("Mapper {} is not a VRC2 or VRC4", mapper_number))}
+        };
+        let maybe_vrc2 = vrc2 || ((mapper_number == 23 || mapper_number == 25) && submapper == 0);
+
+        return Ok(Vrc4 {
+            prg_rom: prg_rom_block.clone(),
+            prg_ram: prg_ram_block.clone(),
+            chr: chr_block.clone(),
+            vram: vec![0u8; 0x1000],
+            mirroring: ines.header.mirroring(),
+            mapper_number: mapper_number,
+            submapper: submapper,
+
+            vrc2: vrc2,
+            a0_lines: a0_lines,
+            a1_lines: a1_lines,
+            chr_shift: if mapper_number == 22 {1} else {0},
+            has_battery: ines.header.has_sram(),
+
+            prg_banks: vec![0usize; 2],
+            prg_swap_mode: false,
+            chr_banks: vec![0usize; 8],
+            has_microwire: maybe_vrc2 && prg_ram_block.len() == 0,
+            microwire_latch: 0,
+
+            irq_scanline_prescaler: 0,
+            irq_latch: 0,
+            irq_scanline_mode: false,
+            irq_enable: false,
+            irq_enable_after_acknowledgement: false,
+            irq_pending: false,
+            irq_counter: 0,
+        });
+    }
+
+    fn _clock_irq_prescaler(&mut self) {
+        self.irq_scanline_prescaler -= 3;
+        if self.irq_scanline_prescaler <= 0 {
+            self._clock_irq_counter();
+            self.irq_scanline_prescaler += 341;
+        }
+    }
+
+    fn _clock_irq_counter(&mut self) {
+        if self.irq_counter == 0xFF {
+            self.irq_counter = self.irq_latch;
+            self.irq_pending = true;
+        } else {
+            self.irq_counter += 1;
+        }
+    }
+
+    fn microwire(&self) -> bool {
+        return self.has_microwire;
+    }
+
+    // Folds the board's wiring back into $x000-$x003, the way the chip sees it
+    fn register_address(&self, address: u16) -> u16 {
+        let a0 = if address & self.a0_lines != 0 {0b01} else {0};
+        let a1 = if address & self.a1_lines != 0 {0b10} else {0};
+        return (address & 0xF000) | a1 | a0;
+    }
+
+    fn prg_bank(&self, address: u16) -> usize {
+        return match (address, self.prg_swap_mode) {
+            (0x8000 ..= 0x9FFF, false) => self.prg_banks[0],
+            (0x8000 ..= 0x9FFF, true)  => 0xFE,
+            (0xA000 ..= 0xBFFF, _)     => self.prg_banks[1],
+            (0xC000 ..= 0xDFFF, false) => 0xFE,
+            (0xC000 ..= 0xDFFF, true)  => self.prg_banks[0],
+            _ => 0xFF
+        };
+    }
+
+    fn chr_bank(&self, address: u16) -> usize {
+        return self.chr_banks[(address >> 10) as usize] >> self.chr_shift;
+    }
+
+    // Each CHR bank is written a nybble at a time; VRC4 takes five bits for the upper one
+    fn write_chr_nybble(&mut self, index: usize, high: bool, data: u8) {
+        if high {
+            let mask = if self.vrc2 {0x0F} else {0x1F};
+            self.chr_banks[index] = (self.chr_banks[index] & 0x0F) | (((data & mask) as usize) << 4);
+        } else {
+            self.chr_banks[index] = (self.chr_banks[index] & 0x1F0) | ((data & 0x0F) as usize);
+        }
+    }
+}
+
+impl Mapper for Vrc4 {
+    fn print_debug_status(&self) {
+        println!("======= {} =======", if self.vrc2 {"VRC2"} else {"VRC4"});
+        println!("Mapper: {}, Submapper: {}", self.mapper_number, self.submapper);
+        println!("PRG Banks: {:?}, Swap Mode: {}", self.prg_banks, self.prg_swap_mode);
+        println!("CHR Banks: {:?}", self.chr_banks);
+        println!("IRQ Latch: {}, Counter: {}, Enabled: {}", self.irq_latch, self.irq_counter, self.irq_enable);
+        println!("Mirroring Mode: {}", mirroring_mode_name(self.mirroring));
+        println!("====================");
+    }
+
+    fn mirroring(&self) -> Mirroring {
+        return self.mirroring;
+    }
+
+    fn clock_cpu(&mut self) {
+        if self.irq_enable {
+            if self.irq_scanline_mode {
+                self._clock_irq_prescaler();
+            } else {
+                self._clock_irq_counter();
+            }
+        }
+    }
+
+    fn irq_flag(&self) -> bool {
+        return self.irq_pending;
+    }
+
+    fn debug_read_cpu(&self, address: u16) -> Option<u8> {
+        match address {
+            // Only bit 0 is driven; the rest would be open bus
+            0x6000 ..= 0x6FFF if self.microwire() => Some(self.microwire_latch),
+            0x6000 ..= 0x7FFF => self.prg_ram.wrapping_read((address - 0x6000) as usize),
+            0x8000 ..= 0xFFFF => self.prg_rom.banked_read(0x2000, self.prg_bank(address), address as usize & 0x1FFF),
+            _ => None
+        }
+    }
+
+    fn prg_rom_address(&self, address: u16) -> Option<usize> {
+        match address {
+            0x8000 ..= 0xFFFF => self.prg_rom.banked_address(0x2000, self.prg_bank(address), address as usize & 0x1FFF),
+            _ => None
+        }
+    }
+
+    fn chr_rom_address(&self, address: u16) -> Option<usize> {
+        match address {
+            0x0000 ..= 0x1FFF if self.chr.is_readonly() => self.chr.banked_address(0x400, self.chr_bank(address), address as usize & 0x3FF),
+            _ => None
+        }
+    }
+
+    fn write_cpu(&mut self, address: u16, data: u8) {
+        match address {
+            0x6000 ..= 0x6FFF if self.microwire() => {self.microwire_latch = data & 0b0000_0001;},
+            // VRC4's WRAM enable bit is ignored; games that have WRAM leave it enabled
+            0x6000 ..= 0x7FFF => {self.prg_ram.wrapping_write((address - 0x6000) as usize, data);},
+            0x8000 ..= 0xFFFF => {
+                match self.register_address(address) {
+                    0x8000 ..= 0x8003 => {self.prg_banks[0] = (data & 0b0001_1111) as usize;},
+                    0x9000 ..= 0x9003 if self.vrc2 => {
+                        self.mirroring = if data & 0b1 == 0 {Mirroring::Vertical} else {Mirroring::Horizontal};
+                    },
+                    0x9000 | 0x9001 => {
+                        match data & 0b0000_0011 {
+                            0 => self.mirroring = Mirroring::Vertical,
+                            1 => self.mirroring = Mirroring::Horizontal,
+                            2 => self.mirroring = Mirroring::OneScreenLower,
+                            3 => self.mirroring = Mirroring::OneScreenUpper,
+                            _ => {}
+                        }
+                    },
+                    0x9002 | 0x9003 => {self.prg_swap_mode = (data & 0b0000_0010) != 0;},
+                    0xA000 ..= 0xA003 => {self.prg_banks[1] = (data & 0b0001_1111) as usize;},
+                    register @ 0xB000 ..= 0xE003 => {
+                        // Two registers, low then high nybble, for each of the eight banks
+                        let index = (((register >> 12) - 0xB) * 2 + ((register & 0b10) >> 1)) as usize;
+                        self.write_chr_nybble(index, (register & 0b01) != 0, data);
+                    },
+                    0xF000 if !self.vrc2 => {self.irq_latch = (self.irq_latch & 0xF0) | (data & 0x0F);},
+                    0xF001 if !self.vrc2 => {self.irq_latch = (self.irq_latch & 0x0F) | ((data & 0x0F) << 4);},
+                    0xF002 if !self.vrc2 => {
+                        self.irq_scanline_mode = ((data & 0b0000_0100) >> 2) == 0;
+                        self.irq_enable = (data & 0b0000_0010) != 0;
+                        self.irq_enable_after_acknowledgement = (data & 0b0000_0001) != 0;
+
+                        // acknowledge the pending IRQ if there is one
+                        self.irq_pending = false;
+
+                        // If the enable bit is set, setup for the next IRQ immediately, otherwise
+                        // do nothing (we may already have one in flight)
+                        if self.irq_enable {
+                            self.irq_counter = self.irq_latch;
+                            self.irq_scanline_prescaler = 341;
+                        }
+                    },
+                    0xF003 if !self.vrc2 => {
+                        self.irq_pending = false;
+                        self.irq_enable = self.irq_enable_after_acknowledgement;
+                    },
+                    _ => {}
+                }
+            },
+            _ => {}
+        }
+    }
+
+    fn debug_read_ppu(&self, address: u16) -> Option<u8> {
+        match address {
+            0x0000 ..= 0x1FFF => self.chr.banked_read(0x400, self.chr_bank(address), address as usize & 0x3FF),
+            0x2000 ..= 0x3FFF => match self.mirroring {
+                Mirroring::Horizontal => Some(self.vram[mirroring::horizontal_mirroring(address) as usize]),
+                Mirroring::Vertical   => Some(self.vram[mirroring::vertical_mirroring(address) as usize]),
+                Mirroring::OneScreenLower => Some(self.vram[mirroring::one_screen_lower(address) as usize]),
+                Mirroring::OneScreenUpper => Some(self.vram[mirroring::one_screen_upper(address) as usize]),
+                _ => None
+            },
+            _ => None
+        }
+    }
+
+    fn write_ppu(&mut self, address: u16, data: u8) {
+        match address {
+            0x0000 ..= 0x1FFF => {
+                let chr_bank = self.chr_bank(address);
+                self.chr.banked_write(0x400, chr_bank, address as usize & 0x3FF, data)
+            },
+            0x2000 ..= 0x3FFF => match self.mirroring {
+                Mirroring::Horizontal => self.vram[mirroring::horizontal_mirroring(address) as usize] = data,
+                Mirroring::Vertical   => self.vram[mirroring::vertical_mirroring(address) as usize] = data,
+                Mirroring::OneScreenLower => self.vram[mirroring::one_screen_lower(address) as usize] = data,
+                Mirroring::OneScreenUpper => self.vram[mirroring::one_screen_upper(address) as usize] = data,
+                _ => {}
+            },
+            _ => {}
+        }
+    }
+
+    fn has_sram(&self) -> bool {
+        return self.has_battery;
+    }
+
+    fn get_sram(&self) -> Vec<u8> {
+        return self.prg_ram.as_vec().clone();
+    }
+
+    fn load_sram(&mut self, sram_data: Vec<u8>) {
+        *self.prg_ram.as_mut_vec() = sram_data;
+    }
+
+    fn rom_checksum(&self) -> u32 {
+        return self.prg_rom.checksum();
+    }
+
+    fn save_state(&self, buff: &mut Vec<u8>) {
+        self.prg_ram.save_state(buff);
+        self.chr.save_state(buff);
+        save_vec_u8(buff, &self.vram);
+        save_mirroring(buff, self.mirroring);
+
+        save_usize(buff, self.prg_banks[0]);
+        save_usize(buff, self.prg_banks[1]);
+        save_bool(buff, self.prg_swap_mode);
+        for i in 0 .. 8 {
+            save_usize(buff, self.chr_banks[i]);
+        }
+        save_u8(buff, self.microwire_latch);
+
+        save_i16(buff, self.irq_scanline_prescaler);
+        save_u8(buff, self.irq_latch);
+        save_bool(buff, self.irq_scanline_mode);
+        save_bool(buff, self.irq_enable);
+        save_bool(buff, self.irq_enable_after_acknowledgement);
+        save_bool(buff, self.irq_pending);
+        save_u8(buff, self.irq_counter);
+    }
+
+    fn load_state(&mut self, buff: &mut &[u8]) -> Result<(), String> {
+        self.prg_ram.load_state(buff)?;
+        self.chr.load_state(buff)?;
+        load_vec_u8(buff, &mut self.vram)?;
+        self.mirroring = load_mirroring(buff)?;
+
+        self.prg_banks[0] = load_usize(buff)?;
+        self.prg_banks[1] = load_usize(buff)?;
+        self.prg_swap_mode = load_bool(buff)?;
+        for i in 0 .. 8 {
+            self.chr_banks[i] = load_usize(buff)?;
+        }
+        self.microwire_latch = load_u8(buff)?;
+
+        self.irq_scanline_prescaler = load_i16(buff)?;
+        self.irq_latch = load_u8(buff)?;
+        self.irq_scanline_mode = load_bool(buff)?;
+        self.irq_enable = load_bool(buff)?;
+        self.irq_enable_after_acknowledgement = load_bool(buff)?;
+        self.irq_pending = load_bool(buff)?;
+        self.irq_counter = load_u8(buff)?;
+        return Ok(());
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use asm::Opcode::*;
+    use asm::AddressingMode::*;
+    use mmc::test_rom::*;
+    use nes::NesState;
+
+    // Writes $03 to $6000, and reads it back into $00
+    fn latch_test(rom: &TestRom) -> u8 {
+        let nes = rom.run(vec![
+            Lda(Immediate(0x03)),
+            Sta(Absolute(0x6000)),
+            Lda(Absolute(0x6000)),
+            Sta(ZeroPage(0x00)),
+        ]);
+        return nes.memory.iram_raw[0];
+    }
+
+    #[test]
+    fn boards_without_a_submapper_or_prg_ram_keep_the_vrc2_latch() {
+        for &mapper in [23, 25].iter() {
+            let rom = TestRom::new(mapper, 0x20000, 0x20000);
+            assert_eq!(latch_test(&rom), 0x01);
+        }
+    }
+
+    #[test]
+    fn boards_with_prg_ram_use_it_instead_of_the_latch() {
+        let mut rom = TestRom::new(23, 0x20000, 0x20000);
+        rom.prg_ram_shift = 7;
+        assert_eq!(latch_test(&rom), 0x03);
+    }
+
+    #[test]
+    fn vrc4_submappers_have_no_latch() {
+        let mut rom = TestRom::new(25, 0x20000, 0x20000);
+        rom.submapper = 1;
+        let nes = rom.run(vec![
+            Lda(Immediate(0x03)),
+            Sta(Absolute(0x6000)),
+        ]);
+        assert_eq!(nes.mapper.debug_read_cpu(0x6000), None);
+    }
+
+    #[test]
+    fn boards_without_a_submapper_listen_to_both_wirings() {
+        // CHR bank 1's low nybble is $B002 on VRC4f and $B008 on VRC4e
+        for &register in [0xB002, 0xB008].iter() {
+            let rom = TestRom::new(23, 0x20000, 0x20000);
+            let nes = rom.run(vec![
+                Lda(Immediate(0x05)),
+                Sta(Absolute(register)),
+            ]);
+            assert_eq!(nes.mapper.debug_read_ppu(0x0400), Some(5));
+        }
+    }
+
+    // Clocks the mapper until it asks for an IRQ, and reports how many clocks that took
+    fn clocks_until_irq(nes: &mut NesState, limit: u32) -> Option<u32> {
+        for clocks in 1 ..= limit {
+            nes.mapper.clock_cpu();
+            if nes.mapper.irq_flag() {
+                return Some(clocks);
+            }
+        }
+        return None;
+    }
+
+    // VRC4f, whose registers are wired straight through
+    fn irq_test_nes(latch: u8, control: u8) -> NesState {
+        let mut rom = TestRom::new(23, 0x20000, 0x20000);
+        rom.submapper = 1;
+        let mut nes = rom.run(vec![]);
+        nes.mapper.write_cpu(0xF000, latch & 0x0F);
+        nes.mapper.write_cpu(0xF001, latch >> 4);
+        nes.mapper.write_cpu(0xF002, control);
+        return nes;
+    }
+
+    #[test]
+    fn cycle_mode_irqs_count_every_cpu_clock() {
+        let mut nes = irq_test_nes(0xFB, 0b110);
+        assert_eq!(clocks_until_irq(&mut nes, 1000), Some(5));
+        // The counter reloads and carries on, with the IRQ held until acknowledged
+        nes.mapper.clock_cpu();
+        assert!(nes.mapper.irq_flag());
+        nes.mapper.write_cpu(0xF003, 0);
+        assert!(!nes.mapper.irq_flag());
+        // Without the acknowledge bit in $F002, acknowledging also disables
+        assert_eq!(clocks_until_irq(&mut nes, 1000), None);
+    }
+
+    #[test]
+    fn scanline_mode_irqs_count_every_341_ppu_dots() {
+        let mut nes = irq_test_nes(0xFE, 0b011);
+        // Two scanlines of 341 dots, three dots per CPU clock
+        assert_eq!(clocks_until_irq(&mut nes, 1000), Some(228));
+        nes.mapper.write_cpu(0xF003, 0);
+        assert!(!nes.mapper.irq_flag());
+        // The acknowledge bit keeps the counter going, and the prescaler keeps its
+        // leftover dots
+        assert_eq!(clocks_until_irq(&mut nes, 1000), Some(227));
+        // Writing $F002 acknowledges too, and restarts from the latch
+        nes.mapper.write_cpu(0xF002, 0b010);
+        assert!(!nes.mapper.irq_flag());
+        assert_eq!(clocks_until_irq(&mut nes, 1000), Some(228));
+    }
+
+    #[test]
+    fn disabled_irqs_never_fire() {
+        let mut nes = irq_test_nes(0xFF, 0b100);
+        assert_eq!(clocks_until_irq(&mut nes, 1000), None);
+    }
+
+    // Each wiring, as (mapper, submapper, VRC2, A0 lines, A1 lines)
+    const WIRINGS: [(u16, u8, bool, u16, u16); 12] = [
+        (21, 1, false, 0x02, 0x04),
+        (21, 2, false, 0x40, 0x80),
+        (21, 0, false, 0x42, 0x84),
+        (22, 0, true,  0x02, 0x01),
+        (23, 1, false, 0x01, 0x02),
+        (23, 2, false, 0x04, 0x08),
+        (23, 3, true,  0x01, 0x02),
+        (23, 0, false, 0x05, 0x0A),
+        (25, 1, false, 0x02, 0x01),
+        (25, 2, false, 0x08, 0x04),
+        (25, 3, true,  0x02, 0x01),
+        (25, 0, false, 0x0A, 0x05),
+    ];
+
+    fn lines(mask: u16) -> Vec<u16> {
+        return (0 .. 16).map(|bit| 1 << bit).filter(|line| mask & line != 0).collect();
+    }
+
+    #[test]
+    fn every_wiring_reaches_the_registers() {
+        for &(mapper, submapper, vrc2, a0_lines, a1_lines) in WIRINGS.iter() {
+            let mut rom = TestRom::new(mapper, 0x20000, 0x20000);
+            rom.submapper = submapper;
+            // VRC2a only sees the upper bits of each CHR bank
+            let chr_scale = if mapper == 22 {2} else {1};
+            for &a0 in lines(a0_lines).iter() {
+                let mut nes = rom.run(vec![]);
+                // Bank 0's high nybble
+                nes.mapper.write_cpu(0xB000 | a0, 0x01);
+                assert_eq!(nes.mapper.debug_read_ppu(0x0000), Some(0x10 / chr_scale), "mapper {}.{}, A0 on ${:02X}", mapper, submapper, a0);
+            }
+            for &a1 in lines(a1_lines).iter() {
+                let mut nes = rom.run(vec![]);
+                // Bank 1's low nybble
+                nes.mapper.write_cpu(0xB000 | a1, 0x0A);
+                assert_eq!(nes.mapper.debug_read_ppu(0x0400), Some(0x0A / chr_scale), "mapper {}.{}, A1 on ${:02X}", mapper, submapper, a1);
+                // Mirroring on VRC2, but the PRG swap mode on VRC4, which moves the
+                // second to last bank (past the test code at its start) to $8000
+                nes.mapper.write_cpu(0x9000 | a1, 0x02);
+                let expected_bank = if vrc2 {0} else {14};
+                assert_eq!(nes.mapper.debug_read_cpu(0x8800), Some(expected_bank), "mapper {}.{}, A1 on ${:02X}", mapper, submapper, a1);
+            }
+        }
+    }
+}
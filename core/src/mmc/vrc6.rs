@@ -6,6 +6,7 @@ use memoryblock::MemoryBlock;
 
 use mmc::mapper::*;
 use mmc::mirroring;
+use save_load::*;
 
 use apu::AudioChannelState;
 use apu::PlaybackRate;
@@ -123,6 +124,31 @@ impl Vrc6PulseChannel {
             _ => {}
         }
     }
+
+    pub fn save_state(&self, buff: &mut Vec<u8>) {
+        save_bool(buff, self.enabled);
+        save_u8(buff, self.duty_compare);
+        save_u8(buff, self.duty_counter);
+        save_u8(buff, self.volume);
+        save_u16(buff, self.period_initial);
+        save_u16(buff, self.period_current);
+        save_bool(buff, self.halt);
+        save_bool(buff, self.scale_256);
+        save_bool(buff, self.scale_16);
+    }
+
+    pub fn load_state(&mut self, buff: &mut &[u8]) -> Result<(), String> {
+        self.enabled = load_bool(buff)?;
+        self.duty_compare = load_u8(buff)?;
+        self.duty_counter = load_u8(buff)?;
+        self.volume = load_u8(buff)?;
+        self.period_initial = load_u16(buff)?;
+        self.period_current = load_u16(buff)?;
+        self.halt = load_bool(buff)?;
+        self.scale_256 = load_bool(buff)?;
+        self.scale_16 = load_bool(buff)?;
+        return Ok(());
+    }
 }
 
 impl AudioChannelState for Vrc6PulseChannel {
@@ -293,6 +319,31 @@ impl Vrc6SawtoothChannel {
             _ => {}
         }
     }
+
+    pub fn save_state(&self, buff: &mut Vec<u8>) {
+        save_bool(buff, self.enabled);
+        save_u8(buff, self.accumulator_rate);
+        save_u8(buff, self.accumulator_step);
+        save_u8(buff, self.accumulator);
+        save_u16(buff, self.period_initial);
+        save_u16(buff, self.period_current);
+        save_bool(buff, self.halt);
+        save_bool(buff, self.scale_256);
+        save_bool(buff, self.scale_16);
+    }
+
+    pub fn load_state(&mut self, buff: &mut &[u8]) -> Result<(), String> {
+        self.enabled = load_bool(buff)?;
+        self.accumulator_rate = load_u8(buff)?;
+        self.accumulator_step = load_u8(buff)?;
+        self.accumulator = load_u8(buff)?;
+        self.period_initial = load_u16(buff)?;
+        self.period_current = load_u16(buff)?;
+        self.halt = load_bool(buff)?;
+        self.scale_256 = load_bool(buff)?;
+        self.scale_16 = load_bool(buff)?;
+        return Ok(());
+    }
 }
 
 impl AudioChannelState for Vrc6SawtoothChannel {
@@ -960,6 +1011,35 @@ impl Mapper for Vrc6 {
         }
     }
 
+    fn prg_rom_address(&self, address: u16) -> Option<usize> {
+        match address {
+            0x8000 ..= 0xBFFF => self.prg_rom.banked_address(0x4000, self.prg_bank_16, address as usize -  0x8000),
+            0xC000 ..= 0xDFFF => self.prg_rom.banked_address(0x2000, self.prg_bank_8, address as usize -  0xC000),
+            0xE000 ..= 0xFFFF => self.prg_rom.banked_address(0x2000, 0xFF, address as usize -  0xE000),
+            _ => None
+        }
+    }
+
+    fn chr_rom_address(&self, address: u16) -> Option<usize> {
+        if address > 0x1FFF || !self.chr.is_readonly() {
+            return None;
+        }
+        // Which register supplies each 1k slot, and whether it's half of a 2k bank
+        let slot = (address >> 10) as usize;
+        let (register, two_k) = match (self.ppu_banking_mode, slot) {
+            (0, _) => (slot, false),
+            (1, _) => (slot >> 1, true),
+            (_, 0 ..= 3) => (slot, false),
+            (_, _) => (4 + ((slot - 4) >> 1), true),
+        };
+        let bank = if two_k && self.chr_a10_rules {
+            (self.r[register] & 0xFE) | (slot & 0x1)
+        } else {
+            self.r[register]
+        };
+        return self.chr.banked_address(0x400, bank, address as usize);
+    }
+
     fn write_cpu(&mut self, address: u16, data: u8) {
         match address {
             0x6000 ..= 0x7FFF => {
@@ -1115,4 +1195,65 @@ impl Mapper for Vrc6 {
         self.pulse2.record_current_output();
         self.sawtooth.record_current_output();
     }
+
+    fn rom_checksum(&self) -> u32 {
+        return self.prg_rom.checksum();
+    }
+
+    fn save_state(&self, buff: &mut Vec<u8>) {
+        self.prg_ram.save_state(buff);
+        self.chr.save_state(buff);
+        save_vec_u8(buff, &self.vram);
+        save_bool(buff, self.prg_ram_enable);
+        save_usize(buff, self.prg_bank_16);
+        save_usize(buff, self.prg_bank_8);
+        save_vec_usize(buff, &self.r);
+        save_u8(buff, self.ppu_banking_mode);
+        save_u8(buff, self.mirroring_mode);
+        save_bool(buff, self.nametable_chrrom);
+        save_bool(buff, self.chr_a10_rules);
+        save_mirroring(buff, self.mirroring);
+        save_u8(buff, self.b003_shadow);
+
+        save_i16(buff, self.irq_scanline_prescaler);
+        save_u8(buff, self.irq_latch);
+        save_bool(buff, self.irq_scanline_mode);
+        save_bool(buff, self.irq_enable);
+        save_bool(buff, self.irq_enable_after_acknowledgement);
+        save_bool(buff, self.irq_pending);
+        save_u8(buff, self.irq_counter);
+
+        self.pulse1.save_state(buff);
+        self.pulse2.save_state(buff);
+        self.sawtooth.save_state(buff);
+    }
+
+    fn load_state(&mut self, buff: &mut &[u8]) -> Result<(), String> {
+        self.prg_ram.load_state(buff)?;
+        self.chr.load_state(buff)?;
+        load_vec_u8(buff, &mut self.vram)?;
+        self.prg_ram_enable = load_bool(buff)?;
+        self.prg_bank_16 = load_usize(buff)?;
+        self.prg_bank_8 = load_usize(buff)?;
+        load_vec_usize(buff, &mut self.r)?;
+        self.ppu_banking_mode = load_u8(buff)?;
+        self.mirroring_mode = load_u8(buff)?;
+        self.nametable_chrrom = load_bool(buff)?;
+        self.chr_a10_rules = load_bool(buff)?;
+        self.mirroring = load_mirroring(buff)?;
+        self.b003_shadow = load_u8(buff)?;
+
+        self.irq_scanline_prescaler = load_i16(buff)?;
+        self.irq_latch = load_u8(buff)?;
+        self.irq_scanline_mode = load_bool(buff)?;
+        self.irq_enable = load_bool(buff)?;
+        self.irq_enable_after_acknowledgement = load_bool(buff)?;
+        self.irq_pending = load_bool(buff)?;
+        self.irq_counter = load_u8(buff)?;
+
+        self.pulse1.load_state(buff)?;
+        self.pulse2.load_state(buff)?;
+        self.sawtooth.load_state(buff)?;
+        return Ok(());
+    }
 }
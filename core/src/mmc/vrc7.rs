@@ -6,6 +6,7 @@ use memoryblock::MemoryBlock;
 
 use mmc::mapper::*;
 use mmc::mirroring;
+use save_load::*;
 
 use apu::AudioChannelState;
 use apu::PlaybackRate;
@@ -148,6 +149,25 @@ impl Mapper for Vrc7 {
         }
     }
 
+    fn prg_rom_address(&self, address: u16) -> Option<usize> {
+        match address {
+            0x8000 ..= 0x9FFF => self.prg_rom.banked_address(0x2000, self.prg_banks[0] as usize, address as usize),
+            0xA000 ..= 0xBFFF => self.prg_rom.banked_address(0x2000, self.prg_banks[1] as usize, address as usize),
+            0xC000 ..= 0xDFFF => self.prg_rom.banked_address(0x2000, self.prg_banks[2] as usize, address as usize),
+            0xE000 ..= 0xFFFF => self.prg_rom.banked_address(0x2000, 0xFF, address as usize),
+            _ => None
+        }
+    }
+
+    fn chr_rom_address(&self, address: u16) -> Option<usize> {
+        match address {
+            0x0000 ..= 0x1FFF if self.chr.is_readonly() => {
+                self.chr.banked_address(0x400, self.chr_banks[(address >> 10) as usize] as usize, address as usize)
+            },
+            _ => None
+        }
+    }
+
     fn write_cpu(&mut self, address: u16, data: u8) {
         match address {
             0x6000 ..= 0x7FFF => {self.prg_ram.wrapping_write((address - 0x6000) as usize, data);},
@@ -295,6 +315,57 @@ impl Mapper for Vrc7 {
     fn record_expansion_audio_output(&mut self, _nes_sample: f32) {
         self.audio.record_output();
     }
+
+    fn rom_checksum(&self) -> u32 {
+        return self.prg_rom.checksum();
+    }
+
+    fn save_state(&self, buff: &mut Vec<u8>) {
+        self.prg_ram.save_state(buff);
+        self.chr.save_state(buff);
+
+        save_mirroring(buff, self.mirroring);
+        save_vec_u8(buff, &self.vram);
+
+        save_vec_u8(buff, &self.chr_banks);
+        save_vec_u8(buff, &self.prg_banks);
+
+        save_i16(buff, self.irq_scanline_prescaler);
+        save_u8(buff, self.irq_latch);
+        save_bool(buff, self.irq_scanline_mode);
+        save_bool(buff, self.irq_enable);
+        save_bool(buff, self.irq_enable_after_acknowledgement);
+        save_bool(buff, self.irq_pending);
+        save_u8(buff, self.irq_counter);
+
+        save_u8(buff, self.audio_register);
+
+        self.audio.save_state(buff);
+    }
+
+    fn load_state(&mut self, buff: &mut &[u8]) -> Result<(), String> {
+        self.prg_ram.load_state(buff)?;
+        self.chr.load_state(buff)?;
+
+        self.mirroring = load_mirroring(buff)?;
+        load_vec_u8(buff, &mut self.vram)?;
+
+        load_vec_u8(buff, &mut self.chr_banks)?;
+        load_vec_u8(buff, &mut self.prg_banks)?;
+
+        self.irq_scanline_prescaler = load_i16(buff)?;
+        self.irq_latch = load_u8(buff)?;
+        self.irq_scanline_mode = load_bool(buff)?;
+        self.irq_enable = load_bool(buff)?;
+        self.irq_enable_after_acknowledgement = load_bool(buff)?;
+        self.irq_pending = load_bool(buff)?;
+        self.irq_counter = load_u8(buff)?;
+
+        self.audio_register = load_u8(buff)?;
+
+        self.audio.load_state(buff)?;
+        return Ok(());
+    }
 }
 
 // TODO: explore and see if we can't somehow make these constant while keeping them
@@ -405,6 +476,27 @@ pub enum EnvState {
     Sustain
 }
 
+fn save_env_state(buff: &mut Vec<u8>, state: EnvState) {
+    let value = match state {
+        EnvState::Damp => 0,
+        EnvState::Attack => 1,
+        EnvState::Decay => 2,
+        EnvState::Sustain => 3,
+    };
+    save_u8(buff, value);
+}
+
+fn load_env_state(buff: &mut &[u8]) -> Result<EnvState, String> {
+    let value = load_u8(buff)?;
+    return match value {
+        0 => Ok(EnvState::Damp),
+        1 => Ok(EnvState::Attack),
+        2 => Ok(EnvState::Decay),
+        3 => Ok(EnvState::Sustain),
+        _ => Err(format!("Invalid VRC7 envelope state {} in save state", value))
+    };
+}
+
 pub struct Vrc7AudioChannel {
     logsin_lut: Vec<u16>,
     exp_lut: Vec<u16>,
@@ -913,6 +1005,127 @@ impl Vrc7AudioChannel {
     pub fn output(&self) -> i16 {
         return self.current_output;
     }
+
+    pub fn save_state(&self, buff: &mut Vec<u8>) {
+        save_u32(buff, self.fnum);
+        save_u32(buff, self.octave);
+        save_u16(buff, self.volume);
+        save_u8(buff, self.instrument_index);
+
+        save_u32(buff, self.carrier_phase);
+        save_u32(buff, self.modulator_phase);
+
+        save_bool(buff, self.modulator_tremolo);
+        save_bool(buff, self.modulator_vibrato);
+        save_bool(buff, self.modulator_sustain_enabled);
+        save_bool(buff, self.modulator_key_scaling);
+        save_usize(buff, self.modulator_multiplier);
+
+        save_bool(buff, self.carrier_tremolo);
+        save_bool(buff, self.carrier_vibrato);
+        save_bool(buff, self.carrier_sustain_enabled);
+        save_bool(buff, self.carrier_key_scaling);
+        save_usize(buff, self.carrier_multiplier);
+
+        save_usize(buff, self.modulator_key_level_scaling);
+        save_u16(buff, self.modulator_output_level);
+
+        save_usize(buff, self.carrier_key_level_scaling);
+        save_bool(buff, self.carrier_rectified);
+        save_bool(buff, self.modulator_rectified);
+        save_u8(buff, self.feedback);
+
+        save_u8(buff, self.modulator_attack_rate);
+        save_u8(buff, self.modulator_decay_rate);
+
+        save_u8(buff, self.carrier_attack_rate);
+        save_u8(buff, self.carrier_decay_rate);
+
+        save_u8(buff, self.modulator_sustain_level);
+        save_u8(buff, self.modulator_release_rate);
+
+        save_u8(buff, self.carrier_sustain_level);
+        save_u8(buff, self.carrier_release_rate);
+
+        save_u32(buff, self.global_counter);
+        save_u8(buff, self.carrier_env_level);
+        save_env_state(buff, self.carrier_env_state);
+        save_u8(buff, self.modulator_env_level);
+        save_env_state(buff, self.modulator_env_state);
+        save_i16(buff, self.modulator_previous_0);
+        save_i16(buff, self.modulator_previous_1);
+
+        save_bool(buff, self.key_on);
+        save_bool(buff, self.sustain_mode);
+
+        save_usize(buff, self.am_pos);
+        save_u8(buff, self.am_counter);
+        save_usize(buff, self.fm_pos);
+        save_u16(buff, self.fm_counter);
+
+        save_i16(buff, self.current_output);
+    }
+
+    pub fn load_state(&mut self, buff: &mut &[u8]) -> Result<(), String> {
+        self.fnum = load_u32(buff)?;
+        self.octave = load_u32(buff)?;
+        self.volume = load_u16(buff)?;
+        self.instrument_index = load_u8(buff)?;
+
+        self.carrier_phase = load_u32(buff)?;
+        self.modulator_phase = load_u32(buff)?;
+
+        self.modulator_tremolo = load_bool(buff)?;
+        self.modulator_vibrato = load_bool(buff)?;
+        self.modulator_sustain_enabled = load_bool(buff)?;
+        self.modulator_key_scaling = load_bool(buff)?;
+        self.modulator_multiplier = load_usize(buff)?;
+
+        self.carrier_tremolo = load_bool(buff)?;
+        self.carrier_vibrato = load_bool(buff)?;
+        self.carrier_sustain_enabled = load_bool(buff)?;
+        self.carrier_key_scaling = load_bool(buff)?;
+        self.carrier_multiplier = load_usize(buff)?;
+
+        self.modulator_key_level_scaling = load_usize(buff)?;
+        self.modulator_output_level = load_u16(buff)?;
+
+        self.carrier_key_level_scaling = load_usize(buff)?;
+        self.carrier_rectified = load_bool(buff)?;
+        self.modulator_rectified = load_bool(buff)?;
+        self.feedback = load_u8(buff)?;
+
+        self.modulator_attack_rate = load_u8(buff)?;
+        self.modulator_decay_rate = load_u8(buff)?;
+
+        self.carrier_attack_rate = load_u8(buff)?;
+        self.carrier_decay_rate = load_u8(buff)?;
+
+        self.modulator_sustain_level = load_u8(buff)?;
+        self.modulator_release_rate = load_u8(buff)?;
+
+        self.carrier_sustain_level = load_u8(buff)?;
+        self.carrier_release_rate = load_u8(buff)?;
+
+        self.global_counter = load_u32(buff)?;
+        self.carrier_env_level = load_u8(buff)?;
+        self.carrier_env_state = load_env_state(buff)?;
+        self.modulator_env_level = load_u8(buff)?;
+        self.modulator_env_state = load_env_state(buff)?;
+        self.modulator_previous_0 = load_i16(buff)?;
+        self.modulator_previous_1 = load_i16(buff)?;
+
+        self.key_on = load_bool(buff)?;
+        self.sustain_mode = load_bool(buff)?;
+
+        self.am_pos = load_usize(buff)?;
+        self.am_counter = load_u8(buff)?;
+        self.fm_pos = load_usize(buff)?;
+        self.fm_counter = load_u16(buff)?;
+
+        self.current_output = load_i16(buff)?;
+        return Ok(());
+    }
 }
 
 pub struct Vrc7Audio {
@@ -1135,6 +1348,31 @@ impl Vrc7Audio {
         self.channel5.record_current_output();
         self.channel6.record_current_output();
     }
+
+    pub fn save_state(&self, buff: &mut Vec<u8>) {
+        save_vec_u8(buff, &self.custom_patch);
+        self.channel1.save_state(buff);
+        self.channel2.save_state(buff);
+        self.channel3.save_state(buff);
+        self.channel4.save_state(buff);
+        self.channel5.save_state(buff);
+        self.channel6.save_state(buff);
+        save_usize(buff, self.current_channel);
+        save_u8(buff, self.delay_counter);
+    }
+
+    pub fn load_state(&mut self, buff: &mut &[u8]) -> Result<(), String> {
+        load_vec_u8(buff, &mut self.custom_patch)?;
+        self.channel1.load_state(buff)?;
+        self.channel2.load_state(buff)?;
+        self.channel3.load_state(buff)?;
+        self.channel4.load_state(buff)?;
+        self.channel5.load_state(buff)?;
+        self.channel6.load_state(buff)?;
+        self.current_channel = load_usize(buff)?;
+        self.delay_counter = load_u8(buff)?;
+        return Ok(());
+    }
 }
 
 impl AudioChannelState for Vrc7AudioChannel {
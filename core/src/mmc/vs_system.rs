@@ -0,0 +1,138 @@
+// Vs. System, the board used by most Vs. UniSystem and DualSystem games
+// Reference capabilities: https://www.nesdev.org/wiki/INES_Mapper_099
+
+use ines::INesCartridge;
+use memoryblock::MemoryBlock;
+
+use mmc::mapper::*;
+use save_load::*;
+use mmc::mirroring;
+
+pub struct VsSystem {
+    pub prg_rom: MemoryBlock,
+    pub chr: MemoryBlock,
+    pub wram: Vec<u8>,
+    pub mirroring: Mirroring,
+    pub bank_select: usize,
+    pub vram: Vec<u8>,
+}
+
+impl VsSystem {
+    pub fn from_ines(ines: INesCartridge) -> Result<VsSystem, String> {
+        let prg_rom_block = ines.prg_rom_block();
+        let chr_block = ines.chr_block()?;
+
+        return Ok(VsSystem {
+            prg_rom: prg_rom_block.clone(),
+            chr: chr_block.clone(),
+            // 2k, shared with the other CPU on a DualSystem cabinet
+            wram: vec![0u8; 0x800],
+            mirroring: ines.header.mirroring(),
+            bank_select: 0,
+            vram: vec![0u8; 0x1000],
+        });
+    }
+}
+
+impl Mapper for VsSystem {
+    fn print_debug_status(&self) {
+        println!("======= Vs. System =======");
+        println!("Bank Select: {}, Mirroring Mode: {}", self.bank_select, mirroring_mode_name(self.mirroring));
+        println!("====================");
+    }
+
+    fn mirroring(&self) -> Mirroring {
+        return self.mirroring;
+    }
+
+    fn debug_read_cpu(&self, address: u16) -> Option<u8> {
+        match address {
+            0x6000 ..= 0x7FFF => {Some(self.wram[(address & 0x7FF) as usize])},
+            0x8000 ..= 0x9FFF => {
+                // Gumshoe carries 40k of PRG, and uses the same bit that selects CHR to swap
+                // in its extra 8k bank. Everything else is a plain 32k.
+                if self.prg_rom.len() > 0x8000 {
+                    self.prg_rom.banked_read(0x2000, self.bank_select * 4, (address - 0x8000) as usize)
+                } else {
+                    self.prg_rom.wrapping_read((address - 0x8000) as usize)
+                }
+            },
+            0xA000 ..= 0xFFFF => {self.prg_rom.wrapping_read((address - 0x8000) as usize)},
+            _ => None
+        }
+    }
+
+    fn prg_rom_address(&self, address: u16) -> Option<usize> {
+        match address {
+            0x8000 ..= 0x9FFF if self.prg_rom.len() > 0x8000 => {
+                self.prg_rom.banked_address(0x2000, self.bank_select * 4, (address - 0x8000) as usize)
+            },
+            0x8000 ..= 0xFFFF => {self.prg_rom.wrapping_address((address - 0x8000) as usize)},
+            _ => None
+        }
+    }
+
+    fn chr_rom_address(&self, address: u16) -> Option<usize> {
+        match address {
+            0x0000 ..= 0x1FFF if self.chr.is_readonly() => {self.chr.banked_address(0x2000, self.bank_select, address as usize)},
+            _ => None
+        }
+    }
+
+    fn write_cpu(&mut self, address: u16, data: u8) {
+        match address {
+            // Shares the controller strobe register; bit 2 drives the bank select line
+            0x4016 => {self.bank_select = ((data & 0b0000_0100) >> 2) as usize;},
+            0x6000 ..= 0x7FFF => {self.wram[(address & 0x7FF) as usize] = data;},
+            _ => {}
+        }
+    }
+
+    fn debug_read_ppu(&self, address: u16) -> Option<u8> {
+        match address {
+            0x0000 ..= 0x1FFF => {self.chr.banked_read(0x2000, self.bank_select, address as usize)},
+            0x2000 ..= 0x3FFF => match self.mirroring {
+                Mirroring::Horizontal => Some(self.vram[mirroring::horizontal_mirroring(address) as usize]),
+                Mirroring::Vertical   => Some(self.vram[mirroring::vertical_mirroring(address) as usize]),
+                // The cabinet supplies the extra nametable RAM, so nearly all Vs. games are four-screen
+                Mirroring::FourScreen => Some(self.vram[mirroring::four_banks(address) as usize]),
+                _ => None
+            },
+            _ => None
+        }
+    }
+
+    fn write_ppu(&mut self, address: u16, data: u8) {
+        match address {
+            0x0000 ..= 0x1FFF => {self.chr.banked_write(0x2000, self.bank_select, address as usize, data)},
+            0x2000 ..= 0x3FFF => match self.mirroring {
+                Mirroring::Horizontal => self.vram[mirroring::horizontal_mirroring(address) as usize] = data,
+                Mirroring::Vertical   => self.vram[mirroring::vertical_mirroring(address) as usize] = data,
+                Mirroring::FourScreen => self.vram[mirroring::four_banks(address) as usize] = data,
+                _ => {}
+            },
+            _ => {}
+        }
+    }
+
+    fn rom_checksum(&self) -> u32 {
+        return self.prg_rom.checksum();
+    }
+
+    fn save_state(&self, buff: &mut Vec<u8>) {
+        self.chr.save_state(buff);
+        save_vec_u8(buff, &self.wram);
+        save_mirroring(buff, self.mirroring);
+        save_usize(buff, self.bank_select);
+        save_vec_u8(buff, &self.vram);
+    }
+
+    fn load_state(&mut self, buff: &mut &[u8]) -> Result<(), String> {
+        self.chr.load_state(buff)?;
+        load_vec_u8(buff, &mut self.wram)?;
+        self.mirroring = load_mirroring(buff)?;
+        self.bank_select = load_usize(buff)?;
+        load_vec_u8(buff, &mut self.vram)?;
+        return Ok(());
+    }
+}
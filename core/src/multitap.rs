@@ -0,0 +1,180 @@
+// Adapters which connect four controllers to a console with two ports. Players 1 and 3
+// are always read through $4016, and players 2 and 4 through $4017, but the two
+// adapters differ in how they share the port.
+// Reference: https://www.nesdev.org/wiki/Four_player_adapters
+
+use input::InputDevice;
+use input::StandardController;
+use ppu::PpuState;
+use save_load::*;
+
+// The NES Four Score plugs into both front ports, so there is one of these on each.
+// After the 8 buttons of the first controller it sends the 8 buttons of the second,
+// then a signature byte games use to detect the adapter, all serially on D0.
+pub struct FourScore {
+    pub buttons: [u8; 2],
+    pub latched_buttons: [u8; 2],
+    pub signature: u8,
+    pub read_count: u8,
+    pub strobe: bool,
+}
+
+impl FourScore {
+    /// The signature differs between the two ports. Sent in read order, $4016 reports
+    /// a 1 on its 20th read, and $4017 on its 19th.
+    pub fn new(port: usize) -> FourScore {
+        return FourScore {
+            buttons: [0; 2],
+            latched_buttons: [0; 2],
+            signature: if port == 1 {0b0000_1000} else {0b0000_0100},
+            read_count: 0,
+            strobe: false,
+        };
+    }
+}
+
+impl InputDevice for FourScore {
+    fn name(&self) -> &'static str {
+        return "Four Score";
+    }
+
+    fn latch(&mut self, strobe: bool) {
+        self.strobe = strobe;
+        if strobe {
+            self.latched_buttons = self.buttons;
+            self.read_count = 0;
+        }
+    }
+
+    fn clock(&mut self) {
+        if self.strobe {
+            self.latched_buttons = self.buttons;
+            self.read_count = 0;
+        }
+        self.read_count = self.read_count.saturating_add(1);
+    }
+
+    fn read(&self, _ppu: &PpuState) -> u8 {
+        if self.strobe {
+            return self.buttons[0] & 0x1;
+        }
+        return match self.read_count {
+            0 ..= 7 => (self.latched_buttons[0] >> self.read_count) & 0x1,
+            8 ..= 15 => (self.latched_buttons[1] >> (self.read_count - 8)) & 0x1,
+            16 ..= 23 => (self.signature >> (self.read_count - 16)) & 0x1,
+            _ => 1
+        };
+    }
+
+    fn buttons(&self) -> u8 {
+        return self.buttons[0];
+    }
+
+    fn set_buttons(&mut self, buttons: u8) {
+        self.buttons[0] = buttons;
+    }
+
+    fn secondary_buttons(&self) -> u8 {
+        return self.buttons[1];
+    }
+
+    fn set_secondary_buttons(&mut self, buttons: u8) {
+        self.buttons[1] = buttons;
+    }
+
+    fn save_state(&self, buff: &mut Vec<u8>) {
+        save_vec_u8(buff, &self.buttons);
+        save_vec_u8(buff, &self.latched_buttons);
+        save_u8(buff, self.read_count);
+        save_bool(buff, self.strobe);
+    }
+
+    fn load_state(&mut self, buff: &mut &[u8]) -> Result<(), String> {
+        load_vec_u8(buff, &mut self.buttons)?;
+        load_vec_u8(buff, &mut self.latched_buttons)?;
+        self.read_count = load_u8(buff)?;
+        self.strobe = load_bool(buff)?;
+        return Ok(());
+    }
+
+    fn save_input(&self, buff: &mut Vec<u8>) {
+        save_vec_u8(buff, &self.buttons);
+    }
+
+    fn load_input(&mut self, buff: &mut &[u8]) -> Result<(), String> {
+        return load_vec_u8(buff, &mut self.buttons);
+    }
+}
+
+// On the Famicom, players 3 and 4 plug into the expansion port instead, and are read
+// in parallel with the built in controllers on D1. Games which support this simply
+// read both bits every time.
+pub struct FamicomFourPlayers {
+    pub primary: StandardController,
+    pub secondary: StandardController,
+}
+
+impl FamicomFourPlayers {
+    pub fn new() -> FamicomFourPlayers {
+        return FamicomFourPlayers {
+            primary: StandardController::new(),
+            secondary: StandardController::new(),
+        };
+    }
+}
+
+impl InputDevice for FamicomFourPlayers {
+    fn name(&self) -> &'static str {
+        return "Famicom Four Players";
+    }
+
+    fn latch(&mut self, strobe: bool) {
+        self.primary.latch(strobe);
+        self.secondary.latch(strobe);
+    }
+
+    fn clock(&mut self) {
+        self.primary.clock();
+        self.secondary.clock();
+    }
+
+    fn read(&self, ppu: &PpuState) -> u8 {
+        return self.primary.read(ppu) | (self.secondary.read(ppu) << 1);
+    }
+
+    fn buttons(&self) -> u8 {
+        return self.primary.buttons;
+    }
+
+    fn set_buttons(&mut self, buttons: u8) {
+        self.primary.buttons = buttons;
+    }
+
+    fn secondary_buttons(&self) -> u8 {
+        return self.secondary.buttons;
+    }
+
+    fn set_secondary_buttons(&mut self, buttons: u8) {
+        self.secondary.buttons = buttons;
+    }
+
+    fn save_state(&self, buff: &mut Vec<u8>) {
+        self.primary.save_state(buff);
+        self.secondary.save_state(buff);
+    }
+
+    fn load_state(&mut self, buff: &mut &[u8]) -> Result<(), String> {
+        self.primary.load_state(buff)?;
+        return self.secondary.load_state(buff);
+    }
+
+    fn save_input(&self, buff: &mut Vec<u8>) {
+        self.primary.save_input(buff);
+        self.secondary.save_input(buff);
+    }
+
+    fn load_input(&mut self, buff: &mut &[u8]) -> Result<(), String> {
+        self.primary.load_input(buff)?;
+        return self.secondary.load_input(buff);
+    }
+}
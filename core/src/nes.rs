@@ -1,48 +1,93 @@
 use apu::ApuState;
 use cartridge;
+use cdl::CodeDataLogger;
+use cheats;
+use cheats::CheatList;
 use cycle_cpu;
 use cycle_cpu::CpuState;
 use cycle_cpu::Registers;
+use debugger::BreakpointHit;
+use debugger::Debugger;
+use debugger::StepTarget;
+use debugger::StopReason;
+use input::ControllerPort;
+use input::StandardController;
 use memory;
 use memory::CpuMemory;
 use ppu::PpuState;
+use profiler::Profiler;
+use region::Region;
+use save_load::*;
 use mmc::mapper::Mapper;
+use trace::TraceLogger;
 use tracked_events::EventTracker;
 
+// Identifies a rustico save state. The version must be bumped whenever the layout of
+// any saved component changes, as older states cannot be read field-by-field.
+const SAVE_STATE_MAGIC: &[u8] = b"RUSTICO-STATE";
+const SAVE_STATE_VERSION: u32 = 4;
+
+// How long a coin is held against the Vs. System's coin switch. Games debounce this
+// signal, so a single frame is often missed.
+const VS_COIN_PULSE_FRAMES: u8 = 3;
+
 pub struct NesState {
+    pub region: Region,
     pub apu: ApuState,
     pub cpu: CpuState,
     pub memory: CpuMemory,
     pub ppu: PpuState,
     pub registers: Registers,
     pub master_clock: u64,
-    pub p1_input: u8,
-    pub p1_data: u8,
-    pub p2_input: u8,
-    pub p2_data: u8,
+    pub port1: ControllerPort,
+    pub port2: ControllerPort,
     pub input_latch: bool,
+    // Vs. System cabinets wire their coin slots and DIP switches into the otherwise
+    // unused bits of the controller ports
+    pub vs_system: bool,
+    pub vs_dip_switches: u8,
+    pub vs_coin_timers: [u8; 2],
     pub mapper: Box<dyn Mapper>,
     pub last_frame: u32,
     pub event_tracker: EventTracker,
+    pub debugger: Debugger,
+    // When present, every instruction is logged here before it runs
+    pub tracer: Option<TraceLogger>,
+    // When present, records how each byte of ROM gets used
+    pub cdl: Option<CodeDataLogger>,
+    // When present, CPU time is attributed to routines here
+    pub profiler: Option<Profiler>,
+    pub cheats: CheatList,
 }
 
 impl NesState {
     pub fn new(m: Box<dyn Mapper>) -> NesState {
+        return NesState::with_region(m, Region::Ntsc);
+    }
+
+    pub fn with_region(m: Box<dyn Mapper>, region: Region) -> NesState {
         return NesState {
-            apu: ApuState::new(),
+            region: region,
+            apu: ApuState::new(region),
             cpu: CpuState::new(),
             memory: CpuMemory::new(),
-            ppu: PpuState::new(),
+            ppu: PpuState::new(region),
             registers: Registers::new(),
             master_clock: 0,
-            p1_input: 0,
-            p1_data: 0,
-            p2_input: 0,
-            p2_data: 0,
+            port1: ControllerPort::new(Box::new(StandardController::new())),
+            port2: ControllerPort::new(Box::new(StandardController::new())),
             input_latch: false,
+            vs_system: false,
+            vs_dip_switches: 0,
+            vs_coin_timers: [0; 2],
             mapper: m,
             last_frame: 0,
             event_tracker: EventTracker::new(),
+            debugger: Debugger::new(),
+            tracer: None,
+            cdl: None,
+            profiler: None,
+            cheats: CheatList::new(),
         }
     }
 
@@ -100,15 +145,34 @@ impl NesState {
     }
 
     pub fn cycle(&mut self) {
+        match self.profiler.take() {
+            Some(mut profiler) => {
+                profiler.log_clock(self);
+                self.profiler = Some(profiler);
+            },
+            None => {}
+        }
         cycle_cpu::run_one_clock(self);
-        self.master_clock = self.master_clock + 12;
-        // Three PPU clocks per every 1 CPU clock
-        self.ppu.clock(&mut *self.mapper);
-        self.ppu.clock(&mut *self.mapper);
-        self.ppu.clock(&mut *self.mapper);
+        // NTSC and Dendy run exactly three PPU clocks per CPU clock, but PAL runs 3.2, so
+        // count how many PPU dots begin during this CPU clock's share of the master clock
+        let cpu_divider = self.region.cpu_divider();
+        let ppu_divider = self.region.ppu_divider();
+        let ppu_clocks = (self.master_clock + cpu_divider) / ppu_divider - self.master_clock / ppu_divider;
+        self.master_clock = self.master_clock + cpu_divider;
+        for _ in 0 .. ppu_clocks {
+            self.ppu.clock(&mut *self.mapper);
+            match (&mut self.cdl, self.ppu.last_pattern_fetch.take()) {
+                (Some(cdl), Some(address)) => cdl.log_chr_render(&*self.mapper, address),
+                _ => {}
+            }
+        }
         self.event_tracker.current_scanline = self.ppu.current_scanline;
         self.event_tracker.current_cycle = self.ppu.current_scanline_cycle;
         self.apu.clock_apu(&mut *self.mapper);
+        match (&mut self.cdl, self.apu.dmc.last_fetch_address.take()) {
+            (Some(cdl), Some(address)) => cdl.log_dmc_read(&*self.mapper, address),
+            _ => {}
+        }
         self.mapper.clock_cpu();
     }
 
@@ -125,6 +189,17 @@ impl NesState {
         if self.ppu.current_frame != self.last_frame {
             self.event_tracker.swap_buffers();
             self.last_frame = self.ppu.current_frame;
+            for timer in self.vs_coin_timers.iter_mut() {
+                *timer = timer.saturating_sub(1);
+            }
+            cheats::apply_ram_freezes(self);
+        }
+    }
+
+    /// Drops a coin into one of the Vs. System's two coin slots
+    pub fn insert_coin(&mut self, slot: usize) {
+        if slot < self.vs_coin_timers.len() {
+            self.vs_coin_timers[slot] = VS_COIN_PULSE_FRAMES;
         }
     }
 
@@ -136,14 +211,103 @@ impl NesState {
     }
 
     pub fn run_until_vblank(&mut self) {
-        while self.ppu.current_scanline == 242 {
+        // Stops on the line after vblank begins, once the NMI has had its chance
+        let stop_scanline = self.region.vblank_scanline() + 1;
+        while self.ppu.current_scanline == stop_scanline {
             self.step();
         }
-        while self.ppu.current_scanline != 242 {
+        while self.ppu.current_scanline != stop_scanline {
             self.step();
         }
     }
 
+    /// Runs until a breakpoint is hit, a step over or step out reaches its destination,
+    /// or the caller's own condition is met, and reports which of these it was. The
+    /// condition is checked after every instruction.
+    pub fn run_until_break<F: FnMut(&NesState) -> bool>(&mut self, finished: F) -> StopReason {
+        self.debugger.collecting_hits = true;
+        let reason = self.run_until_break_or_finished(finished);
+        self.debugger.collecting_hits = false;
+        self.debugger.pending_hits.clear();
+        return reason;
+    }
+
+    fn run_until_break_or_finished<F: FnMut(&NesState) -> bool>(&mut self, mut finished: F) -> StopReason {
+        loop {
+            let fetching = cycle_cpu::opcode_fetch_pending(self);
+            if fetching {
+                let pc = self.registers.pc;
+                let opcode = memory::debug_read_byte(self, pc);
+                match self.debugger.check_execute(pc, opcode) {
+                    Some(hit) if self.breakpoint_triggered(&hit) => {
+                        self.debugger.resume_address = Some(pc);
+                        self.debugger.step_target = None;
+                        return StopReason::Breakpoint(hit);
+                    },
+                    _ => {}
+                }
+            }
+            self.step();
+            let triggered_hit = self.debugger.pending_hits.iter().cloned().find(|hit| self.breakpoint_triggered(hit));
+            self.debugger.pending_hits.clear();
+            match triggered_hit {
+                Some(hit) => {
+                    self.debugger.step_target = None;
+                    return StopReason::Breakpoint(hit);
+                },
+                None => {}
+            }
+            if fetching && self.step_finished() {
+                self.debugger.step_target = None;
+                return StopReason::StepFinished;
+            }
+            if finished(self) {
+                return StopReason::Finished;
+            }
+        }
+    }
+
+    // Whether any breakpoint covering this hit has its condition met, if it has one.
+    // Conditions see the state after the accessing instruction has finished, except
+    // for execute breakpoints, which are checked before it starts.
+    fn breakpoint_triggered(&self, hit: &BreakpointHit) -> bool {
+        return self.debugger.breakpoints.iter().any(|breakpoint| breakpoint.covers(hit) && match &breakpoint.condition {
+            Some(condition) => condition.evaluate_access(self, hit.address, hit.data) != 0,
+            None => true
+        });
+    }
+
+    // Called after each instruction during run_until_break
+    fn step_finished(&self) -> bool {
+        return match self.debugger.step_target {
+            Some(StepTarget::Instruction) => true,
+            Some(StepTarget::ReturnTo{address, stack_pointer}) => {
+                self.registers.pc == address && self.registers.s >= stack_pointer
+            },
+            Some(StepTarget::StackAbove{stack_pointer}) => {
+                // RTS or RTI
+                (self.cpu.opcode == 0x60 || self.cpu.opcode == 0x40) && self.registers.s > stack_pointer
+            },
+            None => false
+        };
+    }
+
+    /// Sets up the next run_until_break to stop after one instruction, treating a JSR
+    /// and the entire routine it calls as a single instruction
+    pub fn step_over(&mut self) {
+        let pc = self.registers.pc;
+        if memory::debug_read_byte(self, pc) == 0x20 {
+            self.debugger.step_target = Some(StepTarget::ReturnTo{address: pc.wrapping_add(3), stack_pointer: self.registers.s});
+        } else {
+            self.debugger.step_target = Some(StepTarget::Instruction);
+        }
+    }
+
+    /// Sets up the next run_until_break to stop once the current routine returns
+    pub fn step_out(&mut self) {
+        self.debugger.step_target = Some(StepTarget::StackAbove{stack_pointer: self.registers.s});
+    }
+
     pub fn nudge_ppu_alignment(&mut self) {
         // Give the PPU a swift kick:
         self.ppu.clock(&mut *self.mapper);
@@ -162,4 +326,158 @@ impl NesState {
             self.mapper.load_sram(sram_data);
         }
     }
+
+    /// The inputs the player is currently holding on every device, in a form that can
+    /// be restored later with load_inputs. Unlike a save state this leaves everything
+    /// else in the system alone.
+    pub fn save_inputs(&self) -> Vec<u8> {
+        let mut buff = Vec::new();
+        self.port1.device.save_input(&mut buff);
+        self.port2.device.save_input(&mut buff);
+        return buff;
+    }
+
+    pub fn load_inputs(&mut self, data: &[u8]) -> Result<(), String> {
+        let mut buff = data;
+        self.port1.device.load_input(&mut buff)?;
+        self.port2.device.load_input(&mut buff)?;
+        return Ok(());
+    }
+
+    pub fn save_state(&self) -> Vec<u8> {
+        let mut buff = Vec::new();
+        buff.extend_from_slice(SAVE_STATE_MAGIC);
+        save_u32(&mut buff, SAVE_STATE_VERSION);
+        save_u32(&mut buff, self.mapper.rom_checksum());
+        save_u8(&mut buff, self.region as u8);
+
+        self.apu.save_state(&mut buff);
+        self.cpu.save_state(&mut buff);
+        self.memory.save_state(&mut buff);
+        self.ppu.save_state(&mut buff);
+        self.registers.save_state(&mut buff);
+        save_u64(&mut buff, self.master_clock);
+        self.port1.save_state(&mut buff);
+        self.port2.save_state(&mut buff);
+        save_bool(&mut buff, self.input_latch);
+        save_u8(&mut buff, self.vs_dip_switches);
+        save_u8(&mut buff, self.vs_coin_timers[0]);
+        save_u8(&mut buff, self.vs_coin_timers[1]);
+        save_u32(&mut buff, self.last_frame);
+        self.mapper.save_state(&mut buff);
+        return buff;
+    }
+
+    /// Restores a state produced by save_state. The header is fully validated before
+    /// anything is modified, so a state for the wrong game or an older version of the
+    /// format leaves the running game untouched. A state that turns out to be truncated
+    /// or corrupt partway through fails with an error, and the system is put back the
+    /// way it was.
+    pub fn load_state(&mut self, data: &[u8]) -> Result<(), String> {
+        if !data.starts_with(SAVE_STATE_MAGIC) {
+            return Err(String::from("Not a rustico save state"));
+        }
+        let mut buff = &data[SAVE_STATE_MAGIC.len() ..];
+        let version = load_u32(&mut buff)?;
+        if version != SAVE_STATE_VERSION {
+            return Err(format!("Unsupported save state version {}, expected {}", version, SAVE_STATE_VERSION));
+        }
+        let checksum = load_u32(&mut buff)?;
+        if checksum != self.mapper.rom_checksum() {
+            return Err(format!("Save state was made with a different ROM (checksum {:08X}, loaded ROM is {:08X})", checksum, self.mapper.rom_checksum()));
+        }
+        let region = load_u8(&mut buff)?;
+        if region != self.region as u8 {
+            return Err(format!("Save state was made for a different region than the running {} system", self.region.name()));
+        }
+
+        // Everything is loaded in place, so keep a copy of what was there to fall back on
+        let backup = self.save_state();
+        match self.load_state_contents(&mut buff) {
+            Ok(()) => {return Ok(())},
+            Err(why) => {
+                self.load_state(&backup).expect("Couldn't restore the state from before a failed load");
+                return Err(why);
+            }
+        }
+    }
+
+    fn load_state_contents(&mut self, buff: &mut &[u8]) -> Result<(), String> {
+        self.apu.load_state(buff)?;
+        self.cpu.load_state(buff)?;
+        self.memory.load_state(buff)?;
+        self.ppu.load_state(buff)?;
+        self.registers.load_state(buff)?;
+        self.master_clock = load_u64(buff)?;
+        self.port1.load_state(buff)?;
+        self.port2.load_state(buff)?;
+        self.input_latch = load_bool(buff)?;
+        self.vs_dip_switches = load_u8(buff)?;
+        self.vs_coin_timers[0] = load_u8(buff)?;
+        self.vs_coin_timers[1] = load_u8(buff)?;
+        self.last_frame = load_u32(buff)?;
+        self.mapper.load_state(buff)?;
+        if buff.len() != 0 {
+            return Err(format!("Save state has {} unexpected trailing bytes", buff.len()));
+        }
+        return Ok(());
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use asm::Opcode::*;
+    use asm::AddressingMode::*;
+    use mmc::test_rom::*;
+    use nes::NesState;
+
+    // An MMC3 game which switches banks, then counts forever in RAM and on the screen
+    fn counting_nes() -> NesState {
+        let rom = TestRom::new(4, 0x20000, 0x20000);
+        return rom.boot(vec![
+            Lda(Immediate(6)),
+            Sta(Absolute(0x8000)),
+            Lda(Immediate(5)),
+            Sta(Absolute(0x8001)),
+            Lda(Immediate(0x1E)),
+            Sta(Absolute(0x2001)),
+            Label(String::from("loop")),
+            Inc(ZeroPage(0x00)),
+            Lda(ZeroPage(0x00)),
+            Sta(Absolute(0x2006)),
+            Sta(Absolute(0x2007)),
+            Jmp(AbsoluteLabel(String::from("loop"))),
+        ], vec![Rti]);
+    }
+
+    #[test]
+    fn loaded_states_run_on_exactly_as_before() {
+        let mut nes = counting_nes();
+        run_cycles(&mut nes, 50_000);
+        let state = nes.save_state();
+        run_cycles(&mut nes, 50_000);
+        let later = nes.save_state();
+
+        nes.load_state(&state).unwrap();
+        assert_eq!(nes.save_state(), state);
+        assert_eq!(nes.mapper.debug_read_cpu(0x8000), Some(5));
+        run_cycles(&mut nes, 50_000);
+        assert!(nes.save_state() == later);
+    }
+
+    #[test]
+    fn failed_loads_leave_the_system_as_it_was() {
+        let mut nes = counting_nes();
+        run_cycles(&mut nes, 50_000);
+        let state = nes.save_state();
+        run_cycles(&mut nes, 50_000);
+        let before = nes.save_state();
+
+        let mut padded = state.clone();
+        padded.push(0);
+        for broken in [&state[.. state.len() / 2], &state[.. state.len() - 1], &padded[..]].iter() {
+            assert!(nes.load_state(broken).is_err());
+            assert!(nes.save_state() == before);
+        }
+    }
 }
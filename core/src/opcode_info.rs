@@ -1,3 +1,5 @@
+use disassembler;
+
 pub fn alu_block(addressing_mode_index: u8, opcode_index: u8) -> (&'static str, &'static str) {
   let addressing_mode = match addressing_mode_index {
     // Zero Page Mode
@@ -147,19 +149,21 @@ pub fn addressing_bytes(addressing_mode: &str) -> u8 {
 	}
 }
 
-pub fn disassemble_instruction(opcode: u8, _: u8, _: u8) -> (String, u8) {
-  let logic_block = opcode & 0b0000_0011;
-  let addressing_mode_index = (opcode & 0b0001_1100) >> 2;
-  let opcode_index = (opcode & 0b1110_0000) >> 5;
-
-  let (opcode_name, addressing_mode) = match logic_block {
-    0b00 => control_block(opcode),
-    0b01 => alu_block(addressing_mode_index, opcode_index),
-    0b10 => rmw_block(opcode, addressing_mode_index, opcode_index),
-    _ => ("???", "")
+// Without knowing where the instruction lives, branches can only show how far they jump,
+// as *+n from the start of the branch. See disassembler::disassemble for the full picture.
+pub fn disassemble_instruction(opcode: u8, data1: u8, data2: u8) -> (String, u8) {
+  let mode = disassembler::addressing_mode(opcode);
+  let instruction = disassembler::Instruction {
+    address: 0,
+    opcode: opcode,
+    mnemonic: disassembler::mnemonic(opcode),
+    mode: mode,
+    bytes: vec![opcode, data1, data2][.. 1 + mode.operand_bytes() as usize].to_vec(),
+    bank: None,
   };
-
-  let instruction = format!("{} {}", opcode_name, addressing_mode);
-  let data_bytes = addressing_bytes(addressing_mode);
-  return (instruction, data_bytes);
+  let text = match mode {
+    disassembler::AddressingMode::Relative => format!("{} *{:+}", instruction.mnemonic, data1 as i8 as i32 + 2),
+    _ => instruction.text()
+  };
+  return (text, mode.operand_bytes() as u8);
 }
\ No newline at end of file
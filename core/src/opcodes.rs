@@ -394,6 +394,8 @@ pub fn service_interrupt(nes: &mut NesState) {
       } else {
         nes.cpu.temp_address = 0xFFFE;
       }
+      let nmi = nes.cpu.temp_address == 0xFFFA;
+      nes.debugger.snoop_interrupt(nes.cpu.opcode_address, nes.cpu.temp_address, nmi);
       let status_byte = nes.registers.status_as_byte(false);
       push(nes, status_byte);
       nes.cpu.upcoming_write = false;
@@ -513,3 +513,76 @@ pub const NTSC_PAL: [u8; 64 * 8 * 3] = [
 0x5e, 0x5e, 0x5e,
 0x00, 0x00, 0x00,
 0x00, 0x00, 0x00];
+
+// The RGB PPUs used in the Vs. System and PlayChoice-10 (2C03, 2C04 and 2C05) drive
+// each color channel directly from a 3-bit DAC instead of generating a composite
+// signal, so their palette is exact. Each entry is written in octal as RGB levels.
+// Reference: https://www.nesdev.org/wiki/PPU_palettes#2C03_and_2C05
+const RGB_PPU_COLORS: [u16; 64] = [
+    0o333, 0o014, 0o006, 0o326, 0o403, 0o503, 0o510, 0o420, 0o320, 0o120, 0o031, 0o040, 0o022, 0o000, 0o000, 0o000,
+    0o555, 0o036, 0o027, 0o407, 0o507, 0o704, 0o700, 0o630, 0o430, 0o140, 0o040, 0o053, 0o044, 0o111, 0o000, 0o000,
+    0o777, 0o357, 0o447, 0o637, 0o707, 0o737, 0o740, 0o750, 0o660, 0o360, 0o070, 0o276, 0o077, 0o222, 0o000, 0o000,
+    0o777, 0o567, 0o657, 0o757, 0o747, 0o755, 0o764, 0o772, 0o773, 0o572, 0o473, 0o276, 0o467, 0o444, 0o000, 0o000];
+
+// The 2C04 shares the 2C03's colors, but each of its four revisions scrambles the order
+// in which they appear. Games were written for one specific revision, which made it harder
+// for operators to swap ROMs between cabinets. These tables map the index a game writes
+// to palette RAM onto the 2C03 color that the chip actually outputs.
+pub const RP2C03_LUT: [u8; 64] = [
+    0x00, 0x01, 0x02, 0x03, 0x04, 0x05, 0x06, 0x07, 0x08, 0x09, 0x0A, 0x0B, 0x0C, 0x0D, 0x0E, 0x0F,
+    0x10, 0x11, 0x12, 0x13, 0x14, 0x15, 0x16, 0x17, 0x18, 0x19, 0x1A, 0x1B, 0x1C, 0x1D, 0x1E, 0x1F,
+    0x20, 0x21, 0x22, 0x23, 0x24, 0x25, 0x26, 0x27, 0x28, 0x29, 0x2A, 0x2B, 0x2C, 0x2D, 0x2E, 0x2F,
+    0x30, 0x31, 0x32, 0x33, 0x34, 0x35, 0x36, 0x37, 0x38, 0x39, 0x3A, 0x3B, 0x3C, 0x3D, 0x3E, 0x3F];
+
+pub const RP2C04_0001_LUT: [u8; 64] = [
+    0x35, 0x23, 0x16, 0x22, 0x1C, 0x09, 0x1D, 0x15, 0x20, 0x00, 0x27, 0x05, 0x04, 0x28, 0x08, 0x20,
+    0x21, 0x3E, 0x1F, 0x29, 0x3C, 0x32, 0x36, 0x12, 0x3F, 0x2B, 0x2E, 0x1E, 0x3D, 0x2D, 0x24, 0x01,
+    0x0E, 0x31, 0x33, 0x2A, 0x2C, 0x0C, 0x1B, 0x14, 0x2E, 0x07, 0x34, 0x06, 0x13, 0x02, 0x26, 0x2E,
+    0x2E, 0x19, 0x10, 0x0A, 0x39, 0x03, 0x37, 0x17, 0x0F, 0x11, 0x0B, 0x0D, 0x38, 0x25, 0x18, 0x3A];
+
+pub const RP2C04_0002_LUT: [u8; 64] = [
+    0x2E, 0x27, 0x18, 0x39, 0x3A, 0x25, 0x1C, 0x31, 0x16, 0x13, 0x38, 0x34, 0x20, 0x23, 0x3C, 0x0B,
+    0x0F, 0x21, 0x06, 0x3D, 0x1B, 0x29, 0x1E, 0x22, 0x1D, 0x24, 0x0E, 0x2B, 0x32, 0x08, 0x2E, 0x03,
+    0x04, 0x36, 0x26, 0x33, 0x11, 0x1F, 0x10, 0x02, 0x14, 0x3F, 0x00, 0x09, 0x12, 0x2E, 0x28, 0x20,
+    0x3E, 0x0D, 0x2A, 0x17, 0x0C, 0x01, 0x15, 0x19, 0x2E, 0x2C, 0x07, 0x37, 0x35, 0x05, 0x0A, 0x2D];
+
+pub const RP2C04_0003_LUT: [u8; 64] = [
+    0x14, 0x25, 0x3A, 0x10, 0x0B, 0x20, 0x31, 0x09, 0x01, 0x2E, 0x36, 0x08, 0x15, 0x3D, 0x3E, 0x3C,
+    0x22, 0x1C, 0x05, 0x12, 0x19, 0x18, 0x17, 0x1B, 0x00, 0x03, 0x2E, 0x02, 0x16, 0x06, 0x34, 0x35,
+    0x23, 0x0F, 0x0E, 0x37, 0x0D, 0x27, 0x26, 0x20, 0x29, 0x04, 0x21, 0x24, 0x11, 0x2D, 0x2E, 0x1F,
+    0x2C, 0x1E, 0x39, 0x33, 0x07, 0x2A, 0x28, 0x1D, 0x0A, 0x2E, 0x32, 0x38, 0x13, 0x2B, 0x3F, 0x0C];
+
+pub const RP2C04_0004_LUT: [u8; 64] = [
+    0x18, 0x03, 0x1C, 0x28, 0x2E, 0x35, 0x01, 0x17, 0x10, 0x1F, 0x2A, 0x0E, 0x36, 0x37, 0x0B, 0x39,
+    0x25, 0x1E, 0x12, 0x34, 0x2E, 0x1D, 0x06, 0x26, 0x3E, 0x1B, 0x22, 0x19, 0x04, 0x2E, 0x3A, 0x21,
+    0x05, 0x0A, 0x07, 0x02, 0x13, 0x14, 0x00, 0x15, 0x0C, 0x3D, 0x11, 0x0F, 0x0D, 0x38, 0x2D, 0x24,
+    0x33, 0x20, 0x08, 0x16, 0x3F, 0x2B, 0x20, 0x3C, 0x2E, 0x27, 0x23, 0x31, 0x29, 0x32, 0x2C, 0x09];
+
+// Expands a lookup table into the same layout as NTSC_PAL: 64 colors for each of the 8
+// emphasis combinations. Rather than dimming the other channels like the 2C02, an RGB
+// PPU's emphasis bits drive their channel to full brightness.
+const fn rgb_palette(lut: &[u8; 64]) -> [u8; 64 * 8 * 3] {
+    let mut palette = [0u8; 64 * 8 * 3];
+    let mut i = 0;
+    while i < 64 * 8 {
+        let emphasis = i >> 6;
+        let color = RGB_PPU_COLORS[lut[i & 0x3F] as usize];
+        let mut channel = 0;
+        while channel < 3 {
+            let mut level = (color >> (6 - channel * 3)) & 0b111;
+            if emphasis & (1 << channel) != 0 {
+                level = 0b111;
+            }
+            palette[i * 3 + channel] = ((level * 255 + 3) / 7) as u8;
+            channel += 1;
+        }
+        i += 1;
+    }
+    return palette;
+}
+
+pub const RP2C03_PAL: [u8; 64 * 8 * 3] = rgb_palette(&RP2C03_LUT);
+pub const RP2C04_0001_PAL: [u8; 64 * 8 * 3] = rgb_palette(&RP2C04_0001_LUT);
+pub const RP2C04_0002_PAL: [u8; 64 * 8 * 3] = rgb_palette(&RP2C04_0002_LUT);
+pub const RP2C04_0003_PAL: [u8; 64 * 8 * 3] = rgb_palette(&RP2C04_0003_LUT);
+pub const RP2C04_0004_PAL: [u8; 64 * 8 * 3] = rgb_palette(&RP2C04_0004_LUT);
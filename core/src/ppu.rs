@@ -3,6 +3,54 @@
 // and prototype stages.
 
 use mmc::mapper::*;
+use palettes;
+use region::Region;
+use save_load::*;
+
+// Which PPU chip is drawing the picture. Home consoles all use the composite 2C02 (or
+// its PAL cousins); the RGB variants appear in arcade hardware like the Vs. System.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum PpuModel {
+    Rp2c02,
+    Rp2c03,
+    Rp2c04_0001,
+    Rp2c04_0002,
+    Rp2c04_0003,
+    Rp2c04_0004,
+    // The 2C05 uses the 2C03 palette, but reports an identifying value in the low bits
+    // of PPUSTATUS, which games check as a form of copy protection
+    Rc2c05(u8),
+}
+
+impl PpuModel {
+    /// The RGB table for the values in PpuState::screen, laid out like palettes::NTSC_PAL
+    pub fn palette(&self) -> &'static [u8; 64 * 8 * 3] {
+        return match self {
+            PpuModel::Rp2c02 => &palettes::NTSC_PAL,
+            PpuModel::Rp2c03 => &palettes::RP2C03_PAL,
+            PpuModel::Rp2c04_0001 => &palettes::RP2C04_0001_PAL,
+            PpuModel::Rp2c04_0002 => &palettes::RP2C04_0002_PAL,
+            PpuModel::Rp2c04_0003 => &palettes::RP2C04_0003_PAL,
+            PpuModel::Rp2c04_0004 => &palettes::RP2C04_0004_PAL,
+            PpuModel::Rc2c05(_) => &palettes::RP2C03_PAL,
+        };
+    }
+
+    /// The 2C05 swaps the addresses of PPUCTRL and PPUMASK
+    pub fn swaps_control_and_mask(&self) -> bool {
+        return match self {
+            PpuModel::Rc2c05(_) => true,
+            _ => false
+        };
+    }
+
+    pub fn status_id(&self) -> Option<u8> {
+        return match self {
+            PpuModel::Rc2c05(id) => Some(*id),
+            _ => None
+        };
+    }
+}
 
 #[derive(Copy, Clone)]
 pub struct SpriteLatch {
@@ -73,9 +121,33 @@ impl SpriteLatch {
                 ((self.bitmap_low  & 0b1000_0000) >> 7);
         }
     }
+
+    pub fn save_state(&self, buff: &mut Vec<u8>) {
+        save_u8(buff, self.tile_index);
+        save_u8(buff, self.bitmap_high);
+        save_u8(buff, self.bitmap_low);
+        save_u8(buff, self.attributes);
+        save_u8(buff, self.x_counter);
+        save_u8(buff, self.y_pos);
+        save_bool(buff, self.active);
+    }
+
+    pub fn load_state(&mut self, buff: &mut &[u8]) -> Result<(), String> {
+        self.tile_index = load_u8(buff)?;
+        self.bitmap_high = load_u8(buff)?;
+        self.bitmap_low = load_u8(buff)?;
+        self.attributes = load_u8(buff)?;
+        self.x_counter = load_u8(buff)?;
+        self.y_pos = load_u8(buff)?;
+        self.active = load_bool(buff)?;
+        return Ok(());
+    }
 }
 
 pub struct PpuState {
+    pub region: Region,
+    pub model: PpuModel,
+
     // PPU Memory (incl. cart CHR ROM for now)
     pub internal_vram: Vec<u8>,
     pub oam: Vec<u8>,
@@ -137,6 +209,8 @@ pub struct PpuState {
     // Debug Viewer
     pub recent_reads: Vec<u16>,
     pub recent_writes: Vec<u16>,
+    // The last pattern table address read, for the code/data logger
+    pub last_pattern_fetch: Option<u16>,
 }
 
 fn debug_default_palette() -> Vec<u8> {
@@ -159,8 +233,10 @@ fn debug_default_palette() -> Vec<u8> {
 }
 
 impl PpuState {
-    pub fn new() -> PpuState {
+    pub fn new(region: Region) -> PpuState {
         return PpuState {
+            region: region,
+            model: PpuModel::Rp2c02,
             internal_vram: vec!(0u8; 0x1000),  // 4k for four-screen mirroring, most games only use upper 2k
             oam: vec!(0u8; 0x100),
             secondary_oam: vec!(SpriteLatch::new(); 8),
@@ -208,6 +284,7 @@ impl PpuState {
             // Debug
             recent_reads: Vec::new(),
             recent_writes: Vec::new(),
+            last_pattern_fetch: None,
        };
     }
 
@@ -260,6 +337,9 @@ impl PpuState {
         match masked_address {
             0x0000 ..= 0x3EFF => {
                 //println!("PPU: Read from 0x{:04X}, dot {} of scanline {}", masked_address, self.current_scanline_cycle, self.current_scanline);
+                if masked_address < 0x2000 {
+                    self.last_pattern_fetch = Some(masked_address);
+                }
                 self.open_bus = match mapper.read_ppu(masked_address) {
                     Some(byte) => byte,
                     None => self.open_bus
@@ -651,7 +731,7 @@ impl PpuState {
                 }
             },
             340 => {
-                if self.rendering_enabled() {
+                if self.rendering_enabled() && self.region.skips_odd_frame_dot() {
                     if self.current_frame & 0x1 != 0 {
                         // Skip ahead one cycle on odd frames. This jitter produces a cleaner image
                         // for NTSC signal generation.
@@ -742,6 +822,9 @@ impl PpuState {
     }
 
     pub fn clock(&mut self, mapper: &mut dyn Mapper) {
+        // NTSC: vblank begins at 241, and the frame ends with pre-render on 261
+        let vblank_scanline = self.region.vblank_scanline();
+        let prerender_scanline = self.region.prerender_scanline();
         match self.current_scanline {
             0 => {
                 if self.current_scanline_cycle == 1 {
@@ -758,8 +841,8 @@ impl PpuState {
                     let _ = self.read_byte(mapper, vram_address);
                 }
             }
-            241 => self.vblank_scanline(),
-            261 => self.prerender_scanline(mapper),
+            scanline if scanline == vblank_scanline => self.vblank_scanline(),
+            scanline if scanline == prerender_scanline => self.prerender_scanline(mapper),
             _ => ()
         }
 
@@ -768,7 +851,7 @@ impl PpuState {
         if self.current_scanline_cycle > 340 {
             self.current_scanline_cycle = 0;
             self.current_scanline += 1;
-            if self.current_scanline > 261 {
+            if self.current_scanline > prerender_scanline {
                 self.current_scanline = 0;
                 self.current_frame += 1;
             }
@@ -834,6 +917,95 @@ impl PpuState {
             }
         }
     }
+
+    pub fn save_state(&self, buff: &mut Vec<u8>) {
+        save_vec_u8(buff, &self.internal_vram);
+        save_vec_u8(buff, &self.oam);
+        for entry in self.secondary_oam.iter() {
+            entry.save_state(buff);
+        }
+        save_usize(buff, self.secondary_oam_index);
+        save_vec_u8(buff, &self.palette);
+
+        save_u8(buff, self.latch);
+        save_u8(buff, self.open_bus);
+        save_u8(buff, self.read_buffer);
+        save_u8(buff, self.control);
+        save_u8(buff, self.mask);
+        save_u8(buff, self.status);
+        save_u8(buff, self.oam_addr);
+        save_u8(buff, self.oam_dma_high);
+
+        save_u32(buff, self.current_frame);
+        save_u16(buff, self.current_scanline);
+        save_u16(buff, self.current_scanline_cycle);
+        save_usize(buff, self.overall_cycle);
+        save_usize(buff, self.frame_starting_cycle);
+
+        save_vec_u16(buff, &self.screen);
+
+        save_bool(buff, self.write_toggle);
+
+        save_u16(buff, self.current_vram_address);
+        save_u16(buff, self.temporary_vram_address);
+        save_u8(buff, self.fine_x);
+        save_u16(buff, self.tile_shift_low);
+        save_u16(buff, self.tile_shift_high);
+        save_u8(buff, self.tile_low);
+        save_u8(buff, self.tile_high);
+        save_u8(buff, self.tile_index);
+        save_u8(buff, self.palette_shift_low);
+        save_u8(buff, self.palette_shift_high);
+        save_u8(buff, self.palette_latch);
+        save_u8(buff, self.attribute_byte);
+
+        save_bool(buff, self.sprite_zero_on_scanline);
+    }
+
+    pub fn load_state(&mut self, buff: &mut &[u8]) -> Result<(), String> {
+        load_vec_u8(buff, &mut self.internal_vram)?;
+        load_vec_u8(buff, &mut self.oam)?;
+        for entry in self.secondary_oam.iter_mut() {
+            entry.load_state(buff)?;
+        }
+        self.secondary_oam_index = load_usize(buff)?;
+        load_vec_u8(buff, &mut self.palette)?;
+
+        self.latch = load_u8(buff)?;
+        self.open_bus = load_u8(buff)?;
+        self.read_buffer = load_u8(buff)?;
+        self.control = load_u8(buff)?;
+        self.mask = load_u8(buff)?;
+        self.status = load_u8(buff)?;
+        self.oam_addr = load_u8(buff)?;
+        self.oam_dma_high = load_u8(buff)?;
+
+        self.current_frame = load_u32(buff)?;
+        self.current_scanline = load_u16(buff)?;
+        self.current_scanline_cycle = load_u16(buff)?;
+        self.overall_cycle = load_usize(buff)?;
+        self.frame_starting_cycle = load_usize(buff)?;
+
+        load_vec_u16(buff, &mut self.screen)?;
+
+        self.write_toggle = load_bool(buff)?;
+
+        self.current_vram_address = load_u16(buff)?;
+        self.temporary_vram_address = load_u16(buff)?;
+        self.fine_x = load_u8(buff)?;
+        self.tile_shift_low = load_u16(buff)?;
+        self.tile_shift_high = load_u16(buff)?;
+        self.tile_low = load_u8(buff)?;
+        self.tile_high = load_u8(buff)?;
+        self.tile_index = load_u8(buff)?;
+        self.palette_shift_low = load_u8(buff)?;
+        self.palette_shift_high = load_u8(buff)?;
+        self.palette_latch = load_u8(buff)?;
+        self.attribute_byte = load_u8(buff)?;
+
+        self.sprite_zero_on_scanline = load_bool(buff)?;
+        return Ok(());
+    }
 }
 
 const PHASED_SIN: [f32; 12] = [
@@ -0,0 +1,386 @@
+// Attributes CPU cycles to the routines that spent them, for finding out where the frame
+// went. A routine starts with a JSR, an interrupt or a BRK, and ends once the stack pointer
+// climbs back to where it was beforehand, which covers RTS and RTI along with the usual
+// stack tricks (popping a return address to bail out early, pushing one to jump through
+// RTS) without losing track. The call's own cycles, from the JSR through the RTS, count
+// towards the routine being called.
+//
+// Cycles where OAM or DMC DMA has halted the CPU are counted as calls to two pseudo
+// routines, so they show up in the inclusive time of whatever was interrupted.
+//
+// Inclusive cycles count everything that happened between entering and leaving a routine,
+// while exclusive cycles leave out time spent in the routines it called. Each routine
+// also remembers its worst single frame.
+
+use std::collections::HashMap;
+
+use disassembler;
+use memory::debug_read_byte;
+use nes::NesState;
+use symbols::SymbolTable;
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash, PartialOrd, Ord)]
+pub enum RoutineKind {
+    // Anything not inside a routine we know about, usually the main loop
+    TopLevel,
+    Subroutine,
+    Nmi,
+    Irq,
+    Brk,
+    OamDma,
+    DmcDma,
+}
+
+impl RoutineKind {
+    pub fn name(&self) -> &'static str {
+        return match self {
+            RoutineKind::TopLevel => "Top Level",
+            RoutineKind::Subroutine => "Subroutine",
+            RoutineKind::Nmi => "NMI",
+            RoutineKind::Irq => "IRQ",
+            RoutineKind::Brk => "BRK",
+            RoutineKind::OamDma => "OAM DMA",
+            RoutineKind::DmcDma => "DMC DMA",
+        };
+    }
+}
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash, PartialOrd, Ord)]
+pub struct Routine {
+    pub kind: RoutineKind,
+    // The entry point, and the 16k PRG bank it was in, as FCEUX numbers them.
+    // Pseudo routines use address 0.
+    pub address: u16,
+    pub bank: Option<usize>,
+}
+
+impl Routine {
+    /// The routine's label if it has one, otherwise its address, as in 02:8000
+    pub fn name(&self, symbols: &SymbolTable) -> String {
+        return match self.kind {
+            RoutineKind::TopLevel | RoutineKind::OamDma | RoutineKind::DmcDma => self.kind.name().to_string(),
+            _ => match symbols.label(self.address, self.bank) {
+                Some(label) => label.to_string(),
+                None => disassembler::format_address(self.address, self.bank)
+            }
+        };
+    }
+}
+
+#[derive(Clone, Copy, Debug, Default)]
+pub struct RoutineStats {
+    pub calls: u64,
+    pub inclusive_cycles: u64,
+    pub exclusive_cycles: u64,
+    pub worst_frame_cycles: u64,
+    pub worst_frame: u32,
+    // Inclusive cycles so far during the current frame
+    frame_cycles: u64,
+}
+
+struct ActiveCall {
+    routine: Routine,
+    entry_cycle: u64,
+    // The stack pointer before the call pushed anything. Pseudo routines use values above
+    // 0xFF, so that no stack pointer can end them.
+    stack_pointer: u16,
+    child_cycles: u64,
+}
+
+const TOP_LEVEL: Routine = Routine{kind: RoutineKind::TopLevel, address: 0, bank: None};
+const NO_STACK: u16 = 0x100;
+// Deep enough for any sane program; past this, calls are simply not tracked
+const MAX_DEPTH: usize = 256;
+
+pub struct Profiler {
+    pub routines: HashMap<Routine, RoutineStats>,
+    pub frames: u32,
+    pub total_cycles: u64,
+    calls: Vec<ActiveCall>,
+    start_cycle: u64,
+    frame_start_cycle: u64,
+    current_frame: u32,
+    // Noted as an interrupt begins, and turned into a call once its handler starts
+    pending_interrupt: Option<(u64, u8)>,
+    pending_brk: Option<(u64, u8)>,
+    dma: Option<RoutineKind>,
+    // Added to the console's cycle count, so that time keeps going forward when the
+    // profile moves to a new console
+    cycle_offset: u64,
+}
+
+impl Profiler {
+    pub fn new(nes: &NesState) -> Profiler {
+        let cycle = Profiler::cpu_cycle(nes);
+        return Profiler {
+            routines: HashMap::new(),
+            frames: 0,
+            total_cycles: 0,
+            calls: vec![ActiveCall{routine: TOP_LEVEL, entry_cycle: cycle, stack_pointer: NO_STACK, child_cycles: 0}],
+            start_cycle: cycle,
+            frame_start_cycle: cycle,
+            current_frame: nes.ppu.current_frame,
+            pending_interrupt: None,
+            pending_brk: None,
+            dma: None,
+            cycle_offset: 0,
+        };
+    }
+
+    pub fn cpu_cycle(nes: &NesState) -> u64 {
+        return nes.master_clock / nes.region.cpu_divider();
+    }
+
+    fn now(&self, nes: &NesState) -> u64 {
+        return Profiler::cpu_cycle(nes) + self.cycle_offset;
+    }
+
+    /// Carries on profiling with a new console, as after a power cycle. Calls in
+    /// progress end where the old console stopped, since nothing will return from them.
+    pub fn continue_with(&mut self, nes: &NesState) {
+        let cycle = self.start_cycle + self.total_cycles;
+        while self.calls.len() > 1 {
+            self.leave(cycle);
+        }
+        self.pending_interrupt = None;
+        self.pending_brk = None;
+        self.dma = None;
+        self.current_frame = nes.ppu.current_frame;
+        self.cycle_offset = cycle - Profiler::cpu_cycle(nes);
+    }
+
+    fn enter(&mut self, routine: Routine, cycle: u64, stack_pointer: u16) {
+        if self.calls.len() >= MAX_DEPTH {
+            return;
+        }
+        self.routines.entry(routine).or_insert(RoutineStats::default()).calls += 1;
+        self.calls.push(ActiveCall{routine: routine, entry_cycle: cycle, stack_pointer: stack_pointer, child_cycles: 0});
+    }
+
+    fn leave(&mut self, cycle: u64) {
+        // The top level is never left
+        if self.calls.len() <= 1 {
+            return;
+        }
+        let call = self.calls.pop().unwrap();
+        let elapsed = cycle - call.entry_cycle;
+        let frame_elapsed = cycle - call.entry_cycle.max(self.frame_start_cycle);
+        // With recursion, the outermost call already covers the inner ones
+        let recursive = self.calls.iter().any(|outer| outer.routine == call.routine);
+        let stats = self.routines.entry(call.routine).or_insert(RoutineStats::default());
+        stats.exclusive_cycles += elapsed - call.child_cycles;
+        if !recursive {
+            stats.inclusive_cycles += elapsed;
+            stats.frame_cycles += frame_elapsed;
+        }
+        match self.calls.last_mut() {
+            Some(caller) => {caller.child_cycles += elapsed},
+            None => {}
+        }
+    }
+
+    fn routine_at(nes: &NesState, kind: RoutineKind, address: u16) -> Routine {
+        return Routine{kind: kind, address: address, bank: disassembler::prg_bank(nes, address)};
+    }
+
+    // Which DMA, if any, will halt the CPU for the coming clock
+    fn dma_in_progress(nes: &NesState) -> Option<RoutineKind> {
+        if nes.cpu.oam_dma_active {
+            return Some(RoutineKind::OamDma);
+        }
+        if !nes.cpu.upcoming_write && nes.apu.dmc.rdy_line {
+            return Some(RoutineKind::DmcDma);
+        }
+        return None;
+    }
+
+    /// Called once per CPU clock, before it runs
+    pub fn log_clock(&mut self, nes: &NesState) {
+        let cycle = self.now(nes);
+        let dma = Profiler::dma_in_progress(nes);
+        if nes.ppu.current_frame != self.current_frame {
+            self.finish_frame(cycle);
+            self.current_frame = nes.ppu.current_frame;
+        }
+        if dma != self.dma {
+            if self.dma.is_some() {
+                self.leave(cycle);
+            }
+            match dma {
+                Some(kind) => self.enter(Routine{kind: kind, address: 0, bank: None}, cycle, NO_STACK + 1),
+                None => {}
+            }
+            self.dma = dma;
+        }
+        self.total_cycles = cycle - self.start_cycle;
+    }
+
+    /// Called as the CPU begins servicing an NMI or IRQ
+    pub fn log_interrupt(&mut self, nes: &NesState) {
+        self.pending_interrupt = Some((self.now(nes), nes.registers.s));
+    }
+
+    /// Called as the CPU is about to fetch an opcode
+    pub fn log_instruction(&mut self, nes: &NesState) {
+        let cycle = self.now(nes);
+        let stack_pointer = nes.registers.s as u16;
+        while self.calls.last().map_or(false, |call| call.stack_pointer <= stack_pointer) {
+            self.leave(cycle);
+        }
+
+        let pc = nes.registers.pc;
+        match self.pending_interrupt.take() {
+            Some((entry_cycle, entry_stack_pointer)) => {
+                // The vector is still in the CPU's scratch address, as nothing has run since
+                let kind = if nes.cpu.temp_address == 0xFFFA {RoutineKind::Nmi} else {RoutineKind::Irq};
+                self.enter(Profiler::routine_at(nes, kind, pc), entry_cycle, entry_stack_pointer as u16);
+            },
+            None => {}
+        }
+        match self.pending_brk.take() {
+            Some((entry_cycle, entry_stack_pointer)) => {
+                self.enter(Profiler::routine_at(nes, RoutineKind::Brk, pc), entry_cycle, entry_stack_pointer as u16);
+            },
+            None => {}
+        }
+
+        match debug_read_byte(nes, pc) {
+            // JSR
+            0x20 => {
+                let target = debug_read_byte(nes, pc.wrapping_add(1)) as u16 | ((debug_read_byte(nes, pc.wrapping_add(2)) as u16) << 8);
+                self.enter(Profiler::routine_at(nes, RoutineKind::Subroutine, target), cycle, stack_pointer);
+            },
+            // BRK, whose handler we won't know until the vector is read
+            0x00 => {
+                self.pending_brk = Some((cycle, nes.registers.s));
+            },
+            _ => {}
+        }
+    }
+
+    fn finish_frame(&mut self, cycle: u64) {
+        // Calls still in progress count their share of this frame now
+        let frame_start_cycle = self.frame_start_cycle;
+        for depth in 0 .. self.calls.len() {
+            let call = &self.calls[depth];
+            // Only the outermost of any recursive calls, as in leave()
+            if self.calls[.. depth].iter().any(|outer| outer.routine == call.routine) {
+                continue;
+            }
+            let frame_elapsed = cycle - call.entry_cycle.max(frame_start_cycle);
+            self.routines.entry(call.routine).or_insert(RoutineStats::default()).frame_cycles += frame_elapsed;
+        }
+        for stats in self.routines.values_mut() {
+            if stats.frame_cycles > stats.worst_frame_cycles {
+                stats.worst_frame_cycles = stats.frame_cycles;
+                stats.worst_frame = self.current_frame;
+            }
+            stats.frame_cycles = 0;
+        }
+        self.frame_start_cycle = cycle;
+        self.frames += 1;
+    }
+
+    /// The statistics for every routine seen, including time spent in calls which
+    /// haven't returned yet, sorted with the most expensive first
+    pub fn report(&self) -> Vec<(Routine, RoutineStats)> {
+        let mut routines = self.routines.clone();
+        let now = self.start_cycle + self.total_cycles;
+        let mut child_cycles = 0;
+        for (depth, call) in self.calls.iter().enumerate().rev() {
+            let elapsed = now - call.entry_cycle;
+            let recursive = self.calls[.. depth].iter().any(|outer| outer.routine == call.routine);
+            let stats = routines.entry(call.routine).or_insert(RoutineStats::default());
+            stats.exclusive_cycles += elapsed - call.child_cycles - child_cycles;
+            if !recursive {
+                stats.inclusive_cycles += elapsed;
+            }
+            child_cycles = elapsed;
+        }
+        let mut report: Vec<(Routine, RoutineStats)> = routines.into_iter().collect();
+        report.sort_by(|a, b| b.1.inclusive_cycles.cmp(&a.1.inclusive_cycles).then(a.0.cmp(&b.0)));
+        return report;
+    }
+
+    /// The average length of a frame, in CPU cycles, to measure budgets against
+    pub fn cycles_per_frame(&self) -> f64 {
+        if self.frames == 0 {
+            return 0.0;
+        }
+        return (self.frame_start_cycle - self.start_cycle) as f64 / self.frames as f64;
+    }
+
+    /// How many frames the profile covers, counting the one in progress as a fraction, so
+    /// that averages don't depend on where in a frame profiling stopped
+    pub fn frames_elapsed(&self) -> f64 {
+        let cycles_per_frame = self.cycles_per_frame();
+        if cycles_per_frame <= 0.0 {
+            return 1.0;
+        }
+        return (self.total_cycles as f64 / cycles_per_frame).max(1.0);
+    }
+
+    /// The report as CSV, one routine per line. Budgets are percentages of an average frame.
+    pub fn to_csv(&self, symbols: &SymbolTable) -> String {
+        let cycles_per_frame = self.cycles_per_frame();
+        let percent = |cycles: f64| if cycles_per_frame > 0.0 {cycles * 100.0 / cycles_per_frame} else {0.0};
+        let frames = self.frames_elapsed();
+        let mut csv = String::from("routine,address,kind,calls,inclusive_cycles,exclusive_cycles,inclusive_per_frame,frame_percent,worst_frame_cycles,worst_frame_percent,worst_frame\n");
+        for (routine, stats) in self.report() {
+            let address = match routine.kind {
+                RoutineKind::TopLevel | RoutineKind::OamDma | RoutineKind::DmcDma => String::new(),
+                _ => disassembler::format_address(routine.address, routine.bank)
+            };
+            let per_frame = stats.inclusive_cycles as f64 / frames;
+            csv.push_str(&format!("\"{}\",{},{},{},{},{},{:.1},{:.2},{},{:.2},{}\n",
+                routine.name(symbols).replace("\"", "\"\""), address, routine.kind.name(),
+                stats.calls, stats.inclusive_cycles, stats.exclusive_cycles,
+                per_frame, percent(per_frame),
+                stats.worst_frame_cycles, percent(stats.worst_frame_cycles as f64), stats.worst_frame));
+        }
+        return csv;
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use asm::Opcode::*;
+    use asm::AddressingMode::*;
+    use mmc::test_rom::*;
+    use profiler::*;
+
+    fn subroutine_cycles(profiler: &Profiler) -> u64 {
+        return profiler.report().iter()
+            .filter(|(routine, _)| routine.kind == RoutineKind::Subroutine && routine.address == IRQ_ADDRESS)
+            .map(|(_, stats)| stats.inclusive_cycles)
+            .sum();
+    }
+
+    #[test]
+    fn carries_on_with_a_new_console() {
+        let rom = TestRom::new(0, 0x8000, 0x2000);
+        let boot = || rom.boot(vec![
+            Label(String::from("loop")),
+            Jsr(Absolute(IRQ_ADDRESS)),
+            Jmp(AbsoluteLabel(String::from("loop"))),
+        ], vec![Inx, Rts]);
+
+        let mut nes = boot();
+        nes.profiler = Some(Profiler::new(&nes));
+        run_cycles(&mut nes, 10_000);
+        let profiler = nes.profiler.take().unwrap();
+        let cycles_before = profiler.total_cycles;
+        let subroutine_before = subroutine_cycles(&profiler);
+        assert!(subroutine_before > 0);
+
+        // The new console's clock starts over, but the profile's doesn't
+        let mut nes = boot();
+        let mut profiler = profiler;
+        profiler.continue_with(&nes);
+        nes.profiler = Some(profiler);
+        run_cycles(&mut nes, 10_000);
+        let profiler = nes.profiler.as_ref().unwrap();
+        assert!(profiler.total_cycles > cycles_before);
+        assert!(subroutine_cycles(profiler) > subroutine_before);
+    }
+}
@@ -0,0 +1,122 @@
+// The TV system the console was built for. This affects nearly every timing in the
+// machine: the master clock and its dividers, the number of scanlines per frame, how
+// long vblank lasts, and several APU tables. Documentation:
+// https://www.nesdev.org/wiki/Cycle_reference_chart
+// https://www.nesdev.org/wiki/Dendy
+
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum Region {
+    Ntsc,
+    Pal,
+    // Famiclones common in the former USSR. These pair PAL video timing with an NTSC-like
+    // CPU divider, and delay vblank so that NTSC games run with the correct timing.
+    Dendy,
+}
+
+impl Region {
+    pub fn from_name(name: &str) -> Option<Region> {
+        return match name.to_lowercase().as_str() {
+            "ntsc" => Some(Region::Ntsc),
+            "pal" => Some(Region::Pal),
+            "dendy" => Some(Region::Dendy),
+            _ => None
+        };
+    }
+
+    pub fn name(&self) -> &'static str {
+        return match self {
+            Region::Ntsc => "NTSC",
+            Region::Pal => "PAL",
+            Region::Dendy => "Dendy",
+        };
+    }
+
+    /// Master clocks per CPU clock
+    pub fn cpu_divider(&self) -> u64 {
+        return match self {
+            Region::Ntsc => 12,
+            Region::Pal => 16,
+            Region::Dendy => 15,
+        };
+    }
+
+    /// Master clocks per PPU dot
+    pub fn ppu_divider(&self) -> u64 {
+        return match self {
+            Region::Ntsc => 4,
+            Region::Pal => 5,
+            Region::Dendy => 5,
+        };
+    }
+
+    pub fn cpu_clock_rate(&self) -> u64 {
+        return match self {
+            Region::Ntsc => 1_789_773,
+            Region::Pal => 1_662_607,
+            Region::Dendy => 1_773_448,
+        };
+    }
+
+    pub fn scanlines_per_frame(&self) -> u16 {
+        return match self {
+            Region::Ntsc => 262,
+            Region::Pal => 312,
+            Region::Dendy => 312,
+        };
+    }
+
+    /// The scanline on which the vblank flag (and NMI) is raised
+    pub fn vblank_scanline(&self) -> u16 {
+        return match self {
+            Region::Ntsc => 241,
+            Region::Pal => 241,
+            // Dendy keeps 50 idle lines after the picture, so that vblank itself lasts
+            // the same 20 lines it would on NTSC
+            Region::Dendy => 291,
+        };
+    }
+
+    /// The last line of the frame, used to prepare rendering for the next one
+    pub fn prerender_scanline(&self) -> u16 {
+        return self.scanlines_per_frame() - 1;
+    }
+
+    /// Only the NTSC PPU shortens odd frames by one dot when rendering is enabled
+    pub fn skips_odd_frame_dot(&self) -> bool {
+        return *self == Region::Ntsc;
+    }
+
+    /// CPU cycles at which the APU frame sequencer acts: the first three quarter
+    /// frames, then the final step of the 4-step and 5-step sequences. Dendy shares
+    /// the NTSC timings.
+    pub fn frame_sequencer_steps(&self) -> [u16; 5] {
+        return match self {
+            Region::Pal => [8313, 16627, 24939, 33253, 41565],
+            _ => [7457, 14913, 22371, 29829, 37281],
+        };
+    }
+
+    pub fn noise_period_table(&self) -> &'static [u16; 16] {
+        return match self {
+            Region::Pal => &PAL_NOISE_PERIODS,
+            _ => &NTSC_NOISE_PERIODS,
+        };
+    }
+
+    pub fn dmc_period_table(&self) -> &'static [u16; 16] {
+        return match self {
+            Region::Pal => &PAL_DMC_PERIODS,
+            _ => &NTSC_DMC_PERIODS,
+        };
+    }
+}
+
+pub const NTSC_NOISE_PERIODS: [u16; 16] = [
+    4, 8, 16, 32, 64, 96, 128, 160, 202, 254, 380, 508, 762, 1016, 2034, 4068];
+pub const PAL_NOISE_PERIODS: [u16; 16] = [
+    4, 8, 14, 30, 60, 88, 118, 148, 188, 236, 354, 472, 708, 944, 1890, 3778];
+
+pub const NTSC_DMC_PERIODS: [u16; 16] = [
+    428, 380, 340, 320, 286, 254, 226, 214, 190, 160, 142, 128, 106,  84,  72,  54];
+pub const PAL_DMC_PERIODS: [u16; 16] = [
+    398, 354, 316, 298, 276, 236, 210, 198, 176, 148, 132, 118,  98,  78,  66,  50];
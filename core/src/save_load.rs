@@ -0,0 +1,200 @@
+// Helpers for the binary save state format. Saving appends fields to the end
+// of a byte buffer in a fixed order, and loading consumes them from the front
+// of a slice in that same order. All multi-byte values are little endian.
+// There is no per-field tagging; instead the entire state carries a version
+// number, which must be bumped whenever any component changes its layout.
+
+use mmc::mapper::Mirroring;
+
+pub fn save_u8(buff: &mut Vec<u8>, data: u8) {
+    buff.push(data);
+}
+
+pub fn save_u16(buff: &mut Vec<u8>, data: u16) {
+    buff.extend_from_slice(&data.to_le_bytes());
+}
+
+pub fn save_u32(buff: &mut Vec<u8>, data: u32) {
+    buff.extend_from_slice(&data.to_le_bytes());
+}
+
+pub fn save_u64(buff: &mut Vec<u8>, data: u64) {
+    buff.extend_from_slice(&data.to_le_bytes());
+}
+
+pub fn save_usize(buff: &mut Vec<u8>, data: usize) {
+    // Always stored as 64 bits, so states are portable between platforms
+    save_u64(buff, data as u64);
+}
+
+pub fn save_i8(buff: &mut Vec<u8>, data: i8) {
+    save_u8(buff, data as u8);
+}
+
+pub fn save_i16(buff: &mut Vec<u8>, data: i16) {
+    save_u16(buff, data as u16);
+}
+
+pub fn save_i32(buff: &mut Vec<u8>, data: i32) {
+    save_u32(buff, data as u32);
+}
+
+pub fn save_f32(buff: &mut Vec<u8>, data: f32) {
+    save_u32(buff, data.to_bits());
+}
+
+pub fn save_bool(buff: &mut Vec<u8>, data: bool) {
+    save_u8(buff, data as u8);
+}
+
+pub fn save_vec_u8(buff: &mut Vec<u8>, data: &[u8]) {
+    save_usize(buff, data.len());
+    buff.extend_from_slice(data);
+}
+
+pub fn save_vec_u16(buff: &mut Vec<u8>, data: &[u16]) {
+    save_usize(buff, data.len());
+    for &value in data {
+        save_u16(buff, value);
+    }
+}
+
+pub fn save_vec_usize(buff: &mut Vec<u8>, data: &[usize]) {
+    save_usize(buff, data.len());
+    for &value in data {
+        save_usize(buff, value);
+    }
+}
+
+pub fn save_mirroring(buff: &mut Vec<u8>, mirroring: Mirroring) {
+    let value = match mirroring {
+        Mirroring::Horizontal => 0,
+        Mirroring::Vertical => 1,
+        Mirroring::OneScreenLower => 2,
+        Mirroring::OneScreenUpper => 3,
+        Mirroring::FourScreen => 4,
+    };
+    save_u8(buff, value);
+}
+
+fn take<'a>(buff: &mut &'a [u8], length: usize) -> Result<&'a [u8], String> {
+    if buff.len() < length {
+        return Err(format!("Save state is truncated: needed {} more bytes, only {} remain", length, buff.len()));
+    }
+    let (data, remaining) = buff.split_at(length);
+    *buff = remaining;
+    return Ok(data);
+}
+
+pub fn load_u8(buff: &mut &[u8]) -> Result<u8, String> {
+    let data = take(buff, 1)?;
+    return Ok(data[0]);
+}
+
+pub fn load_u16(buff: &mut &[u8]) -> Result<u16, String> {
+    let data = take(buff, 2)?;
+    return Ok(u16::from_le_bytes([data[0], data[1]]));
+}
+
+pub fn load_u32(buff: &mut &[u8]) -> Result<u32, String> {
+    let data = take(buff, 4)?;
+    return Ok(u32::from_le_bytes([data[0], data[1], data[2], data[3]]));
+}
+
+pub fn load_u64(buff: &mut &[u8]) -> Result<u64, String> {
+    let data = take(buff, 8)?;
+    let mut bytes = [0u8; 8];
+    bytes.copy_from_slice(data);
+    return Ok(u64::from_le_bytes(bytes));
+}
+
+pub fn load_usize(buff: &mut &[u8]) -> Result<usize, String> {
+    let data = load_u64(buff)?;
+    return Ok(data as usize);
+}
+
+pub fn load_i8(buff: &mut &[u8]) -> Result<i8, String> {
+    let data = load_u8(buff)?;
+    return Ok(data as i8);
+}
+
+pub fn load_i16(buff: &mut &[u8]) -> Result<i16, String> {
+    let data = load_u16(buff)?;
+    return Ok(data as i16);
+}
+
+pub fn load_i32(buff: &mut &[u8]) -> Result<i32, String> {
+    let data = load_u32(buff)?;
+    return Ok(data as i32);
+}
+
+pub fn load_f32(buff: &mut &[u8]) -> Result<f32, String> {
+    let data = load_u32(buff)?;
+    return Ok(f32::from_bits(data));
+}
+
+pub fn load_bool(buff: &mut &[u8]) -> Result<bool, String> {
+    let data = load_u8(buff)?;
+    return Ok(data != 0);
+}
+
+fn load_length(buff: &mut &[u8], expected_length: usize) -> Result<usize, String> {
+    let length = load_usize(buff)?;
+    if length != expected_length {
+        return Err(format!("Save state size mismatch: expected a block of {} entries, found {}", expected_length, length));
+    }
+    return Ok(length);
+}
+
+/// Loads a buffer that was written with save_vec_u8. The destination must already
+/// be the correct size; a state that disagrees is rejected rather than resized.
+pub fn load_vec_u8(buff: &mut &[u8], data: &mut [u8]) -> Result<(), String> {
+    let length = load_length(buff, data.len())?;
+    data.copy_from_slice(take(buff, length)?);
+    return Ok(());
+}
+
+pub fn load_vec_u16(buff: &mut &[u8], data: &mut [u16]) -> Result<(), String> {
+    load_length(buff, data.len())?;
+    for value in data.iter_mut() {
+        *value = load_u16(buff)?;
+    }
+    return Ok(());
+}
+
+pub fn load_vec_usize(buff: &mut &[u8], data: &mut [usize]) -> Result<(), String> {
+    load_length(buff, data.len())?;
+    for value in data.iter_mut() {
+        *value = load_usize(buff)?;
+    }
+    return Ok(());
+}
+
+pub fn load_mirroring(buff: &mut &[u8]) -> Result<Mirroring, String> {
+    let value = load_u8(buff)?;
+    return match value {
+        0 => Ok(Mirroring::Horizontal),
+        1 => Ok(Mirroring::Vertical),
+        2 => Ok(Mirroring::OneScreenLower),
+        3 => Ok(Mirroring::OneScreenUpper),
+        4 => Ok(Mirroring::FourScreen),
+        _ => Err(format!("Invalid mirroring mode {} in save state", value))
+    };
+}
+
+/// Standard CRC-32 (as used by zip and most ROM databases), used to tie a
+/// save state to the ROM it was created from.
+pub fn crc32(crc: u32, data: &[u8]) -> u32 {
+    let mut crc = !crc;
+    for &byte in data {
+        crc ^= byte as u32;
+        for _ in 0 .. 8 {
+            if crc & 1 != 0 {
+                crc = (crc >> 1) ^ 0xEDB88320;
+            } else {
+                crc = crc >> 1;
+            }
+        }
+    }
+    return !crc;
+}
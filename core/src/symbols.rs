@@ -0,0 +1,338 @@
+// Names for addresses, so that debugging output can say "jsr update_player" rather than
+// "JSR $C4F2". Code in PRG ROM moves around as the mapper switches banks, so labels there
+// belong to a position in the ROM itself, and only apply while that part of the ROM is
+// mapped in. From the outside these are addressed by CPU address plus PRG bank, with
+// banks counted in 16k units as FCEUX does. Labels elsewhere, such as those for RAM and
+// registers, apply to whatever happens to be at their address.
+//
+// Three formats can be loaded:
+//   ld65 .dbg files, as written by ld65 --dbgfile, which also map code back to the
+//     source lines it was assembled from
+//   FCEUX .nl files, one per bank (game.nes.0.nl, game.nes.1.nl, ...) plus game.nes.ram.nl
+//   Mesen .mlb files, covering the whole ROM at once
+
+use std::collections::HashMap;
+
+use nes::NesState;
+
+// The size of an iNES header, which precedes PRG ROM in the files ld65 writes
+const INES_HEADER_SIZE: usize = 16;
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash)]
+enum Location {
+    Cpu(u16),
+    PrgRom(usize),
+}
+
+fn bank_location(address: u16, bank: Option<usize>) -> Location {
+    return match bank {
+        Some(bank) => Location::PrgRom(bank * 0x4000 + (address as usize & 0x3FFF)),
+        None => Location::Cpu(address)
+    };
+}
+
+pub struct SymbolTable {
+    labels: HashMap<Location, String>,
+    // The file and line each instruction was assembled from, as an index into files
+    source_lines: HashMap<Location, (usize, u32)>,
+    files: Vec<String>,
+}
+
+impl SymbolTable {
+    pub fn new() -> SymbolTable {
+        return SymbolTable {
+            labels: HashMap::new(),
+            source_lines: HashMap::new(),
+            files: Vec::new(),
+        };
+    }
+
+    pub fn len(&self) -> usize {
+        return self.labels.len();
+    }
+
+    pub fn clear(&mut self) {
+        self.labels.clear();
+        self.source_lines.clear();
+        self.files.clear();
+    }
+
+    fn insert_label(&mut self, location: Location, name: &str) {
+        // ca65 cheap locals (@loop) often share an address with a more useful name
+        let replace = match self.labels.get(&location) {
+            Some(existing) => existing.starts_with("@") && !name.starts_with("@"),
+            None => true
+        };
+        if replace && name.len() > 0 {
+            self.labels.insert(location, name.to_string());
+        }
+    }
+
+    pub fn add_label(&mut self, address: u16, bank: Option<usize>, name: &str) {
+        self.insert_label(bank_location(address, bank), name);
+    }
+
+    /// The label for an address within a particular bank, falling back to one which
+    /// applies to any bank
+    pub fn label(&self, address: u16, bank: Option<usize>) -> Option<&str> {
+        if bank.is_some() {
+            match self.labels.get(&bank_location(address, bank)) {
+                Some(name) => {return Some(name)},
+                None => {}
+            }
+        }
+        return self.labels.get(&Location::Cpu(address)).map(|name| name.as_str());
+    }
+
+    // Where to look for this address: the ROM byte mapped there, if any, then the
+    // address itself
+    fn locations(nes: &NesState, address: u16) -> Vec<Location> {
+        let mut locations = Vec::new();
+        match nes.mapper.prg_rom_address(address) {
+            Some(offset) => {locations.push(Location::PrgRom(offset))},
+            None => {}
+        }
+        locations.push(Location::Cpu(address));
+        return locations;
+    }
+
+    /// The label for whatever the CPU currently sees at this address
+    pub fn label_at(&self, nes: &NesState, address: u16) -> Option<&str> {
+        if self.labels.len() == 0 {
+            return None;
+        }
+        return SymbolTable::locations(nes, address).iter()
+            .filter_map(|location| self.labels.get(location))
+            .next().map(|name| name.as_str());
+    }
+
+    /// The source file and line which produced the code currently at this address,
+    /// as in main.s:120
+    pub fn source_line_at(&self, nes: &NesState, address: u16) -> Option<String> {
+        if self.source_lines.len() == 0 {
+            return None;
+        }
+        return SymbolTable::locations(nes, address).iter()
+            .filter_map(|location| self.source_lines.get(location))
+            .next().map(|&(file, line)| format!("{}:{}", self.files[file], line));
+    }
+
+    /// Loads symbols from a file, choosing the format by its name
+    pub fn load_file(&mut self, filename: &str, data: &[u8]) -> Result<(), String> {
+        let text = String::from_utf8_lossy(data);
+        if filename.ends_with(".dbg") {
+            return self.load_dbg(&text);
+        }
+        if filename.ends_with(".mlb") {
+            return self.load_mlb(&text);
+        }
+        if filename.ends_with(".nl") {
+            // The bank sits just before the extension, in hex: game.nes.1F.nl
+            let stem = &filename[.. filename.len() - 3];
+            let bank_name = match stem.rfind('.') {
+                Some(dot) => &stem[dot + 1 ..],
+                None => {return Err(format!("Can't tell which bank {} belongs to", filename))}
+            };
+            let bank = if bank_name == "ram" {
+                None
+            } else {
+                match usize::from_str_radix(bank_name, 16) {
+                    Ok(bank) => Some(bank),
+                    Err(_) => {return Err(format!("Can't tell which bank {} belongs to", filename))}
+                }
+            };
+            return self.load_nl(&text, bank);
+        }
+        return Err(format!("Unrecognized symbol file: {}", filename));
+    }
+
+    /// FCEUX name lists, with lines like $C000#Reset#Comment. Array labels, written
+    /// $0200/100#OAM#, name the first byte only.
+    pub fn load_nl(&mut self, text: &str, bank: Option<usize>) -> Result<(), String> {
+        for line in text.lines() {
+            if !line.starts_with("$") {
+                continue;
+            }
+            let fields: Vec<&str> = line.splitn(3, '#').collect();
+            if fields.len() < 2 {
+                continue;
+            }
+            let address_text = fields[0][1 ..].split('/').next().unwrap_or("");
+            let address = match u16::from_str_radix(address_text.trim(), 16) {
+                Ok(address) => address,
+                Err(_) => {return Err(format!("Invalid address in name list: {}", line))}
+            };
+            // RAM and registers are the same no matter which bank's file they're in
+            let label_bank = if address >= 0x8000 {bank} else {None};
+            self.add_label(address, label_bank, fields[1].trim());
+        }
+        return Ok(());
+    }
+
+    /// Mesen label files, with lines like P:0010:Reset:Comment. P labels are offsets
+    /// into PRG ROM, R into internal RAM, S and W into cartridge RAM, and G are CPU
+    /// addresses. Mesen 2's longer names (NesPrgRom, NesInternalRam, ...) work too.
+    pub fn load_mlb(&mut self, text: &str) -> Result<(), String> {
+        for line in text.lines() {
+            let fields: Vec<&str> = line.splitn(4, ':').collect();
+            if fields.len() < 3 {
+                continue;
+            }
+            let address_text = fields[1].split('-').next().unwrap_or("");
+            let address = match usize::from_str_radix(address_text.trim(), 16) {
+                Ok(address) => address,
+                Err(_) => {return Err(format!("Invalid address in label file: {}", line))}
+            };
+            let location = match fields[0] {
+                "P" | "NesPrgRom" => Location::PrgRom(address),
+                "R" | "NesInternalRam" => Location::Cpu(address as u16 & 0x7FF),
+                "S" | "W" | "NesSaveRam" | "NesWorkRam" => Location::Cpu(0x6000 + (address as u16 & 0x1FFF)),
+                "G" | "NesMemory" => Location::Cpu(address as u16),
+                // Other memory types, like CHR, have no CPU address to label
+                _ => {continue}
+            };
+            self.insert_label(location, fields[2].trim());
+        }
+        return Ok(());
+    }
+
+    /// ld65 debug info. Labels are placed by segment: those in segments which ld65 wrote
+    /// into the ROM are tied to that spot in PRG ROM, and the rest (zero page, BSS) to
+    /// their CPU address. Source lines come from the spans of code each line produced.
+    pub fn load_dbg(&mut self, text: &str) -> Result<(), String> {
+        // id -> (start address, where the segment landed in PRG ROM)
+        let mut segments: HashMap<usize, (usize, Option<usize>)> = HashMap::new();
+        // id -> (segment, offset into segment)
+        let mut spans: HashMap<usize, (usize, usize)> = HashMap::new();
+        let mut files: HashMap<usize, String> = HashMap::new();
+        let mut symbols: Vec<(String, usize, Option<usize>)> = Vec::new();
+        let mut lines: Vec<(usize, u32, Vec<usize>)> = Vec::new();
+
+        for line in text.lines() {
+            let mut parts = line.splitn(2, |c: char| c.is_whitespace());
+            let record_type = parts.next().unwrap_or("");
+            let fields = parse_dbg_fields(parts.next().unwrap_or(""));
+            let id = dbg_number(&fields, "id");
+            match record_type {
+                "seg" => {
+                    let start = dbg_number(&fields, "start").unwrap_or(0);
+                    let rom_offset = match (dbg_number(&fields, "ooffs"), fields.get("oname")) {
+                        // Segments copied to RAM are labeled where they run, not where they're stored
+                        (Some(ooffs), Some(oname)) if start >= 0x8000 => {
+                            if oname.ends_with(".nes") {
+                                ooffs.checked_sub(INES_HEADER_SIZE)
+                            } else {
+                                Some(ooffs)
+                            }
+                        },
+                        _ => None
+                    };
+                    segments.insert(id.unwrap_or(0), (start, rom_offset));
+                },
+                "span" => {
+                    spans.insert(id.unwrap_or(0), (dbg_number(&fields, "seg").unwrap_or(0), dbg_number(&fields, "start").unwrap_or(0)));
+                },
+                "file" => {
+                    let name = fields.get("name").cloned().unwrap_or(String::new());
+                    // Only the file's own name fits comfortably in a debug window
+                    let short_name = name.rsplit(|c| c == '/' || c == '\\').next().unwrap_or("").to_string();
+                    files.insert(id.unwrap_or(0), short_name);
+                },
+                "sym" => {
+                    if fields.get("type").map(|t| t.as_str()) == Some("lab") {
+                        match (fields.get("name"), dbg_number(&fields, "val")) {
+                            (Some(name), Some(value)) => symbols.push((name.clone(), value, dbg_number(&fields, "seg"))),
+                            _ => {}
+                        }
+                    }
+                },
+                "line" => {
+                    // Type 2 lines are macro expansions, which would all point at the macro
+                    if dbg_number(&fields, "type").unwrap_or(0) != 2 {
+                        match (dbg_number(&fields, "file"), dbg_number(&fields, "line"), fields.get("span")) {
+                            (Some(file), Some(line_number), Some(span_list)) => {
+                                let line_spans = span_list.split('+').filter_map(|span| span.parse().ok()).collect();
+                                lines.push((file, line_number as u32, line_spans));
+                            },
+                            _ => {}
+                        }
+                    }
+                },
+                _ => {}
+            }
+        }
+
+        let locate = |segment: usize, address: usize| -> Location {
+            return match segments.get(&segment) {
+                Some(&(start, Some(rom_offset))) if address >= start => Location::PrgRom(rom_offset + (address - start)),
+                _ => Location::Cpu(address as u16)
+            };
+        };
+
+        for (name, value, segment) in symbols {
+            let location = match segment {
+                Some(segment) => locate(segment, value),
+                None => Location::Cpu(value as u16)
+            };
+            self.insert_label(location, &name);
+        }
+
+        let mut file_indices: HashMap<usize, usize> = HashMap::new();
+        for (file, line_number, line_spans) in lines {
+            let file_index = match file_indices.get(&file) {
+                Some(&index) => index,
+                None => {
+                    self.files.push(files.get(&file).cloned().unwrap_or(format!("file{}", file)));
+                    file_indices.insert(file, self.files.len() - 1);
+                    self.files.len() - 1
+                }
+            };
+            for span in line_spans {
+                match spans.get(&span) {
+                    Some(&(segment, offset)) => {
+                        let start = segments.get(&segment).map(|&(start, _)| start).unwrap_or(0);
+                        let location = locate(segment, start + offset);
+                        self.source_lines.entry(location).or_insert((file_index, line_number));
+                    },
+                    None => {}
+                }
+            }
+        }
+        return Ok(());
+    }
+}
+
+// Splits key=value,key=value, where values may be quoted strings containing commas
+fn parse_dbg_fields(text: &str) -> HashMap<String, String> {
+    let mut fields = HashMap::new();
+    let mut current = String::new();
+    let mut quoted = false;
+    let mut entries = Vec::new();
+    for c in text.chars() {
+        match c {
+            '"' => {quoted = !quoted},
+            ',' if !quoted => {
+                entries.push(current);
+                current = String::new();
+            },
+            _ => {current.push(c)}
+        }
+    }
+    entries.push(current);
+    for entry in entries {
+        let mut parts = entry.splitn(2, '=');
+        match (parts.next(), parts.next()) {
+            (Some(key), Some(value)) => {fields.insert(key.trim().to_string(), value.trim().to_string());},
+            _ => {}
+        }
+    }
+    return fields;
+}
+
+fn dbg_number(fields: &HashMap<String, String>, key: &str) -> Option<usize> {
+    let value = fields.get(key)?;
+    if value.starts_with("0x") {
+        return usize::from_str_radix(&value[2 ..], 16).ok();
+    }
+    return value.parse().ok();
+}
@@ -0,0 +1,69 @@
+// Execution traces, one line per instruction in the format of nestest.log:
+//
+// C000  4C F5 C5  JMP $C5F5                       A:00 X:00 Y:00 P:24 SP:FD PPU:  0, 21 CYC:7
+//
+// Lines are written just as each opcode is about to be fetched, so the registers and PPU
+// position are those from before the instruction runs. Unofficial opcodes are marked with
+// a * before the mnemonic. CYC counts CPU cycles since power on. Since other emulators
+// can produce the same format, a plain diff of two traces points straight at the first
+// instruction where they disagree.
+
+use std::io::Write;
+
+use disassembler;
+use nes::NesState;
+
+pub struct TraceLogger {
+    output: Box<dyn Write + Send>,
+    // Only instructions within this range of addresses are logged, inclusive
+    pub address_range: Option<(u16, u16)>,
+    // Only instructions run during this range of frames are logged, inclusive
+    pub frame_range: Option<(u32, u32)>,
+}
+
+impl TraceLogger {
+    pub fn new(output: Box<dyn Write + Send>) -> TraceLogger {
+        return TraceLogger {
+            output: output,
+            address_range: None,
+            frame_range: None,
+        };
+    }
+
+    pub fn wants(&self, nes: &NesState) -> bool {
+        match self.address_range {
+            Some((start, end)) if nes.registers.pc < start || nes.registers.pc > end => return false,
+            _ => {}
+        }
+        match self.frame_range {
+            Some((start, end)) if nes.ppu.current_frame < start || nes.ppu.current_frame > end => return false,
+            _ => {}
+        }
+        return true;
+    }
+
+    /// Called by the CPU as it is about to fetch an opcode
+    pub fn log_instruction(&mut self, nes: &NesState) {
+        if self.wants(nes) {
+            // If the output goes away there's nobody to tell, so the trace just stops
+            let _ = writeln!(self.output, "{}", trace_line(nes));
+        }
+    }
+
+    pub fn flush(&mut self) {
+        let _ = self.output.flush();
+    }
+}
+
+/// The trace line for the instruction at the program counter
+pub fn trace_line(nes: &NesState) -> String {
+    let instruction = disassembler::disassemble(nes, nes.registers.pc);
+    let bytes: Vec<String> = instruction.bytes.iter().map(|byte| format!("{:02X}", byte)).collect();
+    let marker = if instruction.official() {" "} else {"*"};
+    let text = format!("{}{}", instruction.text(), instruction.annotation(nes));
+    let cycles = nes.master_clock / nes.region.cpu_divider();
+    return format!("{:04X}  {:<8} {}{:<32}A:{:02X} X:{:02X} Y:{:02X} P:{:02X} SP:{:02X} PPU:{:>3},{:>3} CYC:{}",
+        instruction.address, bytes.join(" "), marker, text,
+        nes.registers.a, nes.registers.x, nes.registers.y, nes.registers.status_as_byte(false), nes.registers.s,
+        nes.ppu.current_scanline, nes.ppu.current_scanline_cycle, cycles);
+}
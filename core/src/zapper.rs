@@ -0,0 +1,112 @@
+// The NES Zapper, a light gun. Rather than knowing where it is pointed, the Zapper has
+// a photodiode that notices when the picture directly in front of it is bright. A game
+// flashes targets on screen and polls the gun while the beam draws them; if the gun
+// sees light at that moment, it was on target. The trigger is reported on D4, and the
+// light sensor on D3, which reads 0 while light is detected.
+// Reference: https://www.nesdev.org/wiki/Zapper
+
+use input::InputDevice;
+use ppu::PpuState;
+use save_load::*;
+
+// The sensor sees a small patch of the screen, not a single pixel
+const SENSOR_RADIUS: i32 = 2;
+// After the beam passes, the phosphors and the sensor circuit keep reporting light
+// for a while. This is roughly 10-25 scanlines on real hardware.
+const LIGHT_PERSISTENCE_SCANLINES: i32 = 20;
+// Luma (0-255) at which a pixel is considered lit
+const LIGHT_THRESHOLD: u32 = 0xA0;
+
+pub struct Zapper {
+    pub x: i32,
+    pub y: i32,
+    pub trigger: bool,
+}
+
+impl Zapper {
+    pub fn new() -> Zapper {
+        return Zapper {
+            x: -1,
+            y: -1,
+            trigger: false,
+        };
+    }
+
+    pub fn senses_light(&self, ppu: &PpuState) -> bool {
+        let palette = ppu.model.palette();
+        let beam_scanline = ppu.current_scanline as i32;
+        let beam_dot = ppu.current_scanline_cycle as i32;
+        for y in (self.y - SENSOR_RADIUS) ..= (self.y + SENSOR_RADIUS) {
+            let scanlines_since_drawn = beam_scanline - y;
+            if y < 0 || y >= 240 || scanlines_since_drawn < 0 || scanlines_since_drawn > LIGHT_PERSISTENCE_SCANLINES {
+                continue;
+            }
+            for x in (self.x - SENSOR_RADIUS) ..= (self.x + SENSOR_RADIUS) {
+                // Dot 0 is idle, so pixel x is drawn on dot x + 1
+                if x < 0 || x >= 256 || (scanlines_since_drawn == 0 && x + 1 >= beam_dot) {
+                    continue;
+                }
+                let palette_index = (ppu.screen[(y * 256 + x) as usize] as usize) * 3;
+                let r = palette[palette_index + 0] as u32;
+                let g = palette[palette_index + 1] as u32;
+                let b = palette[palette_index + 2] as u32;
+                let luma = (r * 299 + g * 587 + b * 114) / 1000;
+                if luma >= LIGHT_THRESHOLD {
+                    return true;
+                }
+            }
+        }
+        return false;
+    }
+}
+
+impl InputDevice for Zapper {
+    fn name(&self) -> &'static str {
+        return "Zapper";
+    }
+
+    // The Zapper has no shift register; its lines are live, and ignore the strobe
+    fn latch(&mut self, _strobe: bool) {}
+    fn clock(&mut self) {}
+
+    fn read(&self, ppu: &PpuState) -> u8 {
+        let mut result = 0;
+        if !self.senses_light(ppu) {
+            result |= 0b0000_1000;
+        }
+        if self.trigger {
+            result |= 0b0001_0000;
+        }
+        return result;
+    }
+
+    fn aim(&mut self, x: i32, y: i32) {
+        self.x = x;
+        self.y = y;
+    }
+
+    fn set_trigger(&mut self, pulled: bool) {
+        self.trigger = pulled;
+    }
+
+    fn save_state(&self, buff: &mut Vec<u8>) {
+        self.save_input(buff);
+    }
+
+    fn load_state(&mut self, buff: &mut &[u8]) -> Result<(), String> {
+        return self.load_input(buff);
+    }
+
+    fn save_input(&self, buff: &mut Vec<u8>) {
+        save_i32(buff, self.x);
+        save_i32(buff, self.y);
+        save_bool(buff, self.trigger);
+    }
+
+    fn load_input(&mut self, buff: &mut &[u8]) -> Result<(), String> {
+        self.x = load_i32(buff)?;
+        self.y = load_i32(buff)?;
+        self.trigger = load_bool(buff)?;
+        return Ok(());
+    }
+}
@@ -20,6 +20,7 @@ pub struct GameWindow {
     pub game_window_scale: usize,
     pub sram_path: PathBuf,
     pub has_sram: bool,
+    pub last_mouse_position: (i32, i32),
 }
 
 impl GameWindow {
@@ -34,6 +35,7 @@ impl GameWindow {
             game_window_scale: 2,
             sram_path: PathBuf::new(),
             has_sram: false,
+            last_mouse_position: (-1, -1),
         };
     }
 
@@ -131,6 +133,26 @@ impl GameWindow {
                         ui.close_menu();
                     }
                     ui.separator();
+                    let has_cartridge = !self.sram_path.as_os_str().is_empty();
+                    ui.add_enabled_ui(has_cartridge, |ui| {
+                        ui.menu_button("Save State", |ui| {
+                            for slot in 1 ..= 9 {
+                                if ui.button(format!("Slot {}", slot)).clicked() {
+                                    let _ = runtime_tx.send(events::Event::SaveState(slot));
+                                    ui.close_menu();
+                                }
+                            }
+                        });
+                        ui.menu_button("Load State", |ui| {
+                            for slot in 1 ..= 9 {
+                                if ui.button(format!("Slot {}", slot)).clicked() {
+                                    let _ = runtime_tx.send(events::Event::LoadState(slot));
+                                    ui.close_menu();
+                                }
+                            }
+                        });
+                    });
+                    ui.separator();
                     if ui.button("Exit").clicked() {
                         ctx.send_viewport_cmd(egui::ViewportCommand::Close);
                         ui.close_menu();
@@ -200,13 +222,33 @@ impl GameWindow {
         let game_window_width = (self.texture_handle.size()[0] * self.game_window_scale) as f32;
         let game_window_height = (self.texture_handle.size()[1] * self.game_window_scale) as f32;
         egui::CentralPanel::default().frame(egui::Frame::none()).show(ctx, |ui| {
-            ui.add(
+            let response = ui.add(
                 egui::Image::new(egui::load::SizedTexture::from_handle(&self.texture_handle))
                     .fit_to_exact_size([
                         game_window_width,
                         game_window_height
                     ].into())
+                    .sense(egui::Sense::click())
             );
+            // Mouse positions are reported in canvas pixels, the same as the SDL shell
+            match response.hover_pos() {
+                Some(position) => {
+                    let canvas_position = (
+                        ((position.x - response.rect.min.x) / self.game_window_scale as f32) as i32,
+                        ((position.y - response.rect.min.y) / self.game_window_scale as f32) as i32);
+                    if canvas_position != self.last_mouse_position {
+                        self.last_mouse_position = canvas_position;
+                        let _ = runtime_tx.send(events::Event::MouseMove(canvas_position.0, canvas_position.1));
+                    }
+                    if ui.input(|i| i.pointer.primary_pressed()) {
+                        let _ = runtime_tx.send(events::Event::MouseClick(canvas_position.0, canvas_position.1));
+                    }
+                },
+                None => {}
+            }
+            if ui.input(|i| i.pointer.primary_released()) {
+                let _ = runtime_tx.send(events::Event::MouseRelease);
+            }
         });
 
         let menubar_height = ctx.style().spacing.interact_size[1];
@@ -9,6 +9,7 @@ use cpal::traits::{DeviceTrait, HostTrait, StreamTrait};
 use std::collections::VecDeque;
 use std::fs::File;
 use std::io::Write;
+use std::path::{Path, PathBuf};
 use std::sync::Arc;
 use std::sync::Mutex;
 use std::thread;
@@ -35,6 +36,7 @@ struct Worker {
     _audio_stream: Box<dyn StreamTrait>,
     runtime_state: RusticoRuntimeState,
     game_window: GameWindow,
+    cartridge_path: String,
 
     exit_requested: bool,
 }
@@ -51,6 +53,7 @@ impl Worker {
             _audio_stream: audio_stream,
             runtime_state: runtime_state,
             game_window: game_window,
+            cartridge_path: String::new(),
             exit_requested: false
         };
     }
@@ -91,15 +94,25 @@ impl Worker {
     pub fn handle_event(&mut self, event: events::Event) -> Vec<events::Event> {
         // For now, the WORKER doesn't need to do anything with runtime events. Later it might
         // and this is where those would get handled. Setting this up now for consistency.
-        let events: Vec<events::Event> = Vec::new();
+        let mut events: Vec<events::Event> = Vec::new();
         match event {
-            rustico_ui_common::Event::CartridgeLoaded(_id) => {
+            rustico_ui_common::Event::CartridgeLoaded(id) => {
+                self.cartridge_path = id;
                 let has_sram = self.runtime_state.nes.mapper.has_sram();
                 let _ = self.shell_tx.send(app::ShellEvent::HasSram(has_sram));
             }
             rustico_ui_common::Event::SaveSram(sram_id, sram_data) => {
                 self.save_sram(sram_id, &sram_data);
             },
+            rustico_ui_common::Event::SaveStateData(slot, state_data) => {
+                self.save_state(slot, &state_data);
+            },
+            rustico_ui_common::Event::LoadState(slot) => {
+                events.push(self.load_state(slot));
+            },
+            rustico_ui_common::Event::LoadFailed(reason) => {
+                println!("Load failed: {}", reason);
+            },
             rustico_ui_common::Event::CloseApplication => {
                 println!("WORKER: application close requested, will exit after processing remaining events...");
                 self.exit_requested = true;
@@ -142,6 +155,30 @@ impl Worker {
         };
     }
 
+    fn save_state_path(&self, slot: usize) -> PathBuf {
+        return Path::new(&self.cartridge_path).with_extension(format!("ss{}", slot));
+    }
+
+    pub fn save_state(&self, slot: usize, state_data: &[u8]) {
+        let filename = self.save_state_path(slot);
+        match std::fs::write(&filename, state_data) {
+            Err(why) => {
+                println!("Couldn't write {}: {}", filename.display(), why.to_string());
+            },
+            Ok(_) => {
+                println!("Saved state to: {}", filename.display());
+            },
+        };
+    }
+
+    pub fn load_state(&self, slot: usize) -> events::Event {
+        let filename = self.save_state_path(slot);
+        return match std::fs::read(&filename) {
+            Ok(state_data) => events::Event::LoadStateData(slot, Arc::new(state_data)),
+            Err(why) => events::Event::LoadFailed(format!("Couldn't read {}: {}", filename.display(), why.to_string())),
+        };
+    }
+
     pub fn step_emulator(&mut self) {
         // Quickly poll the length of the audio buffer
         let audio_output_buffer = AUDIO_OUTPUT_BUFFER.lock().expect("wat");
@@ -154,6 +191,10 @@ impl Worker {
         // (2048 is arbitrary, make this configurable later!)
         let mut repaint_needed = false;
         while output_buffer_len < 512 {
+            if !self.runtime_state.running {
+                // Paused, possibly by a breakpoint
+                break;
+            }
             self.dispatch_event(events::Event::NesRunScanline);
             if self.runtime_state.nes.ppu.current_scanline == 242 {
                 // we just finished a game frame, so have the game window repaint itself
@@ -8,6 +8,7 @@ use rustico_ui_common;
 pub struct CartridgeManager {
   pub game_path: String,
   pub sram_path: String,
+  pub cdl_path: String,
 }
 
 impl CartridgeManager {
@@ -15,6 +16,7 @@ impl CartridgeManager {
     return CartridgeManager {
       game_path: String::from(""),
       sram_path: String::from(""),
+      cdl_path: String::from(""),
     }
   }
 
@@ -71,6 +73,47 @@ impl CartridgeManager {
     };
   }
 
+  pub fn save_code_data_log(&self, filename: String, cdl_data: &[u8]) {
+    match std::fs::write(&filename, cdl_data) {
+      Err(why) => {
+        println!("Couldn't write code/data log {}: {}", filename, why.to_string());
+      },
+      Ok(_) => {
+        println!("Wrote code/data log to: {}", filename);
+      }
+    }
+  }
+
+  pub fn save_state_path(&self, slot: usize) -> String {
+    // Save states live alongside the .sav file, one file per slot
+    return PathBuf::from(&self.sram_path).with_extension(format!("ss{}", slot)).to_str().unwrap().to_string();
+  }
+
+  pub fn save_state(&self, slot: usize, state_data: &[u8]) {
+    let filename = self.save_state_path(slot);
+    match std::fs::write(&filename, state_data) {
+      Err(why) => {
+        println!("Couldn't write save state {}: {}", filename, why.to_string());
+      },
+      Ok(_) => {
+        println!("Wrote save state slot {} to: {}", slot, filename);
+      }
+    }
+  }
+
+  pub fn load_state(&self, slot: usize) -> rustico_ui_common::Event {
+    let filename = self.save_state_path(slot);
+    match std::fs::read(&filename) {
+      Ok(state_data) => {
+        return rustico_ui_common::Event::LoadStateData(slot, Arc::new(state_data));
+      },
+      Err(reason) => {
+        println!("Failed to load save state {}: {}", filename, reason);
+        return rustico_ui_common::Event::LoadFailed(reason.to_string());
+      }
+    }
+  }
+
   pub fn handle_event(&mut self, event: rustico_ui_common::Event) -> Vec<rustico_ui_common::Event> {
     let mut responses: Vec<rustico_ui_common::Event> = Vec::new();
     match event {
@@ -78,6 +121,7 @@ impl CartridgeManager {
         match open_file_dialog() {
           Ok(file_path) => {
             responses.push(rustico_ui_common::Event::RequestSramSave(self.sram_path.clone()));
+            responses.push(rustico_ui_common::Event::RequestCodeDataLog(self.cdl_path.clone()));
             responses.push(self.open_cartridge_with_sram(&file_path));
           },
           Err(reason) => {
@@ -93,6 +137,15 @@ impl CartridgeManager {
         self.game_path = cart_id.to_string();
         self.sram_path = PathBuf::from(cart_id).with_extension("sav").to_str().unwrap().to_string();
         println!("Cartridge loading success! Storing save path as: {}", self.sram_path);
+        // A code/data log from an earlier session picks up where it left off
+        self.cdl_path = PathBuf::from(&self.game_path).with_extension("cdl").to_str().unwrap().to_string();
+        match std::fs::read(&self.cdl_path) {
+          Ok(cdl_data) => {
+            println!("Continuing code/data log from: {}", self.cdl_path);
+            responses.push(rustico_ui_common::Event::LoadCodeDataLog(Arc::new(cdl_data)));
+          },
+          Err(_) => {}
+        }
       },
       rustico_ui_common::Event::LoadFailed(reason) => {
         println!("Loading failed: {}", reason);
@@ -103,6 +156,15 @@ impl CartridgeManager {
       rustico_ui_common::Event::SaveSram(sram_id, sram_data) => {
         self.save_sram(sram_id, &sram_data);
       },
+      rustico_ui_common::Event::SaveCodeDataLog(cdl_id, cdl_data) => {
+        self.save_code_data_log(cdl_id, &cdl_data);
+      },
+      rustico_ui_common::Event::SaveStateData(slot, state_data) => {
+        self.save_state(slot, &state_data);
+      },
+      rustico_ui_common::Event::LoadState(slot) => {
+        responses.push(self.load_state(slot));
+      },
       _ => {}
     }
     return responses;
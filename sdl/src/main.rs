@@ -42,6 +42,8 @@ use rustico_ui_common::event_window::EventWindow;
 use rustico_ui_common::memory_window::MemoryWindow;
 use rustico_ui_common::piano_roll_window::PianoRollWindow;
 use rustico_ui_common::ppu_window::PpuWindow;
+use rustico_ui_common::profiler_window::ProfilerWindow;
+use rustico_ui_common::ram_search_window::RamSearchWindow;
 
 use cartridge_manager::CartridgeManager;
 use platform_window::PlatformWindow;
@@ -96,6 +98,8 @@ pub fn main() {
   windows.push(PlatformWindow::from_panel(&video_subsystem, Box::new(MemoryWindow::new())));
   windows.push(PlatformWindow::from_panel(&video_subsystem, Box::new(PianoRollWindow::new())));
   windows.push(PlatformWindow::from_panel(&video_subsystem, Box::new(PpuWindow::new())));
+  windows.push(PlatformWindow::from_panel(&video_subsystem, Box::new(ProfilerWindow::new())));
+  windows.push(PlatformWindow::from_panel(&video_subsystem, Box::new(RamSearchWindow::new())));
 
   let mut texture_creators: Vec<TextureCreator<WindowContext>> = Vec::new();
   for i in 0 .. windows.len() {
@@ -126,6 +130,8 @@ pub fn main() {
 
   let mut ctrl_mod = false;
   let mut dump_audio = false;
+  let mut save_state_slot: usize = 1;
+  let mut rewinding = false;
 
   let args: Vec<_> = env::args().collect();
   if args.len() > 1 {
@@ -160,6 +166,13 @@ pub fn main() {
                 }
               }
             },
+            Event::MouseButtonUp{ window_id: id, mouse_btn: MouseButton::Left, .. } => {
+              for i in 0 .. windows.len() {
+                if id == windows[i].canvas.window().id() {
+                  application_events.extend(windows[i].panel.handle_event(&runtime_state, events::Event::MouseRelease));
+                }
+              }
+            },
             Event::MouseMotion{ window_id: id, x: omx, y: omy, .. } => {
               for i in 0 .. windows.len() {
                 if id == windows[i].canvas.window().id() {
@@ -169,6 +182,13 @@ pub fn main() {
                 }
               }
             },
+            Event::MouseWheel{ window_id: id, y: wheel_y, .. } => {
+              for i in 0 .. windows.len() {
+                if id == windows[i].canvas.window().id() {
+                  application_events.extend(windows[i].panel.handle_event(&runtime_state, events::Event::CpuViewerScroll(-3 * wheel_y)));
+                }
+              }
+            },
             Event::Window { window_id: id, win_event: WindowEvent::Close, .. } => {
               for i in 0 .. windows.len() {
                 if id == windows[i].canvas.window().id() {
@@ -206,6 +226,7 @@ pub fn main() {
                     Keycode::Down =>   {application_events.push(events::Event::StandardControllerPress(0, StandardControllerButton::DPadDown))},
                     Keycode::Left =>   {application_events.push(events::Event::StandardControllerPress(0, StandardControllerButton::DPadLeft))},
                     Keycode::Right =>  {application_events.push(events::Event::StandardControllerPress(0, StandardControllerButton::DPadRight))},
+                    Keycode::Backspace => {rewinding = true;},
                     _ => {}
                   }
                 },
@@ -214,6 +235,9 @@ pub fn main() {
                   if key == Keycode::LCtrl || key == Keycode::RCtrl {
                     ctrl_mod = false;
                   }
+                  if key == Keycode::Backspace {
+                    rewinding = false;
+                  }
                   if ctrl_mod {
                     match key {
                       Keycode::Q => { break 'running },
@@ -259,16 +283,33 @@ pub fn main() {
                       Keycode::F5 => {application_events.push(events::Event::ShowPianoRollWindow);},
                       Keycode::F6 => {application_events.push(events::Event::ShowEventWindow);},
 
+                      Keycode::F7 => {application_events.push(events::Event::SaveState(save_state_slot));},
+                      Keycode::F8 => {application_events.push(events::Event::LoadState(save_state_slot));},
                       Keycode::F9 => {application_events.push(events::Event::NesNudgeAlignment);},
+                      Keycode::F10 => {application_events.push(events::Event::InsertCoin(0));},
+                      Keycode::F11 => {application_events.push(events::Event::InsertCoin(1));},
+                      Keycode::F12 => {application_events.push(events::Event::ShowProfilerWindow);},
+                      Keycode::M => {application_events.push(events::Event::ShowRamSearchWindow);},
+
+                      // Slots 1-9, matching the egui shell's menus
+                      Keycode::Num1 | Keycode::Num2 | Keycode::Num3 | Keycode::Num4 | Keycode::Num5 |
+                      Keycode::Num6 | Keycode::Num7 | Keycode::Num8 | Keycode::Num9 => {
+                        save_state_slot = (key as i32 - Keycode::Num0 as i32) as usize;
+                        println!("Selected save state slot {}", save_state_slot);
+                      },
 
                       Keycode::Period => {application_events.push(events::Event::MemoryViewerNextPage);},
                       Keycode::Comma => {application_events.push(events::Event::MemoryViewerPreviousPage);},
                       Keycode::Slash => {application_events.push(events::Event::MemoryViewerNextBus);},
+                      Keycode::PageUp => {application_events.push(events::Event::CpuViewerScroll(-16));},
+                      Keycode::PageDown => {application_events.push(events::Event::CpuViewerScroll(16));},
+                      Keycode::Home => {application_events.push(events::Event::CpuViewerFollowPc);},
 
                       Keycode::N => {application_events.push(events::Event::ToggleBooleanSetting("video.ntsc_filter".to_string()));},
                       Keycode::F => {application_events.push(events::Event::ToggleBooleanSetting("video.display_fps".to_string()));},
 
                       Keycode::S => {application_events.push(events::Event::RequestSramSave(cartridge_state.sram_path.clone()));},
+                      Keycode::L => {application_events.push(events::Event::StartCodeDataLog);},
 
                       Keycode::P => {application_events.push(events::Event::NesToggleEmulation);}
                       Keycode::R => {application_events.push(events::Event::NesReset);}
@@ -276,6 +317,8 @@ pub fn main() {
                       Keycode::C => {application_events.push(events::Event::NesRunCycle);},
                       Keycode::H => {application_events.push(events::Event::NesRunScanline);},
                       Keycode::V => {application_events.push(events::Event::NesRunFrame);},
+                      Keycode::O => {application_events.push(events::Event::NesStepOver);},
+                      Keycode::U => {application_events.push(events::Event::NesStepOut);},
 
 
                       Keycode::X =>      {application_events.push(events::Event::StandardControllerRelease(0, StandardControllerButton::A))},
@@ -309,7 +352,13 @@ pub fn main() {
     //println!("device queue: {}, emulator queue: {}", device.size(), runtime_state.nes.apu.samples_queued());
     while (device.size() as usize) + (runtime_state.nes.apu.samples_queued() * 2) < 4096 {
       new_frames += 1;
-      if runtime_state.running {
+      if runtime_state.running && rewinding {
+        // While the rewind key is held, step backwards one frame at a time. The
+        // audio from the past would sound awful, so play silence instead.
+        application_events.push(events::Event::NesRewind(1));
+        let buffer = vec!(0i16; 44100 / 60);
+        _ = device.queue_audio(&buffer);
+      } else if runtime_state.running {
         // Play Audio (leave this loop when this buffer fills)
         if runtime_state.nes.apu.buffer_full {
           let buffer_size = runtime_state.nes.apu.output_buffer.len();
@@ -324,8 +373,9 @@ pub fn main() {
           }
         }
 
-        // Run one frame, by running 262 scanlines (so we can capture events inbetween)
-        while runtime_state.nes.ppu.current_scanline == 242 {
+        // Run one frame, by running 262 scanlines (so we can capture events inbetween).
+        // A breakpoint may pause emulation partway through.
+        while runtime_state.running && runtime_state.nes.ppu.current_scanline == 242 {
           application_events.push(events::Event::NesRunScanline);
           let events_to_process = application_events.clone();
           application_events.clear();
@@ -333,7 +383,7 @@ pub fn main() {
             application_events.extend(dispatch_event(&mut windows, &mut runtime_state, &mut cartridge_state, event));
           }
         }
-        while runtime_state.nes.ppu.current_scanline != 242 {
+        while runtime_state.running && runtime_state.nes.ppu.current_scanline != 242 {
           application_events.push(events::Event::NesRunScanline);
           let events_to_process = application_events.clone();
           application_events.clear();
@@ -397,6 +447,7 @@ pub fn main() {
 
   println!("Exiting application! Attempting SRAM save one last time.");
   application_events.push(events::Event::RequestSramSave(cartridge_state.sram_path.clone()));
+  application_events.push(events::Event::RequestCodeDataLog(cartridge_state.cdl_path.clone()));
   while application_events.len() > 0 {
     let events_to_process = application_events.clone();
     application_events.clear();
@@ -1,12 +1,36 @@
 use std::sync::Arc;
 
+use debug_server;
+use debug_server::DebugServer;
 use events::Event;
 use events::StandardControllerButton;
 
+use movie::Movie;
+use movie::MovieFrame;
+use movie::MovieState;
+use rewind::RewindBuffer;
 use settings::SettingsState;
 
 use rustico_core::nes::NesState;
+use rustico_core::region::Region;
+use rustico_core::symbols::SymbolTable;
 use rustico_core::cartridge::mapper_from_file;
+use rustico_core::cartridge::cartridge_from_file;
+use rustico_core::cartridge::CartridgeInfo;
+use rustico_core::cdl::CodeDataLogger;
+use rustico_core::cheats::Cheat;
+use rustico_core::debugger::Breakpoint;
+use rustico_core::debugger::Debugger;
+use rustico_core::debugger::StopReason;
+use rustico_core::mmc::mapper::Mapper;
+use rustico_core::profiler::Profiler;
+use rustico_core::ines::ConsoleType;
+use rustico_core::ines::ExpansionDevice;
+use rustico_core::input::InputDevice;
+use rustico_core::input::StandardController;
+use rustico_core::multitap::FamicomFourPlayers;
+use rustico_core::multitap::FourScore;
+use rustico_core::zapper::Zapper;
 
 use rustico_core::apu::AudioChannelState;
 
@@ -14,6 +38,7 @@ use rustico_core::apu::AudioChannelState;
 
 pub struct RuntimeState {
     pub nes: NesState,
+    pub cartridge_info: CartridgeInfo,
     pub running: bool,
     pub file_loaded: bool,
     pub last_frame: u32,
@@ -21,13 +46,26 @@ pub struct RuntimeState {
     pub last_apu_quarter_frame_count: u32,
     pub last_apu_half_frame_count: u32,
     pub settings: SettingsState,
+    pub rewind: RewindBuffer,
+    pub movie: MovieState,
+    pub symbols: SymbolTable,
+    // Kept so that the console can be power cycled
+    pub cartridge_data: Vec<u8>,
+    pub bios_data: Vec<u8>,
+    pub debug_server: Option<DebugServer>,
+    pub debug_server_port: u16,
+    // Freezes made from the RAM search. Unlike cheats these aren't saved, and only last
+    // until another game is loaded.
+    pub ram_freezes: Vec<String>,
 }
 
 impl RuntimeState {
     pub fn new() -> RuntimeState {
-        let initial_cartridge = mapper_from_file(include_bytes!("assets/rustico_no_cart.nes")).unwrap();
+        let initial_cartridge_data = include_bytes!("assets/rustico_no_cart.nes");
+        let initial_cartridge = mapper_from_file(initial_cartridge_data).unwrap();
         let mut state = RuntimeState {
             nes: NesState::new(initial_cartridge),
+            cartridge_info: CartridgeInfo::new(),
             file_loaded: true,
             running: true,
             last_frame: 0,
@@ -35,6 +73,14 @@ impl RuntimeState {
             last_apu_quarter_frame_count: 0,
             last_apu_half_frame_count: 0,
             settings: SettingsState::new(),
+            rewind: RewindBuffer::new(15, 720),
+            movie: MovieState::new(),
+            symbols: SymbolTable::new(),
+            cartridge_data: initial_cartridge_data.to_vec(),
+            bios_data: Vec::new(),
+            debug_server: None,
+            debug_server_port: 6502,
+            ram_freezes: Vec::new(),
         };
         state.nes.power_on();
         return state;
@@ -42,12 +88,18 @@ impl RuntimeState {
 
     pub fn load_cartridge(&mut self, cart_id: String, file_data: &[u8]) -> Vec<Event> {
         let mut responses: Vec<Event> = Vec::new();
-        let maybe_mapper = mapper_from_file(file_data);
-        match maybe_mapper {
-            Ok(mapper) => {
-
-                self.nes = NesState::new(mapper);
+        let maybe_cartridge = cartridge_from_file(file_data);
+        match maybe_cartridge {
+            Ok((mapper, info)) => {
+                responses.extend(self.stop_movie());
+                self.ram_freezes.clear();
+                self.insert_cartridge(mapper, info);
+                // Symbols describe one particular ROM, so the old ones can only mislead
+                self.symbols.clear();
+                self.cartridge_data = file_data.to_vec();
+                self.bios_data = Vec::new();
                 self.file_loaded = true;
+                println!("Game ID: {}", self.game_id());
                 responses.push(Event::CartridgeLoaded(cart_id));
                 if self.nes.mapper.needs_bios() {
                     responses.push(Event::RequestBios);
@@ -66,6 +118,184 @@ impl RuntimeState {
         }
     }
 
+    // Sets up a fresh NES around the cartridge, ready to be powered on
+    fn insert_cartridge(&mut self, mapper: Box<dyn Mapper>, info: CartridgeInfo) {
+        // Breakpoints and traces belong to the person debugging, and outlive the console
+        let mut debugger = std::mem::replace(&mut self.nes.debugger, Debugger::new());
+        debugger.step_target = None;
+        debugger.resume_address = None;
+        let tracer = self.nes.tracer.take();
+        self.nes = NesState::with_region(mapper, self.region_for(&info));
+        self.nes.debugger = debugger;
+        self.nes.tracer = tracer;
+        self.nes.ppu.model = info.ppu_model();
+        self.nes.vs_system = info.console_type == ConsoleType::VsSystem;
+        self.cartridge_info = info;
+        self.plug_input_devices();
+        self.rewind.clear();
+        self.load_cheats();
+    }
+
+    /// Identifies the game in the cartridge slot, for settings which belong to just one
+    pub fn game_id(&self) -> String {
+        return format!("{:08X}", self.nes.mapper.rom_checksum());
+    }
+
+    fn load_cheats(&mut self) {
+        self.nes.cheats.clear();
+        for code in self.settings.get_cheats(&self.game_id()) {
+            match Cheat::parse(&code) {
+                Ok(cheat) => self.nes.cheats.add(cheat),
+                Err(why) => println!("Skipping cheat: {}", why)
+            }
+        }
+        for code in self.ram_freezes.iter() {
+            match Cheat::parse(code) {
+                Ok(cheat) => self.nes.cheats.add(cheat),
+                Err(why) => println!("Skipping RAM freeze: {}", why)
+            }
+        }
+    }
+
+    /// Adds a cheat for this game, and remembers it for next time
+    pub fn add_cheat(&mut self, code: &str) -> Result<(), String> {
+        let cheat = Cheat::parse(code)?;
+        let game_id = self.game_id();
+        let mut codes = self.settings.get_cheats(&game_id);
+        if !codes.contains(&cheat.code) {
+            codes.push(cheat.code.clone());
+        }
+        self.settings.set_cheats(&game_id, codes);
+        self.nes.cheats.add(cheat);
+        return Ok(());
+    }
+
+    pub fn remove_cheat(&mut self, code: &str) {
+        self.nes.cheats.remove(code);
+        // Keep whatever's still in use, and anything we couldn't make sense of
+        let game_id = self.game_id();
+        let codes: Vec<String> = self.settings.get_cheats(&game_id).into_iter()
+            .filter(|saved| Cheat::parse(saved).is_err() || self.nes.cheats.cheats.iter().any(|cheat| &cheat.code == saved))
+            .collect();
+        self.settings.set_cheats(&game_id, codes);
+    }
+
+    /// Adds a cheat for as long as this game stays loaded, without saving it
+    pub fn add_ram_freeze(&mut self, code: &str) -> Result<(), String> {
+        let cheat = Cheat::parse(code)?;
+        if !self.ram_freezes.contains(&cheat.code) {
+            self.ram_freezes.push(cheat.code.clone());
+        }
+        self.nes.cheats.add(cheat);
+        return Ok(());
+    }
+
+    pub fn remove_ram_freeze(&mut self, code: &str) {
+        self.ram_freezes.retain(|frozen| frozen != code);
+        // Rebuilt rather than removed from, so a saved cheat for the same value stays on
+        self.load_cheats();
+    }
+
+    /// Turns the console off and back on. The cartridge is rebuilt from its file, so
+    /// that everything, cartridge RAM included, starts out exactly as it did the first
+    /// time. Returns the settings, which need to be applied again to the new NES.
+    pub fn power_cycle(&mut self) -> Result<Vec<Event>, String> {
+        let (mut mapper, info) = cartridge_from_file(&self.cartridge_data)?;
+        if mapper.needs_bios() {
+            mapper.load_bios(self.bios_data.clone());
+            if mapper.needs_bios() {
+                return Err("Can't power cycle without a BIOS loaded".to_string());
+            }
+        }
+        // Buttons being held don't let go just because the power went out. The game is
+        // the same one, so its code/data log and any profile in progress carry on too.
+        let inputs = self.nes.save_inputs();
+        let cdl = self.nes.cdl.take();
+        let profiler = self.nes.profiler.take();
+        self.insert_cartridge(mapper, info);
+        let _ = self.nes.load_inputs(&inputs);
+        self.nes.cdl = cdl;
+        self.nes.power_on();
+        match profiler {
+            Some(mut profiler) => {
+                profiler.continue_with(&self.nes);
+                self.nes.profiler = Some(profiler);
+            },
+            None => {}
+        }
+        self.skip_timing_events();
+        return Ok(self.settings.apply_settings());
+    }
+
+    /// The region to run a cartridge in: whatever the user picked, or if they left it
+    /// on "auto", whatever the cartridge header asks for
+    pub fn region_for(&self, info: &CartridgeInfo) -> Region {
+        return self.settings.get_string("emulation.region".to_string())
+            .and_then(|name| Region::from_name(&name))
+            .unwrap_or(info.timing.region());
+    }
+
+    /// The device the settings call for in the given port (1 or 2). When left on "auto",
+    /// this is whatever the cartridge header suggests, and otherwise a standard controller.
+    /// A four player adapter takes over both ports.
+    pub fn input_device_for(&self, port: usize) -> Box<dyn InputDevice> {
+        let four_players_setting = self.settings.get_string("input.four_players".to_string()).unwrap_or("auto".to_string());
+        let four_players = match four_players_setting.as_str() {
+            "auto" => match self.cartridge_info.default_expansion_device {
+                ExpansionDevice::FourScore => "four_score",
+                ExpansionDevice::FamicomFourPlayers => "famicom",
+                _ => "none"
+            },
+            other => other
+        };
+        match four_players {
+            "four_score" => {return Box::new(FourScore::new(port))},
+            "famicom" => {return Box::new(FamicomFourPlayers::new())},
+            "none" => {},
+            _ => {
+                println!("Unrecognized four player adapter {}, expected auto, none, four_score or famicom", four_players);
+            }
+        }
+
+        let setting = self.settings.get_string(format!("input.port{}", port)).unwrap_or("auto".to_string());
+        let device_name = match setting.as_str() {
+            "auto" => match (port, self.cartridge_info.default_expansion_device) {
+                (2, ExpansionDevice::Zapper) => "zapper",
+                (_, ExpansionDevice::TwoZappers) => "zapper",
+                _ => "standard"
+            },
+            other => other
+        };
+        return match device_name {
+            "zapper" => Box::new(Zapper::new()),
+            "standard" => Box::new(StandardController::new()),
+            _ => {
+                println!("Unrecognized input device {} for port {}, expected auto, standard or zapper", device_name, port);
+                Box::new(StandardController::new())
+            }
+        };
+    }
+
+    /// Swaps out any device that doesn't match the settings. Devices that are already
+    /// correct are left alone, so buttons being held aren't dropped.
+    pub fn plug_input_devices(&mut self) {
+        let port1_device = self.input_device_for(1);
+        let port2_device = self.input_device_for(2);
+        let mut changed = false;
+        if port1_device.name() != self.nes.port1.device.name() {
+            self.nes.port1.plug(port1_device);
+            changed = true;
+        }
+        if port2_device.name() != self.nes.port2.device.name() {
+            self.nes.port2.plug(port2_device);
+            changed = true;
+        }
+        if changed {
+            // Logged inputs are in the shape of the old devices, and can't be replayed
+            self.rewind.clear();
+        }
+    }
+
     pub fn load_sram(&mut self, file_data: &[u8]) {
         if self.nes.mapper.has_sram() {
             if file_data.len() > 0 {
@@ -76,6 +306,7 @@ impl RuntimeState {
 
     pub fn load_bios(&mut self, file_data: &[u8]) {
         self.nes.mapper.load_bios(file_data.to_vec());
+        self.bios_data = file_data.to_vec();
         // Set ourselves to running (but only if that succeeded)
         if !self.nes.mapper.needs_bios() {
             self.nes.power_on();
@@ -83,38 +314,290 @@ impl RuntimeState {
         }
     }
 
-    pub fn button_press(&mut self, player_index: usize, button: StandardControllerButton) {
-        let controllers = [
-            &mut self.nes.p1_input,
-            &mut self.nes.p2_input
-        ];
+    /// Begins logging how the ROM is used, unless we already are
+    pub fn start_code_data_log(&mut self) -> Result<(), String> {
+        if self.nes.cdl.is_none() {
+            if self.cartridge_info.prg_rom_size == 0 {
+                return Err("Only iNES cartridges can be code/data logged".to_string());
+            }
+            self.nes.cdl = Some(CodeDataLogger::new(self.cartridge_info.prg_rom_size, self.cartridge_info.chr_rom_size));
+        }
+        return Ok(());
+    }
 
-        if player_index > controllers.len() {
+    pub fn load_code_data_log(&mut self, cdl_data: &[u8]) -> Result<(), String> {
+        self.start_code_data_log()?;
+        return match self.nes.cdl {
+            Some(ref mut cdl) => cdl.load(cdl_data),
+            None => Ok(())
+        };
+    }
+
+    pub fn load_state(&mut self, state_data: &[u8]) -> Result<(), String> {
+        self.nes.load_state(state_data)?;
+        self.rewind.clear();
+        self.skip_timing_events();
+        return Ok(());
+    }
+
+    pub fn rewind(&mut self, frames: u32) -> Result<u32, String> {
+        // The player's hands are still on the controller; whatever they're holding
+        // right now should win over the inputs stored in the old state.
+        let inputs = self.nes.save_inputs();
+        let frame = self.rewind.rewind(&mut self.nes, frames)?;
+        self.nes.load_inputs(&inputs)?;
+        self.rewind.record_input(&self.nes);
+        // Audio generated while re-emulating is from the past, so don't play it
+        let _ = self.nes.apu.consume_samples();
+        self.skip_timing_events();
+        return Ok(frame);
+    }
+
+    // Used after jumping around in time, so that the jump isn't reported as new
+    // frames / scanlines to the windows
+    fn skip_timing_events(&mut self) {
+        self.last_frame = self.nes.ppu.current_frame;
+        self.last_scanline = self.nes.ppu.current_scanline;
+        self.last_apu_quarter_frame_count = self.nes.apu.quarter_frame_counter;
+        self.last_apu_half_frame_count = self.nes.apu.half_frame_counter;
+    }
+
+    // Players 1 and 2 are the controllers in ports 1 and 2. Players 3 and 4 only exist
+    // with a four player adapter, which reads them through ports 1 and 2 respectively.
+    fn player_buttons(&self, player_index: usize) -> u8 {
+        let port = if player_index % 2 == 0 {&self.nes.port1} else {&self.nes.port2};
+        if player_index < 2 {
+            return port.device.buttons();
+        }
+        return port.device.secondary_buttons();
+    }
+
+    fn set_player_buttons(&mut self, player_index: usize, buttons: u8) {
+        let port = if player_index % 2 == 0 {&mut self.nes.port1} else {&mut self.nes.port2};
+        if player_index < 2 {
+            port.device.set_buttons(buttons);
+        } else {
+            port.device.set_secondary_buttons(buttons);
+        }
+    }
+
+    pub fn button_press(&mut self, player_index: usize, button: StandardControllerButton) {
+        if player_index >= 4 {
             return;
         }
 
-        let old_controller_byte = *controllers[player_index];
+        self.movie.before_input_change(&mut self.nes);
+        let old_controller_byte = self.player_buttons(player_index);
         let pressed_button = 0b1 << (button.clone() as u8);
         let new_controller_byte = old_controller_byte | pressed_button;
         let fixed_controller_byte = fix_dpad(new_controller_byte, button.clone());
-        *controllers[player_index] = fixed_controller_byte;
+        self.set_player_buttons(player_index, fixed_controller_byte);
+        self.movie.after_input_change(&mut self.nes);
+        self.rewind.record_input(&self.nes);
     }
 
     pub fn button_release(&mut self, player_index: usize, button: StandardControllerButton) {
-        let controllers = [
-            &mut self.nes.p1_input,
-            &mut self.nes.p2_input
-        ];
-
-        if player_index > controllers.len() {
+        if player_index >= 4 {
             return;
         }
 
-        let old_controller_byte = *controllers[player_index];
+        self.movie.before_input_change(&mut self.nes);
+        let old_controller_byte = self.player_buttons(player_index);
         let released_button = 0b1 << (button as u8);
         let release_mask = 0b1111_1111 ^ released_button;
         let new_controller_byte = old_controller_byte & release_mask;
-        *controllers[player_index] = new_controller_byte;
+        self.set_player_buttons(player_index, new_controller_byte);
+        self.movie.after_input_change(&mut self.nes);
+        self.rewind.record_input(&self.nes);
+    }
+
+    // Resets, power cycles, disk swaps and coins all come through here. With no movie
+    // running they happen right away. While recording they wait for the next frame,
+    // so that playback can do them at the same moment, and during playback only the
+    // movie's own commands are allowed.
+    fn run_command(&mut self, command: MovieFrame) -> Vec<Event> {
+        if self.movie.recording() {
+            self.movie.pending.add_commands(&command);
+            return Vec::new();
+        }
+        if self.movie.playing() {
+            println!("Ignoring command during movie playback");
+            return Vec::new();
+        }
+        return self.apply_commands(&command);
+    }
+
+    fn apply_commands(&mut self, command: &MovieFrame) -> Vec<Event> {
+        let mut responses: Vec<Event> = Vec::new();
+        if command.power_cycle {
+            match self.power_cycle() {
+                Ok(events) => {responses.extend(events)},
+                Err(why) => {println!("Power cycle failed: {}", why)}
+            }
+        }
+        if command.reset {
+            self.nes.reset();
+            self.rewind.clear();
+        }
+        match command.change_disk {
+            Some(internal_side_num) => {
+                self.nes.mapper.switch_disk(internal_side_num);
+                // Replaying forward wouldn't know to swap the disk, so the
+                // history before this point can't be reproduced
+                self.rewind.clear();
+            },
+            None => {}
+        }
+        for slot in 0 .. 2 {
+            if command.coins[slot] {
+                self.nes.insert_coin(slot);
+            }
+        }
+        return responses;
+    }
+
+    /// Powers the console on fresh and starts logging everything the player does
+    pub fn record_movie(&mut self) -> Vec<Event> {
+        let mut responses = self.stop_movie();
+        match self.power_cycle() {
+            Ok(events) => {responses.extend(events)},
+            Err(why) => {
+                println!("Can't record a movie: {}", why);
+                return responses;
+            }
+        }
+        self.movie.record(&self.nes);
+        println!("Recording movie from power on");
+        return responses;
+    }
+
+    /// Powers the console on fresh and plays back a recorded or imported movie
+    pub fn play_movie(&mut self, movie_data: &[u8]) -> Vec<Event> {
+        let mut responses = self.stop_movie();
+        let movie = match Movie::load(movie_data) {
+            Ok(movie) => movie,
+            Err(why) => {
+                responses.push(Event::LoadFailed(why));
+                return responses;
+            }
+        };
+        match self.power_cycle() {
+            Ok(events) => {responses.extend(events)},
+            Err(why) => {
+                responses.push(Event::LoadFailed(why));
+                return responses;
+            }
+        }
+        let frame_count = movie.frames.len();
+        match self.movie.play(movie, &mut self.nes) {
+            Ok(_) => {println!("Playing movie, {} frames", frame_count)},
+            Err(why) => {
+                self.movie.stop();
+                responses.push(Event::LoadFailed(why));
+            }
+        }
+        return responses;
+    }
+
+    /// Ends recording or playback. A finished recording is handed to the shell to save.
+    pub fn stop_movie(&mut self) -> Vec<Event> {
+        let mut responses: Vec<Event> = Vec::new();
+        if self.movie.recording() {
+            // Give the player back whatever they were holding
+            let _ = self.nes.load_inputs(&self.movie.pending.inputs);
+            let movie = self.movie.stop().unwrap();
+            println!("Recorded movie, {} frames", movie.frames.len());
+            responses.push(Event::SaveMovie(Arc::new(movie.save())));
+        } else if self.movie.playing() {
+            self.movie.stop();
+        }
+        return responses;
+    }
+
+    fn advance_movie(&mut self) -> Vec<Event> {
+        let mut responses: Vec<Event> = Vec::new();
+        if !self.movie.active() {
+            return responses;
+        }
+        match self.movie.next_frame(&self.nes) {
+            Ok(Some(frame)) => {
+                responses.extend(self.apply_commands(&frame));
+                match self.nes.load_inputs(&frame.inputs) {
+                    Ok(_) => {self.movie.current_inputs = frame.inputs},
+                    Err(why) => {
+                        println!("Stopping movie, couldn't load its inputs: {}", why);
+                        responses.extend(self.stop_movie());
+                    }
+                }
+            },
+            Ok(None) => {
+                println!("Movie playback finished after {} frames", self.movie.frame_count());
+                responses.extend(self.stop_movie());
+            },
+            Err(why) => {
+                println!("Stopping movie: {}", why);
+                responses.extend(self.stop_movie());
+            }
+        }
+        return responses;
+    }
+
+    pub fn start_debug_server(&mut self) -> Result<(), String> {
+        // Dropping the old server first frees its port, in case we're rebinding the same one
+        self.debug_server = None;
+        self.debug_server = Some(DebugServer::new(self.debug_server_port)?);
+        println!("Debug server listening on 127.0.0.1:{}", self.debug_server_port);
+        return Ok(());
+    }
+
+    pub fn stop_debug_server(&mut self) {
+        self.debug_server = None;
+    }
+
+    fn notify_debug_clients(&mut self, notification: &::serde_json::Value) {
+        match self.debug_server {
+            Some(ref mut server) => server.broadcast(notification),
+            None => {}
+        }
+    }
+
+    // Requests are run with the server taken out of self, so that they have the whole
+    // RuntimeState to work with. Anything they stop on is reported in their reply instead.
+    fn poll_debug_server(&mut self) -> Vec<Event> {
+        let mut responses: Vec<Event> = Vec::new();
+        let mut server = match self.debug_server.take() {
+            Some(server) => server,
+            None => {return responses}
+        };
+        for (client_id, line) in server.poll() {
+            let (reply, events) = debug_server::handle_request(self, &line);
+            server.send(client_id, &reply);
+            responses.extend(events);
+        }
+        server.flush();
+        self.debug_server = Some(server);
+        return responses;
+    }
+
+    // Used in place of the ordinary run functions while breakpoints are set, or a
+    // step over / step out is underway. Emulation pauses wherever it stops early.
+    fn run_until_break<F: FnMut(&NesState) -> bool>(&mut self, finished: F) -> Vec<Event> {
+        let mut responses: Vec<Event> = Vec::new();
+        match self.nes.run_until_break(finished) {
+            StopReason::Breakpoint(hit) => {
+                println!("{} breakpoint hit at 0x{:04X}, from PC 0x{:04X}", hit.kind.name(), hit.address, hit.program_counter);
+                self.running = false;
+                self.notify_debug_clients(&debug_server::breakpoint_notification(&hit));
+                responses.push(Event::BreakpointHit(hit));
+            },
+            StopReason::StepFinished => {
+                self.running = false;
+                let notification = debug_server::step_notification(&self.nes);
+                self.notify_debug_clients(&notification);
+            },
+            StopReason::Finished => {}
+        }
+        return responses;
     }
 
     pub fn collect_timing_events(&mut self) -> Vec<Event> {
@@ -122,6 +605,8 @@ impl RuntimeState {
         if self.nes.ppu.current_frame != self.last_frame {
             responses.push(Event::NesNewFrame);
             self.last_frame = self.nes.ppu.current_frame;
+            responses.extend(self.advance_movie());
+            self.rewind.capture(&self.nes);
         }
         if self.nes.ppu.current_scanline != self.last_scanline {
             responses.push(Event::NesNewScanline);
@@ -145,6 +630,59 @@ impl RuntimeState {
             Event::ApplyBooleanSetting(path, value) => {
                 match path.as_str() {
                     "audio.multiplexing" => {self.nes.mapper.audio_multiplexing(value)},
+                    "rewind.enabled" => {
+                        self.rewind.enabled = value;
+                        if !value {
+                            self.rewind.clear();
+                        }
+                    },
+                    "debug_server.enabled" => {
+                        if !value {
+                            self.stop_debug_server();
+                        } else if self.debug_server.is_none() {
+                            match self.start_debug_server() {
+                                Ok(_) => {},
+                                Err(why) => {println!("{}", why)}
+                            }
+                        }
+                    },
+                    _ => {}
+                }
+            },
+            Event::ApplyStringSetting(path, value) => {
+                match path.as_str() {
+                    "input.port1" | "input.port2" | "input.four_players" => {self.plug_input_devices()},
+                    "emulation.region" => {
+                        if Region::from_name(&value).is_none() && value != "auto" {
+                            println!("Unrecognized region {}, expected auto, ntsc, pal or dendy", value);
+                        }
+                        let region = self.region_for(&self.cartridge_info);
+                        if region != self.nes.region {
+                            println!("Switching to {} will take effect when the next cartridge is loaded.", region.name());
+                        }
+                    },
+                    _ => {}
+                }
+            },
+            Event::ApplyIntegerSetting(path, value) => {
+                match path.as_str() {
+                    "rewind.snapshot_interval" => {self.rewind.snapshot_interval = value.max(1) as u32},
+                    "rewind.capacity" => {self.rewind.capacity = value.max(1) as usize},
+                    "vs_system.dip_switches" => {self.nes.vs_dip_switches = value as u8},
+                    "debug_server.port" => {
+                        self.debug_server_port = value as u16;
+                        // Move a running server over to the new port
+                        let moved = match self.debug_server {
+                            Some(ref server) => server.port != self.debug_server_port,
+                            None => false
+                        };
+                        if moved {
+                            match self.start_debug_server() {
+                                Ok(_) => {},
+                                Err(why) => {println!("{}", why)}
+                            }
+                        }
+                    },
                     _ => {}
                 }
             },
@@ -170,10 +708,14 @@ impl RuntimeState {
             },
             
             Event::ChangeDisk(disk_num, side_num) => {
-                let internal_side_num = disk_num * 2 + side_num;
-                self.nes.mapper.switch_disk(internal_side_num);
+                responses.extend(self.run_command(MovieFrame{change_disk: Some(disk_num * 2 + side_num), .. MovieFrame::default()}));
             },
 
+            Event::InsertCoin(slot) => {
+                let mut coins = [false; 2];
+                coins[slot.min(1)] = true;
+                responses.extend(self.run_command(MovieFrame{coins: coins, .. MovieFrame::default()}));
+            },
             Event::LoadCartridge(cart_id, file_data, sram_data) => {
                 responses.extend(self.load_cartridge(cart_id, &file_data));
                 self.load_sram(&sram_data);
@@ -187,12 +729,64 @@ impl RuntimeState {
             Event::LoadSram(sram_data) => {
                 self.load_sram(&sram_data);
             },
+            Event::LoadCodeDataLog(cdl_data) => {
+                match self.load_code_data_log(&cdl_data) {
+                    Ok(_) => {},
+                    Err(why) => {
+                        responses.push(Event::LoadFailed(why));
+                    }
+                }
+            },
+            Event::StartCodeDataLog => {
+                match self.start_code_data_log() {
+                    Ok(_) => {println!("Code/data logging started")},
+                    Err(why) => {println!("Couldn't start code/data logging: {}", why)}
+                }
+            },
+            Event::StartProfiler => {
+                self.nes.profiler = Some(Profiler::new(&self.nes));
+            },
+            Event::StopProfiler => {
+                self.nes.profiler = None;
+            },
+            Event::LoadSymbols(filename, symbol_data) => {
+                match self.symbols.load_file(&filename, &symbol_data) {
+                    Ok(_) => {
+                        println!("Loaded symbols from {}, {} labels in total", filename, self.symbols.len());
+                    },
+                    Err(why) => {
+                        responses.push(Event::LoadFailed(why));
+                    }
+                }
+            },
+            Event::LoadStateData(slot, state_data) => {
+                // The movie can't follow us to another point in time
+                responses.extend(self.stop_movie());
+                match self.load_state(&state_data) {
+                    Ok(_) => {
+                        println!("Loaded save state from slot {}", slot);
+                    },
+                    Err(why) => {
+                        responses.push(Event::LoadFailed(why));
+                    }
+                }
+            },
             Event::NesRunCycle => {
                 self.nes.cycle();
                 responses.extend(self.collect_timing_events());
             },
             Event::NesRunFrame => {
-                self.nes.run_until_vblank();
+                if self.nes.debugger.active() {
+                    // The same place run_until_vblank stops, just after vblank begins
+                    let stop_scanline = self.nes.region.vblank_scanline() + 1;
+                    let mut left_vblank = false;
+                    responses.extend(self.run_until_break(|nes| {
+                        left_vblank |= nes.ppu.current_scanline != stop_scanline;
+                        return left_vblank && nes.ppu.current_scanline == stop_scanline;
+                    }));
+                } else {
+                    self.nes.run_until_vblank();
+                }
                 responses.extend(self.collect_timing_events());
             },
             Event::NesRenderNTSC(width) => {
@@ -202,11 +796,69 @@ impl RuntimeState {
                 self.nes.step();
             },
             Event::NesRunScanline => {
-                self.nes.run_until_hblank();
+                if self.nes.debugger.active() {
+                    let scanline = self.nes.ppu.current_scanline;
+                    responses.extend(self.run_until_break(|nes| nes.ppu.current_scanline != scanline));
+                } else {
+                    self.nes.run_until_hblank();
+                }
                 responses.extend(self.collect_timing_events());
             },
+            Event::NesStepOver => {
+                self.nes.step_over();
+                self.running = true;
+            },
+            Event::NesStepOut => {
+                self.nes.step_out();
+                self.running = true;
+            },
+            Event::AddBreakpoint(breakpoint) => {
+                self.nes.debugger.add_breakpoint(breakpoint);
+            },
+            Event::RemoveBreakpoint(index) => {
+                self.nes.debugger.remove_breakpoint(index);
+            },
+            Event::ToggleBreakpoint(kind, address) => {
+                match self.nes.debugger.find_breakpoint(kind, address) {
+                    Some(index) => {self.nes.debugger.remove_breakpoint(index)},
+                    None => {self.nes.debugger.add_breakpoint(Breakpoint::new(kind, address));}
+                }
+            },
+            Event::AddCheat(code) => {
+                match self.add_cheat(&code) {
+                    Ok(_) => {println!("Added cheat {}", code)},
+                    Err(why) => {println!("Couldn't add cheat {}", why)}
+                }
+            },
+            Event::RemoveCheat(code) => {
+                self.remove_cheat(&code);
+            },
+            Event::AddRamFreeze(code) => {
+                match self.add_ram_freeze(&code) {
+                    Ok(_) => {},
+                    Err(why) => {println!("Couldn't freeze {}", why)}
+                }
+            },
+            Event::RemoveRamFreeze(code) => {
+                self.remove_ram_freeze(&code);
+            },
+            Event::ClearBreakpoints => {
+                self.nes.debugger.clear_breakpoints();
+            },
             Event::NesReset => {
-                self.nes.reset();
+                responses.extend(self.run_command(MovieFrame{reset: true, .. MovieFrame::default()}));
+            },
+            Event::NesPowerCycle => {
+                responses.extend(self.run_command(MovieFrame{power_cycle: true, .. MovieFrame::default()}));
+            },
+            Event::NesRewind(frames) => {
+                responses.extend(self.stop_movie());
+                match self.rewind(frames) {
+                    Ok(_) => {},
+                    Err(why) => {
+                        println!("Rewind failed: {}", why);
+                    }
+                }
             },
             
             // These three events should ideally move to some sort of FrameTiming manager
@@ -222,6 +874,7 @@ impl RuntimeState {
 
             Event::NesNudgeAlignment => {
                 self.nes.nudge_ppu_alignment();
+                self.rewind.clear();
             }
 
             Event::RequestSramSave(sram_id) => {
@@ -229,15 +882,50 @@ impl RuntimeState {
                     responses.push(Event::SaveSram(sram_id, Arc::new(self.nes.sram())));
                 }
             },
+            Event::RequestCodeDataLog(cdl_id) => {
+                match self.nes.cdl {
+                    Some(ref cdl) => {responses.push(Event::SaveCodeDataLog(cdl_id, Arc::new(cdl.save())));},
+                    None => {}
+                }
+            },
+            Event::Update => {
+                responses.extend(self.poll_debug_server());
+            },
+            Event::MovieRecord => {
+                responses.extend(self.record_movie());
+            },
+            Event::MoviePlay(movie_data) => {
+                responses.extend(self.play_movie(&movie_data));
+            },
+            Event::MovieStop => {
+                responses.extend(self.stop_movie());
+            },
+            Event::SaveState(slot) => {
+                responses.push(Event::SaveStateData(slot, Arc::new(self.nes.save_state())));
+            },
 
-            // Input is due for an overhaul. Ideally the IoBus should handle its own
-            // events, rather than doing this here.
+            // Whatever is plugged into the port decides what these buttons mean; devices
+            // without any will simply ignore them.
             Event::StandardControllerPress(controller_index, button) => {
                 self.button_press(controller_index, button);
             },
             Event::StandardControllerRelease(controller_index, button) => {
                 self.button_release(controller_index, button);
             },
+            Event::ZapperAim(x, y) => {
+                self.movie.before_input_change(&mut self.nes);
+                self.nes.port1.device.aim(x, y);
+                self.nes.port2.device.aim(x, y);
+                self.movie.after_input_change(&mut self.nes);
+                self.rewind.record_input(&self.nes);
+            },
+            Event::ZapperTrigger(pulled) => {
+                self.movie.before_input_change(&mut self.nes);
+                self.nes.port1.device.set_trigger(pulled);
+                self.nes.port2.device.set_trigger(pulled);
+                self.movie.after_input_change(&mut self.nes);
+                self.rewind.record_input(&self.nes);
+            },
             _ => {}
         }
         return responses;
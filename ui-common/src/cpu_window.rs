@@ -6,14 +6,26 @@ use drawing::SimpleBuffer;
 use events::Event;
 use panel::Panel;
 
+use rustico_core::debugger::BreakpointHit;
+use rustico_core::debugger::BreakpointKind;
+use rustico_core::disassembler;
 use rustico_core::nes::NesState;
-use rustico_core::opcode_info::disassemble_instruction;
-use rustico_core::memory;
+use rustico_core::symbols::SymbolTable;
+
+const DISASSEMBLY_LINES: usize = 30;
+// Instructions shown above the program counter while following it
+const CONTEXT_LINES: usize = 4;
 
 pub struct CpuWindow {
     pub canvas: SimpleBuffer,
     pub font: Font,
     pub shown: bool,
+    pub last_hit: Option<BreakpointHit>,
+    // Where the listing starts once scrolled away from the program counter. While this
+    // is None, the listing follows the program counter instead.
+    pub top_address: Option<u16>,
+    // The instruction on each line of the last listing drawn, if any, for mouse clicks
+    line_addresses: Vec<Option<u16>>,
 }
 
 impl CpuWindow {
@@ -21,9 +33,12 @@ impl CpuWindow {
         let font = Font::from_raw(include_bytes!("assets/8x8_font.png"), 8);
 
         return CpuWindow {
-            canvas: SimpleBuffer::new(256, 300),
+            canvas: SimpleBuffer::new(400, 300),
             font: font,
             shown: false,
+            last_hit: None,
+            top_address: None,
+            line_addresses: Vec::new(),
         };
     }
 
@@ -57,39 +72,121 @@ impl CpuWindow {
             Color::rgb(128, 192, 128));
     }
 
-    pub fn draw_disassembly(&mut self, nes: &NesState, x: u32, y: u32) {
-        drawing::text(&mut self.canvas, &self.font, x, y, 
-        "===== Disassembly =====", Color::rgb(255, 255, 255));
-
-        let mut data_bytes_to_skip = 0;
-        for i in 0 .. 30 {
-            let pc = nes.registers.pc + (i as u16);
-            let opcode = memory::debug_read_byte(nes, pc);
-            let data1 = memory::debug_read_byte(nes, pc + 1);
-            let data2 = memory::debug_read_byte(nes, pc + 2);
-            let (instruction, data_bytes) = disassemble_instruction(opcode, data1, data2);
-            let mut text_color = Color::rgb(255, 255, 255);
+    pub fn draw_break_status(&mut self, x: u32, y: u32) {
+        match self.last_hit {
+            Some(hit) => {
+                let description = match hit.kind {
+                    BreakpointKind::Execute => format!("Break: Execute 0x{:04X}", hit.address),
+                    BreakpointKind::Irq | BreakpointKind::Nmi => format!("Break: {} from 0x{:04X}", hit.kind.name(), hit.program_counter),
+                    _ => format!("Break: {} 0x{:04X} = 0x{:02X} @ 0x{:04X}", hit.kind.name(), hit.address, hit.data, hit.program_counter),
+                };
+                drawing::text(&mut self.canvas, &self.font, x, y, &description, Color::rgb(255, 96, 96));
+            },
+            None => {}
+        }
+    }
+
+    // Where the program counter is, in terms of the program's own source, when we have
+    // symbols for it
+    pub fn draw_location(&mut self, nes: &NesState, symbols: &SymbolTable, x: u32, y: u32) {
+        drawing::text(&mut self.canvas, &self.font, x, y,
+            "===== Location =====",
+            Color::rgb(192, 192, 192));
+        match symbols.label_at(nes, nes.registers.pc) {
+            Some(name) => drawing::text(&mut self.canvas, &self.font, x, y + 8, name, Color::rgb(128, 192, 255)),
+            None => {}
+        }
+        match symbols.source_line_at(nes, nes.registers.pc) {
+            Some(line) => drawing::text(&mut self.canvas, &self.font, x, y + 16, &line, Color::rgb(192, 192, 192)),
+            None => {}
+        }
+    }
+
+    // The first address in the listing, either where we've scrolled to, or a few
+    // instructions ahead of the program counter
+    fn listing_start(&self, nes: &NesState) -> u16 {
+        return match self.top_address {
+            Some(address) => address,
+            None => {
+                let mut address = nes.registers.pc;
+                for _ in 0 .. CONTEXT_LINES {
+                    address = disassembler::previous_instruction(nes, address);
+                }
+                address
+            }
+        };
+    }
 
-            if data_bytes_to_skip > 0 {
-                text_color = Color::rgb(64, 64, 64);
-                data_bytes_to_skip -= 1;
+    pub fn scroll(&mut self, nes: &NesState, lines: i32) {
+        let mut address = self.listing_start(nes);
+        for _ in 0 .. lines.abs() {
+            if lines < 0 {
+                address = disassembler::previous_instruction(nes, address);
             } else {
-                data_bytes_to_skip = data_bytes;
+                address = address.wrapping_add(disassembler::disassemble(nes, address).length());
             }
+        }
+        self.top_address = Some(address);
+    }
 
-            drawing::text(&mut self.canvas, &self.font, x, y + 16 + (i as u32 * 8),
-                &format!("0x{:04X} - 0x{:02X}:  {}", pc, opcode, instruction),
-                text_color);
+    pub fn draw_disassembly(&mut self, nes: &NesState, symbols: &SymbolTable, x: u32, y: u32) {
+        let title = if self.top_address.is_some() {"===== Disassembly (Home: follow PC) ====="} else {"===== Disassembly ====="};
+        drawing::text(&mut self.canvas, &self.font, x, y, title, Color::rgb(255, 255, 255));
+
+        let width = self.canvas.width;
+        self.line_addresses.clear();
+        let mut address = self.listing_start(nes);
+        let mut labeled_address = None;
+        while self.line_addresses.len() < DISASSEMBLY_LINES {
+            let line_y = y + 16 + (self.line_addresses.len() as u32 * 8);
+            match symbols.label_at(nes, address) {
+                Some(name) if labeled_address != Some(address) => {
+                    // Labels get a line of their own, just above their instruction
+                    drawing::text(&mut self.canvas, &self.font, x, line_y, &format!("{}:", name), Color::rgb(128, 192, 255));
+                    self.line_addresses.push(None);
+                    labeled_address = Some(address);
+                    continue;
+                },
+                _ => {}
+            }
+            let instruction = disassembler::disassemble(nes, address);
+            let mut text_color = Color::rgb(255, 255, 255);
+            if !instruction.official() {
+                text_color = Color::rgb(128, 128, 128);
+            }
+            if address == nes.registers.pc {
+                drawing::rect(&mut self.canvas, x, line_y, width - x, 8, Color::rgb(64, 64, 0));
+                text_color = Color::rgb(255, 255, 128);
+            }
+            if nes.debugger.find_breakpoint(BreakpointKind::Execute, address).is_some() {
+                text_color = Color::rgb(255, 96, 96);
+            }
+            drawing::text(&mut self.canvas, &self.font, x, line_y, &instruction.listing_line(nes, symbols), text_color);
+            self.line_addresses.push(Some(address));
+            address = address.wrapping_add(instruction.length());
         }
     }
 
-    fn draw(&mut self, nes: &NesState) {
+    fn draw(&mut self, nes: &NesState, symbols: &SymbolTable) {
         // Clear!
         let width = self.canvas.width;
         let height = self.canvas.height;
         drawing::rect(&mut self.canvas, 0, 0, width, height, Color::rgb(0,0,0));
         self.draw_registers(nes, 0, 0);
-        self.draw_disassembly(nes, 0, 40);    
+        self.draw_location(nes, symbols, 184, 0);
+        self.draw_break_status(0, 32);
+        self.draw_disassembly(nes, symbols, 0, 40);
+    }
+
+    // Clicking a line of the disassembly toggles an execute breakpoint there
+    fn breakpoint_at(&self, my: i32) -> Vec<Event> {
+        if my < 56 {
+            return Vec::new();
+        }
+        return match self.line_addresses.get(((my - 56) / 8) as usize) {
+            Some(Some(address)) => vec![Event::ToggleBreakpoint(BreakpointKind::Execute, *address)],
+            _ => Vec::new()
+        };
     }
 }
 
@@ -103,13 +200,23 @@ impl Panel for CpuWindow {
     }
 
     fn handle_event(&mut self, runtime: &RuntimeState, event: Event) -> Vec<Event> {
+        let mut responses: Vec<Event> = Vec::new();
         match event {
-            Event::RequestFrame => {self.draw(&runtime.nes)},
+            Event::RequestFrame => {self.draw(&runtime.nes, &runtime.symbols)},
             Event::ShowCpuWindow => {self.shown = true},
             Event::CloseWindow => {self.shown = false},
+            Event::BreakpointHit(hit) => {
+                // Jump back to wherever we stopped
+                self.last_hit = Some(hit);
+                self.top_address = None;
+            },
+            Event::CpuViewerScroll(lines) => {self.scroll(&runtime.nes, lines)},
+            Event::CpuViewerFollowPc => {self.top_address = None},
+            Event::NesResumeEmulation | Event::NesToggleEmulation | Event::NesStepOver | Event::NesStepOut => {self.last_hit = None},
+            Event::MouseClick(_x, y) => {responses.extend(self.breakpoint_at(y))},
             _ => {}
         }
-        return Vec::<Event>::new();
+        return responses;
     }
     
     fn active_canvas(&self) -> &SimpleBuffer {
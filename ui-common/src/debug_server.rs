@@ -0,0 +1,772 @@
+// A small debug server, so that editor plugins and test harnesses can drive the emulator
+// from outside. It listens on localhost only, and speaks JSON one line at a time: each
+// request is an object with a "command" and usually an "id", and gets exactly one reply
+// carrying the same id, with "ok" set and either the results or an "error". Replies go out
+// in the order requests arrived.
+//
+// Besides replies, the server sends notifications to every client when emulation stops by
+// itself, say at a breakpoint hit while running freely. These have an "event" field in
+// place of an id.
+//
+// Sockets are never allowed to block: everything is polled once per Update, so a request
+// takes effect between frames, the same as a key press would.
+
+use std::io::ErrorKind;
+use std::io::Read;
+use std::io::Write;
+use std::net::TcpListener;
+use std::net::TcpStream;
+
+use image::ColorType;
+use image::png::PNGEncoder;
+use serde_json::Map;
+use serde_json::Value;
+
+use application::RuntimeState;
+use events::Event;
+use events::StandardControllerButton;
+
+use rustico_core::debugger::Breakpoint;
+use rustico_core::debugger::BreakpointHit;
+use rustico_core::debugger::BreakpointKind;
+use rustico_core::expression::Expression;
+use rustico_core::memory;
+use rustico_core::nes::NesState;
+use rustico_core::tracked_events::EventType;
+
+// Stepping over or out of a routine that never comes back shouldn't hang the server
+// forever; after 60 frames' worth of scanlines we reply anyway, and leave the emulator
+// running.
+const STEP_FRAME_LIMIT: u32 = 60;
+
+struct DebugClient {
+    id: usize,
+    stream: TcpStream,
+    incoming: Vec<u8>,
+    outgoing: Vec<u8>,
+    // The client has finished sending, but may still be waiting on replies
+    finished_sending: bool,
+    closed: bool,
+}
+
+pub struct DebugServer {
+    pub port: u16,
+    listener: TcpListener,
+    clients: Vec<DebugClient>,
+    next_client_id: usize,
+    had_client: bool,
+}
+
+impl DebugServer {
+    pub fn new(port: u16) -> Result<DebugServer, String> {
+        let listener = TcpListener::bind(("127.0.0.1", port)).map_err(|why| format!("Couldn't listen on port {}: {}", port, why))?;
+        listener.set_nonblocking(true).map_err(|why| why.to_string())?;
+        // Port 0 picks any free port, so ask which one we got
+        let bound_port = listener.local_addr().map_err(|why| why.to_string())?.port();
+        return Ok(DebugServer {
+            port: bound_port,
+            listener: listener,
+            clients: Vec::new(),
+            next_client_id: 0,
+            had_client: false,
+        });
+    }
+
+    fn accept_clients(&mut self) {
+        loop {
+            match self.listener.accept() {
+                Ok((stream, address)) => {
+                    if stream.set_nonblocking(true).is_err() {
+                        continue;
+                    }
+                    println!("Debug server: {} connected", address);
+                    self.clients.push(DebugClient {
+                        id: self.next_client_id,
+                        stream: stream,
+                        incoming: Vec::new(),
+                        outgoing: Vec::new(),
+                        finished_sending: false,
+                        closed: false,
+                    });
+                    self.next_client_id += 1;
+                    self.had_client = true;
+                },
+                Err(_) => {return}
+            }
+        }
+    }
+
+    /// Accepts new connections, and collects every complete request line received since
+    /// the last poll, along with the client it came from. A client which has finished
+    /// sending still gets its requests run, including a last line with no newline.
+    pub fn poll(&mut self) -> Vec<(usize, String)> {
+        self.accept_clients();
+        let mut requests: Vec<(usize, String)> = Vec::new();
+        let mut buffer = [0u8; 4096];
+        for client in self.clients.iter_mut().filter(|client| !client.finished_sending && !client.closed) {
+            loop {
+                match client.stream.read(&mut buffer) {
+                    Ok(0) => {client.finished_sending = true; break;},
+                    Ok(length) => {client.incoming.extend_from_slice(&buffer[.. length])},
+                    Err(ref why) if why.kind() == ErrorKind::WouldBlock => {break},
+                    Err(ref why) if why.kind() == ErrorKind::Interrupted => {},
+                    Err(_) => {client.closed = true; break;}
+                }
+            }
+            if client.finished_sending && client.incoming.len() > 0 {
+                client.incoming.push(b'\n');
+            }
+            while let Some(newline) = client.incoming.iter().position(|&byte| byte == b'\n') {
+                let line: Vec<u8> = client.incoming.drain(.. newline + 1).collect();
+                let text = String::from_utf8_lossy(&line).trim().to_string();
+                if text.len() > 0 {
+                    requests.push((client.id, text));
+                }
+            }
+        }
+        return requests;
+    }
+
+    pub fn send(&mut self, client_id: usize, message: &Value) {
+        for client in self.clients.iter_mut().filter(|client| client.id == client_id) {
+            client.outgoing.extend_from_slice(message.to_string().as_bytes());
+            client.outgoing.push(b'\n');
+        }
+    }
+
+    /// Queues a notification for every connected client
+    pub fn broadcast(&mut self, message: &Value) {
+        for client in self.clients.iter_mut() {
+            client.outgoing.extend_from_slice(message.to_string().as_bytes());
+            client.outgoing.push(b'\n');
+        }
+    }
+
+    /// Writes out as much queued output as the sockets will take, and forgets about
+    /// clients that have gone away, or have finished sending and have had every reply
+    pub fn flush(&mut self) {
+        for client in self.clients.iter_mut() {
+            while client.outgoing.len() > 0 && !client.closed {
+                match client.stream.write(&client.outgoing) {
+                    Ok(0) => {client.closed = true},
+                    Ok(length) => {client.outgoing.drain(.. length);},
+                    Err(ref why) if why.kind() == ErrorKind::WouldBlock => {break},
+                    Err(ref why) if why.kind() == ErrorKind::Interrupted => {},
+                    Err(_) => {client.closed = true}
+                }
+            }
+            if client.finished_sending && client.outgoing.len() == 0 {
+                client.closed = true;
+            }
+        }
+        for client in self.clients.iter().filter(|client| client.closed) {
+            println!("Debug server: client {} disconnected", client.id);
+        }
+        self.clients.retain(|client| !client.closed);
+    }
+
+    pub fn client_count(&self) -> usize {
+        return self.clients.len();
+    }
+
+    /// True once somebody has connected and every client has since left; a headless
+    /// shell can use this to know when its harness is done with it
+    pub fn finished(&self) -> bool {
+        return self.had_client && self.clients.len() == 0;
+    }
+}
+
+pub fn breakpoint_notification(hit: &BreakpointHit) -> Value {
+    let mut notification = breakpoint_hit_json(hit);
+    notification.insert("event".to_string(), Value::from("breakpoint"));
+    return Value::Object(notification);
+}
+
+pub fn step_notification(nes: &NesState) -> Value {
+    return json!({"event": "step", "pc": nes.registers.pc});
+}
+
+fn breakpoint_hit_json(hit: &BreakpointHit) -> Map<String, Value> {
+    let mut fields = Map::new();
+    fields.insert("kind".to_string(), Value::from(kind_name(hit.kind)));
+    fields.insert("address".to_string(), Value::from(hit.address));
+    fields.insert("data".to_string(), Value::from(hit.data));
+    fields.insert("pc".to_string(), Value::from(hit.program_counter));
+    return fields;
+}
+
+fn kind_name(kind: BreakpointKind) -> &'static str {
+    return match kind {
+        BreakpointKind::Execute => "execute",
+        BreakpointKind::Read => "read",
+        BreakpointKind::Write => "write",
+        BreakpointKind::PpuRead => "ppu_read",
+        BreakpointKind::PpuWrite => "ppu_write",
+        BreakpointKind::Irq => "irq",
+        BreakpointKind::Nmi => "nmi",
+    };
+}
+
+fn parse_kind(name: &str) -> Result<BreakpointKind, String> {
+    return match name {
+        "execute" | "exec" | "pc" => Ok(BreakpointKind::Execute),
+        "read" => Ok(BreakpointKind::Read),
+        "write" => Ok(BreakpointKind::Write),
+        "ppu_read" => Ok(BreakpointKind::PpuRead),
+        "ppu_write" => Ok(BreakpointKind::PpuWrite),
+        "irq" => Ok(BreakpointKind::Irq),
+        "nmi" => Ok(BreakpointKind::Nmi),
+        _ => Err(format!("Unknown breakpoint kind: {}", name))
+    };
+}
+
+fn parse_button(name: &str) -> Result<StandardControllerButton, String> {
+    return match name {
+        "a" => Ok(StandardControllerButton::A),
+        "b" => Ok(StandardControllerButton::B),
+        "select" => Ok(StandardControllerButton::Select),
+        "start" => Ok(StandardControllerButton::Start),
+        "up" => Ok(StandardControllerButton::DPadUp),
+        "down" => Ok(StandardControllerButton::DPadDown),
+        "left" => Ok(StandardControllerButton::DPadLeft),
+        "right" => Ok(StandardControllerButton::DPadRight),
+        _ => Err(format!("Unknown button: {}", name))
+    };
+}
+
+// Numbers may be given as JSON numbers, or as strings in hex ("$C000" or "0xC000")
+fn parse_number(value: &Value) -> Result<u64, String> {
+    return match value {
+        Value::Number(number) => number.as_u64().ok_or(format!("Not a positive integer: {}", number)),
+        Value::String(text) => {
+            let parsed = if text.starts_with("$") {
+                u64::from_str_radix(&text[1 ..], 16)
+            } else if text.starts_with("0x") {
+                u64::from_str_radix(&text[2 ..], 16)
+            } else {
+                text.parse()
+            };
+            parsed.map_err(|_| format!("Not a number: {}", text))
+        },
+        _ => Err(format!("Not a number: {}", value))
+    };
+}
+
+fn required_number(request: &Value, field: &str, max: u64) -> Result<u64, String> {
+    let number = match request.get(field) {
+        Some(value) => parse_number(value)?,
+        None => {return Err(format!("Missing field: {}", field))}
+    };
+    if number > max {
+        return Err(format!("{} is out of range: {}", field, number));
+    }
+    return Ok(number);
+}
+
+fn optional_number(request: &Value, field: &str, max: u64, default: u64) -> Result<u64, String> {
+    return match request.get(field) {
+        Some(_) => required_number(request, field, max),
+        None => Ok(default)
+    };
+}
+
+fn optional_string<'a>(request: &'a Value, field: &str, default: &'a str) -> Result<&'a str, String> {
+    return match request.get(field) {
+        Some(Value::String(text)) => Ok(text),
+        Some(value) => Err(format!("{} should be a string, not {}", field, value)),
+        None => Ok(default)
+    };
+}
+
+fn registers_json(nes: &NesState) -> Value {
+    return json!({
+        "cpu": {
+            "a": nes.registers.a,
+            "x": nes.registers.x,
+            "y": nes.registers.y,
+            "s": nes.registers.s,
+            "p": nes.registers.status_as_byte(false),
+            "pc": nes.registers.pc,
+        },
+        "ppu": {
+            "control": nes.ppu.control,
+            "mask": nes.ppu.mask,
+            "status": nes.ppu.status,
+            "oam_addr": nes.ppu.oam_addr,
+            "v": nes.ppu.current_vram_address,
+            "t": nes.ppu.temporary_vram_address,
+            "fine_x": nes.ppu.fine_x,
+            "write_toggle": nes.ppu.write_toggle,
+            "scanline": nes.ppu.current_scanline,
+            "cycle": nes.ppu.current_scanline_cycle,
+            "frame": nes.ppu.current_frame,
+        },
+    });
+}
+
+fn read_memory(runtime: &RuntimeState, request: &Value) -> Result<Value, String> {
+    let space = optional_string(request, "space", "cpu")?;
+    let length = optional_number(request, "length", 0x10000, 1)? as usize;
+    let nes = &runtime.nes;
+    let data: Vec<u8> = match space {
+        "cpu" => {
+            let address = required_number(request, "address", 0xFFFF)? as usize;
+            (address .. address + length).map(|a| memory::debug_read_byte(nes, a as u16)).collect()
+        },
+        "ppu" => {
+            let address = required_number(request, "address", 0x3FFF)? as usize;
+            (address .. address + length).map(|a| nes.ppu.debug_read_byte(&*nes.mapper, (a & 0x3FFF) as u16)).collect()
+        },
+        "oam" => {
+            let address = required_number(request, "address", 0xFF)? as usize;
+            (address .. address + length).map(|a| nes.ppu.oam[a & 0xFF]).collect()
+        },
+        _ => {return Err(format!("Unknown memory space: {}", space))}
+    };
+    return Ok(json!({"data": data}));
+}
+
+// Internal RAM and OAM are poked directly. Anything else goes over the bus like an
+// ordinary write would, so writes to registers have their usual side effects.
+fn write_memory(runtime: &mut RuntimeState, request: &Value) -> Result<Value, String> {
+    let space = optional_string(request, "space", "cpu")?;
+    let address = required_number(request, "address", 0xFFFF)? as usize;
+    let data: Vec<u8> = match request.get("data") {
+        Some(Value::Array(values)) => {
+            let mut bytes = Vec::new();
+            for value in values {
+                let byte = parse_number(value)?;
+                if byte > 0xFF {
+                    return Err(format!("Not a byte: {}", byte));
+                }
+                bytes.push(byte as u8);
+            }
+            bytes
+        },
+        _ => {return Err("data should be an array of bytes".to_string())}
+    };
+    let nes = &mut runtime.nes;
+    for (offset, &byte) in data.iter().enumerate() {
+        let target = address + offset;
+        match space {
+            "cpu" => {
+                let cpu_address = (target & 0xFFFF) as u16;
+                match cpu_address {
+                    0x0000 ..= 0x1FFF => {nes.memory.iram_raw[(cpu_address & 0x7FF) as usize] = byte},
+                    0x2000 ..= 0x401F => {memory::write_byte(nes, cpu_address, byte)},
+                    _ => {nes.mapper.write_cpu(cpu_address, byte)}
+                }
+            },
+            "ppu" => {nes.ppu.write_byte(&mut *nes.mapper, (target & 0x3FFF) as u16, byte)},
+            "oam" => {nes.ppu.oam[target & 0xFF] = byte},
+            _ => {return Err(format!("Unknown memory space: {}", space))}
+        }
+    }
+    return Ok(json!({"written": data.len()}));
+}
+
+fn set_registers(runtime: &mut RuntimeState, request: &Value) -> Result<Value, String> {
+    let registers = &mut runtime.nes.registers;
+    if request.get("a").is_some() {registers.a = required_number(request, "a", 0xFF)? as u8}
+    if request.get("x").is_some() {registers.x = required_number(request, "x", 0xFF)? as u8}
+    if request.get("y").is_some() {registers.y = required_number(request, "y", 0xFF)? as u8}
+    if request.get("s").is_some() {registers.s = required_number(request, "s", 0xFF)? as u8}
+    if request.get("p").is_some() {registers.set_status_from_byte(required_number(request, "p", 0xFF)? as u8)}
+    if request.get("pc").is_some() {registers.pc = required_number(request, "pc", 0xFFFF)? as u16}
+    return Ok(registers_json(&runtime.nes));
+}
+
+fn add_breakpoint(runtime: &mut RuntimeState, request: &Value) -> Result<Value, String> {
+    let kind = parse_kind(optional_string(request, "kind", "execute")?)?;
+    let mut breakpoint = match kind {
+        BreakpointKind::Irq | BreakpointKind::Nmi => Breakpoint::new(kind, 0),
+        _ => {
+            let start_address = required_number(request, "address", 0xFFFF)?;
+            let end_address = optional_number(request, "end_address", 0xFFFF, start_address)?;
+            Breakpoint::range(kind, start_address as u16, end_address as u16)
+        }
+    };
+    match request.get("condition") {
+        Some(Value::String(condition)) => {breakpoint.condition = Some(Expression::parse(condition)?)},
+        Some(value) => {return Err(format!("condition should be a string, not {}", value))},
+        None => {}
+    }
+    let index = runtime.nes.debugger.add_breakpoint(breakpoint);
+    return Ok(json!({"index": index}));
+}
+
+fn list_breakpoints(runtime: &RuntimeState) -> Value {
+    let breakpoints: Vec<Value> = runtime.nes.debugger.breakpoints.iter().enumerate().map(|(index, breakpoint)| json!({
+        "index": index,
+        "kind": kind_name(breakpoint.kind),
+        "address": breakpoint.start_address,
+        "end_address": breakpoint.end_address,
+        "enabled": breakpoint.enabled,
+        "conditional": breakpoint.condition.is_some(),
+    })).collect();
+    return json!({"breakpoints": breakpoints});
+}
+
+// Stepping pauses emulation, then runs synchronously so that the reply describes where
+// things stopped. Stepping over or out is the exception when it runs out of patience.
+fn step(runtime: &mut RuntimeState, request: &Value, events: &mut Vec<Event>) -> Result<Value, String> {
+    let unit = optional_string(request, "unit", "instruction")?;
+    let count = optional_number(request, "count", 1_000_000, 1)?;
+    let mut responses: Vec<Event> = Vec::new();
+    match unit {
+        "instruction" | "cycle" | "scanline" | "frame" => {
+            let event = match unit {
+                "instruction" => Event::NesRunOpcode,
+                "cycle" => Event::NesRunCycle,
+                "scanline" => Event::NesRunScanline,
+                _ => Event::NesRunFrame,
+            };
+            runtime.running = false;
+            for _ in 0 .. count {
+                responses.extend(runtime.handle_event(event.clone()));
+                if responses.iter().any(|response| match response {Event::BreakpointHit(_) => true, _ => false}) {
+                    break;
+                }
+            }
+        },
+        "over" | "out" => {
+            responses.extend(runtime.handle_event(if unit == "over" {Event::NesStepOver} else {Event::NesStepOut}));
+            let scanline_limit = STEP_FRAME_LIMIT * runtime.nes.region.scanlines_per_frame() as u32;
+            let mut scanlines = 0;
+            while runtime.running && scanlines < scanline_limit {
+                responses.extend(runtime.handle_event(Event::NesRunScanline));
+                scanlines += 1;
+            }
+        },
+        _ => {return Err(format!("Unknown step unit: {}", unit))}
+    }
+
+    let mut reply = registers_json(&runtime.nes);
+    reply["running"] = Value::from(runtime.running);
+    for response in responses.iter() {
+        match response {
+            Event::BreakpointHit(hit) => {reply["breakpoint"] = Value::Object(breakpoint_hit_json(hit))},
+            _ => {}
+        }
+    }
+    events.extend(responses);
+    return Ok(reply);
+}
+
+fn screenshot(runtime: &RuntimeState) -> Result<Value, String> {
+    let nes = &runtime.nes;
+    let palette = nes.ppu.model.palette();
+    let mut pixels: Vec<u8> = Vec::with_capacity(256 * 240 * 3);
+    for &color in nes.ppu.screen.iter() {
+        let palette_index = (color as usize) * 3;
+        pixels.extend_from_slice(&palette[palette_index .. palette_index + 3]);
+    }
+    let mut png: Vec<u8> = Vec::new();
+    PNGEncoder::new(&mut png).encode(&pixels, 256, 240, ColorType::RGB(8)).map_err(|why| why.to_string())?;
+    let png_hex: String = png.iter().map(|byte| format!("{:02x}", byte)).collect();
+    return Ok(json!({"width": 256, "height": 240, "png": png_hex}));
+}
+
+fn button_event(request: &Value, pressed: bool) -> Result<Event, String> {
+    let player = optional_number(request, "player", 3, 0)? as usize;
+    let button = match request.get("button") {
+        Some(Value::String(name)) => parse_button(name)?,
+        _ => {return Err("button should be one of a, b, select, start, up, down, left, right".to_string())}
+    };
+    return Ok(if pressed {Event::StandardControllerPress(player, button)} else {Event::StandardControllerRelease(player, button)});
+}
+
+fn tracked_events(runtime: &RuntimeState, request: &Value) -> Result<Value, String> {
+    let tracker = &runtime.nes.event_tracker;
+    let tracked = match optional_string(request, "frame", "last")? {
+        "last" => tracker.events_last_frame(),
+        "current" => tracker.events_this_frame(),
+        frame => {return Err(format!("frame should be last or current, not {}", frame))}
+    };
+    let mut listed: Vec<Value> = Vec::new();
+    for event in tracked {
+        let (kind, program_counter, address, data) = match event.event_type {
+            EventType::CpuRead{program_counter, address, data} => ("read", program_counter, address, data),
+            EventType::CpuWrite{program_counter, address, data} => ("write", program_counter, address, data),
+            EventType::CpuExecute{program_counter, data} => ("execute", program_counter, program_counter, data),
+            EventType::NullEvent => {continue}
+        };
+        listed.push(json!({
+            "scanline": event.scanline,
+            "cycle": event.cycle,
+            "type": kind,
+            "pc": program_counter,
+            "address": address,
+            "data": data,
+        }));
+    }
+    return Ok(json!({"events": listed}));
+}
+
+fn run_command(runtime: &mut RuntimeState, request: &Value, events: &mut Vec<Event>) -> Result<Value, String> {
+    let command = match request.get("command") {
+        Some(Value::String(command)) => command.as_str(),
+        _ => {return Err("Missing command".to_string())}
+    };
+    return match command {
+        "ping" => Ok(json!({})),
+        "status" => Ok(json!({
+            "running": runtime.running,
+            "frame": runtime.nes.ppu.current_frame,
+            "scanline": runtime.nes.ppu.current_scanline,
+            "cycle": runtime.nes.ppu.current_scanline_cycle,
+            "checksum": format!("{:08X}", runtime.nes.mapper.rom_checksum()),
+        })),
+        "read_memory" => read_memory(runtime, request),
+        "write_memory" => write_memory(runtime, request),
+        "registers" => Ok(registers_json(&runtime.nes)),
+        "set_registers" => set_registers(runtime, request),
+        "add_breakpoint" => add_breakpoint(runtime, request),
+        "remove_breakpoint" => {
+            let index = required_number(request, "index", u32::max_value() as u64)? as usize;
+            if index >= runtime.nes.debugger.breakpoints.len() {
+                return Err(format!("No breakpoint at index {}", index));
+            }
+            events.extend(runtime.handle_event(Event::RemoveBreakpoint(index)));
+            Ok(json!({}))
+        },
+        "clear_breakpoints" => {
+            events.extend(runtime.handle_event(Event::ClearBreakpoints));
+            Ok(json!({}))
+        },
+        "breakpoints" => Ok(list_breakpoints(runtime)),
+        "pause" => {
+            events.extend(runtime.handle_event(Event::NesPauseEmulation));
+            Ok(json!({}))
+        },
+        "resume" => {
+            events.extend(runtime.handle_event(Event::NesResumeEmulation));
+            Ok(json!({}))
+        },
+        "step" => step(runtime, request, events),
+        "screenshot" => screenshot(runtime),
+        "press" | "release" => {
+            let event = button_event(request, command == "press")?;
+            events.extend(runtime.handle_event(event));
+            Ok(json!({}))
+        },
+        "events" => tracked_events(runtime, request),
+        _ => Err(format!("Unknown command: {}", command))
+    };
+}
+
+/// Runs one request line against the emulator, returning the reply along with any
+/// events the shell should see, as though they had come from the runtime itself
+pub fn handle_request(runtime: &mut RuntimeState, line: &str) -> (Value, Vec<Event>) {
+    let mut events: Vec<Event> = Vec::new();
+    let request: Value = match ::serde_json::from_str(line) {
+        Ok(request) => request,
+        Err(why) => {return (json!({"id": Value::Null, "ok": false, "error": format!("Invalid JSON: {}", why)}), events)}
+    };
+    let id = request.get("id").cloned().unwrap_or(Value::Null);
+    let reply = match run_command(runtime, &request, &mut events) {
+        Ok(mut results) => {
+            results["id"] = id;
+            results["ok"] = Value::from(true);
+            results
+        },
+        Err(why) => json!({"id": id, "ok": false, "error": why})
+    };
+    return (reply, events);
+}
+
+#[cfg(test)]
+mod tests {
+    use std::io::ErrorKind;
+    use std::io::Read;
+    use std::io::Write;
+    use std::net::Shutdown;
+    use std::net::TcpStream;
+    use std::time::Duration;
+
+    use serde_json::Value;
+
+    use application::RuntimeState;
+    use debug_server::DebugServer;
+    use events::Event;
+
+    // A runtime with a server on a free port, and one client connected to it
+    struct Harness {
+        runtime: RuntimeState,
+        client: TcpStream,
+        received: Vec<u8>,
+        client_closed: bool,
+    }
+
+    impl Harness {
+        fn new() -> Harness {
+            let mut runtime = RuntimeState::new();
+            let server = DebugServer::new(0).unwrap();
+            let client = TcpStream::connect(("127.0.0.1", server.port)).unwrap();
+            client.set_read_timeout(Some(Duration::from_millis(5))).unwrap();
+            runtime.debug_server = Some(server);
+            return Harness {
+                runtime: runtime,
+                client: client,
+                received: Vec::new(),
+                client_closed: false,
+            };
+        }
+
+        fn send(&mut self, text: &str) {
+            self.client.write_all(text.as_bytes()).unwrap();
+        }
+
+        // Polls the server until the client has a whole line to read
+        fn receive(&mut self) -> Value {
+            for _ in 0 .. 200 {
+                match self.received.iter().position(|&byte| byte == b'\n') {
+                    Some(newline) => {
+                        let line: Vec<u8> = self.received.drain(.. newline + 1).collect();
+                        return ::serde_json::from_slice(&line).unwrap();
+                    },
+                    None => {}
+                }
+                self.runtime.handle_event(Event::Update);
+                self.read_available();
+            }
+            panic!("No reply from the debug server");
+        }
+
+        fn read_available(&mut self) {
+            let mut buffer = [0u8; 4096];
+            match self.client.read(&mut buffer) {
+                Ok(0) => {self.client_closed = true},
+                Ok(length) => {self.received.extend_from_slice(&buffer[.. length])},
+                Err(ref why) if why.kind() == ErrorKind::WouldBlock || why.kind() == ErrorKind::TimedOut => {},
+                Err(why) => panic!("Couldn't read from the debug server: {}", why)
+            }
+        }
+
+        fn request(&mut self, request: Value) -> Value {
+            self.send(&format!("{}\n", request));
+            let reply = self.receive();
+            assert_eq!(reply["ok"], Value::from(true), "{} failed: {}", request, reply);
+            return reply;
+        }
+
+        // Loads a program into RAM at $0200 and points the CPU at it
+        fn load_program(&mut self, program: &[u8]) {
+            self.request(json!({"command": "write_memory", "address": "$0200", "data": program}));
+            self.request(json!({"command": "set_registers", "pc": 0x0200}));
+        }
+    }
+
+    #[test]
+    fn writes_and_reads_memory() {
+        let mut harness = Harness::new();
+        let reply = harness.request(json!({"id": 7, "command": "write_memory", "address": "$0010", "data": [1, 2, "0xFF"]}));
+        assert_eq!(reply["id"], Value::from(7));
+        assert_eq!(reply["written"], Value::from(3));
+        let reply = harness.request(json!({"id": 8, "command": "read_memory", "address": 16, "length": 3}));
+        assert_eq!(reply["id"], Value::from(8));
+        assert_eq!(reply["data"], json!([1, 2, 255]));
+        // Mirrors of internal RAM see the same bytes
+        let reply = harness.request(json!({"command": "read_memory", "address": "0x0810", "length": 1}));
+        assert_eq!(reply["data"], json!([1]));
+    }
+
+    #[test]
+    fn steps_one_instruction_at_a_time() {
+        let mut harness = Harness::new();
+        // INX, INX
+        harness.load_program(&[0xE8, 0xE8]);
+        harness.request(json!({"command": "set_registers", "x": 0}));
+        let reply = harness.request(json!({"command": "step"}));
+        assert_eq!(reply["cpu"]["x"], Value::from(1));
+        assert_eq!(reply["cpu"]["pc"], Value::from(0x0201));
+        assert_eq!(reply["running"], Value::from(false));
+    }
+
+    #[test]
+    fn notifies_clients_when_a_breakpoint_is_hit() {
+        let mut harness = Harness::new();
+        let reply = harness.request(json!({"command": "add_breakpoint", "kind": "execute", "address": "$8000", "end_address": "$FFFF"}));
+        assert_eq!(reply["index"], Value::from(0));
+        let pc = harness.request(json!({"command": "registers"}))["cpu"]["pc"].clone();
+        harness.request(json!({"command": "resume"}));
+        harness.runtime.handle_event(Event::NesRunFrame);
+        assert!(!harness.runtime.running);
+        let notification = harness.receive();
+        assert_eq!(notification["event"], Value::from("breakpoint"));
+        assert_eq!(notification["kind"], Value::from("execute"));
+        assert_eq!(notification["address"], pc);
+        assert_eq!(notification["pc"], pc);
+        let reply = harness.request(json!({"command": "breakpoints"}));
+        assert_eq!(reply["breakpoints"][0]["kind"], Value::from("execute"));
+    }
+
+    #[test]
+    fn pressed_buttons_reach_the_controller_port() {
+        let mut harness = Harness::new();
+        // Strobe the controller, then read the A button: LDA #1, STA $4016, LDA #0,
+        // STA $4016, LDA $4016
+        let program = [0xA9, 0x01, 0x8D, 0x16, 0x40, 0xA9, 0x00, 0x8D, 0x16, 0x40, 0xAD, 0x16, 0x40];
+        harness.request(json!({"command": "press", "player": 0, "button": "a"}));
+        harness.load_program(&program);
+        let reply = harness.request(json!({"command": "step", "count": 5}));
+        assert_eq!(reply["cpu"]["a"].as_u64().unwrap() & 0x01, 1);
+
+        harness.request(json!({"command": "release", "player": 0, "button": "a"}));
+        harness.load_program(&program);
+        let reply = harness.request(json!({"command": "step", "count": 5}));
+        assert_eq!(reply["cpu"]["a"].as_u64().unwrap() & 0x01, 0);
+
+        harness.send("{\"command\": \"press\", \"button\": \"turbo\"}\n");
+        let reply = harness.receive();
+        assert_eq!(reply["ok"], Value::from(false));
+    }
+
+    #[test]
+    fn screenshots_are_png_images() {
+        let mut harness = Harness::new();
+        let reply = harness.request(json!({"command": "screenshot"}));
+        assert_eq!(reply["width"], Value::from(256));
+        assert_eq!(reply["height"], Value::from(240));
+        assert!(reply["png"].as_str().unwrap().starts_with("89504e470d0a1a0a"));
+    }
+
+    #[test]
+    fn malformed_requests_get_an_error_reply() {
+        let mut harness = Harness::new();
+        harness.send("{\"command\": \"registers\"\n");
+        let reply = harness.receive();
+        assert_eq!(reply["ok"], Value::from(false));
+        assert_eq!(reply["id"], Value::Null);
+        assert!(reply["error"].as_str().unwrap().starts_with("Invalid JSON"));
+        harness.send("{\"id\": 3, \"command\": \"launch_missiles\"}\n");
+        let reply = harness.receive();
+        assert_eq!(reply["id"], Value::from(3));
+        assert_eq!(reply["ok"], Value::from(false));
+        // The connection is still good afterwards
+        harness.request(json!({"command": "ping"}));
+    }
+
+    #[test]
+    fn replies_to_clients_which_stop_sending() {
+        let mut harness = Harness::new();
+        // The last request has no newline, as from printf piped into nc
+        harness.send("{\"id\": 1, \"command\": \"registers\"}\n{\"id\": 2, \"command\": \"ping\"}");
+        harness.client.shutdown(Shutdown::Write).unwrap();
+        let reply = harness.receive();
+        assert_eq!(reply["id"], Value::from(1));
+        assert_eq!(reply["ok"], Value::from(true));
+        let reply = harness.receive();
+        assert_eq!(reply["id"], Value::from(2));
+        // With every reply sent, the server hangs up
+        for _ in 0 .. 200 {
+            if harness.client_closed {
+                break;
+            }
+            harness.runtime.handle_event(Event::Update);
+            harness.read_available();
+        }
+        assert!(harness.client_closed);
+        assert!(harness.runtime.debug_server.as_ref().unwrap().finished());
+    }
+}
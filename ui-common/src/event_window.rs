@@ -7,7 +7,7 @@ use events::Event;
 use panel::Panel;
 
 use rustico_core::nes::NesState;
-use rustico_core::palettes::NTSC_PAL;
+use rustico_core::symbols::SymbolTable;
 use rustico_core::tracked_events::TrackedEvent;
 use rustico_core::tracked_events::EventType;
 
@@ -38,6 +38,24 @@ fn cpu_register_label(address: u16) -> String {
     return label.to_string();
 }
 
+// Registers by name, and anything else by whatever the symbols call it. Banks are
+// looked up as they are now, which for events from earlier in the frame is usually,
+// but not always, how they were at the time.
+fn address_label(nes: &NesState, symbols: &SymbolTable, address: u16) -> String {
+    let label = cpu_register_label(address);
+    if label.len() > 0 {
+        return label;
+    }
+    return symbols.label_at(nes, address).unwrap_or("").to_string();
+}
+
+fn program_counter_line(nes: &NesState, symbols: &SymbolTable, program_counter: u16) -> String {
+    return match symbols.label_at(nes, program_counter) {
+        Some(name) => format!("PC:       ${:04X} {}", program_counter, name),
+        None => format!("PC:       ${:04X}", program_counter)
+    };
+}
+
 fn cpu_register_color(address: u16) -> Color {
     match address {
         0x2000 => Color::rgb(255, 94, 94),
@@ -68,10 +86,10 @@ fn longest(strings: &Vec<String>)  -> usize {
     return length;
 }
 
-fn freshness(scanline: u16, cycle: u16, current_scanline: u16, current_cycle: u16) -> f32 {
+fn freshness(scanline: u16, cycle: u16, current_scanline: u16, current_cycle: u16, scanlines_per_frame: u16) -> f32 {
     let test_progress = (scanline as u32) * 341 + (cycle as u32);
     let current_progress = (current_scanline as u32) * 341 + (current_cycle as u32);
-    let max_distance = 262 * 341;
+    let max_distance = (scanlines_per_frame as u32) * 341;
     let distance = (test_progress + max_distance - current_progress) % max_distance;
     return (distance as f32) / (max_distance as f32);
 }
@@ -98,7 +116,7 @@ impl EventWindow {
         return x == (self.mx as u32) && y == (self.my as u32)
     }
 
-    fn draw_tooltip(&mut self, event: TrackedEvent) {
+    fn draw_tooltip(&mut self, nes: &NesState, symbols: &SymbolTable, event: TrackedEvent) {
         let outline_color = Color::rgb(0x80, 0x80, 0x40);
         let background_color = Color::rgb(0xFF, 0xFF, 0xE0);
         let font_color = Color::rgb(0x20, 0x20, 0x05);
@@ -106,15 +124,15 @@ impl EventWindow {
 
         let title = match event.event_type {
             EventType::CpuRead{address, data: _, program_counter: _} => {
-                let label = cpu_register_label(address);
+                let label = address_label(nes, symbols, address);
                 format!("Read: {}", label)
             },
             EventType::CpuWrite{address, data: _, program_counter: _} => {
-                let label = cpu_register_label(address);
+                let label = address_label(nes, symbols, address);
                 format!("Write: {}", label)
             },
             EventType::CpuExecute{program_counter, data: _} => {
-                let label = cpu_register_label(program_counter);
+                let label = address_label(nes, symbols, program_counter);
                 format!("Execute: {}", label)
             },
             _ => {format!("Huh!?")}
@@ -124,21 +142,21 @@ impl EventWindow {
         let mut contents = match event.event_type {
             EventType::CpuRead{program_counter, address, data} => {
                 vec![
-                    format!("PC:       ${:04X}", program_counter),
+                    program_counter_line(nes, symbols, program_counter),
                     format!("Address:  ${:04X}", address),
                     format!("Data:     ${:02X} ({})", data, data),
                 ]
             },
             EventType::CpuWrite{program_counter, address, data} => {
                 vec![
-                    format!("PC:       ${:04X}", program_counter),
+                    program_counter_line(nes, symbols, program_counter),
                     format!("Address:  ${:04X}", address),
                     format!("Data:     ${:02X} ({})", data, data)
                 ]
             },
             EventType::CpuExecute{program_counter, data} => {
                 vec![
-                    format!("PC:       ${:04X}", program_counter),
+                    program_counter_line(nes, symbols, program_counter),
                     format!("Data:     ${:02X} ({})", data, data)
                 ]
             },
@@ -192,6 +210,9 @@ impl EventWindow {
 
         let x = event.cycle as u32;
         let y = event.scanline as u32;
+        if x >= self.canvas.width || y >= self.canvas.height {
+            return;
+        }
         // Make the outline be very fancy and gracefully handle canvas edges
         // (todo later: make this part of rect?)
         let mut rx = x;
@@ -206,10 +227,10 @@ impl EventWindow {
             ry -= 1;
             rh += 1;
         }
-        if x < 340 {
+        if x < self.canvas.width - 1 {
             rw += 1;
         }
-        if y < 261 {
+        if y < self.canvas.height - 1 {
             rh += 1;
         }
 
@@ -234,20 +255,27 @@ impl EventWindow {
         }
     }
 
-    fn draw(&mut self, nes: &NesState) {
+    fn draw(&mut self, nes: &NesState, symbols: &SymbolTable) {
+        // One row per scanline, which PAL and Dendy have more of
+        let scanlines = nes.ppu.region.scanlines_per_frame();
+        if self.canvas.height != scanlines as u32 {
+            self.canvas = SimpleBuffer::new(341, scanlines as u32);
+        }
+
         // Clear!
-        drawing::rect(&mut self.canvas, 0, 0, 341, 262, Color::rgb(50,50,50));
+        drawing::rect(&mut self.canvas, 0, 0, 341, scanlines as u32, Color::rgb(50,50,50));
 
         // First, draw the current game screen, and a visualization of the electron beam
+        let palette = nes.ppu.model.palette();
         for x in 0 .. 341 {
-            for y in 0 .. 262 {
-                let pixel_freshness = freshness(y as u16, x as u16, nes.ppu.current_scanline, nes.ppu.current_scanline_cycle);
+            for y in 0 .. scanlines as u32 {
+                let pixel_freshness = freshness(y as u16, x as u16, nes.ppu.current_scanline, nes.ppu.current_scanline_cycle, scanlines);
                 if x  > 0 && x <= 256 && y < 240 {
                     let palette_index = ((nes.ppu.screen[(y * 256 + x - 1) as usize]) as usize) * 3;
                     let color = Color::rgba(
-                            NTSC_PAL[palette_index + 0],
-                            NTSC_PAL[palette_index + 1],
-                            NTSC_PAL[palette_index + 2],
+                            palette[palette_index + 0],
+                            palette[palette_index + 1],
+                            palette[palette_index + 2],
                             192);
                     let scanline_freshness = (pixel_freshness.powf(32.0) * 255.0) as u8;
                     //let freshness8 = (scanline_freshness + cycle_freshness).min(255.0) as u8;
@@ -290,7 +318,7 @@ impl EventWindow {
             if event.scanline > nes.ppu.current_scanline ||
                (event.scanline == nes.ppu.current_scanline && event.cycle > nes.ppu.current_scanline_cycle) {
                 if self.tooltip_visible(event) {
-                    self.draw_tooltip(event);
+                    self.draw_tooltip(nes, symbols, event);
                 }
             }
         }
@@ -298,7 +326,7 @@ impl EventWindow {
             if event.scanline < nes.ppu.current_scanline ||
                (event.scanline == nes.ppu.current_scanline && event.cycle <= nes.ppu.current_scanline_cycle) {
                 if self.tooltip_visible(event) {
-                    self.draw_tooltip(event);
+                    self.draw_tooltip(nes, symbols, event);
                 }
             }
         }
@@ -324,7 +352,7 @@ impl Panel for EventWindow {
 
     fn handle_event(&mut self, runtime: &RuntimeState, event: Event) -> Vec<Event> {
         match event {
-            Event::RequestFrame => {self.draw(&runtime.nes)},
+            Event::RequestFrame => {self.draw(&runtime.nes, &runtime.symbols)},
             Event::ShowEventWindow => {self.shown = true},
             Event::CloseWindow => {self.shown = false},
 
@@ -1,5 +1,9 @@
 use std::sync::Arc;
 
+use rustico_core::debugger::Breakpoint;
+use rustico_core::debugger::BreakpointHit;
+use rustico_core::debugger::BreakpointKind;
+
 #[derive(Clone, Debug)]
 pub enum StandardControllerButton {
     A,
@@ -14,28 +18,46 @@ pub enum StandardControllerButton {
 
 #[derive(Clone, Debug)]
 pub enum Event {
+    AddBreakpoint(Breakpoint),
+    AddCheat(String),
+    AddRamFreeze(String),
     ApplyBooleanSetting(String, bool),
     ApplyFloatSetting(String, f64),
     ApplyIntegerSetting(String, i64),
     ApplyStringSetting(String, String),
+    BreakpointHit(BreakpointHit),
+    ClearBreakpoints,
     CloseApplication,
     CloseWindow,
     CartridgeLoaded(String),
     CartridgeRejected(String, String),
     ChangeDisk(usize, usize),
+    // Positive numbers of lines scroll down, negative up
+    CpuViewerScroll(i32),
+    CpuViewerFollowPc,
     GameToggleOverscan,
     GameIncreaseScale,
     GameDecreaseScale,
+    InsertCoin(usize),
     LoadCartridge(String, Arc<Vec<u8>>,Arc<Vec<u8>>),
     LoadSram(Arc<Vec<u8>>),
+    LoadState(usize),
+    LoadStateData(usize, Arc<Vec<u8>>),
     LoadBios(Arc<Vec<u8>>),
+    // Merges a .cdl file into the code/data log, starting one if needed
+    LoadCodeDataLog(Arc<Vec<u8>>),
     LoadFailed(String),
+    // A symbol file's name, which decides its format, and its contents
+    LoadSymbols(String, Arc<Vec<u8>>),
     MouseMove(i32, i32),
     MouseClick(i32, i32),
     MouseRelease,
     MemoryViewerNextPage,
     MemoryViewerPreviousPage,
     MemoryViewerNextBus,
+    MoviePlay(Arc<Vec<u8>>),
+    MovieRecord,
+    MovieStop,
     MuteChannel(String, String),
     UnmuteChannel(String, String),
     NesNudgeAlignment,
@@ -44,19 +66,31 @@ pub enum Event {
     NesNewFrame,
     NesNewScanline,
     NesPauseEmulation,
+    NesPowerCycle,
     NesRenderNTSC(usize),
     NesResumeEmulation,
     NesReset,
+    NesRewind(u32),
     NesRunCycle,
     NesRunFrame,
     NesRunOpcode,
     NesRunScanline,
+    NesStepOut,
+    NesStepOver,
     NesToggleEmulation,
+    RemoveBreakpoint(usize),
+    RemoveCheat(String),
+    RemoveRamFreeze(String),
     RequestFrame,
     RequestCartridgeDialog,
     RequestSramSave(String),
+    RequestCodeDataLog(String),
     RequestBios,
+    SaveMovie(Arc<Vec<u8>>),
     SaveSram(String, Arc<Vec<u8>>),
+    SaveCodeDataLog(String, Arc<Vec<u8>>),
+    SaveState(usize),
+    SaveStateData(usize, Arc<Vec<u8>>),
     ShowApuWindow,
     ShowCpuWindow,
     ShowGameWindow,
@@ -64,13 +98,21 @@ pub enum Event {
     ShowMemoryWindow,
     ShowPianoRollWindow,
     ShowPpuWindow,
+    ShowProfilerWindow,
+    ShowRamSearchWindow,
     ShowTestWindow,
+    StartCodeDataLog,
+    StartProfiler,
     StandardControllerPress(usize, StandardControllerButton),
     StandardControllerRelease(usize, StandardControllerButton),
     StoreBooleanSetting(String, bool),
     StoreFloatSetting(String, f64),
     StoreIntegerSetting(String, i64),
     StoreStringSetting(String, String),
+    StopProfiler,
     ToggleBooleanSetting(String),
+    ToggleBreakpoint(BreakpointKind, u16),
     Update,
+    ZapperAim(i32, i32),
+    ZapperTrigger(bool),
 }
@@ -9,7 +9,6 @@ use panel::Panel;
 use std::time::Instant;
 
 use rustico_core::nes::NesState;
-use rustico_core::palettes::NTSC_PAL;
 
 pub struct GameWindow {
     pub canvas: SimpleBuffer,
@@ -59,6 +58,7 @@ impl GameWindow {
 
     fn draw(&mut self, nes: &NesState) {
         let overscan: u32 = if self.simulate_overscan {8} else {0};
+        let palette = nes.ppu.model.palette();
 
         // Update the game screen
         for x in overscan .. 256 - overscan {
@@ -80,9 +80,9 @@ impl GameWindow {
                         x - overscan,
                         y - overscan,
                         Color::rgb(
-                            NTSC_PAL[palette_index + 0],
-                            NTSC_PAL[palette_index + 1],
-                            NTSC_PAL[palette_index + 2])
+                            palette[palette_index + 0],
+                            palette[palette_index + 1],
+                            palette[palette_index + 2])
                     );
                 }
             }
@@ -94,6 +94,13 @@ impl GameWindow {
         }
     }
 
+    // Converts a position on our canvas back to the NES pixel it shows
+    fn screen_position(&self, x: i32, y: i32) -> (i32, i32) {
+        let overscan: i32 = if self.simulate_overscan {8} else {0};
+        let canvas_scale: i32 = if self.ntsc_filter {self.scale as i32} else {1};
+        return (x / canvas_scale + overscan, y / canvas_scale + overscan);
+    }
+
     fn increase_scale(&mut self) {
         if self.scale < 8 {
             self.scale += 1;
@@ -138,6 +145,18 @@ impl Panel for GameWindow {
                     responses.push(Event::NesRenderNTSC(256 * (self.scale as usize)));
                 }
             },
+            Event::MouseMove(x, y) => {
+                let (screen_x, screen_y) = self.screen_position(x, y);
+                responses.push(Event::ZapperAim(screen_x, screen_y));
+            },
+            Event::MouseClick(x, y) => {
+                let (screen_x, screen_y) = self.screen_position(x, y);
+                responses.push(Event::ZapperAim(screen_x, screen_y));
+                responses.push(Event::ZapperTrigger(true));
+            },
+            Event::MouseRelease => {
+                responses.push(Event::ZapperTrigger(false));
+            },
             Event::ShowGameWindow => {self.shown = true},
             Event::CloseWindow => {self.shown = false},
 
@@ -2,12 +2,17 @@ extern crate csscolorparser;
 extern crate image;
 extern crate regex;
 extern crate rustico_core;
+#[macro_use]
+extern crate serde_json;
 extern crate toml;
 
 pub mod application;
+pub mod debug_server;
 pub mod events;
 pub mod panel;
 pub mod drawing;
+pub mod movie;
+pub mod rewind;
 
 pub use events::Event;
 
@@ -19,4 +24,6 @@ pub mod memory_window;
 pub mod test_window;
 pub mod piano_roll_window;
 pub mod ppu_window;
+pub mod profiler_window;
+pub mod ram_search_window;
 pub mod settings;
\ No newline at end of file
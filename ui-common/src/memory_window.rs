@@ -7,7 +7,28 @@ use events::Event;
 use panel::Panel;
 
 use rustico_core::nes::NesState;
+use rustico_core::cdl;
 use rustico_core::memory;
+use rustico_core::symbols::SymbolTable;
+
+// Shades ROM by how the code/data logger has seen it used: orange for code (or CHR that was
+// drawn), teal for data (or CHR read by the CPU), and purple for both
+fn coverage_color(first_use: bool, second_use: bool, light: bool) -> Option<Color> {
+    let b = if light {16} else {0};
+    return match (first_use, second_use) {
+        (true, false) => Some(Color::rgb(80 + b, 48 + b, 24 + b)),
+        (false, true) => Some(Color::rgb(24 + b, 64 + b, 72 + b)),
+        (true, true) => Some(Color::rgb(72 + b, 40 + b, 80 + b)),
+        (false, false) => None
+    };
+}
+
+fn percentage(count: usize, total: usize) -> f32 {
+    if total == 0 {
+        return 0.0;
+    }
+    return count as f32 * 100.0 / total as f32;
+}
 
 pub struct MemoryWindow {
     pub canvas: SimpleBuffer,
@@ -16,6 +37,8 @@ pub struct MemoryWindow {
     pub shown: bool,
     pub view_ppu: bool,
     pub memory_page: u16,
+    // The cell under the mouse, if any, whose label is shown above the grid
+    pub hover_address: Option<u16>,
 }
 
 impl MemoryWindow {
@@ -29,10 +52,11 @@ impl MemoryWindow {
             shown: false,
             view_ppu: false,
             memory_page: 0x0000,
+            hover_address: None,
         };
     }
 
-    pub fn draw_memory_page(&mut self, nes: &NesState, sx: u32, sy: u32) {
+    pub fn draw_memory_page(&mut self, nes: &NesState, symbols: &SymbolTable, sx: u32, sy: u32) {
         for y in 0 .. 16 {
             for x in 0 .. 16 {
                 let address = self.memory_page + (x as u16) + (y as u16 * 16);
@@ -44,6 +68,12 @@ impl MemoryWindow {
                 if self.view_ppu {
                     let masked_address = address & 0x3FFF;
                     byte = nes.ppu.debug_read_byte(& *nes.mapper, masked_address);
+                    match nes.cdl.as_ref().and_then(|cdl| cdl.chr_flags_at(&*nes.mapper, masked_address)) {
+                        Some(flags) => {
+                            bg_color = coverage_color(flags & cdl::CHR_RENDERED != 0, flags & cdl::CHR_READ != 0, (x + y) % 2 == 0).unwrap_or(bg_color);
+                        },
+                        None => {}
+                    }
                     if masked_address == (nes.ppu.current_vram_address & 0x3FFF) {
                         bg_color = Color::rgb(128, 32, 32);
                     } else if nes.ppu.recent_reads.contains(&masked_address) {
@@ -65,6 +95,16 @@ impl MemoryWindow {
                     }
                 } else {
                     byte = memory::debug_read_byte(nes, address);
+                    // Labeled bytes get a blue tint, so that variables stand out from the rest
+                    if symbols.label_at(nes, address).is_some() {
+                        bg_color = if (x + y) % 2 == 0 {Color::rgb(48, 56, 88)} else {Color::rgb(32, 40, 72)};
+                    }
+                    match nes.cdl.as_ref().and_then(|cdl| cdl.prg_flags_at(&*nes.mapper, address)) {
+                        Some(flags) => {
+                            bg_color = coverage_color(flags & cdl::PRG_CODE != 0, flags & cdl::PRG_DATA != 0, (x + y) % 2 == 0).unwrap_or(bg_color);
+                        },
+                        None => {}
+                    }
                     if address == nes.registers.pc {
                         bg_color = Color::rgb(128, 32, 32);
                     } else if address == (nes.registers.s as u16 + 0x100) {
@@ -102,7 +142,24 @@ impl MemoryWindow {
         }
     }
 
-    pub fn draw(&mut self, nes: &NesState) {
+    // The address under the mouse, and its label if it has one
+    pub fn draw_hover_label(&mut self, nes: &NesState, symbols: &SymbolTable, x: u32, y: u32) {
+        let width = self.canvas.width;
+        drawing::rect(&mut self.canvas, x, y, width - x, 11, Color::rgb(0,0,0));
+        match self.hover_address {
+            Some(address) => {
+                let label = if self.view_ppu {None} else {symbols.label_at(nes, address)};
+                let description = match label {
+                    Some(name) => format!("0x{:04X} {}", address, name),
+                    None => format!("0x{:04X}", address)
+                };
+                drawing::text(&mut self.canvas, &self.font, x + 2, y + 2, &description, Color::rgba(255, 255, 255, 192));
+            },
+            None => {}
+        }
+    }
+
+    pub fn draw(&mut self, nes: &NesState, symbols: &SymbolTable) {
         let width = self.canvas.width;
         let height = self.canvas.height;
         
@@ -112,6 +169,20 @@ impl MemoryWindow {
             if self.view_ppu {"PPU"} else {"CPU"}, self.memory_page), 
             Color::rgb(255, 255, 255));
 
+        match nes.cdl {
+            Some(ref cdl) => {
+                let coverage = if self.view_ppu {
+                    let (rendered, read) = cdl.chr_coverage();
+                    format!("Drawn {:.1}% Read {:.1}%", percentage(rendered, cdl.chr.len()), percentage(read, cdl.chr.len()))
+                } else {
+                    let (code, data) = cdl.prg_coverage();
+                    format!("Code {:.1}% Data {:.1}%", percentage(code, cdl.prg.len()), percentage(data, cdl.prg.len()))
+                };
+                drawing::text(&mut self.canvas, &self.font, 152, 0, &coverage, Color::rgba(255, 255, 255, 192));
+            },
+            None => {}
+        }
+
         // Draw memory region selector
         for i in 0x0 .. 0x10 {
             // Highest Nybble
@@ -142,7 +213,8 @@ impl MemoryWindow {
                 self.memory_page + (i as u16 * 0x10)), 
                 Color::rgba(255, 255, 255, 64));
         }
-        self.draw_memory_page(nes, 56, 44);
+        self.draw_hover_label(nes, symbols, 56, 33);
+        self.draw_memory_page(nes, symbols, 56, 44);
     }
 
     pub fn handle_click(&mut self, mx: i32, my: i32) {
@@ -158,6 +230,16 @@ impl MemoryWindow {
             self.memory_page = (self.memory_page & 0xF0FF) | (low_nybble << 8);
         }
     }
+
+    pub fn handle_move(&mut self, mx: i32, my: i32) {
+        if mx >= 56 && mx < 56 + 16 * 19 && my >= 44 && my < 44 + 16 * 11 {
+            let column = ((mx - 56) / 19) as u16;
+            let row = ((my - 44) / 11) as u16;
+            self.hover_address = Some(self.memory_page.wrapping_add(row * 16 + column));
+        } else {
+            self.hover_address = None;
+        }
+    }
 }
 
 
@@ -172,7 +254,7 @@ impl Panel for MemoryWindow {
 
     fn handle_event(&mut self, runtime: &RuntimeState, event: Event) -> Vec<Event> {
         match event {
-            Event::RequestFrame => {self.draw(&runtime.nes)},
+            Event::RequestFrame => {self.draw(&runtime.nes, &runtime.symbols)},
             Event::ShowMemoryWindow => {self.shown = true},
             Event::CloseWindow => {self.shown = false},
             Event::MemoryViewerNextPage => {
@@ -185,6 +267,7 @@ impl Panel for MemoryWindow {
                 self.view_ppu = !self.view_ppu;
             },
             Event::MouseClick(x, y) => {self.handle_click(x, y);},
+            Event::MouseMove(x, y) => {self.handle_move(x, y);},
             _ => {}
         }
         return Vec::<Event>::new();
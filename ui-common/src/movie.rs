@@ -0,0 +1,432 @@
+// Movies: a log of everything the player did, one entry per frame, starting from the
+// moment the console was powered on. Emulation is deterministic, so feeding the same
+// inputs back in on the same frames reproduces the original session exactly, which
+// makes a movie a much smaller (and more useful) attachment to a bug report than a
+// video of it.
+//
+// Each frame records the inputs held on both controller ports, in the same form the
+// rewind buffer uses, along with any resets, power cycles, disk swaps and coins that
+// happened. While recording, changes the player makes partway through a frame are
+// held back until the start of the next one. Playback can only apply inputs on frame
+// boundaries, so this is what keeps the recorded session and its replay identical.
+//
+// FCEUX .fm2 movies can also be imported. These are text, and describe the same
+// things with one line per frame.
+// Reference: https://fceux.com/web/help/fm2.html
+
+use rustico_core::input::InputDevice;
+use rustico_core::input::StandardController;
+use rustico_core::multitap::FourScore;
+use rustico_core::nes::NesState;
+use rustico_core::region::Region;
+use rustico_core::save_load::*;
+use rustico_core::zapper::Zapper;
+
+const MOVIE_MAGIC: &[u8] = b"RUSTICO-MOVIE";
+const MOVIE_VERSION: u32 = 1;
+
+const COMMAND_POWER_CYCLE: u8 = 0b0000_0001;
+const COMMAND_RESET: u8 =       0b0000_0010;
+const COMMAND_CHANGE_DISK: u8 = 0b0000_0100;
+const COMMAND_COIN_1: u8 =      0b0000_1000;
+const COMMAND_COIN_2: u8 =      0b0001_0000;
+
+#[derive(Clone, Default)]
+pub struct MovieFrame {
+    pub power_cycle: bool,
+    pub reset: bool,
+    // The internal side number, as in disk * 2 + side
+    pub change_disk: Option<usize>,
+    pub coins: [bool; 2],
+    pub inputs: Vec<u8>,
+}
+
+impl MovieFrame {
+    pub fn add_commands(&mut self, other: &MovieFrame) {
+        self.power_cycle |= other.power_cycle;
+        self.reset |= other.reset;
+        self.change_disk = other.change_disk.or(self.change_disk);
+        self.coins[0] |= other.coins[0];
+        self.coins[1] |= other.coins[1];
+    }
+}
+
+pub struct Movie {
+    // Imported movies identify their ROM with a hash we don't compute, so this is
+    // only checked when present
+    pub rom_checksum: Option<u32>,
+    pub region: Region,
+    pub devices: [String; 2],
+    pub frames: Vec<MovieFrame>,
+}
+
+impl Movie {
+    pub fn new(nes: &NesState) -> Movie {
+        return Movie {
+            rom_checksum: Some(nes.mapper.rom_checksum()),
+            region: nes.region,
+            devices: [nes.port1.device.name().to_string(), nes.port2.device.name().to_string()],
+            frames: Vec::new(),
+        };
+    }
+
+    /// Reads either a rustico movie or an FCEUX .fm2, based on its contents
+    pub fn load(data: &[u8]) -> Result<Movie, String> {
+        if data.starts_with(MOVIE_MAGIC) {
+            return Movie::from_native(data);
+        }
+        if data.starts_with(b"version ") {
+            return match std::str::from_utf8(data) {
+                Ok(text) => Movie::from_fm2(text),
+                Err(_) => Err("FCEUX movie is not valid text".to_string())
+            };
+        }
+        return Err("Not a rustico or FCEUX movie".to_string());
+    }
+
+    pub fn save(&self) -> Vec<u8> {
+        let mut buff = Vec::new();
+        buff.extend_from_slice(MOVIE_MAGIC);
+        save_u32(&mut buff, MOVIE_VERSION);
+        save_bool(&mut buff, self.rom_checksum.is_some());
+        save_u32(&mut buff, self.rom_checksum.unwrap_or(0));
+        save_vec_u8(&mut buff, self.region.name().as_bytes());
+        save_vec_u8(&mut buff, self.devices[0].as_bytes());
+        save_vec_u8(&mut buff, self.devices[1].as_bytes());
+        save_usize(&mut buff, self.frames.len());
+        for frame in &self.frames {
+            let mut commands = 0;
+            if frame.power_cycle {commands |= COMMAND_POWER_CYCLE};
+            if frame.reset {commands |= COMMAND_RESET};
+            if frame.change_disk.is_some() {commands |= COMMAND_CHANGE_DISK};
+            if frame.coins[0] {commands |= COMMAND_COIN_1};
+            if frame.coins[1] {commands |= COMMAND_COIN_2};
+            save_u8(&mut buff, commands);
+            match frame.change_disk {
+                Some(side) => save_usize(&mut buff, side),
+                None => {}
+            }
+            save_vec_u8(&mut buff, &frame.inputs);
+        }
+        return buff;
+    }
+
+    fn from_native(data: &[u8]) -> Result<Movie, String> {
+        let mut buff = &data[MOVIE_MAGIC.len() ..];
+        let version = load_u32(&mut buff)?;
+        if version != MOVIE_VERSION {
+            return Err(format!("Unsupported movie version {}, expected {}", version, MOVIE_VERSION));
+        }
+        let has_checksum = load_bool(&mut buff)?;
+        let checksum = load_u32(&mut buff)?;
+        let region_name = load_string(&mut buff)?;
+        let region = match Region::from_name(&region_name) {
+            Some(region) => region,
+            None => {return Err(format!("Movie has unrecognized region {}", region_name))}
+        };
+        let devices = [load_string(&mut buff)?, load_string(&mut buff)?];
+        let frame_count = load_usize(&mut buff)?;
+        let mut frames = Vec::new();
+        for _ in 0 .. frame_count {
+            let commands = load_u8(&mut buff)?;
+            let change_disk = if commands & COMMAND_CHANGE_DISK != 0 {Some(load_usize(&mut buff)?)} else {None};
+            let input_length = load_usize(&mut buff)?;
+            if input_length > buff.len() {
+                return Err("Movie is truncated".to_string());
+            }
+            let mut inputs = vec![0u8; input_length];
+            inputs.copy_from_slice(&buff[.. input_length]);
+            buff = &buff[input_length ..];
+            frames.push(MovieFrame{
+                power_cycle: commands & COMMAND_POWER_CYCLE != 0,
+                reset: commands & COMMAND_RESET != 0,
+                change_disk: change_disk,
+                coins: [commands & COMMAND_COIN_1 != 0, commands & COMMAND_COIN_2 != 0],
+                inputs: inputs,
+            });
+        }
+        return Ok(Movie {
+            rom_checksum: if has_checksum {Some(checksum)} else {None},
+            region: region,
+            devices: devices,
+            frames: frames,
+        });
+    }
+
+    /// Converts an FCEUX text movie. Each input line looks like |commands|port0|port1|port2|,
+    /// with controllers written as RLDUTSBA (a '.' or space where the button is up), and
+    /// zappers as "x y buttons ...". With the Four Score attached, there are four
+    /// controller fields instead, one per player.
+    pub fn from_fm2(text: &str) -> Result<Movie, String> {
+        let mut pal = false;
+        let mut four_score = false;
+        let mut port_types = [1, 1];
+        let mut frames = Vec::new();
+
+        // FCEUX powers on with the disk inserted, and only lets the side be chosen
+        // while it's ejected
+        let mut disk_inserted = true;
+        let mut selected_side = 0;
+
+        for (line_number, line) in text.lines().enumerate() {
+            if line.starts_with('|') {
+                let fields: Vec<&str> = line.split('|').collect();
+                if fields.len() < 3 {
+                    return Err(format!("Line {} of the FCEUX movie has no inputs", line_number + 1));
+                }
+                let commands: u8 = fields[1].trim().parse().unwrap_or(0);
+                let mut frame = MovieFrame::default();
+                frame.reset = commands & 0x01 != 0;
+                frame.power_cycle = commands & 0x02 != 0;
+                if commands & 0x04 != 0 {
+                    disk_inserted = !disk_inserted;
+                    if disk_inserted {
+                        frame.change_disk = Some(selected_side);
+                    }
+                }
+                if commands & 0x08 != 0 && !disk_inserted {
+                    selected_side += 1;
+                }
+                frame.coins[0] = commands & 0x10 != 0;
+
+                let field = |index: usize| fields.get(index + 2).cloned().unwrap_or("");
+                let mut devices: Vec<Box<dyn InputDevice>> = Vec::new();
+                if four_score {
+                    for port in 1 ..= 2 {
+                        let mut device = FourScore::new(port);
+                        device.set_buttons(fm2_buttons(field(port - 1)));
+                        device.set_secondary_buttons(fm2_buttons(field(port + 1)));
+                        devices.push(Box::new(device));
+                    }
+                } else {
+                    for port in 0 .. 2 {
+                        devices.push(fm2_device(port_types[port], field(port)));
+                    }
+                }
+                for device in devices {
+                    device.save_input(&mut frame.inputs);
+                }
+                frames.push(frame);
+                continue;
+            }
+
+            let mut words = line.splitn(2, ' ');
+            let key = words.next().unwrap_or("");
+            let value = words.next().unwrap_or("").trim();
+            match key {
+                "binary" => {
+                    if value != "0" {
+                        return Err("Binary FCEUX movies are not supported".to_string());
+                    }
+                },
+                "palFlag" => {pal = value == "1"},
+                "fourscore" => {four_score = value == "1"},
+                "port0" => {port_types[0] = value.parse().unwrap_or(1)},
+                "port1" => {port_types[1] = value.parse().unwrap_or(1)},
+                "savestate" => {
+                    return Err("FCEUX movies which start from a save state are not supported".to_string());
+                },
+                _ => {}
+            }
+        }
+
+        let devices = if four_score {
+            [FourScore::new(1).name().to_string(), FourScore::new(2).name().to_string()]
+        } else {
+            [fm2_device(port_types[0], "").name().to_string(), fm2_device(port_types[1], "").name().to_string()]
+        };
+        return Ok(Movie {
+            rom_checksum: None,
+            region: if pal {Region::Pal} else {Region::Ntsc},
+            devices: devices,
+            frames: frames,
+        });
+    }
+}
+
+fn load_string(buff: &mut &[u8]) -> Result<String, String> {
+    let length = load_usize(buff)?;
+    if length > buff.len() {
+        return Err("Movie is truncated".to_string());
+    }
+    let text = String::from_utf8_lossy(&buff[.. length]).to_string();
+    *buff = &buff[length ..];
+    return Ok(text);
+}
+
+// FCEUX writes buttons from the high bit down, which is the reverse of our order
+fn fm2_buttons(field: &str) -> u8 {
+    let mut buttons = 0;
+    for (i, c) in field.chars().take(8).enumerate() {
+        if c != '.' && c != ' ' {
+            buttons |= 0x80 >> i;
+        }
+    }
+    return buttons;
+}
+
+// Port types are 0 for nothing, 1 for a controller and 2 for a zapper. An empty port
+// behaves like a controller with nothing held.
+fn fm2_device(port_type: u8, field: &str) -> Box<dyn InputDevice> {
+    if port_type == 2 {
+        let mut zapper = Zapper::new();
+        let values: Vec<i32> = field.split_whitespace().map(|value| value.parse().unwrap_or(0)).collect();
+        zapper.aim(values.get(0).cloned().unwrap_or(0), values.get(1).cloned().unwrap_or(0));
+        zapper.set_trigger(values.get(2).cloned().unwrap_or(0) != 0);
+        return Box::new(zapper);
+    }
+    let mut controller = StandardController::new();
+    if port_type == 1 {
+        controller.set_buttons(fm2_buttons(field));
+    }
+    return Box::new(controller);
+}
+
+#[derive(Clone, Copy, PartialEq)]
+pub enum MovieMode {
+    Inactive,
+    Recording,
+    Playing,
+}
+
+pub struct MovieState {
+    pub mode: MovieMode,
+    pub movie: Option<Movie>,
+    // The next frame to be played back
+    pub position: usize,
+    // While recording, what the next frame will contain. Inputs the player changes are
+    // collected here, and only reach the NES once that frame begins.
+    pub pending: MovieFrame,
+    // The inputs for the frame currently being emulated
+    pub current_inputs: Vec<u8>,
+}
+
+impl MovieState {
+    pub fn new() -> MovieState {
+        return MovieState {
+            mode: MovieMode::Inactive,
+            movie: None,
+            position: 0,
+            pending: MovieFrame::default(),
+            current_inputs: Vec::new(),
+        };
+    }
+
+    pub fn active(&self) -> bool {
+        return self.mode != MovieMode::Inactive;
+    }
+
+    pub fn recording(&self) -> bool {
+        return self.mode == MovieMode::Recording;
+    }
+
+    pub fn playing(&self) -> bool {
+        return self.mode == MovieMode::Playing;
+    }
+
+    pub fn frame_count(&self) -> usize {
+        return self.movie.as_ref().map(|movie| movie.frames.len()).unwrap_or(0);
+    }
+
+    /// Begins a new recording. The NES should have just been powered on.
+    pub fn record(&mut self, nes: &NesState) {
+        let mut movie = Movie::new(nes);
+        let first_frame = MovieFrame {
+            inputs: nes.save_inputs(),
+            .. MovieFrame::default()
+        };
+        self.current_inputs = first_frame.inputs.clone();
+        self.pending = first_frame.clone();
+        movie.frames.push(first_frame);
+        self.movie = Some(movie);
+        self.position = 1;
+        self.mode = MovieMode::Recording;
+    }
+
+    /// Begins playing back a movie. The NES should have just been powered on; this
+    /// applies the inputs for the very first frame.
+    pub fn play(&mut self, movie: Movie, nes: &mut NesState) -> Result<(), String> {
+        match movie.rom_checksum {
+            Some(checksum) if checksum != nes.mapper.rom_checksum() => {
+                return Err("Movie was recorded with a different ROM".to_string());
+            },
+            _ => {}
+        }
+        if movie.region != nes.region {
+            return Err(format!("Movie was recorded in {} mode, but the console is running in {}", movie.region.name(), nes.region.name()));
+        }
+        self.movie = Some(movie);
+        self.position = 0;
+        self.mode = MovieMode::Playing;
+        match self.next_frame(nes)? {
+            Some(frame) => {
+                nes.load_inputs(&frame.inputs)?;
+                self.current_inputs = frame.inputs;
+            },
+            None => {}
+        }
+        return Ok(());
+    }
+
+    /// Stops recording or playback, and hands back the movie
+    pub fn stop(&mut self) -> Option<Movie> {
+        self.mode = MovieMode::Inactive;
+        self.position = 0;
+        self.pending = MovieFrame::default();
+        return self.movie.take();
+    }
+
+    /// Called at the start of every frame. Returns whatever this frame should do, which
+    /// the caller applies before loading its inputs. None means playback has finished.
+    pub fn next_frame(&mut self, nes: &NesState) -> Result<Option<MovieFrame>, String> {
+        let movie = match &mut self.movie {
+            Some(movie) => movie,
+            None => {return Ok(None)}
+        };
+        let devices = [nes.port1.device.name(), nes.port2.device.name()];
+        if devices[0] != movie.devices[0] || devices[1] != movie.devices[1] {
+            return Err(format!("Movie expects a {} and a {} to be plugged in, but found a {} and a {}",
+                movie.devices[0], movie.devices[1], devices[0], devices[1]));
+        }
+        match self.mode {
+            MovieMode::Recording => {
+                let frame = self.pending.clone();
+                self.pending = MovieFrame {
+                    inputs: frame.inputs.clone(),
+                    .. MovieFrame::default()
+                };
+                movie.frames.push(frame.clone());
+                self.position += 1;
+                return Ok(Some(frame));
+            },
+            MovieMode::Playing => {
+                if self.position >= movie.frames.len() {
+                    return Ok(None);
+                }
+                self.position += 1;
+                return Ok(Some(movie.frames[self.position - 1].clone()));
+            },
+            MovieMode::Inactive => {return Ok(None)}
+        }
+    }
+
+    /// Call before changing the inputs on any device. While recording, this brings back
+    /// the changes made so far this frame, so the new change builds on top of them.
+    pub fn before_input_change(&mut self, nes: &mut NesState) {
+        if self.recording() {
+            let _ = nes.load_inputs(&self.pending.inputs);
+        }
+    }
+
+    /// Call after changing the inputs on any device. While a movie is active the NES
+    /// keeps the current frame's inputs: recorded changes are deferred to the next
+    /// frame, and during playback the player's own inputs are ignored.
+    pub fn after_input_change(&mut self, nes: &mut NesState) {
+        if self.recording() {
+            self.pending.inputs = nes.save_inputs();
+        }
+        if self.active() {
+            let _ = nes.load_inputs(&self.current_inputs);
+        }
+    }
+}
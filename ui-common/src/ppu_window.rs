@@ -9,7 +9,6 @@ use panel::Panel;
 use rustico_core::mmc::mapper::Mapper;
 use rustico_core::nes::NesState;
 use rustico_core::ppu;
-use rustico_core::palettes::NTSC_PAL;
 
 fn draw_tile(mapper: &dyn Mapper, pattern_address: u16, tile_index: u16, buffer: &mut SimpleBuffer, dx: u32, dy: u32, palette: &[u8]) {
     for py in 0 .. 8 {
@@ -109,12 +108,13 @@ impl PpuWindow {
 
     pub fn update_palette_cache(&mut self, nes: &NesState) {
         // Initialize all palette colors with a straight copy
+        let palette = nes.ppu.model.palette();
         for p in 0 .. 8 {
             for i in 0 .. 4 {
                 let palette_color = nes.ppu.debug_read_byte(& *nes.mapper, 0x3F00 + p * 4 + i) as usize * 3;
-                self.palette_cache[p as usize][i as usize * 4 + 0] = NTSC_PAL[palette_color + 0];
-                self.palette_cache[p as usize][i as usize * 4 + 1] = NTSC_PAL[palette_color + 1];
-                self.palette_cache[p as usize][i as usize * 4 + 2] = NTSC_PAL[palette_color + 2];
+                self.palette_cache[p as usize][i as usize * 4 + 0] = palette[palette_color + 0];
+                self.palette_cache[p as usize][i as usize * 4 + 1] = palette[palette_color + 1];
+                self.palette_cache[p as usize][i as usize * 4 + 2] = palette[palette_color + 2];
                 self.palette_cache[p as usize][i as usize * 4 + 3] = 255;
             }
         }
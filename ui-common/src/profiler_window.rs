@@ -0,0 +1,106 @@
+use application::RuntimeState;
+use drawing;
+use drawing::Color;
+use drawing::Font;
+use drawing::SimpleBuffer;
+use events::Event;
+use panel::Panel;
+
+use rustico_core::profiler::Profiler;
+use rustico_core::profiler::RoutineKind;
+use rustico_core::symbols::SymbolTable;
+
+const ROUTINE_LINES: usize = 32;
+
+pub struct ProfilerWindow {
+    pub canvas: SimpleBuffer,
+    pub font: Font,
+    pub shown: bool,
+}
+
+impl ProfilerWindow {
+    pub fn new() -> ProfilerWindow {
+        let font = Font::from_raw(include_bytes!("assets/8x8_font.png"), 8);
+
+        return ProfilerWindow {
+            canvas: SimpleBuffer::new(464, 300),
+            font: font,
+            shown: false,
+        };
+    }
+
+    fn kind_color(kind: RoutineKind) -> Color {
+        return match kind {
+            RoutineKind::Nmi => Color::rgb(255, 160, 96),
+            RoutineKind::Irq | RoutineKind::Brk => Color::rgb(255, 224, 96),
+            RoutineKind::OamDma | RoutineKind::DmcDma => Color::rgb(128, 192, 255),
+            RoutineKind::TopLevel => Color::rgb(160, 160, 160),
+            RoutineKind::Subroutine => Color::rgb(224, 224, 224),
+        };
+    }
+
+    // Per-frame figures are averages, except for the worst frame. Budget is the worst
+    // frame as a share of an average frame's cycles.
+    pub fn draw_report(&mut self, profiler: &Profiler, symbols: &SymbolTable, x: u32, y: u32) {
+        let cycles_per_frame = profiler.cycles_per_frame();
+        let frames = profiler.frames_elapsed();
+        drawing::text(&mut self.canvas, &self.font, x, y,
+            &format!("Frames: {}  Cycles/frame: {:.1}  (click to restart)", profiler.frames, cycles_per_frame),
+            Color::rgb(192, 192, 192));
+        drawing::text(&mut self.canvas, &self.font, x, y + 16,
+            "Routine          Calls  Incl/f  Excl/f   Worst  Budget",
+            Color::rgb(192, 192, 192));
+        let mut line_y = y + 26;
+        for (routine, stats) in profiler.report().iter().take(ROUTINE_LINES) {
+            // Labels from symbol files aren't always ASCII, so this counts characters
+            let name: String = routine.name(symbols).chars().take(16).collect();
+            let budget = if cycles_per_frame > 0.0 {stats.worst_frame_cycles as f64 * 100.0 / cycles_per_frame} else {0.0};
+            let line = format!("{:<16}{:>6}{:>8}{:>8}{:>8}{:>7.1}%",
+                name, stats.calls,
+                (stats.inclusive_cycles as f64 / frames) as u64, (stats.exclusive_cycles as f64 / frames) as u64,
+                stats.worst_frame_cycles, budget);
+            drawing::text(&mut self.canvas, &self.font, x, line_y, &line, ProfilerWindow::kind_color(routine.kind));
+            line_y += 8;
+        }
+    }
+
+    fn draw(&mut self, runtime: &RuntimeState) {
+        let width = self.canvas.width;
+        let height = self.canvas.height;
+        drawing::rect(&mut self.canvas, 0, 0, width, height, Color::rgb(0,0,0));
+        match runtime.nes.profiler {
+            Some(ref profiler) => self.draw_report(profiler, &runtime.symbols, 0, 0),
+            None => drawing::text(&mut self.canvas, &self.font, 0, 0, "Click to start profiling", Color::rgb(192, 192, 192))
+        }
+    }
+}
+
+impl Panel for ProfilerWindow {
+    fn title(&self) -> &str {
+        return "Profiler";
+    }
+
+    fn shown(&self) -> bool {
+        return self.shown;
+    }
+
+    fn handle_event(&mut self, runtime: &RuntimeState, event: Event) -> Vec<Event> {
+        let mut responses: Vec<Event> = Vec::new();
+        match event {
+            Event::RequestFrame => {self.draw(runtime)},
+            Event::ShowProfilerWindow => {self.shown = true},
+            Event::CloseWindow => {self.shown = false},
+            Event::MouseClick(_x, _y) => {responses.push(Event::StartProfiler)},
+            _ => {}
+        }
+        return responses;
+    }
+
+    fn active_canvas(&self) -> &SimpleBuffer {
+        return &self.canvas;
+    }
+
+    fn scale_factor(&self) -> u32 {
+        return 2;
+    }
+}
@@ -0,0 +1,434 @@
+use application::RuntimeState;
+use drawing;
+use drawing::Color;
+use drawing::Font;
+use drawing::SimpleBuffer;
+use events::Event;
+use panel::Panel;
+
+use rustico_core::nes::NesState;
+use rustico_core::symbols::SymbolTable;
+
+const RESULT_LINES: usize = 12;
+const WATCH_LINES: usize = 5;
+
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum Comparison {
+    // Against the value each candidate had at the last search
+    Equal,
+    NotEqual,
+    Greater,
+    Less,
+    // Against a value of our choosing
+    EqualTo(i64),
+}
+
+// Searches internal RAM and, when the cartridge has some, the work RAM at $6000-$7FFF for
+// variables, by repeatedly narrowing down the addresses whose values behave the way the
+// variable should. Each search compares against the values seen by the one before.
+pub struct RamSearch {
+    pub candidates: Vec<u16>,
+    pub previous: Vec<i64>,
+    pub word: bool,
+    pub signed: bool,
+}
+
+impl RamSearch {
+    pub fn new() -> RamSearch {
+        return RamSearch {
+            candidates: Vec::new(),
+            previous: Vec::new(),
+            word: false,
+            signed: false,
+        };
+    }
+
+    fn has_work_ram(nes: &NesState) -> bool {
+        return nes.mapper.debug_read_cpu(0x6000).is_some();
+    }
+
+    fn read_byte(nes: &NesState, address: u16) -> u8 {
+        return match address {
+            0x0000 ..= 0x07FF => nes.memory.iram_raw[address as usize],
+            _ => nes.mapper.debug_read_cpu(address).unwrap_or(0)
+        };
+    }
+
+    /// The value at this address, as the current size and signedness would read it.
+    /// Words are little endian.
+    pub fn read(&self, nes: &NesState, address: u16) -> i64 {
+        return read_value(nes, address, self.word, self.signed);
+    }
+
+    /// Starts over, with every address as a candidate
+    pub fn reset(&mut self, nes: &NesState) {
+        let last_byte = if self.word {1} else {0};
+        let mut candidates: Vec<u16> = (0x0000 .. 0x0800 - last_byte).collect();
+        if RamSearch::has_work_ram(nes) {
+            candidates.extend(0x6000 .. 0x8000 - last_byte);
+        }
+        self.candidates = candidates;
+        self.refresh(nes);
+    }
+
+    /// Takes the current values as the ones the next search compares against
+    pub fn refresh(&mut self, nes: &NesState) {
+        self.previous = self.candidates.iter().map(|&address| self.read(nes, address)).collect();
+    }
+
+    pub fn filter(&mut self, nes: &NesState, comparison: Comparison) {
+        let mut candidates: Vec<u16> = Vec::new();
+        let mut previous: Vec<i64> = Vec::new();
+        for (&address, &old_value) in self.candidates.iter().zip(self.previous.iter()) {
+            let value = self.read(nes, address);
+            let keep = match comparison {
+                Comparison::Equal => value == old_value,
+                Comparison::NotEqual => value != old_value,
+                Comparison::Greater => value > old_value,
+                Comparison::Less => value < old_value,
+                Comparison::EqualTo(target) => value == target,
+            };
+            if keep {
+                candidates.push(address);
+                previous.push(value);
+            }
+        }
+        self.candidates = candidates;
+        self.previous = previous;
+    }
+}
+
+pub fn read_value(nes: &NesState, address: u16, word: bool, signed: bool) -> i64 {
+    let low = RamSearch::read_byte(nes, address);
+    if !word {
+        return if signed {low as i8 as i64} else {low as i64};
+    }
+    let high = RamSearch::read_byte(nes, address.wrapping_add(1));
+    let value = ((high as u16) << 8) | (low as u16);
+    return if signed {value as i16 as i64} else {value as i64};
+}
+
+fn format_value(value: i64, word: bool) -> String {
+    return if word {
+        format!("{:04X} {:>6}", value as u16, value)
+    } else {
+        format!("{:02X} {:>4}", value as u8, value)
+    };
+}
+
+pub struct Watch {
+    pub address: u16,
+    pub word: bool,
+    pub signed: bool,
+    // The cheat codes holding this value in place, while it's frozen
+    pub freeze_codes: Vec<String>,
+}
+
+#[derive(Clone, Copy, Debug, PartialEq)]
+enum Action {
+    NewSearch,
+    ToggleWord,
+    ToggleSigned,
+    Search(Comparison),
+    IncrementDigit(u32),
+    AdjustValue(i64),
+    WatchResult(usize),
+    FreezeResult(usize),
+    ToggleFreeze(usize),
+    RemoveWatch(usize),
+    ScrollResults(i32),
+}
+
+pub struct RamSearchWindow {
+    pub canvas: SimpleBuffer,
+    pub font: Font,
+    pub shown: bool,
+    pub search: RamSearch,
+    pub watches: Vec<Watch>,
+    // The value searched for by Comparison::EqualTo
+    pub target_value: i64,
+    pub first_result: usize,
+    // Where each clickable thing was last drawn
+    buttons: Vec<(u32, u32, u32, Action)>,
+}
+
+impl RamSearchWindow {
+    pub fn new() -> RamSearchWindow {
+        let font = Font::from_raw(include_bytes!("assets/8x8_font.png"), 8);
+
+        return RamSearchWindow {
+            canvas: SimpleBuffer::new(312, 264),
+            font: font,
+            shown: false,
+            search: RamSearch::new(),
+            watches: Vec::new(),
+            target_value: 0,
+            first_result: 0,
+            buttons: Vec::new(),
+        };
+    }
+
+    fn button(&mut self, x: u32, y: u32, label: &str, highlighted: bool, action: Action) -> u32 {
+        let width = label.len() as u32 * 8 + 4;
+        let background = if highlighted {Color::rgb(96, 96, 128)} else {Color::rgb(48, 48, 64)};
+        drawing::rect(&mut self.canvas, x, y, width, 10, background);
+        drawing::text(&mut self.canvas, &self.font, x + 2, y + 1, label, Color::rgba(255, 255, 255, 224));
+        self.buttons.push((x, y, width, action));
+        return x + width + 4;
+    }
+
+    pub fn draw_controls(&mut self, x: u32, y: u32) {
+        let word = self.search.word;
+        let signed = self.search.signed;
+        let mut bx = self.button(x, y, "New Search", false, Action::NewSearch);
+        bx = self.button(bx, y, if word {"16-bit"} else {"8-bit"}, word, Action::ToggleWord);
+        self.button(bx, y, if signed {"Signed"} else {"Unsigned"}, signed, Action::ToggleSigned);
+
+        let target_value = self.target_value;
+        bx = self.button(x, y + 12, "Same", false, Action::Search(Comparison::Equal));
+        bx = self.button(bx, y + 12, "Changed", false, Action::Search(Comparison::NotEqual));
+        bx = self.button(bx, y + 12, "Up", false, Action::Search(Comparison::Greater));
+        bx = self.button(bx, y + 12, "Down", false, Action::Search(Comparison::Less));
+        self.button(bx, y + 12, "= Value", false, Action::Search(Comparison::EqualTo(target_value)));
+
+        // Each digit of the value counts up when clicked
+        drawing::text(&mut self.canvas, &self.font, x, y + 25, "Value:", Color::rgb(192, 192, 192));
+        let digits = if word {4} else {2};
+        let raw_value = if word {self.target_value as u16 as u32} else {self.target_value as u8 as u32};
+        for digit in 0 .. digits {
+            let shift = (digits - 1 - digit) * 4;
+            let label = format!("{:X}", (raw_value >> shift) & 0xF);
+            self.button(x + 56 + digit * 14, y + 24, &label, false, Action::IncrementDigit(shift));
+        }
+        drawing::text(&mut self.canvas, &self.font, x + 56 + digits * 14 + 4, y + 25, &format!("{}", self.target_value), Color::rgb(192, 192, 192));
+        self.button(x + 184, y + 24, "-", false, Action::AdjustValue(-1));
+        self.button(x + 200, y + 24, "+", false, Action::AdjustValue(1));
+    }
+
+    pub fn draw_results(&mut self, nes: &NesState, symbols: &SymbolTable, x: u32, y: u32) {
+        let count = self.search.candidates.len();
+        let summary = if count == 0 {
+            "No candidates: start a new search".to_string()
+        } else {
+            format!("{} candidates", count)
+        };
+        drawing::text(&mut self.canvas, &self.font, x, y, &summary, Color::rgb(255, 255, 255));
+        if count > RESULT_LINES {
+            self.button(x + 232, y - 1, "^", false, Action::ScrollResults(-(RESULT_LINES as i32)));
+            self.button(x + 248, y - 1, "v", false, Action::ScrollResults(RESULT_LINES as i32));
+        }
+        drawing::text(&mut self.canvas, &self.font, x, y + 11, "Addr  Was         Now", Color::rgb(160, 160, 160));
+
+        self.first_result = self.first_result.min(count.saturating_sub(1));
+        let word = self.search.word;
+        for line in 0 .. RESULT_LINES {
+            let index = self.first_result + line;
+            if index >= count {
+                break;
+            }
+            let line_y = y + 22 + line as u32 * 11;
+            let address = self.search.candidates[index];
+            let old_value = self.search.previous[index];
+            let value = self.search.read(nes, address);
+            let color = if value != old_value {Color::rgb(255, 255, 128)} else {Color::rgba(255, 255, 255, 192)};
+            drawing::text(&mut self.canvas, &self.font, x, line_y + 1,
+                &format!("{:04X}  {}  {}", address, format_value(old_value, word), format_value(value, word)), color);
+            match symbols.label_at(nes, address) {
+                Some(name) => {
+                    let name: String = name.chars().take(3).collect();
+                    drawing::text(&mut self.canvas, &self.font, x + 200, line_y + 1, &name, Color::rgb(128, 160, 255));
+                },
+                None => {}
+            }
+            self.button(x + 232, line_y, "W", false, Action::WatchResult(index));
+            self.button(x + 248, line_y, "F", false, Action::FreezeResult(index));
+        }
+    }
+
+    pub fn draw_watches(&mut self, nes: &NesState, symbols: &SymbolTable, x: u32, y: u32) {
+        drawing::text(&mut self.canvas, &self.font, x, y, "Watch", Color::rgb(255, 255, 255));
+        for index in 0 .. self.watches.len().min(WATCH_LINES) {
+            let line_y = y + 11 + index as u32 * 11;
+            let (address, word, signed, frozen) = {
+                let watch = &self.watches[index];
+                (watch.address, watch.word, watch.signed, !watch.freeze_codes.is_empty())
+            };
+            let value = read_value(nes, address, word, signed);
+            let color = if frozen {Color::rgb(128, 192, 255)} else {Color::rgba(255, 255, 255, 192)};
+            drawing::text(&mut self.canvas, &self.font, x, line_y + 1,
+                &format!("{:04X}  {}", address, format_value(value, word)), color);
+            match symbols.label_at(nes, address) {
+                Some(name) => {
+                    let name: String = name.chars().take(12).collect();
+                    drawing::text(&mut self.canvas, &self.font, x + 120, line_y + 1, &name, Color::rgb(128, 160, 255));
+                },
+                None => {}
+            }
+            self.button(x + 232, line_y, "F", frozen, Action::ToggleFreeze(index));
+            self.button(x + 248, line_y, "X", false, Action::RemoveWatch(index));
+        }
+    }
+
+    pub fn draw(&mut self, nes: &NesState, symbols: &SymbolTable) {
+        let width = self.canvas.width;
+        let height = self.canvas.height;
+        drawing::rect(&mut self.canvas, 0, 0, width, height, Color::rgb(0,0,0));
+        self.buttons.clear();
+        self.draw_controls(2, 2);
+        self.draw_results(nes, symbols, 2, 42);
+        self.draw_watches(nes, symbols, 2, 198);
+    }
+
+    fn add_watch(&mut self, address: u16) -> Vec<Event> {
+        let mut responses: Vec<Event> = Vec::new();
+        if self.watches.iter().any(|watch| watch.address == address) {
+            return responses;
+        }
+        self.watches.push(Watch {address: address, word: self.search.word, signed: self.search.signed, freeze_codes: Vec::new()});
+        // The oldest watch makes way once the list is full, letting go of its value if
+        // it was frozen
+        if self.watches.len() > WATCH_LINES {
+            responses.extend(self.unfreeze(0));
+            self.watches.remove(0);
+        }
+        return responses;
+    }
+
+    // Frozen values become RAM freeze cheats, held at whatever they are right now. These
+    // are only kept until another game is loaded, and are never saved.
+    fn freeze(&mut self, nes: &NesState, index: usize) -> Vec<Event> {
+        let watch = &mut self.watches[index];
+        let bytes = if watch.word {2} else {1};
+        watch.freeze_codes = (0 .. bytes).map(|offset| {
+            let address = watch.address.wrapping_add(offset);
+            format!("{:04X}:{:02X}", address, RamSearch::read_byte(nes, address))
+        }).collect();
+        return watch.freeze_codes.iter().map(|code| Event::AddRamFreeze(code.clone())).collect();
+    }
+
+    fn unfreeze(&mut self, index: usize) -> Vec<Event> {
+        let codes = std::mem::take(&mut self.watches[index].freeze_codes);
+        return codes.into_iter().map(Event::RemoveRamFreeze).collect();
+    }
+
+    fn run_action(&mut self, nes: &NesState, action: Action) -> Vec<Event> {
+        let mut responses: Vec<Event> = Vec::new();
+        match action {
+            Action::NewSearch => {
+                self.search.reset(nes);
+                self.first_result = 0;
+            },
+            Action::ToggleWord => {
+                self.search.word = !self.search.word;
+                self.search.reset(nes);
+                self.first_result = 0;
+            },
+            Action::ToggleSigned => {
+                self.search.signed = !self.search.signed;
+                self.search.refresh(nes);
+            },
+            Action::Search(comparison) => {
+                self.search.filter(nes, comparison);
+                self.first_result = 0;
+            },
+            Action::IncrementDigit(shift) => {
+                let digit = (self.target_value >> shift) & 0xF;
+                self.target_value = self.target_value - (digit << shift) + (((digit + 1) & 0xF) << shift);
+                self.clamp_target_value();
+            },
+            Action::AdjustValue(amount) => {
+                self.target_value += amount;
+                self.clamp_target_value();
+            },
+            Action::WatchResult(index) => {
+                let address = self.search.candidates[index];
+                responses.extend(self.add_watch(address));
+            },
+            Action::FreezeResult(index) => {
+                let address = self.search.candidates[index];
+                responses.extend(self.add_watch(address));
+                let watch_index = self.watches.iter().position(|watch| watch.address == address).unwrap();
+                if self.watches[watch_index].freeze_codes.is_empty() {
+                    responses.extend(self.freeze(nes, watch_index));
+                }
+            },
+            Action::ToggleFreeze(index) => {
+                if !self.watches[index].freeze_codes.is_empty() {
+                    responses.extend(self.unfreeze(index));
+                } else {
+                    responses.extend(self.freeze(nes, index));
+                }
+            },
+            Action::RemoveWatch(index) => {
+                responses.extend(self.unfreeze(index));
+                self.watches.remove(index);
+            },
+            Action::ScrollResults(lines) => {
+                let first_result = self.first_result as i32 + lines;
+                self.first_result = first_result.max(0) as usize;
+            },
+        }
+        return responses;
+    }
+
+    // Keeps the value searched for within what the current size and signedness can hold,
+    // wrapping around like the game's own arithmetic would
+    fn clamp_target_value(&mut self) {
+        self.target_value = match (self.search.word, self.search.signed) {
+            (false, false) => self.target_value as u8 as i64,
+            (false, true) => self.target_value as i8 as i64,
+            (true, false) => self.target_value as u16 as i64,
+            (true, true) => self.target_value as i16 as i64,
+        };
+    }
+
+    pub fn handle_click(&mut self, nes: &NesState, mx: i32, my: i32) -> Vec<Event> {
+        let clicked = self.buttons.iter().find(|&&(x, y, width, _)| {
+            mx >= x as i32 && mx < (x + width) as i32 && my >= y as i32 && my < y as i32 + 10
+        }).map(|&(_, _, _, action)| action);
+        return match clicked {
+            Some(action) => {
+                let responses = self.run_action(nes, action);
+                self.clamp_target_value();
+                responses
+            },
+            None => Vec::new()
+        };
+    }
+}
+
+impl Panel for RamSearchWindow {
+    fn title(&self) -> &str {
+        return "RAM Search";
+    }
+
+    fn shown(&self) -> bool {
+        return self.shown;
+    }
+
+    fn handle_event(&mut self, runtime: &RuntimeState, event: Event) -> Vec<Event> {
+        let mut responses: Vec<Event> = Vec::new();
+        match event {
+            Event::RequestFrame => {self.draw(&runtime.nes, &runtime.symbols)},
+            Event::ShowRamSearchWindow => {self.shown = true},
+            Event::CloseWindow => {self.shown = false},
+            // Addresses found in one game mean nothing in the next. Any freezes went
+            // with the old game, so there's nothing to undo.
+            Event::CartridgeLoaded(_) => {
+                self.search = RamSearch::new();
+                self.watches.clear();
+            },
+            Event::MouseClick(x, y) => {responses.extend(self.handle_click(&runtime.nes, x, y))},
+            _ => {}
+        }
+        return responses;
+    }
+
+    fn active_canvas(&self) -> &SimpleBuffer {
+        return &self.canvas;
+    }
+
+    fn scale_factor(&self) -> u32 {
+        return 2;
+    }
+}
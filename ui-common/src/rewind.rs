@@ -0,0 +1,299 @@
+// Rewind history, built on the regular save state format. Every few frames we take
+// a snapshot of the whole NesState. Only the newest snapshot is kept whole; each
+// older one is stored as the XOR against its newer neighbor, run length encoded.
+// Consecutive states are mostly identical, so these deltas are tiny, and since
+// every delta points *forward* in time the oldest entry can be dropped at any point
+// without breaking the chain.
+//
+// Snapshots alone would only let us jump back in coarse steps. To land on an exact
+// frame we also log every change to the controller inputs, stamped with the master
+// clock at which it happened. Rewinding loads the nearest snapshot at or before the
+// target frame and re-emulates forward, replaying those input changes at the same
+// clock they originally occurred, which reproduces the original frames exactly.
+
+use std::collections::VecDeque;
+
+use rustico_core::nes::NesState;
+
+struct Snapshot {
+    frame: u32,
+    master_clock: u64,
+    data: Vec<u8>,
+}
+
+struct InputChange {
+    master_clock: u64,
+    inputs: Vec<u8>,
+}
+
+pub struct RewindBuffer {
+    pub enabled: bool,
+    pub snapshot_interval: u32,
+    pub capacity: usize,
+    newest: Option<Snapshot>,
+    history: VecDeque<Snapshot>,
+    inputs: VecDeque<InputChange>,
+    // Full states for the frames we re-emulated during the last rewind. Holding the
+    // rewind key steps back one frame at a time, and this saves us from replaying
+    // the same stretch of frames over and over to do that.
+    replay_cache: Vec<Snapshot>,
+}
+
+impl RewindBuffer {
+    pub fn new(snapshot_interval: u32, capacity: usize) -> RewindBuffer {
+        return RewindBuffer {
+            enabled: true,
+            snapshot_interval: snapshot_interval,
+            capacity: capacity,
+            newest: None,
+            history: VecDeque::new(),
+            inputs: VecDeque::new(),
+            replay_cache: Vec::new(),
+        };
+    }
+
+    pub fn clear(&mut self) {
+        self.newest = None;
+        self.history.clear();
+        self.inputs.clear();
+        self.replay_cache.clear();
+    }
+
+    pub fn len(&self) -> usize {
+        return self.history.len() + if self.newest.is_some() {1} else {0};
+    }
+
+    pub fn compressed_size(&self) -> usize {
+        let newest_size = match &self.newest {
+            Some(snapshot) => snapshot.data.len(),
+            None => 0
+        };
+        return self.history.iter().fold(newest_size, |total, snapshot| total + snapshot.data.len());
+    }
+
+    pub fn oldest_frame(&self) -> Option<u32> {
+        return match self.history.front() {
+            Some(snapshot) => Some(snapshot.frame),
+            None => self.newest.as_ref().map(|snapshot| snapshot.frame)
+        };
+    }
+
+    fn oldest_master_clock(&self) -> Option<u64> {
+        return match self.history.front() {
+            Some(snapshot) => Some(snapshot.master_clock),
+            None => self.newest.as_ref().map(|snapshot| snapshot.master_clock)
+        };
+    }
+
+    /// Call this whenever the controller inputs change, so that rewinding can
+    /// replay them at the right moment.
+    pub fn record_input(&mut self, nes: &NesState) {
+        if !self.enabled || self.newest.is_none() {
+            return;
+        }
+        self.inputs.push_back(InputChange{
+            master_clock: nes.master_clock,
+            inputs: nes.save_inputs(),
+        });
+    }
+
+    /// Call this once at the start of every emulated frame. A snapshot is taken
+    /// whenever at least snapshot_interval frames have passed since the last one.
+    pub fn capture(&mut self, nes: &NesState) {
+        if !self.enabled {
+            return;
+        }
+
+        // We're moving forward again, so anything we cached while rewinding is stale
+        self.replay_cache.clear();
+
+        let frame = nes.ppu.current_frame;
+        match &self.newest {
+            Some(newest) => {
+                if frame < newest.frame {
+                    // Time went backwards behind our back; the history no longer
+                    // describes how we got here.
+                    self.clear();
+                } else if frame - newest.frame < self.snapshot_interval {
+                    return;
+                }
+            },
+            None => {}
+        }
+
+        let snapshot = Snapshot{
+            frame: frame,
+            master_clock: nes.master_clock,
+            data: nes.save_state(),
+        };
+        match self.newest.take() {
+            Some(previous) => {
+                self.history.push_back(Snapshot{
+                    frame: previous.frame,
+                    master_clock: previous.master_clock,
+                    data: encode_delta(&snapshot.data, &previous.data),
+                });
+            },
+            None => {}
+        }
+        self.newest = Some(snapshot);
+
+        while self.len() > self.capacity && self.history.len() > 0 {
+            self.history.pop_front();
+        }
+        let oldest_master_clock = self.oldest_master_clock().unwrap_or(0);
+        while self.inputs.len() > 0 && self.inputs[0].master_clock < oldest_master_clock {
+            self.inputs.pop_front();
+        }
+    }
+
+    /// Steps the NES backwards by the requested number of frames, or as far as the
+    /// history allows. Everything after the new position is discarded. Returns the
+    /// frame we ended up on.
+    pub fn rewind(&mut self, nes: &mut NesState, frames: u32) -> Result<u32, String> {
+        let oldest_frame = match self.oldest_frame() {
+            Some(frame) => frame,
+            None => {return Err("No rewind history is available".to_string())}
+        };
+        let target_frame = nes.ppu.current_frame.saturating_sub(frames).max(oldest_frame);
+
+        match self.replay_cache.iter().position(|snapshot| snapshot.frame == target_frame) {
+            Some(index) => {
+                nes.load_state(&self.replay_cache[index].data)?;
+                self.replay_cache.truncate(index);
+            },
+            None => {
+                self.replay_cache.clear();
+                self.discard_snapshots_after(target_frame);
+                match &self.newest {
+                    Some(newest) => nes.load_state(&newest.data)?,
+                    None => {return Err("Rewind history is empty".to_string())}
+                }
+                self.replay_until(nes, target_frame);
+            }
+        }
+
+        // The inputs we logged past this point belong to a future that will now
+        // be played out differently
+        while self.inputs.len() > 0 && self.inputs[self.inputs.len() - 1].master_clock > nes.master_clock {
+            self.inputs.pop_back();
+        }
+
+        return Ok(nes.ppu.current_frame);
+    }
+
+    fn discard_snapshots_after(&mut self, target_frame: u32) {
+        loop {
+            let newest = match self.newest.take() {
+                Some(newest) => newest,
+                None => {return}
+            };
+            if newest.frame <= target_frame {
+                self.newest = Some(newest);
+                return;
+            }
+            self.newest = match self.history.pop_back() {
+                Some(delta) => Some(Snapshot{
+                    frame: delta.frame,
+                    master_clock: delta.master_clock,
+                    data: decode_delta(&newest.data, &delta.data),
+                }),
+                None => None
+            };
+        }
+    }
+
+    fn replay_until(&mut self, nes: &mut NesState, target_frame: u32) {
+        let mut next_input = 0;
+        while next_input < self.inputs.len() && self.inputs[next_input].master_clock < nes.master_clock {
+            next_input += 1;
+        }
+
+        loop {
+            while next_input < self.inputs.len() && self.inputs[next_input].master_clock <= nes.master_clock {
+                // These were saved from this same set of devices, so this can't fail
+                let _ = nes.load_inputs(&self.inputs[next_input].inputs);
+                next_input += 1;
+            }
+            if nes.ppu.current_frame >= target_frame {
+                return;
+            }
+            let previous_frame = nes.ppu.current_frame;
+            nes.step();
+            if nes.ppu.current_frame != previous_frame && nes.ppu.current_frame < target_frame {
+                self.replay_cache.push(Snapshot{
+                    frame: nes.ppu.current_frame,
+                    master_clock: nes.master_clock,
+                    data: nes.save_state(),
+                });
+            }
+        }
+    }
+}
+
+fn write_varint(buff: &mut Vec<u8>, mut value: usize) {
+    while value >= 0x80 {
+        buff.push((value as u8 & 0x7F) | 0x80);
+        value >>= 7;
+    }
+    buff.push(value as u8);
+}
+
+fn read_varint(buff: &[u8], position: &mut usize) -> usize {
+    let mut value = 0;
+    let mut shift = 0;
+    while *position < buff.len() {
+        let byte = buff[*position];
+        *position += 1;
+        value |= ((byte & 0x7F) as usize) << shift;
+        if byte & 0x80 == 0 {
+            break;
+        }
+        shift += 7;
+    }
+    return value;
+}
+
+// The delta is a series of (zero run length, literal length, literal bytes) records,
+// where the literal bytes are the XOR of the two states. Stretches where the states
+// agree collapse down to a couple of bytes.
+fn encode_delta(base: &[u8], data: &[u8]) -> Vec<u8> {
+    let xor_at = |i: usize| data[i] ^ base.get(i).cloned().unwrap_or(0);
+    let mut encoded = Vec::new();
+    let mut i = 0;
+    while i < data.len() {
+        let zero_start = i;
+        while i < data.len() && xor_at(i) == 0 {
+            i += 1;
+        }
+        let literal_start = i;
+        // A lone matching byte is cheaper to keep in the literal than to split on
+        while i < data.len() && !(xor_at(i) == 0 && (i + 1 == data.len() || xor_at(i + 1) == 0)) {
+            i += 1;
+        }
+        write_varint(&mut encoded, literal_start - zero_start);
+        write_varint(&mut encoded, i - literal_start);
+        for j in literal_start .. i {
+            encoded.push(xor_at(j));
+        }
+    }
+    return encoded;
+}
+
+fn decode_delta(base: &[u8], encoded: &[u8]) -> Vec<u8> {
+    let mut data = Vec::new();
+    let mut position = 0;
+    while position < encoded.len() {
+        let zero_length = read_varint(encoded, &mut position);
+        let literal_length = read_varint(encoded, &mut position);
+        for _ in 0 .. zero_length {
+            data.push(base.get(data.len()).cloned().unwrap_or(0));
+        }
+        for _ in 0 .. literal_length {
+            let base_byte = base.get(data.len()).cloned().unwrap_or(0);
+            data.push(encoded[position] ^ base_byte);
+            position += 1;
+        }
+    }
+    return data;
+}
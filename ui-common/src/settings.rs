@@ -11,12 +11,32 @@ use events::Event;
 
 
 const DEFAULT_CONFIG: &str = r###"
+[emulation]
+region = "auto"
+
 [video]
 ntsc_filter = false
 simulate_overscan = false
 display_fps = false
 scale_factor = 2
 
+[input]
+port1 = "auto"
+port2 = "auto"
+four_players = "auto"
+
+[rewind]
+enabled = true
+snapshot_interval = 15
+capacity = 720
+
+[vs_system]
+dip_switches = 0
+
+[debug_server]
+enabled = false
+port = 6502
+
 [piano_roll]
 canvas_width = 1280
 canvas_height = 720
@@ -426,6 +446,23 @@ impl SettingsState {
         return SettingsState::_set(path, root_table, new_value);
     }
 
+    // Cheats are kept for each game, as a list of codes under [cheats] named for the
+    // game's ROM checksum
+    pub fn get_cheats(&self, game_id: &str) -> Vec<String> {
+        match self.get(format!("cheats.{}", game_id)) {
+            Some(Value::Array(codes)) => {
+                return codes.iter().filter_map(|code| code.as_str().map(|code| code.to_string())).collect();
+            },
+            _ => {return Vec::new()}
+        }
+    }
+
+    pub fn set_cheats(&mut self, game_id: &str, codes: Vec<String>) {
+        let path = format!("cheats.{}", game_id);
+        self.ensure_path_exists(path.clone(), Value::Array(Vec::new()));
+        self.set(path, Value::Array(codes.into_iter().map(Value::from).collect()));
+    }
+
     pub fn handle_event(&mut self, event: Event) -> Vec<Event> {
         let mut events: Vec<Event> = Vec::new();
         match event {
@@ -11,6 +11,7 @@ const {
   audio_buffer_full, 
   get_audio_buffer, 
   get_sram, set_sram, 
+  save_state, load_state,
   has_sram, update_windows,
   draw_piano_roll_window,
   draw_screen_pixels,
@@ -91,6 +92,8 @@ const rpc_functions = {
   "has_sram": has_sram,
   "get_sram": get_sram,
   "set_sram": set_sram,
+  "save_state": save_state,
+  "load_state": load_state,
 };
 
 function rpc(task, args, reply_channel) {
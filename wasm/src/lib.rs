@@ -7,7 +7,6 @@ extern crate wasm_bindgen;
 use std::sync::Mutex;
 use std::sync::Arc;
 
-use rustico_core::palettes::NTSC_PAL;
 use rustico_core::apu::FilterType;
 use wasm_bindgen::prelude::*;
 
@@ -129,6 +128,7 @@ pub fn render_screen_pixels() {
   let overlay = CRT_OVERLAY.lock().expect("wat");
   let mut render_canvas = GAME_RENDER.lock().expect("wat");
   let pixels = &mut render_canvas.buffer;
+  let palette = nes.ppu.model.palette();
 
   for x in 0 .. 256 {
     for y in 0 .. 240 {
@@ -137,9 +137,9 @@ pub fn render_screen_pixels() {
       // overlay with direct buffer reading
       let alpha = overlay.buffer[pixel_offset] as u16;
       let background_color = [3, 3, 3];
-      let r = (((NTSC_PAL[palette_index + 0] as u16 * alpha) + (background_color[0] * (256 - alpha))) / 256) as u8;
-      let g = (((NTSC_PAL[palette_index + 1] as u16 * alpha) + (background_color[1] * (256 - alpha))) / 256) as u8;
-      let b = (((NTSC_PAL[palette_index + 2] as u16 * alpha) + (background_color[2] * (256 - alpha))) / 256) as u8;
+      let r = (((palette[palette_index + 0] as u16 * alpha) + (background_color[0] * (256 - alpha))) / 256) as u8;
+      let g = (((palette[palette_index + 1] as u16 * alpha) + (background_color[1] * (256 - alpha))) / 256) as u8;
+      let b = (((palette[palette_index + 2] as u16 * alpha) + (background_color[2] * (256 - alpha))) / 256) as u8;
       pixels[pixel_offset + 0] = r;
       pixels[pixel_offset + 1] = g;
       pixels[pixel_offset + 2] = b;
@@ -175,14 +175,14 @@ pub fn draw_piano_roll_window(dest: &mut [u8]) {
 pub fn set_p1_input(keystate: u8) {
   let mut runtime = RUNTIME.lock().expect("wat");
   let nes = &mut runtime.nes;
-  nes.p1_input = keystate;
+  nes.port1.device.set_buttons(keystate);
 }
 
 #[wasm_bindgen]
 pub fn set_p2_input(keystate: u8) {
   let mut runtime = RUNTIME.lock().expect("wat");
   let nes = &mut runtime.nes;
-  nes.p2_input = keystate;
+  nes.port2.device.set_buttons(keystate);
 }
 
 #[wasm_bindgen]
@@ -256,6 +256,21 @@ pub fn has_sram() -> bool {
   return nes.mapper.has_sram();
 }
 
+#[wasm_bindgen]
+pub fn save_state() -> Vec<u8> {
+  let runtime = RUNTIME.lock().expect("wat");
+  let nes = &runtime.nes;
+
+  return nes.save_state();
+}
+
+#[wasm_bindgen]
+pub fn load_state(state: Vec<u8>) -> Result<(), String> {
+  let mut runtime = RUNTIME.lock().expect("wat");
+
+  return runtime.load_state(&state);
+}
+
 #[wasm_bindgen]
 pub fn piano_roll_window_click(mx: i32, my: i32) {
   let mut runtime = RUNTIME.lock().expect("wat");